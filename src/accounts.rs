@@ -0,0 +1,619 @@
+use crate::matching::orderbook::{AccountId, OrderId};
+use std::collections::HashMap;
+
+/// Risk limits for an account, applied to the account and everything below it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AccountLimits {
+    /// Largest notional (price × size) of a single order
+    pub max_order_notional: Option<f64>,
+    /// Largest combined notional of all open orders
+    pub max_open_notional: Option<f64>,
+}
+
+/// Whether an account trades for real
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccountClass {
+    #[default]
+    Live,
+    /// A sandbox account whose orders are matched in the shadow of the real book, see
+    /// [`crate::matching::paper::PaperBook`]
+    Paper,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub id: AccountId,
+    /// The firm (or desk) this account belongs to, None for top-level firms
+    pub parent: Option<AccountId>,
+    pub limits: AccountLimits,
+    pub class: AccountClass,
+}
+
+/// Accounts arranged in a firm → trader hierarchy
+///
+/// Orders and fills are attributed to the account that placed them, while limits set on a parent
+/// apply to the combined activity of all of its sub-accounts.
+#[derive(Debug, Default)]
+pub struct AccountRegistry {
+    accounts: HashMap<AccountId, Account>,
+}
+
+impl AccountRegistry {
+    pub fn new() -> Self {
+        AccountRegistry::default()
+    }
+
+    /// Register an account, optionally under an existing parent
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the account already exists or the parent does not
+    pub fn add_account(
+        &mut self,
+        id: AccountId,
+        parent: Option<AccountId>,
+        limits: AccountLimits,
+    ) -> Result<(), String> {
+        if self.accounts.contains_key(&id) {
+            return Err(format!("Account {} already exists", id));
+        }
+        if let Some(parent) = parent {
+            if !self.accounts.contains_key(&parent) {
+                return Err(format!("Parent account {} does not exist", parent));
+            }
+        }
+        self.accounts.insert(
+            id,
+            Account {
+                id,
+                parent,
+                limits,
+                class: AccountClass::Live,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, id: AccountId) -> Option<&Account> {
+        self.accounts.get(&id)
+    }
+
+    pub fn set_limits(&mut self, id: AccountId, limits: AccountLimits) -> Result<(), String> {
+        match self.accounts.get_mut(&id) {
+            Some(account) => {
+                account.limits = limits;
+                Ok(())
+            }
+            None => Err(format!("Account {} does not exist", id)),
+        }
+    }
+
+    /// Make an account live or paper
+    pub fn set_class(&mut self, id: AccountId, class: AccountClass) -> Result<(), String> {
+        match self.accounts.get_mut(&id) {
+            Some(account) => {
+                account.class = class;
+                Ok(())
+            }
+            None => Err(format!("Account {} does not exist", id)),
+        }
+    }
+
+    /// Whether an account is a paper account; unregistered accounts trade live
+    pub fn is_paper(&self, id: AccountId) -> bool {
+        self.get(id)
+            .is_some_and(|account| account.class == AccountClass::Paper)
+    }
+
+    /// The account followed by each of its ancestors up to the top-level firm
+    pub fn lineage(&self, id: AccountId) -> Vec<AccountId> {
+        let mut lineage = Vec::new();
+        let mut current = self.accounts.get(&id);
+        while let Some(account) = current {
+            lineage.push(account.id);
+            current = account.parent.and_then(|parent| self.accounts.get(&parent));
+        }
+        lineage
+    }
+
+    /// The top-level firm an account belongs to
+    pub fn firm(&self, id: AccountId) -> Option<AccountId> {
+        self.lineage(id).last().copied()
+    }
+
+    /// The account and every account below it
+    pub fn descendants(&self, id: AccountId) -> Vec<AccountId> {
+        if !self.accounts.contains_key(&id) {
+            return Vec::new();
+        }
+        let mut descendants = vec![id];
+        let mut index = 0;
+        while index < descendants.len() {
+            let parent = descendants[index];
+            descendants.extend(
+                self.accounts
+                    .values()
+                    .filter(|account| account.parent == Some(parent))
+                    .map(|account| account.id),
+            );
+            index += 1;
+        }
+        descendants
+    }
+}
+
+/// Funds held against a single open order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reservation {
+    pub account: AccountId,
+    pub asset: String,
+    pub amount: f64,
+}
+
+/// Per-order reservation accounting
+///
+/// Every reservation is keyed by the order it backs, so each operation on an order touches
+/// exactly one entry: amends resize it, fills consume from it and cancels release whatever is
+/// left. Once an order's reservation reaches zero or is released the entry is gone, so a late
+/// cancel, a fill racing a cancel or a repeated release can never hand the same funds back twice.
+#[derive(Debug, Default)]
+pub struct ReservationLedger {
+    reservations: HashMap<OrderId, Reservation>,
+    totals: HashMap<(AccountId, String), f64>,
+}
+
+impl ReservationLedger {
+    pub fn new() -> Self {
+        ReservationLedger::default()
+    }
+
+    /// Reserve `amount` of `asset` for a new order
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the order already holds a reservation
+    pub fn reserve(
+        &mut self,
+        order_id: OrderId,
+        account: AccountId,
+        asset: &str,
+        amount: f64,
+    ) -> Result<(), String> {
+        if self.reservations.contains_key(&order_id) {
+            return Err(format!("Order {} already has a reservation", order_id));
+        }
+        self.adjust_total(account, asset, amount);
+        self.reservations.insert(
+            order_id,
+            Reservation {
+                account,
+                asset: asset.to_string(),
+                amount,
+            },
+        );
+        Ok(())
+    }
+
+    /// Change the amount held for an order, e.g. after an amend
+    ///
+    /// # Returns
+    /// * `Option<f64>` - The change in the reserved amount, None if the order holds no reservation
+    pub fn resize(&mut self, order_id: OrderId, amount: f64) -> Option<f64> {
+        let reservation = self.reservations.get_mut(&order_id)?;
+        let delta = amount - reservation.amount;
+        reservation.amount = amount;
+        let (account, asset) = (reservation.account, reservation.asset.clone());
+        self.adjust_total(account, &asset, delta);
+        if amount <= 0.0 {
+            self.reservations.remove(&order_id);
+        }
+        Some(delta)
+    }
+
+    /// Consume part of an order's reservation for a fill
+    ///
+    /// At most the remaining reservation is consumed; the entry is removed once it is used up.
+    ///
+    /// # Returns
+    /// * `f64` - The amount actually consumed
+    pub fn consume(&mut self, order_id: OrderId, amount: f64) -> f64 {
+        let reservation = match self.reservations.get_mut(&order_id) {
+            Some(reservation) => reservation,
+            None => return 0.0,
+        };
+        let consumed = amount.min(reservation.amount);
+        reservation.amount -= consumed;
+        let (account, asset) = (reservation.account, reservation.asset.clone());
+        if reservation.amount <= 0.0 {
+            self.reservations.remove(&order_id);
+        }
+        self.adjust_total(account, &asset, -consumed);
+        consumed
+    }
+
+    /// Release whatever is left of an order's reservation, e.g. on cancel or expiry
+    ///
+    /// # Returns
+    /// * `f64` - The amount released, zero if nothing was held
+    pub fn release(&mut self, order_id: OrderId) -> f64 {
+        match self.reservations.remove(&order_id) {
+            Some(reservation) => {
+                self.adjust_total(reservation.account, &reservation.asset, -reservation.amount);
+                reservation.amount
+            }
+            None => 0.0,
+        }
+    }
+
+    pub fn get(&self, order_id: OrderId) -> Option<&Reservation> {
+        self.reservations.get(&order_id)
+    }
+
+    /// Total held for an account in one asset
+    pub fn reserved(&self, account: AccountId, asset: &str) -> f64 {
+        self.totals
+            .get(&(account, asset.to_string()))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    fn adjust_total(&mut self, account: AccountId, asset: &str, delta: f64) {
+        let key = (account, asset.to_string());
+        let total = self.totals.entry(key.clone()).or_insert(0.0);
+        *total += delta;
+        if total.abs() < 1e-9 {
+            self.totals.remove(&key);
+        }
+    }
+}
+
+/// Asset balances per account, with funds held back for open orders
+///
+/// An account's available balance is what it holds less what is reserved for its open orders.
+/// Orders are funded when they are accepted, and each fill settles in one step: the reservation
+/// backing it is consumed, the asset paid is debited and the asset bought is credited.
+#[derive(Debug, Default)]
+pub struct BalanceLedger {
+    balances: HashMap<(AccountId, String), f64>,
+    reservations: ReservationLedger,
+}
+
+impl BalanceLedger {
+    pub fn new() -> Self {
+        BalanceLedger::default()
+    }
+
+    /// Credit an account
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the amount is not positive
+    pub fn deposit(&mut self, account: AccountId, asset: &str, amount: f64) -> Result<(), String> {
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(format!("Deposit must be positive, got {}", amount));
+        }
+        *self
+            .balances
+            .entry((account, asset.to_string()))
+            .or_default() += amount;
+        Ok(())
+    }
+
+    /// Debit an account
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the amount is not positive or more than is available
+    pub fn withdraw(&mut self, account: AccountId, asset: &str, amount: f64) -> Result<(), String> {
+        let available = self.available(account, asset);
+        if !amount.is_finite() || amount <= 0.0 || amount > available {
+            return Err(format!(
+                "Cannot withdraw {} {} from account {}, {} is available",
+                amount, asset, account, available
+            ));
+        }
+        self.adjust(account, asset, -amount);
+        Ok(())
+    }
+
+    /// Whether an account has ever been credited, and so has its orders checked against its
+    /// balances
+    pub fn is_funded(&self, account: AccountId) -> bool {
+        self.balances.keys().any(|(owner, _)| *owner == account)
+    }
+
+    pub fn balance(&self, account: AccountId, asset: &str) -> f64 {
+        self.balances
+            .get(&(account, asset.to_string()))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Total held for an account's open orders in one asset
+    pub fn reserved(&self, account: AccountId, asset: &str) -> f64 {
+        self.reservations.reserved(account, asset)
+    }
+
+    /// Balance less reservations
+    pub fn available(&self, account: AccountId, asset: &str) -> f64 {
+        self.balance(account, asset) - self.reserved(account, asset)
+    }
+
+    /// Hold funds for an order
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the account does not have `amount` available or the order is already funded
+    pub fn reserve(
+        &mut self,
+        order_id: OrderId,
+        account: AccountId,
+        asset: &str,
+        amount: f64,
+    ) -> Result<(), String> {
+        self.check_available(account, asset, amount)?;
+        self.reservations.reserve(order_id, account, asset, amount)
+    }
+
+    /// Change the amount held for an order
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if an increase is more than the account has available or the order holds no
+    ///   reservation
+    pub fn resize(&mut self, order_id: OrderId, amount: f64) -> Result<(), String> {
+        let reservation = self
+            .reservations
+            .get(order_id)
+            .ok_or_else(|| format!("Order {} holds no reservation", order_id))?;
+        let (account, asset) = (reservation.account, reservation.asset.clone());
+        self.check_available(account, &asset, amount - reservation.amount)?;
+        self.reservations.resize(order_id, amount);
+        Ok(())
+    }
+
+    /// Release whatever is left of an order's reservation
+    ///
+    /// # Returns
+    /// * `f64` - The amount released, zero if nothing was held
+    pub fn release(&mut self, order_id: OrderId) -> f64 {
+        self.reservations.release(order_id)
+    }
+
+    pub fn reservation(&self, order_id: OrderId) -> Option<&Reservation> {
+        self.reservations.get(order_id)
+    }
+
+    /// Settle one side of a fill
+    ///
+    /// # Arguments
+    /// * `account` - The account that traded
+    /// * `reservation` - The order's reservation and the amount of it the fill uses, None for unfunded orders
+    /// * `paid` - The asset and amount the account gives up
+    /// * `received` - The asset and amount the account gets
+    pub fn settle(
+        &mut self,
+        account: AccountId,
+        reservation: Option<(OrderId, f64)>,
+        paid: (&str, f64),
+        received: (&str, f64),
+    ) {
+        if let Some((order_id, amount)) = reservation {
+            self.reservations.consume(order_id, amount);
+        }
+        self.adjust(account, paid.0, -paid.1);
+        self.adjust(account, received.0, received.1);
+    }
+
+    /// Check that an account has at least `amount` of an asset available
+    pub fn check_available(
+        &self,
+        account: AccountId,
+        asset: &str,
+        amount: f64,
+    ) -> Result<(), String> {
+        let available = self.available(account, asset);
+        match amount > available {
+            true => Err(format!(
+                "Insufficient {} balance for account {}: {} needed, {} available",
+                asset, account, amount, available
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn adjust(&mut self, account: AccountId, asset: &str, delta: f64) {
+        *self
+            .balances
+            .entry((account, asset.to_string()))
+            .or_default() += delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> AccountRegistry {
+        let mut registry = AccountRegistry::new();
+        registry
+            .add_account(1, None, AccountLimits::default())
+            .unwrap();
+        registry
+            .add_account(10, Some(1), AccountLimits::default())
+            .unwrap();
+        registry
+            .add_account(11, Some(1), AccountLimits::default())
+            .unwrap();
+        registry
+            .add_account(100, Some(10), AccountLimits::default())
+            .unwrap();
+        registry
+            .add_account(2, None, AccountLimits::default())
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn hierarchy_queries() {
+        let registry = registry();
+
+        assert_eq!(registry.lineage(100), vec![100, 10, 1]);
+        assert_eq!(registry.firm(100), Some(1));
+        assert_eq!(registry.firm(2), Some(2));
+        assert_eq!(registry.firm(3), None);
+
+        let mut descendants = registry.descendants(1);
+        descendants.sort();
+        assert_eq!(descendants, vec![1, 10, 11, 100]);
+        assert_eq!(registry.descendants(11), vec![11]);
+    }
+
+    #[test]
+    fn rejects_invalid_accounts() {
+        let mut registry = registry();
+
+        assert!(registry
+            .add_account(1, None, AccountLimits::default())
+            .is_err());
+        assert!(registry
+            .add_account(50, Some(7), AccountLimits::default())
+            .is_err());
+        assert!(registry.set_limits(7, AccountLimits::default()).is_err());
+    }
+
+    /// Small xorshift generator so the test is reproducible without extra dependencies
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+    }
+
+    #[test]
+    fn balances_hold_and_settle_order_funds() {
+        let mut balances = BalanceLedger::new();
+        assert!(!balances.is_funded(7));
+        balances.deposit(7, "USD", 1_000.0).unwrap();
+        assert!(balances.is_funded(7));
+
+        balances.reserve(1, 7, "USD", 600.0).unwrap();
+        assert!(balances.reserve(2, 7, "USD", 500.0).is_err());
+        assert!(balances.withdraw(7, "USD", 500.0).is_err());
+        assert!(balances.resize(1, 1_100.0).is_err());
+        balances.resize(1, 800.0).unwrap();
+        assert_eq!(balances.available(7, "USD"), 200.0);
+
+        // Buying 2 at 99 against a reservation of 2 × 100 frees the price improvement
+        balances.settle(7, Some((1, 200.0)), ("USD", 198.0), ("BTC", 2.0));
+        assert_eq!(balances.balance(7, "USD"), 802.0);
+        assert_eq!(balances.reserved(7, "USD"), 600.0);
+        assert_eq!(balances.balance(7, "BTC"), 2.0);
+        assert_eq!(balances.release(1), 600.0);
+        assert_eq!(balances.available(7, "USD"), 802.0);
+    }
+
+    #[test]
+    fn reservations_released_exactly_once() {
+        let mut ledger = ReservationLedger::new();
+        ledger.reserve(1, 7, "USD", 100.0).unwrap();
+        assert!(ledger.reserve(1, 7, "USD", 1.0).is_err());
+
+        assert_eq!(ledger.resize(1, 80.0), Some(-20.0));
+        assert_eq!(ledger.consume(1, 30.0), 30.0);
+        assert_eq!(ledger.reserved(7, "USD"), 50.0);
+        assert_eq!(ledger.release(1), 50.0);
+        // Late cancels, fills and amends find nothing left to release
+        assert_eq!(ledger.release(1), 0.0);
+        assert_eq!(ledger.consume(1, 10.0), 0.0);
+        assert_eq!(ledger.resize(1, 10.0), None);
+        assert_eq!(ledger.reserved(7, "USD"), 0.0);
+    }
+
+    #[test]
+    fn reservations_reconcile_under_concurrent_races() {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        const ORDERS: u64 = 64;
+        const ACCOUNTS: u64 = 4;
+
+        // Open reservation per order as the order lifecycle sees it, alongside the ledger
+        let state = Arc::new(Mutex::new((
+            ReservationLedger::new(),
+            HashMap::<OrderId, (AccountId, f64)>::new(),
+            0.0_f64, // reserved
+            0.0_f64, // consumed + released
+        )));
+
+        let workers = (0..8)
+            .map(|seed| {
+                let state = Arc::clone(&state);
+                thread::spawn(move || {
+                    let mut rng = Rng(0x9E37_79B9_7F4A_7C15 ^ (seed + 1));
+                    for _ in 0..5_000 {
+                        let order_id = rng.next(ORDERS);
+                        let amount = (rng.next(100) + 1) as f64;
+                        let mut guard = state.lock().unwrap();
+                        let (ledger, open, reserved, returned) = &mut *guard;
+                        match rng.next(4) {
+                            0 => {
+                                let account = order_id % ACCOUNTS;
+                                if ledger.reserve(order_id, account, "USD", amount).is_ok() {
+                                    assert!(open.insert(order_id, (account, amount)).is_none());
+                                    *reserved += amount;
+                                }
+                            }
+                            1 => match (ledger.resize(order_id, amount), open.get_mut(&order_id)) {
+                                (Some(delta), Some(held)) => {
+                                    assert_eq!(delta, amount - held.1);
+                                    held.1 = amount;
+                                    *reserved += delta.max(0.0);
+                                    *returned += (-delta).max(0.0);
+                                }
+                                (None, None) => {}
+                                other => panic!("amend disagreed with open orders: {:?}", other),
+                            },
+                            2 => {
+                                let consumed = ledger.consume(order_id, amount);
+                                if let Some(held) = open.get_mut(&order_id) {
+                                    assert_eq!(consumed, amount.min(held.1));
+                                    held.1 -= consumed;
+                                    if held.1 <= 0.0 {
+                                        open.remove(&order_id);
+                                    }
+                                } else {
+                                    assert_eq!(consumed, 0.0);
+                                }
+                                *returned += consumed;
+                            }
+                            _ => {
+                                let released = ledger.release(order_id);
+                                let held = open.remove(&order_id).map_or(0.0, |held| held.1);
+                                assert_eq!(released, held);
+                                *returned += released;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let (ledger, open, reserved, returned) = &*state.lock().unwrap();
+        let mut outstanding = 0.0;
+        for account in 0..ACCOUNTS {
+            let expected: f64 = open
+                .values()
+                .filter(|(owner, _)| *owner == account)
+                .map(|(_, amount)| amount)
+                .sum();
+            assert_eq!(ledger.reserved(account, "USD"), expected);
+            outstanding += expected;
+        }
+        for (order_id, (_, amount)) in open {
+            assert_eq!(ledger.get(*order_id).unwrap().amount, *amount);
+        }
+        assert_eq!(reserved - returned, outstanding);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::matching::numeric::exact_sum;
+use crate::matching::orderbook::AccountId;
+use std::cmp::Ordering;
+
+/// Number of steps in the auto-deleveraging indicator shown to accounts
+pub const ADL_INDICATOR_LEVELS: u8 = 5;
+
+/// An account's open position in one market
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub account: AccountId,
+    /// Signed size in the base currency: positive long, negative short
+    pub size: f64,
+    pub entry_price: f64,
+    /// Margin held against the position, in the quote currency
+    pub margin: f64,
+}
+
+impl Position {
+    /// Profit at `mark` as a fraction of the margin
+    fn profit_ratio(&self, mark: f64) -> f64 {
+        let pnl = (mark - self.entry_price) * self.size;
+        match self.margin > 0.0 {
+            true => pnl / self.margin,
+            false => 0.0,
+        }
+    }
+
+    /// Position value over equity at `mark`
+    fn effective_leverage(&self, mark: f64) -> f64 {
+        let equity = self.margin + (mark - self.entry_price) * self.size;
+        match equity > 0.0 {
+            true => (self.size * mark).abs() / equity,
+            false => f64::INFINITY,
+        }
+    }
+
+    /// Auto-deleveraging priority at `mark`: profitable, highly leveraged positions go first
+    pub fn adl_score(&self, mark: f64) -> f64 {
+        let profit = self.profit_ratio(mark);
+        let leverage = self.effective_leverage(mark);
+        match profit >= 0.0 {
+            true => profit * leverage,
+            false => profit / leverage,
+        }
+    }
+}
+
+/// An account's place in the auto-deleveraging queue for its side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdlRank {
+    pub account: AccountId,
+    pub score: f64,
+    /// 1 (last in line) to [`ADL_INDICATOR_LEVELS`] (first in line)
+    pub indicator: u8,
+}
+
+/// A forced close of an opposing position against a bankrupt one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdlFill {
+    pub account: AccountId,
+    /// Size closed, always positive
+    pub size: f64,
+    /// The bankrupt position's bankruptcy price
+    pub price: f64,
+}
+
+/// Rank the long or the short positions for auto-deleveraging, first in line first
+///
+/// # Arguments
+/// * `positions` - Open positions in one market
+/// * `long` - true to rank the longs, false the shorts
+/// * `mark` - The market's mark price
+pub fn adl_queue(positions: &[Position], long: bool, mark: f64) -> Vec<AdlRank> {
+    let mut side: Vec<(AccountId, f64)> = positions
+        .iter()
+        .filter(|position| position.size != 0.0 && (position.size > 0.0) == long)
+        .map(|position| (position.account, position.adl_score(mark)))
+        .collect();
+    side.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+    let count = side.len();
+    side.into_iter()
+        .enumerate()
+        .map(|(index, (account, score))| AdlRank {
+            account,
+            score,
+            indicator: ADL_INDICATOR_LEVELS - (index * ADL_INDICATOR_LEVELS as usize / count) as u8,
+        })
+        .collect()
+}
+
+/// An account's auto-deleveraging indicator in one market
+///
+/// # Returns
+/// * `Option<u8>` - 1 (last in line) to [`ADL_INDICATOR_LEVELS`] (first in line), None if the account has no
+///   open position
+pub fn adl_indicator(positions: &[Position], account: AccountId, mark: f64) -> Option<u8> {
+    let position = positions
+        .iter()
+        .find(|position| position.account == account && position.size != 0.0)?;
+    adl_queue(positions, position.size > 0.0, mark)
+        .into_iter()
+        .find(|rank| rank.account == account)
+        .map(|rank| rank.indicator)
+}
+
+/// Close a bankrupt position the insurance fund cannot cover against opposing positions
+///
+/// Meant for liquidations whose shortfall [`crate::insurance::InsuranceFund::settle_liquidation`]
+/// reports as uncovered.
+///
+/// Opposing positions are closed in queue order at the bankruptcy price until the bankrupt size is
+/// used up, and the given positions are reduced accordingly.
+///
+/// # Arguments
+/// * `positions` - Open positions in the market, the bankrupt one included
+/// * `bankrupt` - The account being liquidated
+/// * `bankruptcy_price` - Price at which the bankrupt account's margin is exhausted
+/// * `mark` - The market's mark price, used for ranking
+///
+/// # Returns
+/// * `Result<Vec<AdlFill>, String>` - The forced closes, Err if the account has no position or there is not enough
+///   opposing size to close it
+pub fn auto_deleverage(
+    positions: &mut [Position],
+    bankrupt: AccountId,
+    bankruptcy_price: f64,
+    mark: f64,
+) -> Result<Vec<AdlFill>, String> {
+    let size = positions
+        .iter()
+        .find(|position| position.account == bankrupt && position.size != 0.0)
+        .map(|position| position.size)
+        .ok_or_else(|| format!("Account {} has no position to deleverage", bankrupt))?;
+    let queue = adl_queue(positions, size < 0.0, mark);
+    let opposing = exact_sum(
+        queue
+            .iter()
+            .filter_map(|rank| positions.iter().find(|p| p.account == rank.account))
+            .map(|position| position.size.abs()),
+    );
+    if opposing < size.abs() {
+        return Err(format!(
+            "Opposing positions of {} cannot absorb {}",
+            opposing,
+            size.abs()
+        ));
+    }
+
+    let mut remaining = size.abs();
+    let mut fills = Vec::new();
+    for rank in queue {
+        if remaining <= 0.0 {
+            break;
+        }
+        let position = match positions.iter_mut().find(|p| p.account == rank.account) {
+            Some(position) => position,
+            None => continue,
+        };
+        let closed = remaining.min(position.size.abs());
+        remaining -= closed;
+        position.size -= closed * position.size.signum();
+        fills.push(AdlFill {
+            account: rank.account,
+            size: closed,
+            price: bankruptcy_price,
+        });
+    }
+    if let Some(position) = positions.iter_mut().find(|p| p.account == bankrupt) {
+        position.size = 0.0;
+    }
+    Ok(fills)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(account: AccountId, size: f64, entry_price: f64, margin: f64) -> Position {
+        Position {
+            account,
+            size,
+            entry_price,
+            margin,
+        }
+    }
+
+    #[test]
+    fn closes_the_most_profitable_leveraged_positions_first() {
+        let mut positions = [
+            position(1, 2.0, 100.0, 10.0),
+            // Shorts from 120: account 2 is more leveraged than account 3, account 4 is losing
+            position(2, -1.0, 120.0, 10.0),
+            position(3, -2.0, 120.0, 100.0),
+            position(4, -1.0, 80.0, 50.0),
+        ];
+        let queue = adl_queue(&positions, false, 90.0);
+        let order: Vec<AccountId> = queue.iter().map(|rank| rank.account).collect();
+        assert_eq!(order, vec![2, 3, 4]);
+        assert_eq!(queue[0].indicator, ADL_INDICATOR_LEVELS);
+        assert!(queue[2].indicator < queue[1].indicator);
+        assert_eq!(adl_indicator(&positions, 4, 90.0), Some(queue[2].indicator));
+        assert_eq!(
+            adl_indicator(&positions, 1, 90.0),
+            Some(ADL_INDICATOR_LEVELS)
+        );
+        assert_eq!(adl_indicator(&positions, 5, 90.0), None);
+
+        let fills = auto_deleverage(&mut positions, 1, 95.0, 90.0).unwrap();
+        assert_eq!(
+            fills,
+            vec![
+                AdlFill {
+                    account: 2,
+                    size: 1.0,
+                    price: 95.0
+                },
+                AdlFill {
+                    account: 3,
+                    size: 1.0,
+                    price: 95.0
+                },
+            ]
+        );
+        assert_eq!(positions[0].size, 0.0);
+        assert_eq!(positions[1].size, 0.0);
+        assert_eq!(positions[2].size, -1.0);
+
+        assert!(auto_deleverage(&mut positions, 1, 95.0, 90.0).is_err());
+        positions[0].size = 5.0;
+        assert!(auto_deleverage(&mut positions, 1, 95.0, 90.0).is_err());
+    }
+}
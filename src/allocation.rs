@@ -0,0 +1,205 @@
+use crate::accounts::AccountRegistry;
+use crate::matching::clock::Timestamp;
+use crate::matching::numeric::exact_sum;
+use crate::matching::orderbook::{AccountId, Price, TradingPair};
+use crate::matching::trade::{Trade, TradeId};
+use std::collections::HashSet;
+
+/// One leg of a post-trade allocation, kept as an audit record
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllocationRecord {
+    pub pair: TradingPair,
+    pub trade_id: TradeId,
+    /// The account that executed the fill
+    pub from: AccountId,
+    /// The account the fill was given up to
+    pub to: AccountId,
+    pub price: Price,
+    pub size: f64,
+    pub allocated_at: Timestamp,
+}
+
+/// Post-trade allocation of fills across accounts
+///
+/// An account that took part in a trade can split its side of the fill across other registered
+/// accounts, typically its own sub-accounts or a give-up firm. Each side of a trade can only be
+/// allocated once, and every leg is kept in an append-only audit trail.
+#[derive(Debug, Default)]
+pub struct Allocations {
+    allocated: HashSet<(TradingPair, TradeId, AccountId)>,
+    audit: Vec<AllocationRecord>,
+}
+
+impl Allocations {
+    pub fn new() -> Self {
+        Allocations::default()
+    }
+
+    /// Allocate `account`'s side of a trade
+    ///
+    /// # Arguments
+    /// * `accounts` - Registry used to validate the receiving accounts
+    /// * `pair` - The market the trade happened on
+    /// * `trade` - The trade being allocated
+    /// * `account` - The maker or taker account of the trade
+    /// * `splits` - Receiving accounts and sizes, which must add up to the trade size
+    /// * `now` - Time of the allocation
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err, with nothing recorded, if the allocation is invalid
+    pub fn allocate(
+        &mut self,
+        accounts: &AccountRegistry,
+        pair: &TradingPair,
+        trade: &Trade,
+        account: AccountId,
+        splits: &[(AccountId, f64)],
+        now: Timestamp,
+    ) -> Result<(), String> {
+        if trade.maker_account != Some(account) && trade.taker_account != Some(account) {
+            return Err(format!(
+                "Account {} is not a party to trade {}",
+                account, trade.id
+            ));
+        }
+        let key = (pair.clone(), trade.id, account);
+        if self.allocated.contains(&key) {
+            return Err(format!(
+                "Trade {} has already been allocated by account {}",
+                trade.id, account
+            ));
+        }
+        if splits.is_empty() {
+            return Err("An allocation needs at least one account".to_string());
+        }
+        for (to, size) in splits {
+            if accounts.get(*to).is_none() {
+                return Err(format!("Account {} does not exist", to));
+            }
+            if *size <= 0.0 {
+                return Err(format!("Allocation size {} must be positive", size));
+            }
+        }
+        let total = exact_sum(splits.iter().map(|(_, size)| *size));
+        if (total - trade.size).abs() > f64::EPSILON * trade.size.max(1.0) {
+            return Err(format!(
+                "Allocations total {} but trade {} is for {}",
+                total, trade.id, trade.size
+            ));
+        }
+
+        self.allocated.insert(key);
+        self.audit
+            .extend(splits.iter().map(|(to, size)| AllocationRecord {
+                pair: pair.clone(),
+                trade_id: trade.id,
+                from: account,
+                to: *to,
+                price: trade.price,
+                size: *size,
+                allocated_at: now,
+            }));
+        Ok(())
+    }
+
+    /// Every allocation leg, in the order they were made
+    pub fn audit_trail(&self) -> &[AllocationRecord] {
+        &self.audit
+    }
+
+    /// Total size given up to an account
+    pub fn allocated_to(&self, account: AccountId) -> f64 {
+        exact_sum(
+            self.audit
+                .iter()
+                .filter(|record| record.to == account)
+                .map(|record| record.size),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::AccountLimits;
+    use crate::matching::orderbook::OrderType;
+
+    fn trade() -> Trade {
+        Trade {
+            id: 7,
+            price: Price::new(100.0),
+            size: 10.0,
+            aggressor_side: OrderType::Bid,
+            maker_order_id: Some(1),
+            taker_order_id: Some(2),
+            maker_account: Some(1),
+            taker_account: Some(2),
+            maker_tag: None,
+            taker_tag: None,
+            maker_version: 1,
+            taker_version: 1,
+            transaction_time: 0,
+            triggered_stops: false,
+            triggered_band: false,
+            dark: false,
+        }
+    }
+
+    fn accounts() -> AccountRegistry {
+        let mut accounts = AccountRegistry::new();
+        for id in [1, 2] {
+            accounts
+                .add_account(id, None, AccountLimits::default())
+                .unwrap();
+        }
+        for id in [20, 21] {
+            accounts
+                .add_account(id, Some(2), AccountLimits::default())
+                .unwrap();
+        }
+        accounts
+    }
+
+    #[test]
+    fn splits_a_fill_across_sub_accounts() {
+        let accounts = accounts();
+        let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut allocations = Allocations::new();
+
+        allocations
+            .allocate(&accounts, &pair, &trade(), 2, &[(20, 6.0), (21, 4.0)], 5)
+            .unwrap();
+
+        assert_eq!(allocations.audit_trail().len(), 2);
+        assert_eq!(allocations.allocated_to(20), 6.0);
+        assert_eq!(allocations.audit_trail()[1].to, 21);
+        assert_eq!(allocations.audit_trail()[1].allocated_at, 5);
+
+        let again = allocations.allocate(&accounts, &pair, &trade(), 2, &[(20, 10.0)], 6);
+        assert!(again.is_err());
+        // The maker side is allocated independently
+        assert!(allocations
+            .allocate(&accounts, &pair, &trade(), 1, &[(1, 10.0)], 6)
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_allocations() {
+        let accounts = accounts();
+        let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut allocations = Allocations::new();
+
+        let cases: [(AccountId, &[(AccountId, f64)]); 4] = [
+            (3, &[(20, 10.0)]),
+            (2, &[(20, 6.0), (21, 3.0)]),
+            (2, &[(99, 10.0)]),
+            (2, &[]),
+        ];
+        for (account, splits) in cases {
+            assert!(allocations
+                .allocate(&accounts, &pair, &trade(), account, splits, 0)
+                .is_err());
+        }
+        assert!(allocations.audit_trail().is_empty());
+    }
+}
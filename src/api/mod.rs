@@ -0,0 +1,7 @@
+/// Version 1 of the public API
+///
+/// These types only change in ways that keep existing code compiling: new variants and fields
+/// are added behind `#[non_exhaustive]`, and anything removed is marked `#[deprecated]` for at
+/// least one minor release first. Breaking changes go into a new version module alongside this
+/// one. The engine's own types in [`crate::matching`] carry no such promise.
+pub mod v1;
@@ -0,0 +1,342 @@
+use crate::matching::depth::{Depth, DepthLevel};
+use crate::matching::engine::Engine;
+use crate::matching::event::{CancelReason, OrderEvent};
+use crate::matching::orderbook::{Order, OrderType, TradingPair};
+use crate::matching::trade::Trade as EngineTrade;
+use crate::positions::NetPosition;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl From<OrderType> for Side {
+    fn from(side: OrderType) -> Self {
+        match side {
+            OrderType::Bid => Side::Buy,
+            OrderType::Ask => Side::Sell,
+        }
+    }
+}
+
+impl From<Side> for OrderType {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => OrderType::Bid,
+            Side::Sell => OrderType::Ask,
+        }
+    }
+}
+
+/// A new order as submitted by a client
+///
+/// # Example
+/// ```
+/// use orderbook::api::v1::{OrderRequest, Side};
+/// let request = OrderRequest::limit("BTC/USD", Side::Buy, 1.5, 100.0).with_account(7);
+/// assert_eq!(request.price, Some(100.0));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct OrderRequest {
+    /// The market, e.g. "BTC/USD"
+    pub market: String,
+    pub side: Side,
+    pub size: f64,
+    /// Limit price, None for a market order
+    pub price: Option<f64>,
+    pub account: Option<u64>,
+    /// Echoed back on the order's trades
+    pub tag: Option<String>,
+}
+
+impl OrderRequest {
+    pub fn limit(market: &str, side: Side, size: f64, price: f64) -> Self {
+        OrderRequest {
+            price: Some(price),
+            ..OrderRequest::market(market, side, size)
+        }
+    }
+
+    pub fn market(market: &str, side: Side, size: f64) -> Self {
+        OrderRequest {
+            market: market.to_string(),
+            side,
+            size,
+            price: None,
+            account: None,
+            tag: None,
+        }
+    }
+
+    pub fn with_account(mut self, account: u64) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// The engine order this request describes, with its market
+    ///
+    /// # Returns
+    /// * `Result<(TradingPair, Order), String>` - Err if the market is not a valid pair
+    pub fn to_order(&self) -> Result<(TradingPair, Order), String> {
+        let pair = self.market.parse::<TradingPair>()?;
+        let mut order = Order::new(self.side.into(), self.size);
+        if let Some(account) = self.account {
+            order = order.with_account(account);
+        }
+        if let Some(tag) = &self.tag {
+            order = order.with_tag(tag.clone());
+        }
+        Ok((pair, order))
+    }
+}
+
+/// A trade as reported to clients
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Trade {
+    pub id: u64,
+    pub price: f64,
+    pub size: f64,
+    /// The side of the order that took liquidity
+    pub aggressor: Side,
+    pub maker_order_id: Option<u64>,
+    pub taker_order_id: Option<u64>,
+    /// Nanoseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl From<&EngineTrade> for Trade {
+    fn from(trade: &EngineTrade) -> Self {
+        Trade {
+            id: trade.id,
+            price: trade.price.into(),
+            size: trade.size,
+            aggressor: trade.aggressor_side.into(),
+            maker_order_id: trade.maker_order_id,
+            taker_order_id: trade.taker_order_id,
+            timestamp: trade.transaction_time,
+        }
+    }
+}
+
+/// One aggregated price level of a book
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct BookLevel {
+    pub price: f64,
+    pub size: f64,
+    pub orders: usize,
+}
+
+impl From<&DepthLevel> for BookLevel {
+    fn from(level: &DepthLevel) -> Self {
+        BookLevel {
+            price: level.price.into(),
+            size: level.volume,
+            orders: level.order_count,
+        }
+    }
+}
+
+/// A book's price levels, best price first on each side
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Book {
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+impl From<&Depth> for Book {
+    fn from(depth: &Depth) -> Self {
+        Book {
+            bids: depth.bids.iter().map(BookLevel::from).collect(),
+            asks: depth.asks.iter().map(BookLevel::from).collect(),
+        }
+    }
+}
+
+/// Why an order left the book without filling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CancelCause {
+    Requested,
+    Expired,
+    /// The market halted, ended its trading day, cancelled a linked order or pulled quotes
+    Market,
+    /// An immediate-or-cancel or fill-or-kill order could not fill
+    Unfilled,
+    /// A pre-trade risk check turned a stop order away as it triggered
+    Rejected,
+    /// The account could no longer fund a pegged order at its new price
+    Unfunded,
+}
+
+/// A change to an open order outside of a match
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum OrderUpdate {
+    Cancelled {
+        order_id: u64,
+        remaining: f64,
+        cause: CancelCause,
+    },
+    /// The order's price or size changed, with or without losing queue priority
+    Modified {
+        order_id: u64,
+        price: f64,
+        size: f64,
+    },
+}
+
+impl OrderUpdate {
+    /// The client-facing form of an engine event, None for events clients are not sent
+    pub fn from_event(event: &OrderEvent) -> Option<Self> {
+        match *event {
+            OrderEvent::Expired {
+                order_id,
+                remaining,
+                ..
+            } => Some(OrderUpdate::Cancelled {
+                order_id,
+                remaining,
+                cause: CancelCause::Expired,
+            }),
+            OrderEvent::Cancelled {
+                order_id,
+                remaining,
+                reason,
+                ..
+            } => Some(OrderUpdate::Cancelled {
+                order_id,
+                remaining,
+                cause: match reason {
+                    CancelReason::Requested => CancelCause::Requested,
+                    CancelReason::Unfilled => CancelCause::Unfilled,
+                    CancelReason::Rejected => CancelCause::Rejected,
+                    CancelReason::Unfunded => CancelCause::Unfunded,
+                    CancelReason::Halt
+                    | CancelReason::EndOfDay
+                    | CancelReason::Linked
+                    | CancelReason::Pulled => CancelCause::Market,
+                },
+            }),
+            OrderEvent::Amended {
+                order_id,
+                price,
+                size,
+                ..
+            }
+            | OrderEvent::Replaced {
+                order_id,
+                price,
+                size,
+                ..
+            } => Some(OrderUpdate::Modified {
+                order_id,
+                price,
+                size,
+            }),
+            OrderEvent::SuspectedDuplicate { .. } | OrderEvent::QueueAhead { .. } => None,
+        }
+    }
+}
+
+/// Submit an order to the engine: a limit order if it has a price, a market order otherwise
+///
+/// # Returns
+/// * `Result<Vec<Trade>, String>` - The order's fills on arrival, Err if the engine rejects it
+pub fn submit(engine: &mut Engine, request: &OrderRequest) -> Result<Vec<Trade>, String> {
+    let (pair, order) = request.to_order()?;
+    let trades = match request.price {
+        Some(price) => engine.place_limit_order(pair, price, order)?.trades,
+        None => engine.place_market_order(&pair, order)?.trades,
+    };
+    Ok(trades.iter().map(Trade::from).collect())
+}
+
+/// The best `levels` levels of a market's book
+///
+/// # Returns
+/// * `Result<Book, String>` - Err if the market is not a valid pair or does not exist
+pub fn book(engine: &Engine, market: &str, levels: usize) -> Result<Book, String> {
+    let pair = market.parse::<TradingPair>()?;
+    engine.depth(&pair, levels).map(|depth| Book::from(&depth))
+}
+
+/// An account's net position in one market
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Position {
+    /// Positive long, negative short
+    pub size: f64,
+    pub entry_price: f64,
+    pub realized_pnl: f64,
+}
+
+impl From<&NetPosition> for Position {
+    fn from(position: &NetPosition) -> Self {
+        Position {
+            size: position.size(),
+            entry_price: position.entry_price,
+            realized_pnl: position.realized_pnl,
+        }
+    }
+}
+
+/// An account's position in a market, flat if it has not traded there
+///
+/// # Returns
+/// * `Result<Position, String>` - Err if the market is not a valid pair
+pub fn position(engine: &Engine, account: u64, market: &str) -> Result<Position, String> {
+    let pair = market.parse::<TradingPair>()?;
+    Ok(engine
+        .position(account, &pair)
+        .map(Position::from)
+        .unwrap_or_else(|| Position::from(&NetPosition::default())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::OrderBook;
+
+    #[test]
+    fn trades_through_the_engine_in_v1_types() {
+        let mut engine = Engine::new();
+        engine.add_orderbook("BTC/USD".parse().unwrap(), OrderBook::new());
+
+        let ask = OrderRequest::limit("btc-usd", Side::Sell, 2.0, 101.0).with_tag("mm");
+        assert!(submit(&mut engine, &ask).unwrap().is_empty());
+        let book = book(&engine, "BTC/USD", 5).unwrap();
+        assert_eq!(book.asks[0].size, 2.0);
+        assert_eq!(book.asks[0].orders, 1);
+
+        let buy = OrderRequest::market("BTC/USD", Side::Buy, 0.5).with_account(3);
+        let trades = submit(&mut engine, &buy).unwrap();
+        assert_eq!(trades[0].price, 101.0);
+        assert_eq!(trades[0].aggressor, Side::Buy);
+        assert!(submit(&mut engine, &OrderRequest::market("BTC", Side::Buy, 1.0)).is_err());
+
+        assert_eq!(position(&engine, 3, "BTC/USD").unwrap().size, 0.5);
+        assert_eq!(position(&engine, 9, "BTC/USD").unwrap().size, 0.0);
+
+        let id = trades[0].maker_order_id.unwrap();
+        let pair: TradingPair = "BTC/USD".parse().unwrap();
+        let event = engine.cancel_order(&pair, id).unwrap();
+        assert_eq!(
+            OrderUpdate::from_event(&event),
+            Some(OrderUpdate::Cancelled {
+                order_id: id,
+                remaining: 1.5,
+                cause: CancelCause::Requested,
+            })
+        );
+    }
+}
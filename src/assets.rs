@@ -0,0 +1,108 @@
+use crate::matching::orderbook::TradingPair;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    Crypto,
+    Fiat,
+    /// Derived or index assets that are not directly held
+    Synthetic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asset {
+    pub symbol: String,
+    /// Decimal places the asset is accounted in
+    pub precision: u32,
+    pub kind: AssetKind,
+    /// Chain or network the asset lives on, e.g. "ethereum", None for fiat and synthetics
+    pub network: Option<String>,
+}
+
+/// Assets that markets and balances may refer to, keyed by symbol
+#[derive(Debug, Default)]
+pub struct AssetRegistry {
+    assets: BTreeMap<String, Asset>,
+}
+
+impl AssetRegistry {
+    pub fn new() -> Self {
+        AssetRegistry::default()
+    }
+
+    /// Register an asset
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if an asset with the same symbol already exists
+    pub fn register(&mut self, asset: Asset) -> Result<(), String> {
+        if self.assets.contains_key(&asset.symbol) {
+            return Err(format!("Asset {} already exists", asset.symbol));
+        }
+        self.assets.insert(asset.symbol.clone(), asset);
+        Ok(())
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Asset> {
+        self.assets.get(symbol)
+    }
+
+    /// Every registered asset, sorted by symbol
+    pub fn assets(&self) -> impl Iterator<Item = &Asset> {
+        self.assets.values()
+    }
+
+    /// The registered assets of one kind, sorted by symbol
+    pub fn of_kind(&self, kind: AssetKind) -> impl Iterator<Item = &Asset> {
+        self.assets.values().filter(move |asset| asset.kind == kind)
+    }
+
+    /// Check that both assets of a trading pair are registered
+    pub fn validate_pair(&self, trading_pair: &TradingPair) -> Result<(), String> {
+        for symbol in [trading_pair.base(), trading_pair.quote()] {
+            if !self.assets.contains_key(symbol) {
+                return Err(format!("Asset {} is not registered", symbol));
+            }
+        }
+        if trading_pair.base() == trading_pair.quote() {
+            return Err(format!(
+                "Market {} trades an asset against itself",
+                String::from(trading_pair.clone())
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(symbol: &str, kind: AssetKind) -> Asset {
+        Asset {
+            symbol: symbol.to_string(),
+            precision: 8,
+            kind,
+            network: None,
+        }
+    }
+
+    #[test]
+    fn register_and_validate() {
+        let mut assets = AssetRegistry::new();
+        assets.register(asset("BTC", AssetKind::Crypto)).unwrap();
+        assets.register(asset("USD", AssetKind::Fiat)).unwrap();
+        assets.register(asset("ETH", AssetKind::Crypto)).unwrap();
+        assert!(assets.register(asset("BTC", AssetKind::Crypto)).is_err());
+
+        let crypto: Vec<&str> = assets
+            .of_kind(AssetKind::Crypto)
+            .map(|asset| asset.symbol.as_str())
+            .collect();
+        assert_eq!(crypto, vec!["BTC", "ETH"]);
+
+        let pair = |base: &str, quote: &str| TradingPair::new(base.to_string(), quote.to_string());
+        assert!(assets.validate_pair(&pair("BTC", "USD")).is_ok());
+        assert!(assets.validate_pair(&pair("SOL", "USD")).is_err());
+        assert!(assets.validate_pair(&pair("USD", "USD")).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::matching::depth::{Depth, DepthLevel, DepthUpdate, TopOfBook, CHECKSUM_LEVELS};
+use crate::matching::orderbook::{OrderType, Price};
+use std::collections::BTreeMap;
+
+/// A message on a market data stream that carries a book's price levels
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookMessage {
+    /// Every level of the book as of `sequence`
+    Snapshot {
+        sequence: u64,
+        depth: Depth,
+        checksum: u32,
+    },
+    /// The level changes from `sequence - 1` to `sequence`
+    Delta {
+        sequence: u64,
+        updates: Vec<DepthUpdate>,
+        checksum: u32,
+    },
+}
+
+impl BookMessage {
+    /// A snapshot message for a book's full depth
+    pub fn snapshot(sequence: u64, depth: Depth) -> Self {
+        BookMessage::Snapshot {
+            sequence,
+            checksum: depth.checksum(),
+            depth,
+        }
+    }
+
+    /// A delta message taking a book from `previous` to `current`
+    pub fn delta(sequence: u64, previous: &Depth, current: &Depth) -> Self {
+        BookMessage::Delta {
+            sequence,
+            updates: current.diff(previous),
+            checksum: current.checksum(),
+        }
+    }
+
+    pub fn sequence(&self) -> u64 {
+        match self {
+            BookMessage::Snapshot { sequence, .. } | BookMessage::Delta { sequence, .. } => {
+                *sequence
+            }
+        }
+    }
+}
+
+/// A client's copy of a server-side book, built from a snapshot and kept up to date by deltas
+///
+/// Every message is checked before the copy is trusted: deltas must follow each other without a
+/// gap and the copy's checksum must match the server's after each one. A failed check leaves the
+/// book out of sync, ignoring deltas until the next snapshot arrives.
+#[derive(Debug, Default)]
+pub struct LocalBook {
+    /// None until a snapshot is applied, and again after a failed check
+    sequence: Option<u64>,
+    bids: BTreeMap<Price, DepthLevel>,
+    asks: BTreeMap<Price, DepthLevel>,
+}
+
+impl LocalBook {
+    pub fn new() -> Self {
+        LocalBook::default()
+    }
+
+    /// Apply the next message from the stream
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the book is waiting for a snapshot, a delta skips or repeats a
+    ///   sequence number or the checksums disagree; the book then needs a new snapshot
+    pub fn apply(&mut self, message: &BookMessage) -> Result<(), String> {
+        match message {
+            BookMessage::Snapshot {
+                sequence,
+                depth,
+                checksum,
+            } => {
+                self.bids = depth
+                    .bids
+                    .iter()
+                    .map(|level| (level.price, *level))
+                    .collect();
+                self.asks = depth
+                    .asks
+                    .iter()
+                    .map(|level| (level.price, *level))
+                    .collect();
+                self.verify(*sequence, *checksum)
+            }
+            BookMessage::Delta {
+                sequence,
+                updates,
+                checksum,
+            } => {
+                let expected = match self.sequence {
+                    Some(current) => current + 1,
+                    None => return Err("Local book needs a snapshot".to_string()),
+                };
+                if *sequence != expected {
+                    self.sequence = None;
+                    return Err(format!(
+                        "Sequence gap: expected {}, got {}",
+                        expected, sequence
+                    ));
+                }
+                for update in updates {
+                    let side = match update.side {
+                        OrderType::Bid => &mut self.bids,
+                        OrderType::Ask => &mut self.asks,
+                    };
+                    match update.level.volume > 0.0 {
+                        true => side.insert(update.level.price, update.level),
+                        false => side.remove(&update.level.price),
+                    };
+                }
+                self.verify(*sequence, *checksum)
+            }
+        }
+    }
+
+    fn verify(&mut self, sequence: u64, checksum: u32) -> Result<(), String> {
+        if self.depth(CHECKSUM_LEVELS).checksum() != checksum {
+            self.sequence = None;
+            return Err(format!("Checksum mismatch at sequence {}", sequence));
+        }
+        self.sequence = Some(sequence);
+        Ok(())
+    }
+
+    /// Whether the book has been verified against the latest message applied
+    pub fn is_synced(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    /// Sequence number of the last message applied, None while out of sync
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    /// The highest bid price, None if there are no bids
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids.keys().next_back().map(|price| (*price).into())
+    }
+
+    /// The lowest ask price, None if there are no asks
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks.keys().next().map(|price| (*price).into())
+    }
+
+    /// Best ask minus best bid, None unless both sides have orders
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()? - self.best_bid()?)
+    }
+
+    /// Halfway between the best bid and the best ask, None unless both sides have orders
+    pub fn mid_price(&self) -> Option<f64> {
+        Some((self.best_ask()? + self.best_bid()?) / 2.0)
+    }
+
+    /// Best bid and ask with their sizes, the spread and the midprice
+    pub fn top_of_book(&self) -> TopOfBook {
+        TopOfBook {
+            bid: self.bids.values().next_back().copied(),
+            ask: self.asks.values().next().copied(),
+            spread: self.spread(),
+            mid: self.mid_price(),
+        }
+    }
+
+    /// The best `levels` price levels on each side
+    pub fn depth(&self, levels: usize) -> Depth {
+        Depth {
+            bids: self.bids.values().rev().take(levels).copied().collect(),
+            asks: self.asks.values().take(levels).copied().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::clock::ManualClock;
+    use crate::matching::orderbook::{Order, OrderBook};
+    use std::sync::Arc;
+
+    #[test]
+    fn follows_a_server_book_through_snapshot_and_deltas() {
+        let mut server = OrderBook::with_clock(Arc::new(ManualClock::new(0)));
+        server.add(Order::new(OrderType::Bid, 1.0), 99.0);
+        server.add(Order::new(OrderType::Ask, 2.0), 101.0);
+        let mut previous = server.depth(usize::MAX);
+
+        let mut local = LocalBook::new();
+        let early = BookMessage::delta(1, &previous, &previous);
+        assert!(local.apply(&early).is_err());
+        local
+            .apply(&BookMessage::snapshot(1, previous.clone()))
+            .unwrap();
+
+        server.add(Order::new(OrderType::Bid, 3.0), 100.0);
+        server.place_market_order(&mut Order::new(OrderType::Bid, 2.0));
+        let current = server.depth(usize::MAX);
+        local
+            .apply(&BookMessage::delta(2, &previous, &current))
+            .unwrap();
+        assert_eq!(local.sequence(), Some(2));
+        assert_eq!(local.best_bid(), Some(100.0));
+        assert_eq!(local.best_ask(), None);
+        assert_eq!(local.depth(10), server.depth(10));
+        assert_eq!(local.top_of_book(), server.top_of_book());
+        previous = current;
+
+        // A skipped delta knocks the book out of sync until the next snapshot
+        server.add(Order::new(OrderType::Ask, 1.0), 102.0);
+        let current = server.depth(usize::MAX);
+        let gap = local.apply(&BookMessage::delta(4, &previous, &current));
+        assert_eq!(gap.unwrap_err(), "Sequence gap: expected 3, got 4");
+        assert!(!local.is_synced());
+        let next = BookMessage::delta(5, &current, &current);
+        assert!(local.apply(&next).is_err());
+        local
+            .apply(&BookMessage::snapshot(5, current.clone()))
+            .unwrap();
+        assert_eq!(local.best_ask(), Some(102.0));
+
+        // So does a delta that leaves the copy different from the server
+        let corrupt = BookMessage::Delta {
+            sequence: 6,
+            updates: Vec::new(),
+            checksum: current.checksum() ^ 1,
+        };
+        assert_eq!(
+            local.apply(&corrupt).unwrap_err(),
+            "Checksum mismatch at sequence 6"
+        );
+        assert_eq!(local.sequence(), None);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::matching::numeric::exact_sum;
+use crate::matching::orderbook::AccountId;
+use std::collections::{BTreeMap, HashMap};
+
+/// Margin collateral posted by accounts, in any number of assets
+///
+/// Each asset is valued at its latest mark in the valuation asset, less the asset's haircut.
+/// Assets without a mark count for nothing until one arrives. Account values are recalculated
+/// whenever a mark or a posting changes, so they are always current when orders are checked.
+#[derive(Debug)]
+pub struct CollateralLedger {
+    valuation_asset: String,
+    /// Percentage knocked off each asset's market value
+    haircuts: HashMap<String, f64>,
+    /// Price of one unit of each asset in the valuation asset
+    marks: HashMap<String, f64>,
+    posted: HashMap<AccountId, BTreeMap<String, f64>>,
+    values: HashMap<AccountId, f64>,
+}
+
+impl CollateralLedger {
+    /// An empty ledger valuing collateral in `valuation_asset`, e.g. "USD"
+    pub fn new(valuation_asset: &str) -> Self {
+        CollateralLedger {
+            valuation_asset: valuation_asset.to_string(),
+            haircuts: HashMap::new(),
+            marks: HashMap::new(),
+            posted: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn valuation_asset(&self) -> &str {
+        &self.valuation_asset
+    }
+
+    /// Set the percentage knocked off an asset's market value
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err unless the haircut is between 0 and 100
+    pub fn set_haircut(&mut self, asset: &str, percent: f64) -> Result<(), String> {
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(format!(
+                "Haircut must be between 0 and 100, got {}",
+                percent
+            ));
+        }
+        self.haircuts.insert(asset.to_string(), percent);
+        self.revalue_holders(asset);
+        Ok(())
+    }
+
+    pub fn haircut(&self, asset: &str) -> f64 {
+        self.haircuts.get(asset).copied().unwrap_or(0.0)
+    }
+
+    /// Record a new mark for an asset and revalue every account holding it
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the price is negative or not finite
+    pub fn update_mark(&mut self, asset: &str, price: f64) -> Result<(), String> {
+        if !price.is_finite() || price < 0.0 {
+            return Err(format!("Invalid mark {} for {}", price, asset));
+        }
+        self.marks.insert(asset.to_string(), price);
+        self.revalue_holders(asset);
+        Ok(())
+    }
+
+    /// Price of one unit of an asset in the valuation asset, None if it has not been marked
+    pub fn mark(&self, asset: &str) -> Option<f64> {
+        match asset == self.valuation_asset {
+            true => Some(1.0),
+            false => self.marks.get(asset).copied(),
+        }
+    }
+
+    /// Post collateral for an account
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the amount is not positive
+    pub fn post(&mut self, account: AccountId, asset: &str, amount: f64) -> Result<(), String> {
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(format!(
+                "Collateral amount must be positive, got {}",
+                amount
+            ));
+        }
+        *self
+            .posted
+            .entry(account)
+            .or_default()
+            .entry(asset.to_string())
+            .or_default() += amount;
+        self.revalue(account);
+        Ok(())
+    }
+
+    /// Return collateral to an account
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the amount is not positive or more than the account has posted
+    pub fn withdraw(&mut self, account: AccountId, asset: &str, amount: f64) -> Result<(), String> {
+        let posted = self.posted(account, asset);
+        if !amount.is_finite() || amount <= 0.0 || amount > posted {
+            return Err(format!(
+                "Cannot withdraw {} {} from account {}, {} is posted",
+                amount, asset, account, posted
+            ));
+        }
+        if let Some(holdings) = self.posted.get_mut(&account) {
+            match amount == posted {
+                true => holdings.remove(asset),
+                false => holdings.insert(asset.to_string(), posted - amount),
+            };
+        }
+        self.revalue(account);
+        Ok(())
+    }
+
+    /// Amount of an asset an account has posted
+    pub fn posted(&self, account: AccountId, asset: &str) -> f64 {
+        self.posted
+            .get(&account)
+            .and_then(|holdings| holdings.get(asset))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Value of an account's collateral after haircuts, None if it has never posted any
+    pub fn value(&self, account: AccountId) -> Option<f64> {
+        self.values.get(&account).copied()
+    }
+
+    fn revalue(&mut self, account: AccountId) {
+        let holdings = match self.posted.get(&account) {
+            Some(holdings) => holdings,
+            None => return,
+        };
+        let value = exact_sum(holdings.iter().map(|(asset, amount)| {
+            let price = self.mark(asset).unwrap_or(0.0);
+            amount * price * (1.0 - self.haircut(asset) / 100.0)
+        }));
+        self.values.insert(account, value);
+    }
+
+    fn revalue_holders(&mut self, asset: &str) {
+        let holders: Vec<AccountId> = self
+            .posted
+            .iter()
+            .filter(|(_, holdings)| holdings.contains_key(asset))
+            .map(|(account, _)| *account)
+            .collect();
+        for account in holders {
+            self.revalue(account);
+        }
+    }
+}
+
+impl Default for CollateralLedger {
+    /// Collateral valued in US dollars
+    fn default() -> Self {
+        CollateralLedger::new("USD")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_collateral_after_haircuts_at_the_latest_mark() {
+        let mut ledger = CollateralLedger::new("USD");
+        ledger.set_haircut("BTC", 20.0).unwrap();
+        assert!(ledger.set_haircut("ETH", 120.0).is_err());
+
+        ledger.post(1, "USD", 1_000.0).unwrap();
+        ledger.post(1, "BTC", 2.0).unwrap();
+        // BTC counts for nothing until it is marked
+        assert_eq!(ledger.value(1), Some(1_000.0));
+
+        ledger.update_mark("BTC", 500.0).unwrap();
+        assert_eq!(ledger.value(1), Some(1_800.0));
+        ledger.update_mark("BTC", 250.0).unwrap();
+        assert_eq!(ledger.value(1), Some(1_400.0));
+
+        assert!(ledger.withdraw(1, "BTC", 3.0).is_err());
+        ledger.withdraw(1, "BTC", 2.0).unwrap();
+        assert_eq!(ledger.posted(1, "BTC"), 0.0);
+        assert_eq!(ledger.value(1), Some(1_000.0));
+        assert_eq!(ledger.value(2), None);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::matching::clock::Timestamp;
+use std::collections::BTreeMap;
+
+/// Rules for combining external price sources into an index
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexConfig {
+    /// A quote older than this many nanoseconds is ignored
+    pub max_age: u64,
+    /// Quotes further than this percentage from the median of all fresh quotes are rejected
+    pub max_deviation_percent: f64,
+    /// Fewest sources that must survive the filters for the index to be published
+    pub min_sources: usize,
+}
+
+/// How one source's latest quote was treated in the last aggregation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceHealth {
+    Healthy,
+    /// The quote is older than the configured maximum age
+    Stale,
+    /// The quote is too far from the other sources
+    Outlier,
+}
+
+/// An index price computed from several external sources
+///
+/// The index is the median of the fresh quotes that lie within the allowed deviation of the median
+/// of all fresh quotes, so a single bad feed is rejected rather than dragging the price with it.
+#[derive(Debug)]
+pub struct IndexAggregator {
+    config: IndexConfig,
+    quotes: BTreeMap<String, (Timestamp, f64)>,
+}
+
+impl IndexAggregator {
+    pub fn new(config: IndexConfig) -> Self {
+        IndexAggregator {
+            config,
+            quotes: BTreeMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &IndexConfig {
+        &self.config
+    }
+
+    /// Record a source's latest price
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the price is not positive
+    pub fn update(&mut self, source: &str, price: f64, at: Timestamp) -> Result<(), String> {
+        if !price.is_finite() || price <= 0.0 {
+            return Err(format!("Invalid price {} from {}", price, source));
+        }
+        self.quotes.insert(source.to_string(), (at, price));
+        Ok(())
+    }
+
+    /// Stop using a source
+    pub fn remove(&mut self, source: &str) {
+        self.quotes.remove(source);
+    }
+
+    /// The index price at `now`
+    ///
+    /// # Returns
+    /// * `Result<f64, String>` - Err if fewer than the minimum number of sources are fresh and in
+    ///   line with the rest
+    pub fn price(&self, now: Timestamp) -> Result<f64, String> {
+        let mut accepted: Vec<f64> = self
+            .health(now)
+            .into_iter()
+            .filter(|(_, health)| *health == SourceHealth::Healthy)
+            .filter_map(|(source, _)| self.quotes.get(&source).map(|(_, price)| *price))
+            .collect();
+        if accepted.len() < self.config.min_sources {
+            return Err(format!(
+                "Index needs {} healthy sources, has {}",
+                self.config.min_sources,
+                accepted.len()
+            ));
+        }
+        median(&mut accepted).ok_or_else(|| "Index has no sources".to_string())
+    }
+
+    /// How each source's latest quote is treated at `now`, by source name
+    pub fn health(&self, now: Timestamp) -> BTreeMap<String, SourceHealth> {
+        let fresh = |at: Timestamp| at.saturating_add(self.config.max_age) > now;
+        let mut prices: Vec<f64> = self
+            .quotes
+            .values()
+            .filter(|(at, _)| fresh(*at))
+            .map(|(_, price)| *price)
+            .collect();
+        let center = median(&mut prices);
+        self.quotes
+            .iter()
+            .map(|(source, (at, price))| {
+                let health = match center {
+                    _ if !fresh(*at) => SourceHealth::Stale,
+                    Some(center)
+                        if (price - center).abs() / center * 100.0
+                            > self.config.max_deviation_percent =>
+                    {
+                        SourceHealth::Outlier
+                    }
+                    _ => SourceHealth::Healthy,
+                };
+                (source.clone(), health)
+            })
+            .collect()
+    }
+}
+
+fn median(prices: &mut [f64]) -> Option<f64> {
+    if prices.is_empty() {
+        return None;
+    }
+    prices.sort_by(f64::total_cmp);
+    let middle = prices.len() / 2;
+    match prices.len() % 2 {
+        0 => Some((prices[middle - 1] + prices[middle]) / 2.0),
+        _ => Some(prices[middle]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_stale_and_outlying_sources() {
+        let mut index = IndexAggregator::new(IndexConfig {
+            max_age: 100,
+            max_deviation_percent: 1.0,
+            min_sources: 3,
+        });
+        assert!(index.update("bad", -1.0, 0).is_err());
+        index.update("a", 100.0, 0).unwrap();
+        index.update("b", 100.4, 0).unwrap();
+        index.update("c", 99.8, 50).unwrap();
+        index.update("d", 150.0, 50).unwrap();
+
+        assert_eq!(index.price(60), Ok(100.0));
+        let health = index.health(60);
+        assert_eq!(health["d"], SourceHealth::Outlier);
+        assert_eq!(health["a"], SourceHealth::Healthy);
+
+        // Two sources go stale, leaving too few to publish
+        assert_eq!(index.health(100)["a"], SourceHealth::Stale);
+        assert!(index.price(100).is_err());
+        index.update("a", 100.2, 100).unwrap();
+        index.remove("d");
+        assert!(index.price(100).is_err());
+        index.update("b", 100.6, 100).unwrap();
+        assert_eq!(index.price(100), Ok(100.2));
+    }
+}
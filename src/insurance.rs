@@ -0,0 +1,164 @@
+use crate::matching::clock::Timestamp;
+use crate::matching::numeric::exact_sum;
+use crate::matching::orderbook::AccountId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsuranceEntryKind {
+    /// Money paid in to seed or top up the fund
+    Deposit,
+    /// A liquidation closed out better than the account's bankruptcy price
+    Surplus,
+    /// A liquidation closed out worse than the account's bankruptcy price
+    Shortfall,
+}
+
+/// One movement of the insurance fund's balance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InsuranceEntry {
+    pub at: Timestamp,
+    /// The liquidated account, None for deposits
+    pub account: Option<AccountId>,
+    pub kind: InsuranceEntryKind,
+    /// Signed change in the balance
+    pub amount: f64,
+}
+
+/// Public figures of the insurance fund
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InsuranceFundStats {
+    pub balance: f64,
+    pub deposits: f64,
+    /// Liquidation surpluses received
+    pub surpluses: f64,
+    /// Liquidation shortfalls paid out
+    pub shortfalls: f64,
+    /// Shortfalls the fund could not cover, to be socialized
+    pub uncovered: f64,
+    pub liquidations: usize,
+}
+
+/// The fund that absorbs losses from liquidations closed out beyond an account's bankruptcy price
+///
+/// Liquidations that close out better than the bankruptcy price pay the difference into the fund;
+/// those that close out worse are covered from it for as long as its balance lasts.
+#[derive(Debug, Default)]
+pub struct InsuranceFund {
+    balance: f64,
+    uncovered: f64,
+    entries: Vec<InsuranceEntry>,
+}
+
+impl InsuranceFund {
+    pub fn new() -> Self {
+        InsuranceFund::default()
+    }
+
+    /// Pay money into the fund
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the amount is not positive
+    pub fn deposit(&mut self, amount: f64, at: Timestamp) -> Result<(), String> {
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(format!("Deposit must be positive, got {}", amount));
+        }
+        self.record(at, None, InsuranceEntryKind::Deposit, amount);
+        Ok(())
+    }
+
+    /// Settle a liquidation against the fund
+    ///
+    /// # Arguments
+    /// * `account` - The liquidated account
+    /// * `result` - What the close-out made relative to the bankruptcy price: positive is a surplus
+    ///   for the fund, negative a shortfall it has to cover
+    /// * `at` - When the liquidation completed
+    ///
+    /// # Returns
+    /// * `f64` - The part of a shortfall the fund could not cover
+    pub fn settle_liquidation(&mut self, account: AccountId, result: f64, at: Timestamp) -> f64 {
+        if result >= 0.0 {
+            self.record(at, Some(account), InsuranceEntryKind::Surplus, result);
+            return 0.0;
+        }
+        let covered = self.balance.min(-result);
+        let uncovered = -result - covered;
+        self.record(at, Some(account), InsuranceEntryKind::Shortfall, -covered);
+        self.uncovered += uncovered;
+        uncovered
+    }
+
+    fn record(
+        &mut self,
+        at: Timestamp,
+        account: Option<AccountId>,
+        kind: InsuranceEntryKind,
+        amount: f64,
+    ) {
+        self.balance += amount;
+        self.entries.push(InsuranceEntry {
+            at,
+            account,
+            kind,
+            amount,
+        });
+    }
+
+    pub fn balance(&self) -> f64 {
+        self.balance
+    }
+
+    /// Every movement of the balance, oldest first
+    pub fn entries(&self) -> &[InsuranceEntry] {
+        &self.entries
+    }
+
+    /// The fund's figures for publishing
+    pub fn stats(&self) -> InsuranceFundStats {
+        let total = |kind: InsuranceEntryKind| {
+            exact_sum(
+                self.entries
+                    .iter()
+                    .filter(|entry| entry.kind == kind)
+                    .map(|entry| entry.amount.abs()),
+            )
+        };
+        InsuranceFundStats {
+            balance: self.balance,
+            deposits: total(InsuranceEntryKind::Deposit),
+            surpluses: total(InsuranceEntryKind::Surplus),
+            shortfalls: total(InsuranceEntryKind::Shortfall),
+            uncovered: self.uncovered,
+            liquidations: self
+                .entries
+                .iter()
+                .filter(|entry| entry.kind != InsuranceEntryKind::Deposit)
+                .count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absorbs_shortfalls_while_the_balance_lasts() {
+        let mut fund = InsuranceFund::new();
+        fund.deposit(100.0, 1).unwrap();
+        assert!(fund.deposit(-5.0, 1).is_err());
+
+        assert_eq!(fund.settle_liquidation(7, 25.0, 2), 0.0);
+        assert_eq!(fund.settle_liquidation(8, -100.0, 3), 0.0);
+        assert_eq!(fund.balance(), 25.0);
+        assert_eq!(fund.settle_liquidation(9, -40.0, 4), 15.0);
+        assert_eq!(fund.balance(), 0.0);
+
+        let stats = fund.stats();
+        assert_eq!(stats.deposits, 100.0);
+        assert_eq!(stats.surpluses, 25.0);
+        assert_eq!(stats.shortfalls, 125.0);
+        assert_eq!(stats.uncovered, 15.0);
+        assert_eq!(stats.liquidations, 3);
+        assert_eq!(fund.entries()[3].account, Some(9));
+    }
+}
@@ -0,0 +1,14 @@
+pub mod accounts;
+pub mod adl;
+pub mod allocation;
+pub mod api;
+pub mod assets;
+pub mod client;
+pub mod collateral;
+pub mod index;
+pub mod insurance;
+pub mod matching;
+pub mod positions;
+pub mod reconcile;
+pub mod skew;
+pub mod statement;
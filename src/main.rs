@@ -1,8 +1,131 @@
-mod matching;
-use matching::engine::Engine;
-use matching::orderbook::{Order, OrderBook, OrderType, TradingPair};
+use orderbook::matching::config::EngineConfig;
+use orderbook::matching::engine::Engine;
+use orderbook::matching::import::parse_orders_csv;
+use orderbook::matching::orderbook::{Order, OrderBook, OrderType, TradingPair};
+use orderbook::matching::scenario::Scenario;
+use std::{env, fs, fs::File, io::BufReader, process};
 
 fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    match args.first().map(String::as_str) {
+        Some("check-orders") => check_orders(&args[1..]),
+        Some("scenario") => scenario(&args[1..]),
+        Some("check-config") => check_config(&args[1..]),
+        _ => demo(),
+    }
+}
+
+/// `orderbook check-orders <orders.csv> --pair BASE/QUOTE`
+///
+/// Dry run of a batch of orders: places every row of the file as a limit order on a fresh,
+/// throwaway book and prints which rows would be accepted. Nothing is kept once it exits
+fn check_orders(args: &[String]) {
+    let mut path = None;
+    let mut pair = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pair" => pair = args.next(),
+            _ => path = Some(arg),
+        }
+    }
+    let (path, pair) = match (path, pair.and_then(|pair| pair.parse::<TradingPair>().ok())) {
+        (Some(path), Some(pair)) => (path, pair),
+        _ => {
+            eprintln!("usage: orderbook check-orders <orders.csv> --pair BASE/QUOTE");
+            process::exit(2);
+        }
+    };
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Error opening {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let mut engine = Engine::new();
+    engine.add_orderbook(pair.clone(), OrderBook::new());
+
+    let mut total = 0;
+    let mut accepted = 0;
+    for row in parse_orders_csv(BufReader::new(file)) {
+        total += 1;
+        let result = row.and_then(|row| {
+            engine
+                .place_limit_order(pair.clone(), row.price, row.to_order())
+                .map_err(|e| format!("line {}: {}", row.line, e))
+        });
+        match result {
+            Ok(_) => accepted += 1,
+            Err(e) => eprintln!("Rejected {}", e),
+        }
+        if total % 1000 == 0 {
+            eprintln!("{} rows processed", total);
+        }
+    }
+    println!(
+        "Checked {}: {} would be accepted, {} rejected",
+        path,
+        accepted,
+        total - accepted
+    );
+}
+
+/// `orderbook scenario <script>`
+///
+/// Runs a scenario script and exits non-zero if any expectation fails
+fn scenario(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: orderbook scenario <script>");
+            process::exit(2);
+        }
+    };
+    let result = fs::read_to_string(path)
+        .map_err(|e| format!("Error reading {}: {}", path, e))
+        .and_then(|script| Scenario::parse(&script))
+        .and_then(|scenario| scenario.run());
+    match result {
+        Ok(trades) => {
+            for trade in trades {
+                let price: f64 = trade.price.into();
+                println!("trade {}@{}", trade.size, price);
+            }
+        }
+        Err(e) => {
+            eprintln!("Scenario failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `orderbook check-config <config>`
+///
+/// Parses and validates a configuration file and prints the effective configuration, defaults
+/// included, without starting anything; exits non-zero listing every problem if it is invalid
+fn check_config(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: orderbook check-config <config>");
+            process::exit(2);
+        }
+    };
+    let result = fs::read_to_string(path)
+        .map_err(|e| format!("Error reading {}: {}", path, e))
+        .and_then(|text| EngineConfig::parse(&text));
+    match result {
+        Ok(config) => print!("{}", config),
+        Err(e) => {
+            eprintln!("Invalid configuration {}:\n{}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn demo() {
     let buy_from_cole = Order::new(OrderType::Bid, 100.0);
     let buy_from_john = Order::new(OrderType::Bid, 200.0);
 
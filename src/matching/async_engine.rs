@@ -0,0 +1,414 @@
+use super::engine::{Engine, OrderError};
+use super::event::OrderEvent;
+use super::orderbook::{MarketFill, Order, Placement, TradingPair};
+use super::snapshot::EngineSnapshot;
+use super::trade::Trade;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce(&mut Engine) + Send>;
+
+/// Requests that can wait for the engine thread before callers are turned away
+const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// How far a request must get before its [`Reply`] resolves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckLevel {
+    /// Queued for the engine thread, without waiting for the result
+    Accepted,
+    /// Applied by the engine, with the result
+    Applied,
+    /// Applied and written to durable storage
+    Durable,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    /// Why the request will never be answered
+    error: Option<String>,
+}
+
+/// The eventual result of a request to an [`AsyncEngine`]
+///
+/// Works with any executor: the engine thread wakes the task when the result is ready.
+#[derive(Debug)]
+pub struct Reply<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Reply<T> {
+    fn new(value: Option<T>, error: Option<String>) -> Self {
+        Reply {
+            slot: Arc::new(Mutex::new(Slot {
+                value,
+                waker: None,
+                error,
+            })),
+        }
+    }
+}
+
+impl<T> Future for Reply<T> {
+    type Output = Result<T, String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap();
+        if let Some(value) = slot.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+        if let Some(error) = slot.error.take() {
+            return Poll::Ready(Err(error));
+        }
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Completes a [`Reply`], or abandons it if dropped without a value
+struct Responder<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Responder<T> {
+    fn send(self, value: T) {
+        self.slot.lock().unwrap().value = Some(value);
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        let mut slot = self.slot.lock().unwrap();
+        if slot.value.is_none() && slot.error.is_none() {
+            slot.error = Some("Engine has shut down".to_string());
+        }
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// An [`Engine`] running on its own thread, driven through futures
+///
+/// Requests are queued to the engine thread and applied one at a time in the order they were
+/// made, so the engine itself needs no locking. Each request returns a [`Reply`] that resolves to
+/// the same result the synchronous `Engine` call would have given.
+///
+/// The queue is bounded: once it is full, new requests fail straight away instead of piling up,
+/// so callers see backpressure.
+pub struct AsyncEngine {
+    jobs: mpsc::SyncSender<Job>,
+    thread: JoinHandle<Engine>,
+}
+
+impl AsyncEngine {
+    /// Move an engine onto a new thread with the default queue capacity
+    pub fn spawn(engine: Engine) -> Self {
+        AsyncEngine::with_capacity(engine, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Move an engine onto a new thread, queueing at most `capacity` requests for it
+    pub fn with_capacity(mut engine: Engine, capacity: usize) -> Self {
+        let (jobs, queue) = mpsc::sync_channel::<Job>(capacity);
+        let thread = thread::spawn(move || {
+            for job in queue {
+                job(&mut engine);
+            }
+            engine
+        });
+        AsyncEngine { jobs, thread }
+    }
+
+    /// Run a closure against the engine on its thread
+    ///
+    /// # Returns
+    /// * `Reply<T>` - Resolves to the closure's result, Err if the queue is full or the engine thread has stopped
+    pub fn call<T, F>(&self, f: F) -> Reply<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Engine) -> T + Send + 'static,
+    {
+        let reply = Reply::new(None, None);
+        let responder = Responder {
+            slot: reply.slot.clone(),
+        };
+        // A job that is not queued is dropped here, which abandons the reply
+        if let Err(mpsc::TrySendError::Full(_)) = self
+            .jobs
+            .try_send(Box::new(move |engine| responder.send(f(engine))))
+        {
+            reply.slot.lock().unwrap().error = Some("Engine queue is full".to_string());
+        }
+        reply
+    }
+
+    /// See [`Engine::place_limit_order`]
+    pub fn place_limit_order(
+        &self,
+        trading_pair: TradingPair,
+        price: f64,
+        order: Order,
+    ) -> Reply<Result<Placement, OrderError>> {
+        self.call(move |engine| engine.place_limit_order(trading_pair, price, order))
+    }
+
+    /// Place a limit order, choosing how far it must get before the reply resolves
+    ///
+    /// # Returns
+    /// * `Reply<Option<Result<Placement, OrderError>>>` - None once the order is queued for
+    ///   `AckLevel::Accepted`, the placement result for `AckLevel::Applied`. The engine keeps no
+    ///   journal, so `AckLevel::Durable` is rejected
+    pub fn place_limit_order_with_ack(
+        &self,
+        trading_pair: TradingPair,
+        price: f64,
+        order: Order,
+        ack: AckLevel,
+    ) -> Reply<Option<Result<Placement, OrderError>>> {
+        match ack {
+            AckLevel::Accepted => {
+                let queued = self.call(move |engine| {
+                    let _ = engine.place_limit_order(trading_pair, price, order);
+                });
+                let error = queued.slot.lock().unwrap().error.take();
+                Reply::new(error.is_none().then_some(None), error)
+            }
+            AckLevel::Applied => {
+                self.call(move |engine| Some(engine.place_limit_order(trading_pair, price, order)))
+            }
+            AckLevel::Durable => Reply::new(
+                None,
+                Some(
+                    "Durable acknowledgement needs a journal, which this engine does not keep"
+                        .to_string(),
+                ),
+            ),
+        }
+    }
+
+    /// See [`Engine::place_market_order`]
+    pub fn place_market_order(
+        &self,
+        trading_pair: TradingPair,
+        order: Order,
+    ) -> Reply<Result<MarketFill, OrderError>> {
+        self.call(move |engine| engine.place_market_order(&trading_pair, order))
+    }
+
+    /// See [`Engine::halt_market`]
+    pub fn halt_market(&self, trading_pair: TradingPair) -> Reply<Result<Vec<OrderEvent>, String>> {
+        self.call(move |engine| engine.halt_market(&trading_pair))
+    }
+
+    /// See [`Engine::resume_market`]
+    pub fn resume_market(&self, trading_pair: TradingPair) -> Reply<Result<(), String>> {
+        self.call(move |engine| engine.resume_market(&trading_pair))
+    }
+
+    /// See [`Engine::poll_auctions`]
+    pub fn poll_auctions(&self) -> Reply<Vec<(TradingPair, Trade)>> {
+        self.call(|engine| engine.poll_auctions())
+    }
+
+    /// See [`Engine::snapshot`]
+    pub fn snapshot(&self) -> Reply<EngineSnapshot> {
+        self.call(|engine| engine.snapshot())
+    }
+
+    /// Stop the engine thread once queued requests are done and give back the engine
+    pub fn shutdown(self) -> Engine {
+        drop(self.jobs);
+        self.thread.join().expect("engine thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::{OrderBook, OrderType};
+    use std::task::Wake;
+    use std::time::{Duration, Instant};
+
+    struct Unpark(thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn requests_resolve_in_order() {
+        let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut engine = Engine::new();
+        engine.add_orderbook(pair.clone(), OrderBook::new());
+        let engine = AsyncEngine::spawn(engine);
+
+        let placed = engine.place_limit_order(pair.clone(), 100.0, Order::new(OrderType::Ask, 2.0));
+        let traded = engine.place_market_order(pair.clone(), Order::new(OrderType::Bid, 1.5));
+        let missing = engine.place_market_order(
+            TradingPair::new("ETH".to_string(), "USD".to_string()),
+            Order::new(OrderType::Bid, 1.0),
+        );
+
+        assert_eq!(block_on(placed).unwrap().unwrap().id, 1);
+        let fill = block_on(traded).unwrap().unwrap();
+        assert_eq!(fill.trades.len(), 1);
+        assert_eq!(fill.trades[0].size, 1.5);
+        assert_eq!(fill.remaining, 0.0);
+        assert!(block_on(missing).unwrap().is_err());
+
+        let engine = engine.shutdown();
+        assert_eq!(engine.orderbook(&pair).unwrap().open_orders(), 1);
+    }
+
+    #[test]
+    fn acknowledgement_levels() {
+        let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut engine = Engine::new();
+        engine.add_orderbook(pair.clone(), OrderBook::new());
+        let engine = AsyncEngine::spawn(engine);
+        let ask = || Order::new(OrderType::Ask, 1.0);
+
+        let accepted =
+            engine.place_limit_order_with_ack(pair.clone(), 100.0, ask(), AckLevel::Accepted);
+        assert_eq!(block_on(accepted), Ok(None));
+        let applied =
+            engine.place_limit_order_with_ack(pair.clone(), 100.0, ask(), AckLevel::Applied);
+        assert_eq!(block_on(applied).unwrap().unwrap().unwrap().id, 2);
+        let durable =
+            engine.place_limit_order_with_ack(pair.clone(), 100.0, ask(), AckLevel::Durable);
+        assert!(block_on(durable).is_err());
+
+        let engine = engine.shutdown();
+        assert_eq!(engine.orderbook(&pair).unwrap().open_orders(), 2);
+    }
+
+    #[test]
+    fn full_queue_pushes_back() {
+        let engine = AsyncEngine::with_capacity(Engine::new(), 1);
+        let (release, blocked) = mpsc::channel::<()>();
+        let busy = engine.call(move |_| blocked.recv().unwrap());
+        // Fill the single queue slot once the engine thread is stuck in the blocking job
+        while engine.jobs.try_send(Box::new(|_| ())).is_err() {
+            thread::yield_now();
+        }
+        let rejected = engine.call(|_| ());
+        assert_eq!(block_on(rejected), Err("Engine queue is full".to_string()));
+        release.send(()).unwrap();
+        assert!(block_on(busy).is_ok());
+    }
+
+    /// Like `block_on`, but gives up after `timeout` so a lost wakeup fails the test instead of
+    /// hanging it
+    fn block_on_within<F: Future>(future: F, timeout: Duration) -> Option<F::Output> {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return Some(output);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            thread::park_timeout(deadline - now);
+        }
+    }
+
+    #[test]
+    fn reply_handoff_never_loses_a_wakeup() {
+        for round in 0..2_000 {
+            let reply = Reply::new(None, None);
+            let responder = Responder {
+                slot: reply.slot.clone(),
+            };
+            // Race the answer against the first poll; every few rounds abandon it instead
+            let answer = thread::spawn(move || match round % 4 {
+                0 => drop(responder),
+                _ => responder.send(round),
+            });
+            let result = block_on_within(reply, Duration::from_secs(5))
+                .unwrap_or_else(|| panic!("reply {} was never woken", round));
+            match round % 4 {
+                0 => assert_eq!(result, Err("Engine has shut down".to_string())),
+                _ => assert_eq!(result, Ok(round)),
+            }
+            answer.join().unwrap();
+
+            // A client that stops waiting must not trouble the engine thread answering it
+            let reply = Reply::<u32>::new(None, None);
+            let responder = Responder {
+                slot: reply.slot.clone(),
+            };
+            let answer = thread::spawn(move || responder.send(round));
+            drop(reply);
+            answer.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn shutdown_answers_every_queued_request() {
+        const CLIENTS: usize = 4;
+        const REQUESTS: usize = 500;
+        let engine = AsyncEngine::with_capacity(Engine::new(), 16);
+        let replies: Vec<Vec<Reply<usize>>> = thread::scope(|scope| {
+            let clients: Vec<_> = (0..CLIENTS)
+                .map(|client| {
+                    let engine = &engine;
+                    scope.spawn(move || {
+                        (0..REQUESTS)
+                            .map(|request| {
+                                engine.call(move |engine| {
+                                    engine.sequence() as usize + client * REQUESTS + request
+                                })
+                            })
+                            .collect()
+                    })
+                })
+                .collect();
+            clients
+                .into_iter()
+                .map(|client| client.join().unwrap())
+                .collect()
+        });
+        engine.shutdown();
+
+        // After shutdown every reply has settled: applied, or turned away by backpressure
+        let mut applied = 0;
+        for reply in replies.into_iter().flatten() {
+            match block_on_within(reply, Duration::ZERO).expect("reply left pending") {
+                Ok(_) => applied += 1,
+                Err(e) => assert_eq!(e, "Engine queue is full"),
+            }
+        }
+        assert!(applied > 0);
+    }
+
+    #[test]
+    fn panicking_request_is_abandoned() {
+        let engine = AsyncEngine::spawn(Engine::new());
+        let reply = engine.call(|_| -> () { panic!("boom") });
+        assert!(block_on(reply).is_err());
+    }
+}
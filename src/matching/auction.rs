@@ -0,0 +1,195 @@
+use super::depth::Depth;
+use super::numeric::exact_sum;
+use super::orderbook::Price;
+
+/// Picks a random close time for auctions so that nobody can wait for the last instant to
+/// move the indicative price
+///
+/// The offset is drawn uniformly from `0..=window` nanoseconds with a seeded SplitMix64 generator.
+/// Combined with a [`Clock`](super::clock::Clock) this keeps auction timing reproducible in tests.
+#[derive(Debug, Clone)]
+pub struct RandomClose {
+    window: u64,
+    state: u64,
+}
+
+impl RandomClose {
+    pub fn new(window: u64, seed: u64) -> Self {
+        RandomClose {
+            window,
+            state: seed,
+        }
+    }
+
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// The next offset to add to an auction's scheduled end
+    pub fn offset(&mut self) -> u64 {
+        if self.window == 0 {
+            return 0;
+        }
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        match self.window.checked_add(1) {
+            Some(range) => z % range,
+            None => z,
+        }
+    }
+}
+
+/// The price an auction would uncross at if it ended now
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndicativePrice {
+    pub price: Price,
+    /// Size that would execute at `price`
+    pub volume: f64,
+    /// Size left unmatched at `price`, positive for excess bids and negative for excess asks
+    pub imbalance: f64,
+}
+
+/// Find the single price that maximises executable volume across a (possibly crossed) book
+///
+/// Every level price is a candidate. Ties on volume go to the smallest absolute imbalance, then to
+/// the candidate closest to `reference` (typically the last traded price), then to the lower price.
+///
+/// # Returns
+/// * `Option<IndicativePrice>` - None if the book does not cross
+pub fn indicative_price(depth: &Depth, reference: Option<f64>) -> Option<IndicativePrice> {
+    let mut best: Option<IndicativePrice> = None;
+    let candidates = depth
+        .bids
+        .iter()
+        .chain(depth.asks.iter())
+        .map(|level| level.price);
+    for price in candidates {
+        let demand = exact_sum(
+            depth
+                .bids
+                .iter()
+                .filter(|level| level.price >= price)
+                .map(|level| level.volume),
+        );
+        let supply = exact_sum(
+            depth
+                .asks
+                .iter()
+                .filter(|level| level.price <= price)
+                .map(|level| level.volume),
+        );
+        let volume = demand.min(supply);
+        if volume <= 0.0 {
+            continue;
+        }
+        let candidate = IndicativePrice {
+            price,
+            volume,
+            imbalance: demand - supply,
+        };
+        best = match best {
+            Some(current) if !is_better(&candidate, &current, reference) => Some(current),
+            _ => Some(candidate),
+        };
+    }
+    best
+}
+
+fn is_better(
+    candidate: &IndicativePrice,
+    current: &IndicativePrice,
+    reference: Option<f64>,
+) -> bool {
+    if candidate.volume != current.volume {
+        return candidate.volume > current.volume;
+    }
+    if candidate.imbalance.abs() != current.imbalance.abs() {
+        return candidate.imbalance.abs() < current.imbalance.abs();
+    }
+    if let Some(reference) = reference {
+        let distance = |price: Price| (f64::from(price) - reference).abs();
+        if distance(candidate.price) != distance(current.price) {
+            return distance(candidate.price) < distance(current.price);
+        }
+    }
+    candidate.price < current.price
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::depth::DepthLevel;
+
+    fn level(price: f64, volume: f64) -> DepthLevel {
+        DepthLevel {
+            price: Price::new(price),
+            volume,
+            order_count: 1,
+        }
+    }
+
+    #[test]
+    fn maximises_executable_volume() {
+        let depth = Depth {
+            bids: vec![level(102.0, 5.0), level(101.0, 10.0), level(99.0, 10.0)],
+            asks: vec![level(100.0, 8.0), level(101.0, 4.0), level(103.0, 10.0)],
+        };
+
+        let indicative = indicative_price(&depth, None).unwrap();
+        assert_eq!(indicative.price, Price::new(101.0));
+        assert_eq!(indicative.volume, 12.0);
+        assert_eq!(indicative.imbalance, 3.0);
+    }
+
+    #[test]
+    fn ties_break_on_reference_price() {
+        let depth = Depth {
+            bids: vec![level(102.0, 5.0)],
+            asks: vec![level(100.0, 5.0)],
+        };
+
+        assert_eq!(
+            indicative_price(&depth, None).unwrap().price,
+            Price::new(100.0)
+        );
+        assert_eq!(
+            indicative_price(&depth, Some(103.0)).unwrap().price,
+            Price::new(102.0)
+        );
+    }
+
+    #[test]
+    fn random_close_offsets_stay_within_window() {
+        let mut close = RandomClose::new(30, 42);
+        let offsets = (0..3_000).map(|_| close.offset()).collect::<Vec<u64>>();
+
+        assert!(offsets.iter().all(|offset| *offset <= 30));
+        // Every offset in the window shows up, roughly uniformly
+        for value in 0..=30 {
+            let count = offsets.iter().filter(|offset| **offset == value).count();
+            assert!(
+                (40..200).contains(&count),
+                "{} drawn {} times",
+                value,
+                count
+            );
+        }
+
+        let mut replay = RandomClose::new(30, 42);
+        assert_eq!(replay.offset(), offsets[0]);
+        assert_eq!(RandomClose::new(0, 42).offset(), 0);
+    }
+
+    #[test]
+    fn uncrossed_book_has_no_indicative_price() {
+        let depth = Depth {
+            bids: vec![level(99.0, 5.0)],
+            asks: vec![level(100.0, 5.0)],
+        };
+
+        assert_eq!(indicative_price(&depth, None), None);
+    }
+}
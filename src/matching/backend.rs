@@ -0,0 +1,75 @@
+use super::depth::Depth;
+use super::event::OrderEvent;
+use super::orderbook::{MarketStatus, Order, OrderBook, OrderId, Placement};
+use super::trade::Trade;
+
+/// The operations a single market's order book must support
+///
+/// [`OrderBook`] is the reference implementation. Other implementations should pass the checks
+/// in [`crate::matching::conformance`], most easily through
+/// [`book_backend_conformance!`](crate::book_backend_conformance).
+pub trait BookBackend {
+    /// Rest a limit order without matching it
+    fn add(&mut self, order: Order, price: f64) -> OrderId;
+
+    /// Match a limit order up to its price, then rest whatever is left
+    fn place_limit_order(&mut self, order: Order, price: f64) -> Result<Placement, String>;
+
+    /// Match an order against the opposite side without a price limit
+    fn place_market_order(&mut self, order: &mut Order) -> Vec<Trade>;
+
+    /// Remove a resting order, returning its `Cancelled` event if it was open
+    fn cancel(&mut self, id: OrderId) -> Option<OrderEvent>;
+
+    /// Stop matching, returning the cancellations the halt caused
+    fn halt(&mut self) -> Vec<OrderEvent>;
+
+    /// Leave a halt through an auction
+    fn resume(&mut self);
+
+    /// End an auction, matching everything that crosses at a single price
+    fn uncross(&mut self) -> Vec<Trade>;
+
+    fn status(&self) -> MarketStatus;
+
+    /// The best `levels` price levels on each side, best price first
+    fn depth(&self, levels: usize) -> Depth;
+}
+
+impl BookBackend for OrderBook {
+    fn add(&mut self, order: Order, price: f64) -> OrderId {
+        OrderBook::add(self, order, price)
+    }
+
+    fn place_limit_order(&mut self, order: Order, price: f64) -> Result<Placement, String> {
+        OrderBook::place_limit_order(self, order, price)
+    }
+
+    fn place_market_order(&mut self, order: &mut Order) -> Vec<Trade> {
+        OrderBook::place_market_order(self, order)
+    }
+
+    fn cancel(&mut self, id: OrderId) -> Option<OrderEvent> {
+        OrderBook::cancel(self, id)
+    }
+
+    fn halt(&mut self) -> Vec<OrderEvent> {
+        OrderBook::halt(self)
+    }
+
+    fn resume(&mut self) {
+        OrderBook::resume(self)
+    }
+
+    fn uncross(&mut self) -> Vec<Trade> {
+        OrderBook::uncross(self)
+    }
+
+    fn status(&self) -> MarketStatus {
+        OrderBook::status(self)
+    }
+
+    fn depth(&self, levels: usize) -> Depth {
+        OrderBook::depth(self, levels)
+    }
+}
@@ -0,0 +1,263 @@
+use super::clock::Timestamp;
+use super::numeric::exact_sum;
+use std::collections::VecDeque;
+
+/// Market tiers with their default limit-up/limit-down parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// Most liquid markets: tight bands
+    One,
+    /// Everything else: wider bands
+    Two,
+}
+
+/// What price bands do while the external index price is stale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StaleIndex {
+    /// Keep the band around the last index price, widened by this multiplier
+    Widen(f64),
+    /// Allow no trades until a fresh index price arrives
+    Halt,
+    /// Band around the rolling reference of the market's own trades instead
+    LastTrade,
+}
+
+/// Anchoring of price bands to an external index price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexBandConfig {
+    /// An index price older than this many nanoseconds is stale
+    pub max_age: u64,
+    pub on_stale: StaleIndex,
+}
+
+/// Limit-up/limit-down parameters for a market
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandConfig {
+    /// Half-width of the band around the reference price, as a percentage
+    pub percent: f64,
+    /// Trades in this many nanoseconds make up the rolling reference price
+    pub reference_window: u64,
+    /// How long trading pauses when a trade would fall outside the band
+    pub pause: u64,
+    /// Band width multiplier for one reference window after the market reopens
+    pub reopen_multiplier: f64,
+    /// Centre the band on an external index price rather than the market's own trades
+    pub index: Option<IndexBandConfig>,
+}
+
+impl BandConfig {
+    /// Default parameters for a tier: 5% or 10% bands around a 5 minute average, 5 minute pauses
+    pub fn tier(tier: Tier) -> Self {
+        let percent = match tier {
+            Tier::One => 5.0,
+            Tier::Two => 10.0,
+        };
+        BandConfig {
+            percent,
+            reference_window: 300_000_000_000,
+            pause: 300_000_000_000,
+            reopen_multiplier: 2.0,
+            index: None,
+        }
+    }
+}
+
+/// Dynamic price bands around a rolling reference price
+///
+/// The reference is the average price of the trades in the last `reference_window`, so the band
+/// recentres as the market moves. After a reopening the band is widened for one window.
+///
+/// With an index configured the band is centred on the latest index price instead, for as long as
+/// it is fresh; once it goes stale the configured [`StaleIndex`] behaviour applies.
+#[derive(Debug, Clone)]
+pub struct PriceBands {
+    config: BandConfig,
+    trades: VecDeque<(Timestamp, f64)>,
+    last_price: Option<f64>,
+    widened_until: Option<Timestamp>,
+    index: Option<(Timestamp, f64)>,
+}
+
+impl PriceBands {
+    pub fn new(config: BandConfig) -> Self {
+        PriceBands {
+            config,
+            trades: VecDeque::new(),
+            last_price: None,
+            widened_until: None,
+            index: None,
+        }
+    }
+
+    pub fn config(&self) -> &BandConfig {
+        &self.config
+    }
+
+    pub fn record(&mut self, price: f64, at: Timestamp) {
+        self.trades.push_back((at, price));
+        self.last_price = Some(price);
+    }
+
+    /// Record the latest external index price
+    pub fn update_index(&mut self, price: f64, at: Timestamp) {
+        self.index = Some((at, price));
+    }
+
+    /// Whether bands are anchored to an index that has gone stale, or never arrived
+    pub fn index_stale(&self, now: Timestamp) -> bool {
+        let max_age = match self.config.index {
+            Some(index) => index.max_age,
+            None => return false,
+        };
+        match self.index {
+            Some((at, _)) => at.saturating_add(max_age) <= now,
+            None => true,
+        }
+    }
+
+    /// Reset the reference to a single price, e.g. the uncross price of a reopening auction, and
+    /// widen the band for the next reference window
+    pub fn recenter(&mut self, price: f64, at: Timestamp) {
+        self.trades.clear();
+        self.record(price, at);
+        self.widened_until = Some(at.saturating_add(self.config.reference_window));
+    }
+
+    /// The rolling reference price
+    ///
+    /// Falls back to the last trade when no trade is recent enough, and is None before the first
+    /// trade.
+    pub fn reference(&mut self, now: Timestamp) -> Option<f64> {
+        while let Some(&(at, _)) = self.trades.front() {
+            if at.saturating_add(self.config.reference_window) > now {
+                break;
+            }
+            self.trades.pop_front();
+        }
+        if self.trades.is_empty() {
+            return self.last_price;
+        }
+        let total = exact_sum(self.trades.iter().map(|(_, price)| *price));
+        Some(total / self.trades.len() as f64)
+    }
+
+    /// The current lower and upper band
+    ///
+    /// None if there is no reference price yet, or if a stale index halts trading.
+    pub fn bands(&mut self, now: Timestamp) -> Option<(f64, f64)> {
+        let mut percent = self.config.percent;
+        let reference = match (self.config.index, self.index_stale(now)) {
+            (None, _) => self.reference(now)?,
+            (Some(_), false) => self.index.map(|(_, price)| price)?,
+            (Some(index), true) => match index.on_stale {
+                StaleIndex::Widen(multiplier) => {
+                    percent *= multiplier;
+                    match self.index {
+                        Some((_, price)) => price,
+                        None => self.reference(now)?,
+                    }
+                }
+                StaleIndex::Halt => return None,
+                StaleIndex::LastTrade => self.reference(now)?,
+            },
+        };
+        if self.widened_until.is_some_and(|until| now < until) {
+            percent *= self.config.reopen_multiplier;
+        }
+        let offset = reference * percent / 100.0;
+        Some((reference - offset, reference + offset))
+    }
+
+    /// Whether a trade at `price` is allowed now
+    pub fn allows(&mut self, price: f64, now: Timestamp) -> bool {
+        if self.halted_by_index(now) {
+            return false;
+        }
+        match self.bands(now) {
+            Some((lower, upper)) => lower <= price && price <= upper,
+            None => true,
+        }
+    }
+
+    fn halted_by_index(&self, now: Timestamp) -> bool {
+        self.config
+            .index
+            .is_some_and(|index| index.on_stale == StaleIndex::Halt)
+            && self.index_stale(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> BandConfig {
+        BandConfig {
+            percent: 10.0,
+            reference_window: 100,
+            pause: 50,
+            reopen_multiplier: 2.0,
+            index: None,
+        }
+    }
+
+    #[test]
+    fn bands_recenter_on_rolling_reference() {
+        let mut bands = PriceBands::new(config());
+        assert_eq!(bands.bands(0), None);
+        assert!(bands.allows(1_000.0, 0));
+
+        bands.record(100.0, 0);
+        bands.record(110.0, 50);
+        assert_eq!(bands.reference(60), Some(105.0));
+        assert!(bands.allows(115.0, 60));
+        assert!(!bands.allows(116.0, 60));
+
+        // The first trade drops out of the window and the band moves up
+        assert_eq!(bands.reference(120), Some(110.0));
+        assert!(bands.allows(120.0, 120));
+        // With no recent trades the last price is the reference
+        assert_eq!(bands.reference(1_000), Some(110.0));
+    }
+
+    #[test]
+    fn bands_widen_after_reopening() {
+        let mut bands = PriceBands::new(config());
+        bands.recenter(100.0, 0);
+
+        assert!(bands.allows(119.0, 10));
+        assert!(!bands.allows(121.0, 10));
+        bands.record(100.0, 100);
+        assert!(!bands.allows(111.0, 100));
+    }
+
+    #[test]
+    fn bands_follow_the_index_and_fall_back_when_it_is_stale() {
+        let anchored = |on_stale| BandConfig {
+            index: Some(IndexBandConfig {
+                max_age: 100,
+                on_stale,
+            }),
+            ..config()
+        };
+
+        let mut bands = PriceBands::new(anchored(StaleIndex::Widen(2.0)));
+        bands.record(50.0, 0);
+        bands.update_index(100.0, 0);
+        assert_eq!(bands.bands(50), Some((90.0, 110.0)));
+        assert!(bands.index_stale(100));
+        assert_eq!(bands.bands(100), Some((80.0, 120.0)));
+
+        let mut bands = PriceBands::new(anchored(StaleIndex::LastTrade));
+        bands.record(50.0, 0);
+        bands.update_index(100.0, 0);
+        assert_eq!(bands.bands(150), Some((45.0, 55.0)));
+
+        let mut bands = PriceBands::new(anchored(StaleIndex::Halt));
+        assert!(!bands.allows(100.0, 0), "no index has arrived yet");
+        bands.update_index(100.0, 0);
+        assert!(bands.allows(100.0, 99));
+        assert!(!bands.allows(100.0, 100));
+        assert_eq!(bands.bands(100), None);
+    }
+}
@@ -0,0 +1,225 @@
+use super::clock::Timestamp;
+use super::orderbook::{AccountId, Order, OrderType, TimeInForce};
+
+/// Decimal places a price may have, matching the precision of `Price`
+const PRICE_DECIMALS: usize = 5;
+
+/// Step-by-step construction of an order, validated in [`OrderBuilder::build`]
+///
+/// # Example
+/// ```
+/// use orderbook::matching::orderbook::{Order, OrderType, TimeInForce};
+/// let order = Order::builder()
+///     .side(OrderType::Bid)
+///     .price("10050.25")
+///     .size("1.5")
+///     .tif(TimeInForce::Day)
+///     .account(7)
+///     .build()
+///     .unwrap();
+/// assert_eq!(order.price(), Some(10050.25));
+/// assert!(Order::builder().side(OrderType::Bid).size("-1").build().is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OrderBuilder {
+    side: Option<OrderType>,
+    price: Option<String>,
+    size: Option<String>,
+    time_in_force: TimeInForce,
+    account: Option<AccountId>,
+    expires_at: Option<Timestamp>,
+    tag: Option<String>,
+}
+
+/// An order that passed validation, ready to be placed with `Engine::place_order`
+///
+/// Orders without a price are market orders.
+#[derive(Debug)]
+pub struct ValidatedOrder {
+    order: Order,
+    price: Option<f64>,
+}
+
+impl ValidatedOrder {
+    pub fn order(&self) -> &Order {
+        &self.order
+    }
+
+    /// The limit price, None for a market order
+    pub fn price(&self) -> Option<f64> {
+        self.price
+    }
+
+    pub fn into_parts(self) -> (Order, Option<f64>) {
+        (self.order, self.price)
+    }
+}
+
+impl Order {
+    pub fn builder() -> OrderBuilder {
+        OrderBuilder::default()
+    }
+}
+
+impl OrderBuilder {
+    pub fn side(mut self, side: OrderType) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    /// Limit price as a decimal string; leave unset for a market order
+    pub fn price(mut self, price: &str) -> Self {
+        self.price = Some(price.to_string());
+        self
+    }
+
+    /// Size as a decimal string
+    pub fn size(mut self, size: &str) -> Self {
+        self.size = Some(size.to_string());
+        self
+    }
+
+    pub fn tif(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn account(mut self, account: AccountId) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    pub fn expires_at(mut self, expires_at: Timestamp) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Opaque client tag, at most `MAX_TAG_LEN` bytes
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Validate the fields and build the order
+    ///
+    /// # Returns
+    /// * `Result<ValidatedOrder, String>` - The order, Err describing the first invalid field
+    pub fn build(self) -> Result<ValidatedOrder, String> {
+        let side = self.side.ok_or("Order side is required")?;
+        let size = parse_decimal(
+            "size",
+            self.size.as_deref().ok_or("Order size is required")?,
+        )?;
+        let price = match self.price.as_deref() {
+            Some(price) => {
+                let decimals = price
+                    .split_once('.')
+                    .map_or(0, |(_, fraction)| fraction.len());
+                if decimals > PRICE_DECIMALS {
+                    return Err(format!(
+                        "Order price {} has more than {} decimal places",
+                        price, PRICE_DECIMALS
+                    ));
+                }
+                Some(parse_decimal("price", price)?)
+            }
+            None => None,
+        };
+        // Market orders never rest, so they are already immediate-or-cancel
+        let rests = !matches!(self.time_in_force, TimeInForce::Gtc | TimeInForce::Ioc);
+        if price.is_none() && (self.expires_at.is_some() || rests) {
+            return Err("Market orders cannot have a time in force or expiry".to_string());
+        }
+        if self.time_in_force == TimeInForce::Gtd && self.expires_at.is_none() {
+            return Err("Good-till-date orders need an expiry time".to_string());
+        }
+
+        let mut order = Order::new(side, size).with_time_in_force(self.time_in_force);
+        if let Some(account) = self.account {
+            order = order.with_account(account);
+        }
+        if let Some(expires_at) = self.expires_at {
+            order = order.with_expiry(expires_at);
+        }
+        if let Some(tag) = self.tag {
+            order = order.with_tag(tag);
+            order.check_tag()?;
+        }
+        Ok(ValidatedOrder { order, price })
+    }
+}
+
+/// Parse a strictly positive, finite decimal
+fn parse_decimal(field: &str, text: &str) -> Result<f64, String> {
+    let value: f64 = text
+        .trim()
+        .parse()
+        .map_err(|_| format!("Order {} {:?} is not a number", field, text))?;
+    if !value.is_finite() || value <= 0.0 {
+        return Err(format!("Order {} must be positive, got {}", field, text));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_validates_fields() {
+        let order = Order::builder()
+            .side(OrderType::Ask)
+            .price("99.5")
+            .size("2")
+            .account(3)
+            .expires_at(1_000)
+            .build()
+            .unwrap();
+        assert_eq!(order.price(), Some(99.5));
+        assert_eq!(order.order().size(), 2.0);
+        assert_eq!(order.order().account(), Some(3));
+        assert_eq!(order.order().expires_at(), Some(1_000));
+
+        let market = Order::builder()
+            .side(OrderType::Bid)
+            .size("1")
+            .build()
+            .unwrap();
+        assert_eq!(market.price(), None);
+        assert!(Order::builder()
+            .side(OrderType::Bid)
+            .size("1")
+            .tif(TimeInForce::Ioc)
+            .build()
+            .is_ok());
+
+        let invalid = [
+            Order::builder().size("1"),
+            Order::builder().side(OrderType::Bid),
+            Order::builder().side(OrderType::Bid).size("abc"),
+            Order::builder().side(OrderType::Bid).size("0"),
+            Order::builder().side(OrderType::Bid).size("NaN"),
+            Order::builder().side(OrderType::Bid).size("1").price("-5"),
+            Order::builder()
+                .side(OrderType::Bid)
+                .size("1")
+                .price("1.000001"),
+            Order::builder()
+                .side(OrderType::Bid)
+                .size("1")
+                .tif(TimeInForce::Day),
+            Order::builder()
+                .side(OrderType::Bid)
+                .size("1")
+                .price("1")
+                .tif(TimeInForce::Gtd),
+            Order::builder()
+                .side(OrderType::Bid)
+                .size("1")
+                .tag(&"x".repeat(65)),
+        ];
+        for builder in invalid {
+            assert!(builder.build().is_err());
+        }
+    }
+}
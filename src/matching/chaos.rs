@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+
+/// Faults to inject into event publication
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaultConfig {
+    /// Probability in `[0, 1]` that an event is dropped
+    pub drop_rate: f64,
+    /// Number of publications a batch of events is held back for
+    pub delay: usize,
+}
+
+/// Drops and delays published events according to a [`FaultConfig`]
+///
+/// Randomness comes from a seeded generator so that a failing run can be replayed.
+#[derive(Debug, Clone)]
+pub struct FaultInjector<T> {
+    config: FaultConfig,
+    state: u64,
+    held: VecDeque<Vec<T>>,
+}
+
+impl<T> FaultInjector<T> {
+    pub fn new(config: FaultConfig, seed: u64) -> Self {
+        FaultInjector {
+            config,
+            state: seed,
+            held: VecDeque::new(),
+        }
+    }
+
+    pub fn config(&self) -> FaultConfig {
+        self.config
+    }
+
+    /// Pass a batch of events through the injector
+    ///
+    /// # Arguments
+    /// * `events` - The events that would have been published
+    ///
+    /// # Returns
+    /// * `Vec<T>` - The events to actually publish: whatever batch has been held back long
+    ///   enough, minus the dropped events
+    pub fn publish(&mut self, events: Vec<T>) -> Vec<T> {
+        let mut kept = Vec::with_capacity(events.len());
+        for event in events {
+            if self.next_unit() >= self.config.drop_rate {
+                kept.push(event);
+            }
+        }
+        self.held.push_back(kept);
+        if self.held.len() > self.config.delay {
+            self.held.pop_front().unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// A uniform sample from `[0, 1)` (SplitMix64)
+    fn next_unit(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_batches() {
+        let config = FaultConfig {
+            drop_rate: 0.0,
+            delay: 2,
+        };
+        let mut injector = FaultInjector::new(config, 7);
+        assert!(injector.publish(vec![1]).is_empty());
+        assert!(injector.publish(vec![2, 3]).is_empty());
+        assert_eq!(injector.publish(vec![]), vec![1]);
+        assert_eq!(injector.publish(vec![]), vec![2, 3]);
+    }
+
+    #[test]
+    fn drops_events() {
+        let config = FaultConfig {
+            drop_rate: 1.0,
+            delay: 0,
+        };
+        let mut injector = FaultInjector::new(config, 7);
+        assert!(injector.publish(vec![1, 2, 3]).is_empty());
+
+        let config = FaultConfig {
+            drop_rate: 0.5,
+            delay: 0,
+        };
+        let mut injector = FaultInjector::new(config, 7);
+        let kept = injector.publish((0..1_000).collect()).len();
+        assert!(kept > 400 && kept < 600);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Nanoseconds since the UNIX epoch
+pub type Timestamp = u64;
+
+/// Source of time for the matching engine
+///
+/// Everything time-dependent (protection windows, expiries, sessions) reads the time through a
+/// `Clock` so that tests and simulations can control it.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Timestamp;
+}
+
+/// Wall-clock time
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as Timestamp)
+            .unwrap_or(0)
+    }
+}
+
+/// A clock that only moves when told to
+///
+/// Clones share the same underlying time, so a test can keep one handle and pass another to the
+/// engine.
+///
+/// # Example
+/// ```
+/// use orderbook::matching::clock::{Clock, ManualClock};
+/// let clock = ManualClock::new(1_000);
+/// clock.advance(500);
+/// assert_eq!(clock.now(), 1_500);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ManualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new(start: Timestamp) -> Self {
+        ManualClock {
+            now: Arc::new(AtomicU64::new(start)),
+        }
+    }
+
+    pub fn set(&self, now: Timestamp) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, nanos: u64) {
+        self.now.fetch_add(nanos, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        self.now.load(Ordering::SeqCst)
+    }
+}
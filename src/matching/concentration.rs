@@ -0,0 +1,117 @@
+use super::clock::Timestamp;
+use super::depth::{Depth, DepthLevel};
+use super::numeric::exact_sum;
+
+/// How liquidity concentration is measured and how often it is sampled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConcentrationConfig {
+    /// Levels nearest the touch that count as near liquidity
+    pub near_levels: usize,
+    /// Levels looked at in total
+    pub far_levels: usize,
+    /// Nanoseconds between samples
+    pub interval: u64,
+}
+
+impl Default for ConcentrationConfig {
+    /// Top 5 levels against the top 50, sampled every second
+    fn default() -> Self {
+        ConcentrationConfig {
+            near_levels: 5,
+            far_levels: 50,
+            interval: 1_000_000_000,
+        }
+    }
+}
+
+/// How one side's resting size is spread over its levels
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SideConcentration {
+    /// Share of the size in the far levels that rests in the near levels, None for an empty side
+    pub near_share: Option<f64>,
+    /// Shannon entropy of the size over the far levels, divided by its maximum: 0 when all the
+    /// size rests at one level, 1 when every level holds the same size
+    pub entropy: f64,
+    /// Number of levels measured
+    pub levels: usize,
+}
+
+impl SideConcentration {
+    fn measure(levels: &[DepthLevel], near_levels: usize) -> Self {
+        let total = exact_sum(levels.iter().map(|level| level.volume));
+        if total <= 0.0 {
+            return SideConcentration::default();
+        }
+        let near = exact_sum(levels.iter().take(near_levels).map(|level| level.volume));
+        let entropy = match levels.len() > 1 {
+            true => {
+                let entropy = -exact_sum(levels.iter().map(|level| {
+                    let share = level.volume / total;
+                    match share > 0.0 {
+                        true => share * share.ln(),
+                        false => 0.0,
+                    }
+                }));
+                entropy / (levels.len() as f64).ln()
+            }
+            false => 0.0,
+        };
+        SideConcentration {
+            near_share: Some(near / total),
+            entropy,
+            levels: levels.len(),
+        }
+    }
+}
+
+/// Liquidity concentration of a book at one point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LiquidityConcentration {
+    pub at: Timestamp,
+    pub bids: SideConcentration,
+    pub asks: SideConcentration,
+}
+
+impl LiquidityConcentration {
+    /// Measure a depth snapshot taken at `at`
+    ///
+    /// # Arguments
+    /// * `depth` - The book's top `far_levels` levels per side
+    /// * `near_levels` - Levels nearest the touch that count as near liquidity
+    /// * `at` - When the snapshot was taken
+    pub fn measure(depth: &Depth, near_levels: usize, at: Timestamp) -> Self {
+        LiquidityConcentration {
+            at,
+            bids: SideConcentration::measure(&depth.bids, near_levels),
+            asks: SideConcentration::measure(&depth.asks, near_levels),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::{Order, OrderBook, OrderType};
+
+    #[test]
+    fn measures_share_near_the_touch_and_entropy() {
+        let mut orderbook = OrderBook::new();
+        for (price, size) in [(99.0, 3.0), (98.0, 1.0), (97.0, 1.0), (96.0, 1.0)] {
+            orderbook.add(Order::new(OrderType::Bid, size), price);
+        }
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 101.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 102.0);
+
+        let concentration = LiquidityConcentration::measure(&orderbook.depth(50), 1, 7);
+        assert_eq!(concentration.at, 7);
+        assert_eq!(concentration.bids.near_share, Some(0.5));
+        assert_eq!(concentration.bids.levels, 4);
+        assert!(concentration.bids.entropy > 0.0 && concentration.bids.entropy < 1.0);
+        assert_eq!(concentration.asks.near_share, Some(0.5));
+        assert!((concentration.asks.entropy - 1.0).abs() < 1e-12);
+
+        let empty = LiquidityConcentration::measure(&Depth::default(), 5, 0);
+        assert_eq!(empty.bids.near_share, None);
+        assert_eq!(empty.bids.entropy, 0.0);
+    }
+}
@@ -0,0 +1,382 @@
+use super::bands::{BandConfig, Tier};
+use super::engine::Engine;
+use super::market::{InstrumentRegistry, MarketConfig};
+use super::orderbook::{
+    from_lots, to_lots, OrderBook, Price, TradingPair, PRICE_DECIMALS, SIZE_DECIMALS,
+};
+use std::fmt;
+
+/// One market's settings from a configuration file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketSettings {
+    pub pair: TradingPair,
+    pub config: MarketConfig,
+    /// Limit-up/limit-down bands, None if the market has none
+    pub bands: Option<BandConfig>,
+}
+
+/// An engine configuration: the markets to list and how each one trades
+///
+/// The file is a list of `[market BASE/QUOTE]` sections of `key = value` lines, with `#` starting
+/// a comment. Keys left out keep their defaults. `bands = one` or `bands = two` enables price
+/// bands with that tier's defaults, which the `band_*` keys then override.
+///
+/// # Example
+/// ```
+/// use orderbook::matching::config::EngineConfig;
+/// let config = EngineConfig::parse("[market BTC/USD]\nprice_precision = 2\nbands = one").unwrap();
+/// assert_eq!(config.markets()[0].config.tick_size(), 0.01);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EngineConfig {
+    markets: Vec<MarketSettings>,
+}
+
+impl EngineConfig {
+    /// Parse and validate a configuration file
+    ///
+    /// # Returns
+    /// * `Result<EngineConfig, String>` - Err listing every problem found, one per line, rather than
+    ///   stopping at the first
+    pub fn parse(text: &str) -> Result<EngineConfig, String> {
+        let mut markets: Vec<MarketSettings> = Vec::new();
+        let mut errors = Vec::new();
+        let mut band_overrides = false;
+        // Index of the market being read, None before the first section and after a bad header
+        let mut current: Option<usize> = None;
+        let mut in_section = false;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                if let Some(market) = current.map(|index| &markets[index]) {
+                    check_market(market, band_overrides, &mut errors);
+                }
+                band_overrides = false;
+                current = None;
+                in_section = true;
+                let pair = match header.trim().strip_prefix("market ") {
+                    Some(pair) => pair.parse::<TradingPair>(),
+                    None => Err(format!("Unknown section {:?}", header)),
+                };
+                match pair {
+                    Ok(pair) if markets.iter().any(|market| market.pair == pair) => {
+                        let name: String = pair.into();
+                        errors.push(format!(
+                            "line {}: Market {} is listed twice",
+                            line_number, name
+                        ));
+                    }
+                    Ok(pair) => {
+                        current = Some(markets.len());
+                        markets.push(MarketSettings {
+                            pair,
+                            config: MarketConfig::default(),
+                            bands: None,
+                        });
+                    }
+                    Err(e) => errors.push(format!("line {}: {}", line_number, e)),
+                }
+                continue;
+            }
+            let market = match current {
+                Some(index) => &mut markets[index],
+                // The section header was already reported
+                None if in_section => continue,
+                None => {
+                    errors.push(format!(
+                        "line {}: Setting outside a [market] section",
+                        line_number
+                    ));
+                    continue;
+                }
+            };
+            let result = match line.split_once('=') {
+                Some((key, value)) => {
+                    band_overrides |= key.trim().starts_with("band_");
+                    set(market, key.trim(), value.trim())
+                }
+                None => Err(format!("Expected key = value, got {:?}", line)),
+            };
+            if let Err(e) = result {
+                errors.push(format!("line {}: {}", line_number, e));
+            }
+        }
+        if let Some(market) = current.map(|index| &markets[index]) {
+            check_market(market, band_overrides, &mut errors);
+        }
+
+        match errors.is_empty() {
+            true => Ok(EngineConfig { markets }),
+            false => Err(errors.join("\n")),
+        }
+    }
+
+    pub fn markets(&self) -> &[MarketSettings] {
+        &self.markets
+    }
+
+    /// The order rules of every configured market
+    pub fn instruments(&self) -> InstrumentRegistry {
+        let mut instruments = InstrumentRegistry::new();
+        for market in &self.markets {
+            instruments.register(market.pair.clone(), market.config.clone());
+        }
+        instruments
+    }
+
+    /// An engine with an empty book for every configured market
+    pub fn engine(&self) -> Engine {
+        let mut engine = Engine::new();
+        for market in &self.markets {
+            let mut orderbook = OrderBook::new();
+            if let Some(bands) = market.bands {
+                orderbook.set_price_bands(bands);
+            }
+            engine.add_orderbook(market.pair.clone(), orderbook);
+        }
+        engine.set_instruments(self.instruments());
+        engine
+    }
+}
+
+/// Apply one `key = value` line to a market
+fn set(market: &mut MarketSettings, key: &str, value: &str) -> Result<(), String> {
+    let config = &mut market.config;
+    match key {
+        "display_name" => config.display_name = Some(value.to_string()),
+        "price_precision" => config.price_precision = number(key, value)?,
+        "size_precision" => config.size_precision = number(key, value)?,
+        "tick_size" => config.tick_size = Some(positive(key, value)?),
+        "lot_size" => config.lot_size = Some(positive(key, value)?),
+        "min_size" => config.min_size = Some(positive(key, value)?),
+        "max_size" => config.max_size = Some(positive(key, value)?),
+        "min_notional" => config.min_notional = Some(positive(key, value)?),
+        "max_notional" => config.max_notional = Some(positive(key, value)?),
+        "price_collar" => config.price_collar = Some(positive(key, value)?),
+        "listed" => {
+            config.listed = value
+                .parse()
+                .map_err(|_| format!("listed must be true or false, got {:?}", value))?
+        }
+        "bands" => {
+            let tier = match value {
+                "one" => Tier::One,
+                "two" => Tier::Two,
+                _ => return Err(format!("bands must be one or two, got {:?}", value)),
+            };
+            market.bands = Some(BandConfig::tier(tier));
+        }
+        _ if key.starts_with("band_") => {
+            let bands = market
+                .bands
+                .as_mut()
+                .ok_or_else(|| format!("{} needs bands = one or two first", key))?;
+            match key {
+                "band_percent" => bands.percent = positive(key, value)?,
+                "band_reference_window" => bands.reference_window = number(key, value)?,
+                "band_pause" => bands.pause = number(key, value)?,
+                "band_reopen_multiplier" => bands.reopen_multiplier = positive(key, value)?,
+                _ => return Err(format!("Unknown setting {:?}", key)),
+            }
+        }
+        _ => return Err(format!("Unknown setting {:?}", key)),
+    }
+    Ok(())
+}
+
+fn number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be a whole number, got {:?}", key, value))
+}
+
+fn positive(key: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        _ => Err(format!(
+            "{} must be a positive number, got {:?}",
+            key, value
+        )),
+    }
+}
+
+/// Check the settings of a finished market section against each other
+fn check_market(market: &MarketSettings, band_overrides: bool, errors: &mut Vec<String>) {
+    let name: String = market.pair.clone().into();
+    let config = &market.config;
+    let mut problems = Vec::new();
+    if config.price_precision > PRICE_DECIMALS {
+        problems.push(format!(
+            "price_precision {} is finer than the engine's {} decimals",
+            config.price_precision, PRICE_DECIMALS
+        ));
+    }
+    if config.size_precision > SIZE_DECIMALS {
+        problems.push(format!(
+            "size_precision {} is finer than the engine's {} decimals",
+            config.size_precision, SIZE_DECIMALS
+        ));
+    }
+    if let Some(tick_size) = config.tick_size {
+        if f64::from(Price::new(tick_size)) != tick_size {
+            problems.push(format!(
+                "tick_size {} is not a whole number of the engine's {} decimals",
+                tick_size, PRICE_DECIMALS
+            ));
+        }
+    }
+    if let Some(lot_size) = config.lot_size {
+        if from_lots(to_lots(lot_size)) != lot_size {
+            problems.push(format!(
+                "lot_size {} is not a whole number of the engine's {} decimals",
+                lot_size, SIZE_DECIMALS
+            ));
+        }
+    }
+    if let (Some(min), Some(max)) = (config.min_size, config.max_size) {
+        if min > max {
+            problems.push(format!("min_size {} is above max_size {}", min, max));
+        }
+    }
+    if let (Some(min), Some(max)) = (config.min_notional, config.max_notional) {
+        if min > max {
+            problems.push(format!(
+                "min_notional {} is above max_notional {}",
+                min, max
+            ));
+        }
+    }
+    if let Some(min) = config.min_size {
+        if let Err(e) = config.check_size(min) {
+            problems.push(format!("min_size: {}", e));
+        }
+    }
+    if let Some(bands) = market.bands {
+        if bands.percent >= 100.0 {
+            problems.push(format!("band_percent {} must be below 100", bands.percent));
+        }
+        if bands.reopen_multiplier < 1.0 {
+            problems.push(format!(
+                "band_reopen_multiplier {} would narrow the bands",
+                bands.reopen_multiplier
+            ));
+        }
+    }
+    if band_overrides && market.bands.is_none() {
+        problems.push("band settings given without bands".to_string());
+    }
+    errors.extend(
+        problems
+            .into_iter()
+            .map(|problem| format!("{}: {}", name, problem)),
+    );
+}
+
+impl fmt::Display for EngineConfig {
+    /// The effective configuration, defaults included, in the file format
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, market) in self.markets.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            let name: String = market.pair.clone().into();
+            let config = &market.config;
+            writeln!(f, "[market {}]", name)?;
+            if let Some(display_name) = &config.display_name {
+                writeln!(f, "display_name = {}", display_name)?;
+            }
+            writeln!(f, "price_precision = {}", config.price_precision)?;
+            writeln!(f, "size_precision = {}", config.size_precision)?;
+            let limits = [
+                ("tick_size", config.tick_size),
+                ("lot_size", config.lot_size),
+                ("min_size", config.min_size),
+                ("max_size", config.max_size),
+                ("min_notional", config.min_notional),
+                ("max_notional", config.max_notional),
+                ("price_collar", config.price_collar),
+            ];
+            for (key, value) in limits {
+                if let Some(value) = value {
+                    writeln!(f, "{} = {}", key, value)?;
+                }
+            }
+            writeln!(f, "listed = {}", config.listed)?;
+            if let Some(bands) = market.bands {
+                writeln!(f, "bands = one")?;
+                writeln!(f, "band_percent = {}", bands.percent)?;
+                writeln!(f, "band_reference_window = {}", bands.reference_window)?;
+                writeln!(f, "band_pause = {}", bands.pause)?;
+                writeln!(f, "band_reopen_multiplier = {}", bands.reopen_multiplier)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "
+        # Spot markets
+        [market BTC/USD]
+        display_name = Bitcoin / US Dollar
+        price_precision = 2
+        size_precision = 4
+        min_size = 0.001
+        tick_size = 0.5
+        bands = two
+        band_percent = 7.5
+
+        [market eth-usd]
+        listed = false
+    ";
+
+    #[test]
+    fn parses_validates_and_prints_the_effective_config() {
+        let config = EngineConfig::parse(CONFIG).unwrap();
+        let btc = &config.markets()[0];
+        assert_eq!(btc.config.min_size, Some(0.001));
+        assert_eq!(btc.config.tick_size(), 0.5);
+        assert_eq!(btc.bands.unwrap().percent, 7.5);
+        assert_eq!(btc.bands.unwrap().pause, BandConfig::tier(Tier::Two).pause);
+        assert!(!config.markets()[1].config.listed);
+
+        // The printed config reads back as the same config
+        assert_eq!(EngineConfig::parse(&config.to_string()), Ok(config.clone()));
+        let engine = config.engine();
+        assert!(engine
+            .instruments()
+            .get(&"ETH/USD".parse().unwrap())
+            .is_some());
+
+        let broken = "
+            price_precision = 2
+            [market BTC/USD]
+            min_size = 0.00001
+            size_precision = 4
+            band_pause = 10
+            colour = blue
+            [market btc_usd]
+        ";
+        let errors = EngineConfig::parse(broken).unwrap_err();
+        let errors: Vec<&str> = errors.lines().collect();
+        assert_eq!(
+            errors,
+            vec![
+                "line 2: Setting outside a [market] section",
+                "line 6: band_pause needs bands = one or two first",
+                "line 7: Unknown setting \"colour\"",
+                "BTC/USD: min_size: Order size 0.00001 is not a multiple of the lot size 0.0001",
+                "BTC/USD: band settings given without bands",
+                "line 8: Market BTC/USD is listed twice",
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use super::backend::BookBackend;
+use super::depth::DepthLevel;
+use super::orderbook::{MarketStatus, Order, OrderId, OrderType, Price};
+use super::trade::Trade;
+
+/// Define a `#[test]` per conformance check for a [`BookBackend`]
+///
+/// # Example
+/// ```ignore
+/// orderbook::book_backend_conformance!(my_backend, MyBackend::new);
+/// ```
+#[macro_export]
+macro_rules! book_backend_conformance {
+    ($name:ident, $new:expr) => {
+        mod $name {
+            use super::*;
+
+            #[test]
+            fn price_time_priority() {
+                $crate::matching::conformance::price_time_priority($new);
+            }
+
+            #[test]
+            fn cancellation() {
+                $crate::matching::conformance::cancellation($new);
+            }
+
+            #[test]
+            fn auction_uncross() {
+                $crate::matching::conformance::auction_uncross($new);
+            }
+
+            #[test]
+            fn invariants() {
+                $crate::matching::conformance::invariants($new);
+            }
+        }
+    };
+}
+
+/// Run every conformance check against fresh books from `new`
+pub fn run_all<B: BookBackend>(new: impl Fn() -> B) {
+    price_time_priority(&new);
+    cancellation(&new);
+    auction_uncross(&new);
+    invariants(&new);
+}
+
+fn makers(trades: &[Trade]) -> Vec<Option<OrderId>> {
+    trades.iter().map(|trade| trade.maker_order_id).collect()
+}
+
+/// Orders fill best price first, and in arrival order within a price
+pub fn price_time_priority<B: BookBackend>(new: impl Fn() -> B) {
+    let mut book = new();
+    let far = book.add(Order::new(OrderType::Ask, 1.0), 101.0);
+    let first = book.add(Order::new(OrderType::Ask, 1.0), 100.0);
+    let second = book.add(Order::new(OrderType::Ask, 1.0), 100.0);
+
+    let trades = book.place_market_order(&mut Order::new(OrderType::Bid, 2.5));
+    assert_eq!(makers(&trades), vec![Some(first), Some(second), Some(far)]);
+    assert_eq!(trades[0].price, Price::new(100.0));
+    assert_eq!(trades[2].price, Price::new(101.0));
+    assert_eq!(trades[2].size, 0.5);
+    assert_eq!(book.depth(1).asks[0].volume, 0.5);
+}
+
+/// Cancelled orders leave the book at once, never trade and cannot be cancelled twice
+pub fn cancellation<B: BookBackend>(new: impl Fn() -> B) {
+    let mut book = new();
+    let first = book.add(Order::new(OrderType::Bid, 1.0), 99.0);
+    let second = book.add(Order::new(OrderType::Bid, 2.0), 99.0);
+    let third = book.add(Order::new(OrderType::Bid, 3.0), 99.0);
+
+    assert!(book.cancel(second).is_some());
+    assert!(book.cancel(second).is_none());
+    assert!(book.cancel(OrderId::MAX).is_none());
+    let level = book.depth(1).bids[0];
+    assert_eq!((level.volume, level.order_count), (4.0, 2));
+
+    let trades = book.place_market_order(&mut Order::new(OrderType::Ask, 10.0));
+    assert_eq!(makers(&trades), vec![Some(first), Some(third)]);
+    assert!(book.depth(1).bids.is_empty());
+    assert!(book.cancel(first).is_none(), "filled orders are not open");
+}
+
+/// Orders entered during a halt rest without matching, and the auction that ends it matches
+/// everything that crosses at one price
+pub fn auction_uncross<B: BookBackend>(new: impl Fn() -> B) {
+    let mut book = new();
+    book.halt();
+    assert_eq!(book.status(), MarketStatus::Halted);
+    let placed = [
+        book.place_limit_order(Order::new(OrderType::Bid, 2.0), 101.0),
+        book.place_limit_order(Order::new(OrderType::Bid, 1.0), 98.0),
+        book.place_limit_order(Order::new(OrderType::Ask, 1.0), 99.0),
+        book.place_limit_order(Order::new(OrderType::Ask, 2.0), 100.0),
+    ];
+    for placement in placed {
+        assert!(placement
+            .expect("orders rest during a halt")
+            .trades
+            .is_empty());
+    }
+
+    book.resume();
+    assert_eq!(book.status(), MarketStatus::Auction);
+    let trades = book.uncross();
+    assert_eq!(book.status(), MarketStatus::Open);
+    assert!(!trades.is_empty());
+    assert!(trades.iter().all(|trade| trade.price == trades[0].price));
+    assert_eq!(trades.iter().map(|trade| trade.size).sum::<f64>(), 2.0);
+    check_book(&book);
+}
+
+/// Random order flow never leaves the book crossed, unsorted or holding empty levels, and no
+/// trade goes through a taker's limit price
+pub fn invariants<B: BookBackend>(new: impl Fn() -> B) {
+    let mut book = new();
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+    let mut next = |bound: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % bound
+    };
+    let mut resting = Vec::new();
+    for _ in 0..2_000 {
+        let side = match next(2) {
+            0 => OrderType::Bid,
+            _ => OrderType::Ask,
+        };
+        let size = (next(10) + 1) as f64;
+        match next(10) {
+            0..=5 => {
+                let price = 95.0 + next(11) as f64;
+                let placement = book
+                    .place_limit_order(Order::new(side, size), price)
+                    .expect("limit orders on an open book are accepted");
+                for trade in &placement.trades {
+                    let through = match side {
+                        OrderType::Bid => trade.price > Price::new(price),
+                        OrderType::Ask => trade.price < Price::new(price),
+                    };
+                    assert!(
+                        !through,
+                        "trade at {:?} through limit {}",
+                        trade.price, price
+                    );
+                }
+                resting.push(placement.id);
+            }
+            6..=7 if !resting.is_empty() => {
+                let index = next(resting.len() as u64) as usize;
+                book.cancel(resting.swap_remove(index));
+            }
+            _ => {
+                let trades = book.place_market_order(&mut Order::new(side, size));
+                assert!(trades.iter().map(|trade| trade.size).sum::<f64>() <= size);
+            }
+        }
+        check_book(&book);
+    }
+}
+
+/// Check that a book's levels are well formed and its sides do not cross
+fn check_book<B: BookBackend>(book: &B) {
+    let depth = book.depth(usize::MAX);
+    let well_formed = |levels: &[DepthLevel]| {
+        levels
+            .iter()
+            .all(|level| level.volume > 0.0 && level.order_count > 0)
+    };
+    assert!(well_formed(&depth.bids) && well_formed(&depth.asks));
+    assert!(depth
+        .bids
+        .windows(2)
+        .all(|pair| pair[0].price > pair[1].price));
+    assert!(depth
+        .asks
+        .windows(2)
+        .all(|pair| pair[0].price < pair[1].price));
+    if let (Some(bid), Some(ask)) = (depth.bids.first(), depth.asks.first()) {
+        assert!(bid.price < ask.price, "book crossed at {:?}", bid.price);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matching::clock::ManualClock;
+    use crate::matching::orderbook::OrderBook;
+    use std::sync::Arc;
+
+    crate::book_backend_conformance!(reference_book, || OrderBook::with_clock(Arc::new(
+        ManualClock::new(0)
+    )));
+}
@@ -0,0 +1,103 @@
+use super::clock::Timestamp;
+use super::numeric::exact_sum;
+use super::orderbook::{from_lots, Order, OrderType, Price};
+use super::trade::Trade;
+use std::collections::VecDeque;
+
+/// A hidden book of midpoint-pegged orders
+///
+/// Orders rest invisibly (they never show up in the lit book's limits or depth) and match each
+/// other in time priority at the lit book's midprice. While the lit book is one-sided there is no
+/// midprice, so nothing crosses.
+#[derive(Debug)]
+pub struct DarkPool {
+    min_size: f64,
+    bids: VecDeque<Order>,
+    asks: VecDeque<Order>,
+}
+
+impl DarkPool {
+    /// Create a dark pool that only accepts orders of at least `min_size`
+    pub fn new(min_size: f64) -> Self {
+        DarkPool {
+            min_size,
+            bids: VecDeque::new(),
+            asks: VecDeque::new(),
+        }
+    }
+
+    pub fn min_size(&self) -> f64 {
+        self.min_size
+    }
+
+    /// Queue an order in the pool
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the order is below the minimum size
+    pub fn add(&mut self, order: Order) -> Result<(), String> {
+        if order.size() < self.min_size {
+            return Err(format!(
+                "Dark order size {} is below the minimum of {}",
+                order.size(),
+                self.min_size
+            ));
+        }
+        match order.order_type() {
+            OrderType::Bid => self.bids.push_back(order),
+            OrderType::Ask => self.asks.push_back(order),
+        }
+        Ok(())
+    }
+
+    /// Total hidden size resting on one side
+    pub fn volume(&self, side: OrderType) -> f64 {
+        let orders = match side {
+            OrderType::Bid => &self.bids,
+            OrderType::Ask => &self.asks,
+        };
+        exact_sum(orders.iter().map(|order| order.size()))
+    }
+
+    /// Match resting bids against resting asks at `midpoint`
+    ///
+    /// The more recently submitted order of each pair is treated as the aggressor. Trade ids are
+    /// left for the order book to assign.
+    pub fn cross(&mut self, midpoint: Price, now: Timestamp) -> Vec<Trade> {
+        let mut trades = Vec::new();
+        while let (Some(bid), Some(ask)) = (self.bids.front_mut(), self.asks.front_mut()) {
+            let size = bid.lots().min(ask.lots());
+            let (maker, taker) = match bid.id() > ask.id() {
+                true => (&*ask, &*bid),
+                false => (&*bid, &*ask),
+            };
+            trades.push(Trade {
+                id: 0,
+                price: midpoint,
+                size: from_lots(size),
+                aggressor_side: taker.order_type(),
+                maker_order_id: maker.id(),
+                taker_order_id: taker.id(),
+                maker_account: maker.account(),
+                taker_account: taker.account(),
+                maker_tag: maker.tag().map(str::to_string),
+                taker_tag: taker.tag().map(str::to_string),
+                maker_version: maker.version(),
+                taker_version: taker.version(),
+                transaction_time: now,
+                triggered_stops: false,
+                triggered_band: false,
+                dark: true,
+            });
+            bid.reduce(size);
+            ask.reduce(size);
+
+            if bid.is_filled() {
+                self.bids.pop_front();
+            }
+            if ask.is_filled() {
+                self.asks.pop_front();
+            }
+        }
+        trades
+    }
+}
@@ -0,0 +1,114 @@
+use super::orderbook::{to_lots, OrderType, Price};
+
+/// Levels per side covered by [`Depth::checksum`]
+pub const CHECKSUM_LEVELS: usize = 25;
+
+/// Aggregated view of one price level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthLevel {
+    pub price: Price,
+    pub volume: f64,
+    /// Number of open orders resting at the level
+    pub order_count: usize,
+}
+
+/// Where a resting order stands in its level's queue
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueuePosition {
+    /// Open orders ahead of it at the same price
+    pub orders_ahead: usize,
+    /// Combined size of those orders
+    pub size_ahead: f64,
+}
+
+/// Top levels of both sides of a book, best price first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Depth {
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+}
+
+/// The best level on each side of a book, with the spread and midprice between them
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TopOfBook {
+    pub bid: Option<DepthLevel>,
+    pub ask: Option<DepthLevel>,
+    /// None unless both sides have orders
+    pub spread: Option<f64>,
+    /// None unless both sides have orders
+    pub mid: Option<f64>,
+}
+
+/// What a market order would pay if it walked the book as it stands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FillCost {
+    /// Size that would fill, at most the quantity asked for
+    pub filled: f64,
+    /// Volume-weighted average price of the fills, None if nothing would fill
+    pub average_price: Option<f64>,
+    /// Price of the last level reached, None if nothing would fill
+    pub worst_price: Option<f64>,
+    /// Whether the book holds enough liquidity to fill the whole quantity
+    pub sufficient: bool,
+}
+
+/// A change to a single level between two depth snapshots
+///
+/// A level that disappeared is reported with zero volume and order count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthUpdate {
+    pub side: OrderType,
+    pub level: DepthLevel,
+}
+
+impl Depth {
+    /// The level updates that turn `previous` into this snapshot
+    pub fn diff(&self, previous: &Depth) -> Vec<DepthUpdate> {
+        let mut updates = Vec::new();
+        for (side, current, previous) in [
+            (OrderType::Bid, &self.bids, &previous.bids),
+            (OrderType::Ask, &self.asks, &previous.asks),
+        ] {
+            for level in current {
+                if !previous.contains(level) {
+                    updates.push(DepthUpdate {
+                        side,
+                        level: *level,
+                    });
+                }
+            }
+            for level in previous {
+                if !current.iter().any(|current| current.price == level.price) {
+                    updates.push(DepthUpdate {
+                        side,
+                        level: DepthLevel {
+                            price: level.price,
+                            volume: 0.0,
+                            order_count: 0,
+                        },
+                    });
+                }
+            }
+        }
+        updates
+    }
+
+    /// FNV-1a hash of the price and size of the top [`CHECKSUM_LEVELS`] levels on each side
+    ///
+    /// Prices and sizes are hashed as whole ticks and lots, so a book rebuilt from updates hashes
+    /// the same as the book it mirrors.
+    pub fn checksum(&self) -> u32 {
+        let mut hash: u32 = 0x811c_9dc5;
+        for (side, levels) in [(b'b', &self.bids), (b'a', &self.asks)] {
+            for level in levels.iter().take(CHECKSUM_LEVELS) {
+                let bytes = std::iter::once(side)
+                    .chain(level.price.ticks().to_le_bytes())
+                    .chain(to_lots(level.volume).to_le_bytes());
+                for byte in bytes {
+                    hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+                }
+            }
+        }
+        hash
+    }
+}
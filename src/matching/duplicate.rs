@@ -0,0 +1,87 @@
+use super::clock::Timestamp;
+use super::orderbook::{AccountId, OrderType, Price};
+use std::collections::VecDeque;
+
+/// What to do with an order that looks like a resubmission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// Accept the order and publish a `SuspectedDuplicate` event
+    Flag,
+    /// Reject the order
+    Reject,
+}
+
+/// Duplicate order detection for one market
+///
+/// An order is a suspected duplicate when an order with the same account, side, price and size
+/// was placed within the last `window` nanoseconds. Orders carrying a client order id are not
+/// checked, since the id already tells retries apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateCheck {
+    pub window: u64,
+    pub action: DuplicateAction,
+}
+
+type OrderKey = (Option<AccountId>, OrderType, Price, u64);
+
+/// Remembers recently placed orders for a [`DuplicateCheck`]
+#[derive(Debug)]
+pub struct DuplicateDetector {
+    check: DuplicateCheck,
+    recent: VecDeque<(Timestamp, OrderKey)>,
+}
+
+impl DuplicateDetector {
+    pub fn new(check: DuplicateCheck) -> Self {
+        DuplicateDetector {
+            check,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn check(&self) -> DuplicateCheck {
+        self.check
+    }
+
+    /// Record an order and report whether it repeats one inside the window
+    pub fn observe(
+        &mut self,
+        account: Option<AccountId>,
+        side: OrderType,
+        price: Price,
+        size: f64,
+        now: Timestamp,
+    ) -> bool {
+        while self
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now.saturating_sub(*at) > self.check.window)
+        {
+            self.recent.pop_front();
+        }
+        let key = (account, side, price, size.to_bits());
+        let duplicate = self.recent.iter().any(|(_, recent)| *recent == key);
+        self.recent.push_back((now, key));
+        duplicate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_inside_the_window() {
+        let mut detector = DuplicateDetector::new(DuplicateCheck {
+            window: 100,
+            action: DuplicateAction::Reject,
+        });
+        let price = Price::new(10.0);
+        assert!(!detector.observe(Some(1), OrderType::Bid, price, 1.0, 0));
+        assert!(detector.observe(Some(1), OrderType::Bid, price, 1.0, 50));
+        assert!(!detector.observe(Some(2), OrderType::Bid, price, 1.0, 50));
+        assert!(!detector.observe(Some(1), OrderType::Ask, price, 1.0, 50));
+        assert!(!detector.observe(Some(1), OrderType::Bid, price, 2.0, 50));
+        assert!(!detector.observe(Some(1), OrderType::Bid, price, 1.0, 200));
+    }
+}
@@ -1,15 +1,193 @@
-use super::orderbook::{Order, OrderBook, Price, TradingPair};
-use std::collections::HashMap;
+use super::builder::ValidatedOrder;
+use super::clock::Timestamp;
+use super::concentration::{ConcentrationConfig, LiquidityConcentration};
+use super::depth::{Depth, TopOfBook};
+use super::eod::{DailyReport, EndOfDay, EodStep};
+use super::event::{CancelReason, OrderEvent};
+use super::heartbeat::{HeartbeatConfig, HeartbeatMonitor, SessionId};
+use super::market::{InstrumentRegistry, MarketConfig, MarketInfo, OrderRejection};
+use super::numeric::exact_sum;
+use super::oco::{OcoGroups, OcoLeg, OcoPlacement};
+use super::orderbook::{
+    AccountId, MarketFill, Order, OrderBook, OrderId, OrderType, Placement, Price, TradingPair,
+};
+use super::paper::{PaperBook, PaperFill, PaperOrder};
+use super::peg::Peg;
+use super::pricing::RelativePrice;
+use super::risk::{RiskChecker, RiskContext, RiskRejection};
+use super::settlement::{Settlement, SettlementMethod};
+use super::snapshot::{EngineSnapshot, MarketSnapshot};
+use super::stop::StopKind;
+use super::trade::Trade;
+use crate::accounts::{AccountRegistry, BalanceLedger};
+use crate::assets::AssetRegistry;
+use crate::collateral::CollateralLedger;
+use crate::insurance::InsuranceFund;
+use crate::positions::{NetPosition, PositionLedger};
+use std::{cmp::Ordering, collections::HashMap, fmt, sync::Arc};
 
-#[derive(Debug)]
+/// What to rank markets by in a market summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryMetric {
+    LastPrice,
+    Change,
+    Volume,
+    Spread,
+}
+
+/// Overview of a single market
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketSummary {
+    pub pair: TradingPair,
+    pub last_price: Option<f64>,
+    /// Percentage change over the last 24 hours
+    pub change_24h: Option<f64>,
+    pub volume_24h: f64,
+    pub spread: Option<f64>,
+}
+
+impl MarketSummary {
+    fn metric(&self, metric: SummaryMetric) -> Option<f64> {
+        match metric {
+            SummaryMetric::LastPrice => self.last_price,
+            SummaryMetric::Change => self.change_24h,
+            SummaryMetric::Volume => Some(self.volume_24h),
+            SummaryMetric::Spread => self.spread,
+        }
+    }
+}
+
+/// What happened while the clock was moved forward with [`Engine::advance_clock`]
+#[cfg(feature = "test-mode")]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ClockAdvance {
+    pub expired: Vec<(TradingPair, OrderEvent)>,
+    pub auction_trades: Vec<(TradingPair, Trade)>,
+}
+
+/// Why the engine turned an order away
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderError {
+    /// The order breaks its market's trading rules
+    Market(OrderRejection),
+    /// A pre-trade risk check turned the order away
+    Risk(RiskRejection),
+    /// The market does not exist, the account cannot take the order or the book refused it
+    Other(String),
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderError::Market(rejection) => write!(f, "{}", rejection),
+            OrderError::Risk(rejection) => write!(f, "{}", rejection),
+            OrderError::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<OrderRejection> for OrderError {
+    fn from(rejection: OrderRejection) -> Self {
+        OrderError::Market(rejection)
+    }
+}
+
+impl From<RiskRejection> for OrderError {
+    fn from(rejection: RiskRejection) -> Self {
+        OrderError::Risk(rejection)
+    }
+}
+
+impl From<String> for OrderError {
+    fn from(reason: String) -> Self {
+        OrderError::Other(reason)
+    }
+}
+
+impl From<&str> for OrderError {
+    fn from(reason: &str) -> Self {
+        OrderError::Other(reason.to_string())
+    }
+}
+
+impl From<OrderError> for String {
+    fn from(error: OrderError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Funds held for an open limit order of an account with balances
+#[derive(Debug, Clone, Copy)]
+struct Funding {
+    /// The order's key in the balance ledger, since order ids are only unique within a market
+    reservation: OrderId,
+    side: OrderType,
+    price: f64,
+}
+
+/// The asset an order pays with and how much of it each unit of size costs
+fn payment(trading_pair: &TradingPair, side: OrderType, price: f64) -> (&str, f64) {
+    match side {
+        OrderType::Bid => (trading_pair.quote(), price),
+        OrderType::Ask => (trading_pair.base(), 1.0),
+    }
+}
+
+/// Settlement method for markets that have not been configured
+const DEFAULT_SETTLEMENT: SettlementMethod = SettlementMethod::Vwap {
+    window: 15 * 60 * 1_000_000_000,
+};
+
+#[derive(Debug, Default)]
 pub struct Engine {
     orderbooks: HashMap<TradingPair, OrderBook>,
+    accounts: AccountRegistry,
+    balances: BalanceLedger,
+    funding: HashMap<(TradingPair, OrderId), Funding>,
+    positions: PositionLedger,
+    next_reservation: OrderId,
+    collateral: CollateralLedger,
+    insurance: InsuranceFund,
+    settlement_methods: HashMap<TradingPair, SettlementMethod>,
+    settlements: Vec<Settlement>,
+    instruments: InstrumentRegistry,
+    assets: AssetRegistry,
+    oco: OcoGroups,
+    concentration_config: ConcentrationConfig,
+    concentration: HashMap<TradingPair, LiquidityConcentration>,
+    next_concentration_sample: Timestamp,
+    heartbeats: HeartbeatMonitor,
+    paper: HashMap<TradingPair, PaperBook>,
+    paper_fills: Vec<(TradingPair, PaperFill)>,
+    risk_checkers: Vec<Arc<dyn RiskChecker>>,
+    /// Number of state-changing commands applied, across all markets
+    sequence: u64,
 }
 
 impl Engine {
     pub fn new() -> Self {
         Engine {
             orderbooks: HashMap::new(),
+            accounts: AccountRegistry::new(),
+            balances: BalanceLedger::new(),
+            funding: HashMap::new(),
+            positions: PositionLedger::new(),
+            next_reservation: 0,
+            collateral: CollateralLedger::default(),
+            insurance: InsuranceFund::new(),
+            settlement_methods: HashMap::new(),
+            settlements: Vec::new(),
+            instruments: InstrumentRegistry::new(),
+            assets: AssetRegistry::new(),
+            oco: OcoGroups::new(),
+            concentration_config: ConcentrationConfig::default(),
+            concentration: HashMap::new(),
+            next_concentration_sample: 0,
+            heartbeats: HeartbeatMonitor::default(),
+            paper: HashMap::new(),
+            paper_fills: Vec::new(),
+            risk_checkers: Vec::new(),
+            sequence: 0,
         }
     }
 
@@ -23,20 +201,256 @@ impl Engine {
     ///
     /// # Example
     /// ```
-    /// use matching::engine::Engine;
-    /// use matching::orderbook::{Order, OrderBook, OrderType};
+    /// use orderbook::matching::engine::Engine;
+    /// use orderbook::matching::orderbook::{OrderBook, TradingPair};
     /// let mut engine = Engine::new();
     /// let orderbook = OrderBook::new();
     ///
     /// engine.add_orderbook(TradingPair::new("BTC".to_string(), "USD".to_string()), orderbook);
     /// ```
     pub fn add_orderbook(&mut self, trading_pair: TradingPair, orderbook: OrderBook) {
-        self.orderbooks.entry(trading_pair).or_insert(orderbook);
+        self.sequence += 1;
+        self.orderbooks
+            .entry(trading_pair.clone())
+            .or_insert(orderbook);
+        self.configure_orderbook(&trading_pair);
+    }
+
+    /// Hand a market's book the settings it applies by itself: the pre-trade checks run on
+    /// triggered stops and the tick size pegged orders are rounded to
+    fn configure_orderbook(&mut self, trading_pair: &TradingPair) {
+        let tick = self.market_config(trading_pair).tick();
+        if let Some(orderbook) = self.orderbooks.get_mut(trading_pair) {
+            orderbook.set_risk_checkers(trading_pair.clone(), self.risk_checkers.clone());
+            orderbook.set_tick_size(tick);
+        }
+    }
+
+    /// Set the metadata and trading rules of a market
+    pub fn set_market_config(&mut self, trading_pair: TradingPair, config: MarketConfig) {
+        self.instruments.register(trading_pair.clone(), config);
+        self.configure_orderbook(&trading_pair);
+    }
+
+    pub fn market_config(&self, trading_pair: &TradingPair) -> MarketConfig {
+        self.instruments.config(trading_pair)
+    }
+
+    /// Replace the rules of every market, e.g. with a registry loaded at startup
+    pub fn set_instruments(&mut self, instruments: InstrumentRegistry) {
+        self.instruments = instruments;
+        let pairs: Vec<TradingPair> = self.orderbooks.keys().cloned().collect();
+        for pair in pairs {
+            self.configure_orderbook(&pair);
+        }
+    }
+
+    /// The rules incoming orders are validated against
+    pub fn instruments(&self) -> &InstrumentRegistry {
+        &self.instruments
+    }
+
+    /// Every listed market with its metadata and current status, sorted by pair
+    pub fn list_markets(&self) -> Vec<MarketInfo> {
+        let mut markets: Vec<MarketInfo> = self
+            .orderbooks
+            .iter()
+            .map(|(pair, orderbook)| MarketInfo {
+                pair: pair.clone(),
+                base: pair.base().to_string(),
+                quote: pair.quote().to_string(),
+                config: self.market_config(pair),
+                status: orderbook.status(),
+            })
+            .filter(|market| market.config.listed)
+            .collect();
+        markets.sort_by_key(|market| String::from(market.pair.clone()));
+        markets
+    }
+
+    /// The orderbook of a market
+    pub fn orderbook(&self, trading_pair: &TradingPair) -> Option<&OrderBook> {
+        self.orderbooks.get(trading_pair)
+    }
+
+    /// Best bid and ask of a market with their sizes, the spread and the midprice
+    ///
+    /// # Returns
+    /// * `Result<TopOfBook, String>` - The top of the book, Err if the orderbook does not exist
+    pub fn top_of_book(&self, trading_pair: &TradingPair) -> Result<TopOfBook, String> {
+        match self.orderbooks.get(trading_pair) {
+            Some(orderbook) => Ok(orderbook.top_of_book()),
+            None => Err("Orderbook does not exist".to_string()),
+        }
+    }
+
+    /// The best `levels` price levels on each side of a market, e.g. to render a ladder
+    ///
+    /// # Arguments
+    /// * `trading_pair` - The market to read
+    /// * `levels` - Levels per side
+    ///
+    /// # Returns
+    /// * `Result<Depth, String>` - Price, aggregate size and order count per level, best price first, Err if the
+    ///   orderbook does not exist
+    pub fn depth(&self, trading_pair: &TradingPair, levels: usize) -> Result<Depth, String> {
+        match self.orderbooks.get(trading_pair) {
+            Some(orderbook) => Ok(orderbook.depth(levels)),
+            None => Err("Orderbook does not exist".to_string()),
+        }
+    }
+
+    /// Mutable access to the orderbook of a market, counted as a command in [`Engine::sequence`]
+    pub fn orderbook_mut(&mut self, trading_pair: &TradingPair) -> Option<&mut OrderBook> {
+        self.sequence += 1;
+        self.orderbooks.get_mut(trading_pair)
+    }
+
+    /// Add a market whose base and quote assets must be registered
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if either asset is unknown or the market already exists
+    pub fn add_market(
+        &mut self,
+        trading_pair: TradingPair,
+        orderbook: OrderBook,
+    ) -> Result<(), String> {
+        self.assets.validate_pair(&trading_pair)?;
+        if self.orderbooks.contains_key(&trading_pair) {
+            return Err("Orderbook already exists".to_string());
+        }
+        self.sequence += 1;
+        self.orderbooks.insert(trading_pair.clone(), orderbook);
+        self.configure_orderbook(&trading_pair);
+        Ok(())
+    }
+
+    /// The markets that trade an asset, as either base or quote, sorted by pair
+    pub fn markets_for_asset(&self, symbol: &str) -> Vec<TradingPair> {
+        let mut pairs: Vec<TradingPair> = self
+            .orderbooks
+            .keys()
+            .filter(|pair| pair.base() == symbol || pair.quote() == symbol)
+            .cloned()
+            .collect();
+        pairs.sort_by_key(|pair| String::from(pair.clone()));
+        pairs
+    }
+
+    /// Number of state-changing commands the engine has applied
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Take a snapshot of the resting and stop orders of every market at the current sequence
+    ///
+    /// Commands need `&mut self`, so none can be applied while the snapshot is taken and all markets
+    /// are captured at the same point. Through [`AsyncEngine`](super::async_engine::AsyncEngine) the
+    /// snapshot is queued like any other request and sits between the commands before and after it.
+    ///
+    /// # Returns
+    /// * `EngineSnapshot` - The sequence number and the resting and stop orders of each market,
+    ///   sorted by pair
+    pub fn snapshot(&self) -> EngineSnapshot {
+        let mut markets: Vec<MarketSnapshot> = self
+            .orderbooks
+            .keys()
+            .filter_map(|pair| self.market_snapshot(pair))
+            .collect();
+        markets.sort_by_key(|market| String::from(market.pair.clone()));
+        EngineSnapshot {
+            sequence: self.sequence,
+            markets,
+        }
+    }
+
+    /// The resting orders, stop orders and status of one market, None if the orderbook does not
+    /// exist
+    pub fn market_snapshot(&self, trading_pair: &TradingPair) -> Option<MarketSnapshot> {
+        let orderbook = self.orderbooks.get(trading_pair)?;
+        Some(MarketSnapshot {
+            pair: trading_pair.clone(),
+            status: orderbook.status(),
+            orders: orderbook.snapshot_chunk(None, usize::MAX).orders,
+            stops: orderbook.stop_snapshot(),
+            client_orders: orderbook.client_orders(),
+        })
+    }
+
+    /// Add a market from a snapshot taken on another engine, e.g. when a market moves between
+    /// instances
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the orderbook already exists or the snapshot cannot be restored
+    pub fn import_market(&mut self, snapshot: &MarketSnapshot) -> Result<(), String> {
+        if self.orderbooks.contains_key(&snapshot.pair) {
+            return Err(format!(
+                "Orderbook {} already exists",
+                String::from(snapshot.pair.clone())
+            ));
+        }
+        let mut orderbook = OrderBook::new();
+        orderbook.restore(&snapshot.orders)?;
+        orderbook.restore_stops(&snapshot.stops)?;
+        orderbook.restore_client_orders(&snapshot.client_orders)?;
+        self.sequence += 1;
+        self.orderbooks.insert(snapshot.pair.clone(), orderbook);
+        self.configure_orderbook(&snapshot.pair);
+        Ok(())
+    }
+
+    /// Take a market out of the engine, e.g. once it has moved to another instance
+    ///
+    /// The reservations of the market's funded orders are released and its one-cancels-other
+    /// groups dissolved, as its orders no longer live here.
+    ///
+    /// # Returns
+    /// * `Option<OrderBook>` - The market's orderbook, None if it does not exist
+    pub fn remove_market(&mut self, trading_pair: &TradingPair) -> Option<OrderBook> {
+        self.sequence += 1;
+        let orderbook = self.orderbooks.remove(trading_pair)?;
+        self.release_closed(trading_pair);
+        self.oco.remove_market(trading_pair);
+        Some(orderbook)
+    }
+
+    /// Rest the orders of an [`EngineSnapshot`] in this engine's markets, stop orders included, and
+    /// continue from its sequence
+    ///
+    /// Client order ids used before the snapshot stay used, so retries sent across a restart are
+    /// still rejected.
+    ///
+    /// Market status is not restored; halted markets must be halted again.
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if a market of the snapshot does not exist or an order or client order id is
+    ///   already in use
+    pub fn restore_snapshot(&mut self, snapshot: &EngineSnapshot) -> Result<(), String> {
+        if let Some(market) = snapshot
+            .markets
+            .iter()
+            .find(|market| !self.orderbooks.contains_key(&market.pair))
+        {
+            return Err(format!(
+                "Orderbook {} does not exist",
+                String::from(market.pair.clone())
+            ));
+        }
+        for market in &snapshot.markets {
+            if let Some(orderbook) = self.orderbooks.get_mut(&market.pair) {
+                orderbook.restore(&market.orders)?;
+                orderbook.restore_stops(&market.stops)?;
+                orderbook.restore_client_orders(&market.client_orders)?;
+            }
+        }
+        self.sequence = self.sequence.max(snapshot.sequence);
+        Ok(())
     }
 
     /// Place a limit order
     ///
-    /// This function will place a limit order on the orderbook
+    /// This function will match the order against the opposite side of the orderbook up to its
+    /// price and rest whatever is left
     ///
     /// # Arguments
     /// * `trading_pair` - The trading pair to place the order on
@@ -44,30 +458,2732 @@ impl Engine {
     /// * `order` - The order to place
     ///
     /// # Returns
-    /// * `Result<(), String>` - Ok(()) if the order was placed successfully, Err(String) if the orderbook does not exist
+    /// * `Result<Placement, OrderError>` - The order's id and immediate fills, Err if the orderbook does not exist,
+    ///   it breaks the market's tick, lot, size or notional rules, its price is beyond the market's price collar, it is rejected
+    ///   as a duplicate, it would breach the limits of its account or any account above it, the account is a paper
+    ///   account or it costs more than the account's available balance
     ///
     /// # Example
     ///
     /// ```
-    /// use matching::engine::Engine;
-    /// use matching::orderbook::{Order, OrderType};
+    /// use orderbook::matching::engine::Engine;
+    /// use orderbook::matching::orderbook::{Order, OrderType, TradingPair};
     /// let mut engine = Engine::new();
     /// let order = Order::new(OrderType::Bid, 100.0);
-    /// engine.place_limit_order(TradingPair::new("BTC".to_string(), "USD".to_string()), 100.0, order);
-    ///
+    /// let result = engine.place_limit_order(TradingPair::new("BTC".to_string(), "USD".to_string()), 100.0, order);
+    /// assert!(result.is_err());
     /// ```
     pub fn place_limit_order(
         &mut self,
         trading_pair: TradingPair,
         price: f64,
         order: Order,
-    ) -> Result<(), String> {
-        match self.orderbooks.get_mut(&trading_pair) {
-            Some(orderbook) => {
-                orderbook.add(order, price);
-                Ok(())
+    ) -> Result<Placement, OrderError> {
+        self.sequence += 1;
+        if !self.orderbooks.contains_key(&trading_pair) {
+            return Err("Orderbook does not exist".into());
+        }
+        self.check_live(&order)?;
+        let config = self.market_config(&trading_pair);
+        config.validate(Some(price), order.size())?;
+        if let (Some(percent), false) = (config.price_collar, order.collar_override()) {
+            if let Some(orderbook) = self.orderbooks.get(&trading_pair) {
+                orderbook.check_price_collar(order.order_type(), price, percent)?;
             }
+        }
+        self.check_risk(&trading_pair, &order, Some(price))?;
+        if let Some(account) = order.account() {
+            self.check_account_limits(account, price * order.size(), 0.0)?;
+        }
+        let side = order.order_type();
+        let reservation = self.reserve(&trading_pair, &order, price)?;
+        let placement = match self.orderbooks.get_mut(&trading_pair) {
+            Some(orderbook) => orderbook.place_limit_order(order, price),
+            None => Err("Orderbook does not exist".to_string()),
+        };
+        if placement.is_err() {
+            self.unreserve(reservation);
+        }
+        let placement = placement?;
+        self.fund(&trading_pair, placement.id, reservation, side, price);
+        self.after_trades(&trading_pair, &placement.trades);
+        Ok(placement)
+    }
+
+    /// Place a limit order priced relative to the book, see [`RelativePrice`]
+    ///
+    /// The price is resolved against the book as it stands when the engine applies the order, and
+    /// rounded to the market's tick size. The order then goes through [`Engine::place_limit_order`].
+    ///
+    /// # Returns
+    /// * `Result<Placement, OrderError>` - The order's id and its immediate fills, Err if the orderbook does not exist,
+    ///   the price cannot be resolved or the placement is rejected
+    pub fn place_relative_order(
+        &mut self,
+        trading_pair: TradingPair,
+        price: RelativePrice,
+        order: Order,
+    ) -> Result<Placement, OrderError> {
+        let orderbook = self
+            .orderbooks
+            .get(&trading_pair)
+            .ok_or("Orderbook does not exist")?;
+        let price = price.resolve(
+            order.order_type(),
+            orderbook.best_bid(),
+            orderbook.best_ask(),
+            self.market_config(&trading_pair).tick_size(),
+        )?;
+        self.place_limit_order(trading_pair, price, order)
+    }
+
+    /// Place a limit order whose price follows the book, see [`Peg`]
+    ///
+    /// The order goes through [`Engine::place_limit_order`] at its pegged price, then rests
+    /// pegged: its price moves whenever its reference price does.
+    ///
+    /// # Returns
+    /// * `Result<Placement, OrderError>` - The order's id and its immediate fills, Err if the orderbook does not exist,
+    ///   the reference side of the book is empty or the placement is rejected
+    pub fn place_pegged_order(
+        &mut self,
+        trading_pair: TradingPair,
+        peg: Peg,
+        order: Order,
+    ) -> Result<Placement, OrderError> {
+        let price = self
+            .orderbooks
+            .get(&trading_pair)
+            .ok_or("Orderbook does not exist")?
+            .peg_price(order.order_type(), &peg)
+            .ok_or("No reference price to peg the order to")?;
+        let placement = self.place_limit_order(trading_pair.clone(), price, order)?;
+        if let Some(orderbook) = self.orderbooks.get_mut(&trading_pair) {
+            orderbook.peg_order(placement.id, peg);
+        }
+        Ok(placement)
+    }
+
+    /// Place a market order
+    ///
+    /// The order sweeps the opposite side of the market's book, best price first. Whatever cannot
+    /// be filled is reported back rather than rested.
+    ///
+    /// # Arguments
+    /// * `trading_pair` - The trading pair to place the order on
+    /// * `order` - The order to place
+    ///
+    /// # Returns
+    /// * `Result<MarketFill, OrderError>` - The fills and unfilled remainder, Err if the orderbook does not exist or
+    ///   the size breaks the market's lot or size rules
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use orderbook::matching::engine::Engine;
+    /// use orderbook::matching::orderbook::{Order, OrderBook, OrderType, TradingPair};
+    /// let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+    /// let mut engine = Engine::new();
+    /// engine.add_orderbook(pair.clone(), OrderBook::new());
+    /// engine.place_limit_order(pair.clone(), 100.0, Order::new(OrderType::Ask, 1.0)).unwrap();
+    /// let fill = engine.place_market_order(&pair, Order::new(OrderType::Bid, 1.5)).unwrap();
+    /// assert_eq!(fill.trades.len(), 1);
+    /// assert_eq!(fill.remaining, 0.5);
+    /// ```
+    pub fn place_market_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        mut order: Order,
+    ) -> Result<MarketFill, OrderError> {
+        self.sequence += 1;
+        self.check_live(&order)?;
+        self.instruments
+            .validate(trading_pair, None, order.size())?;
+        if let Some(orderbook) = self.orderbooks.get(trading_pair) {
+            orderbook.check_client_order_id(&order)?;
+        }
+        self.check_risk(trading_pair, &order, None)?;
+        if let Some(account) = order
+            .account()
+            .filter(|account| self.balances.is_funded(*account))
+        {
+            let cost = match (order.order_type(), self.orderbooks.get(trading_pair)) {
+                (OrderType::Ask, _) => order.size(),
+                (OrderType::Bid, Some(orderbook)) => {
+                    let cost = orderbook.cost_to_fill(OrderType::Bid, order.size());
+                    cost.filled * cost.average_price.unwrap_or(0.0)
+                }
+                (OrderType::Bid, None) => 0.0,
+            };
+            let (asset, _) = payment(trading_pair, order.order_type(), 0.0);
+            self.balances.check_available(account, asset, cost)?;
+        }
+        let trades = match self.orderbooks.get_mut(trading_pair) {
+            Some(orderbook) => orderbook.place_market_order(&mut order),
+            None => return Err("Orderbook does not exist".into()),
+        };
+        self.after_trades(trading_pair, &trades);
+        Ok(MarketFill {
+            trades,
+            remaining: order.size(),
+        })
+    }
+
+    /// Place an order built with [`Order::builder`]
+    ///
+    /// Orders with a price are placed as limit orders, subject to the same account limits as
+    /// [`Engine::place_limit_order`]; orders without one execute immediately as market orders.
+    ///
+    /// # Returns
+    /// * `Result<Vec<Trade>, OrderError>` - The trades the order made, Err if the orderbook does not exist or a limit
+    ///   order would breach its account limits
+    pub fn place_order(
+        &mut self,
+        trading_pair: TradingPair,
+        order: ValidatedOrder,
+    ) -> Result<Vec<Trade>, OrderError> {
+        match order.into_parts() {
+            (order, Some(price)) => self
+                .place_limit_order(trading_pair, price, order)
+                .map(|placement| placement.trades),
+            (order, None) => self
+                .place_market_order(&trading_pair, order)
+                .map(|fill| fill.trades),
+        }
+    }
+
+    /// Place a stop order, see [`OrderBook::place_stop_order`]
+    ///
+    /// # Returns
+    /// * `Result<OrderId, OrderError>` - The order's id, Err if the orderbook does not exist or the stop is rejected
+    pub fn place_stop_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        order: Order,
+        stop_price: f64,
+        kind: StopKind,
+    ) -> Result<OrderId, OrderError> {
+        self.sequence += 1;
+        let side = order.order_type();
+        let limit_price = kind.limit_price(side, stop_price);
+        let config = self.market_config(trading_pair);
+        config.check_tick(stop_price)?;
+        config.validate(limit_price, order.size())?;
+        self.check_live(&order)?;
+        self.check_risk(trading_pair, &order, limit_price)?;
+        let funded = order
+            .account()
+            .is_some_and(|account| self.balances.is_funded(account));
+        if funded && side == OrderType::Bid && limit_price.is_none() {
+            return Err(
+                "A stop-market bid has no price to reserve funds at, give it a limit price".into(),
+            );
+        }
+        // Sells reserve the base asset, so a stop-market ask needs no price
+        let price = limit_price.unwrap_or(stop_price);
+        let reservation = self.reserve(trading_pair, &order, price)?;
+        let id = match self.orderbooks.get_mut(trading_pair) {
+            Some(orderbook) => orderbook.place_stop_order(order, stop_price, kind),
+            None => Err("Orderbook does not exist".to_string()),
+        };
+        if id.is_err() {
+            self.unreserve(reservation);
+        }
+        let id = id?;
+        self.fund(trading_pair, id, reservation, side, price);
+        Ok(id)
+    }
+
+    /// Cancel a resting order
+    ///
+    /// # Arguments
+    /// * `trading_pair` - The market the order rests on
+    /// * `id` - The id the orderbook assigned to the order
+    ///
+    /// # Returns
+    /// * `Result<OrderEvent, String>` - The `Cancelled` event, Err if the orderbook does not exist or the order is not
+    ///   open
+    pub fn cancel_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+    ) -> Result<OrderEvent, String> {
+        self.sequence += 1;
+        let event = match self.orderbooks.get_mut(trading_pair) {
+            Some(orderbook) => orderbook
+                .cancel(id)
+                .ok_or_else(|| format!("Order {} is not open", id))?,
+            None => return Err("Orderbook does not exist".to_string()),
+        };
+        self.cancel_siblings(trading_pair, [id]);
+        Ok(event)
+    }
+
+    /// Change the price and/or remaining size of a resting order, see [`OrderBook::amend`]
+    ///
+    /// # Returns
+    /// * `Result<Vec<Trade>, OrderError>` - Fills caused by a crossing reprice, Err if the orderbook does not exist, the
+    ///   amend is invalid or the new price or size breaks the rules a new order must pass
+    pub fn amend_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+        new_price: f64,
+        new_size: f64,
+    ) -> Result<Vec<Trade>, OrderError> {
+        self.sequence += 1;
+        self.check_amend(trading_pair, id, new_price, new_size)?;
+        let previous = self.refund(trading_pair, id, new_price, new_size)?;
+        let trades = match self.orderbooks.get_mut(trading_pair) {
+            Some(orderbook) => orderbook.amend(id, new_price, new_size),
+            None => Err("Orderbook does not exist".to_string()),
+        };
+        if trades.is_err() {
+            self.restore_funding(trading_pair, id, previous)?;
+        }
+        let trades = trades?;
+        self.after_trades(trading_pair, &trades);
+        Ok(trades)
+    }
+
+    /// Replace a resting order, giving up its queue priority, see [`OrderBook::replace`]
+    ///
+    /// # Returns
+    /// * `Result<Vec<Trade>, OrderError>` - Fills caused by a crossing price, Err if the orderbook does not exist, the
+    ///   replace is invalid or the new price or size breaks the rules a new order must pass
+    pub fn replace_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+        new_price: f64,
+        new_size: f64,
+    ) -> Result<Vec<Trade>, OrderError> {
+        self.sequence += 1;
+        self.check_amend(trading_pair, id, new_price, new_size)?;
+        let previous = self.refund(trading_pair, id, new_price, new_size)?;
+        let trades = match self.orderbooks.get_mut(trading_pair) {
+            Some(orderbook) => orderbook.replace(id, new_price, new_size),
             None => Err("Orderbook does not exist".to_string()),
+        };
+        if trades.is_err() {
+            self.restore_funding(trading_pair, id, previous)?;
+        }
+        let trades = trades?;
+        self.after_trades(trading_pair, &trades);
+        Ok(trades)
+    }
+
+    /// Place two linked orders where either one trading or leaving the book cancels the other
+    ///
+    /// Typically a take-profit limit order and a stop-loss. The first order is placed first; if it
+    /// trades on arrival the group is already done and the second is never placed. Siblings are
+    /// cancelled with reason `Linked`, and the event is queued on the market's book.
+    ///
+    /// # Returns
+    /// * `Result<OcoPlacement, OrderError>` - The orders' ids and any fills on arrival, Err if either order is rejected, in
+    ///   which case neither is left in the book
+    pub fn place_oco(
+        &mut self,
+        trading_pair: &TradingPair,
+        first: OcoLeg,
+        second: OcoLeg,
+    ) -> Result<OcoPlacement, OrderError> {
+        let (first, mut trades) = self.place_leg(trading_pair, first)?;
+        if !trades.is_empty() {
+            return Ok(OcoPlacement {
+                first,
+                second: None,
+                trades,
+            });
+        }
+        let (second, second_trades) = match self.place_leg(trading_pair, second) {
+            Ok(leg) => leg,
+            Err(error) => {
+                if let Some(orderbook) = self.orderbooks.get_mut(trading_pair) {
+                    orderbook.cancel(first);
+                }
+                self.release_closed(trading_pair);
+                return Err(error);
+            }
+        };
+        self.oco.link(trading_pair, first, second);
+        self.cancel_siblings(trading_pair, traded_orders(&second_trades));
+        trades.extend(second_trades);
+        Ok(OcoPlacement {
+            first,
+            second: Some(second),
+            trades,
+        })
+    }
+
+    fn place_leg(
+        &mut self,
+        trading_pair: &TradingPair,
+        leg: OcoLeg,
+    ) -> Result<(OrderId, Vec<Trade>), OrderError> {
+        match leg {
+            OcoLeg::Limit { order, price } => self
+                .place_limit_order(trading_pair.clone(), price, order)
+                .map(|placement| (placement.id, placement.trades)),
+            OcoLeg::Stop {
+                order,
+                stop_price,
+                kind,
+            } => self
+                .place_stop_order(trading_pair, order, stop_price, kind)
+                .map(|id| (id, Vec::new())),
+        }
+    }
+
+    /// Follow up on trades made on a market: settle balances, update positions, cancel the
+    /// one-cancels-other siblings of the orders that traded and fill paper orders off the prints
+    fn after_trades(&mut self, trading_pair: &TradingPair, trades: &[Trade]) {
+        self.settle_balances(trading_pair, trades);
+        for trade in trades {
+            let sides = [
+                (trade.aggressor_side, trade.taker_account),
+                (trade.maker_side(), trade.maker_account),
+            ];
+            for (side, account) in sides {
+                if let Some(account) = account {
+                    self.positions.record(
+                        account,
+                        trading_pair,
+                        side,
+                        trade.size,
+                        trade.price.into(),
+                    );
+                }
+            }
+        }
+        self.cancel_siblings(trading_pair, traded_orders(trades));
+        if let Some(paper) = self.paper.get_mut(trading_pair) {
+            let fills = paper.record_trades(trades);
+            self.paper_fills
+                .extend(fills.into_iter().map(|fill| (trading_pair.clone(), fill)));
+        }
+    }
+
+    /// Cancel the one-cancels-other siblings of orders on a market that traded or left the book,
+    /// then bring its funding up to date
+    fn cancel_siblings(
+        &mut self,
+        trading_pair: &TradingPair,
+        ids: impl IntoIterator<Item = OrderId>,
+    ) {
+        let mut ids: Vec<OrderId> = ids.into_iter().collect();
+        loop {
+            for id in ids {
+                if let Some(sibling) = self.oco.unlink(trading_pair, id) {
+                    if let Some(orderbook) = self.orderbooks.get_mut(trading_pair) {
+                        orderbook.cancel_linked(sibling);
+                    }
+                }
+            }
+            ids = self.refund_moved(trading_pair);
+            if ids.is_empty() {
+                break;
+            }
+        }
+        self.release_closed(trading_pair);
+    }
+
+    /// Move funds between the balances of the accounts on each side of the trades
+    ///
+    /// Each funded order's reservation is consumed at its own limit price, so a fill at a better
+    /// price leaves the difference available again.
+    fn settle_balances(&mut self, trading_pair: &TradingPair, trades: &[Trade]) {
+        let (base, quote) = (trading_pair.base(), trading_pair.quote());
+        for trade in trades {
+            let notional = trade.size * f64::from(trade.price);
+            let maker_side = match trade.aggressor_side {
+                OrderType::Bid => OrderType::Ask,
+                OrderType::Ask => OrderType::Bid,
+            };
+            let sides = [
+                (
+                    trade.aggressor_side,
+                    trade.taker_account,
+                    trade.taker_order_id,
+                ),
+                (maker_side, trade.maker_account, trade.maker_order_id),
+            ];
+            for (side, account, order_id) in sides {
+                let account = match account {
+                    Some(account) if self.balances.is_funded(account) => account,
+                    _ => continue,
+                };
+                let reservation = order_id
+                    .and_then(|id| self.funding.get(&(trading_pair.clone(), id)))
+                    .map(|funding| {
+                        let (_, rate) = payment(trading_pair, funding.side, funding.price);
+                        (funding.reservation, trade.size * rate)
+                    });
+                match side {
+                    OrderType::Bid => self.balances.settle(
+                        account,
+                        reservation,
+                        (quote, notional),
+                        (base, trade.size),
+                    ),
+                    OrderType::Ask => self.balances.settle(
+                        account,
+                        reservation,
+                        (base, trade.size),
+                        (quote, notional),
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Hold the funds an order from an account with balances needs at `price`
+    ///
+    /// # Returns
+    /// * `Result<Option<OrderId>, String>` - The reservation's key, None if the order's account
+    ///   has no balances; Err if the account does not have the funds available
+    fn reserve(
+        &mut self,
+        trading_pair: &TradingPair,
+        order: &Order,
+        price: f64,
+    ) -> Result<Option<OrderId>, String> {
+        let account = match order.account() {
+            Some(account) if self.balances.is_funded(account) => account,
+            _ => return Ok(None),
+        };
+        let (asset, rate) = payment(trading_pair, order.order_type(), price);
+        self.next_reservation += 1;
+        self.balances
+            .reserve(self.next_reservation, account, asset, order.size() * rate)?;
+        Ok(Some(self.next_reservation))
+    }
+
+    /// Release a reservation made for an order the book turned away
+    fn unreserve(&mut self, reservation: Option<OrderId>) {
+        if let Some(reservation) = reservation {
+            self.balances.release(reservation);
+        }
+    }
+
+    /// Tie a reservation to the order the book accepted
+    fn fund(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+        reservation: Option<OrderId>,
+        side: OrderType,
+        price: f64,
+    ) {
+        if let Some(reservation) = reservation {
+            let funding = Funding {
+                reservation,
+                side,
+                price,
+            };
+            self.funding.insert((trading_pair.clone(), id), funding);
+        }
+    }
+
+    /// Keep the reservations of a market's funded orders in step with the price they rest at,
+    /// which moves when a pegged order is re-pegged
+    ///
+    /// An order its account can no longer fund at its new price is cancelled with reason
+    /// `Unfunded`.
+    ///
+    /// # Returns
+    /// * `Vec<OrderId>` - The orders cancelled
+    fn refund_moved(&mut self, trading_pair: &TradingPair) -> Vec<OrderId> {
+        let orderbook = match self.orderbooks.get(trading_pair) {
+            Some(orderbook) => orderbook,
+            None => return Vec::new(),
+        };
+        let moved: Vec<(OrderId, f64, f64)> = self
+            .funding
+            .iter()
+            .filter(|((pair, _), _)| pair == trading_pair)
+            .filter_map(|((_, id), funding)| {
+                let order = orderbook.order(*id)?;
+                let price = orderbook.order_price(*id)?;
+                (price != funding.price).then(|| (*id, price, order.size() + order.reserve()))
+            })
+            .collect();
+        let mut unfunded = Vec::new();
+        for (id, price, remaining) in moved {
+            let funding = match self.funding.get_mut(&(trading_pair.clone(), id)) {
+                Some(funding) => funding,
+                None => continue,
+            };
+            let (_, rate) = payment(trading_pair, funding.side, price);
+            if self
+                .balances
+                .resize(funding.reservation, remaining * rate)
+                .is_ok()
+            {
+                funding.price = price;
+                continue;
+            }
+            if let Some(orderbook) = self.orderbooks.get_mut(trading_pair) {
+                if orderbook.cancel_queued(id, CancelReason::Unfunded) {
+                    unfunded.push(id);
+                }
+            }
+        }
+        unfunded
+    }
+
+    /// Release the reservations of a market's funded orders that are no longer open
+    fn release_closed(&mut self, trading_pair: &TradingPair) {
+        let orderbook = self.orderbooks.get(trading_pair);
+        let closed: Vec<(TradingPair, OrderId)> = self
+            .funding
+            .keys()
+            .filter(|(pair, id)| {
+                pair == trading_pair
+                    && orderbook.is_none_or(|book| {
+                        book.order(*id).is_none() && book.stop_order(*id).is_none()
+                    })
+            })
+            .cloned()
+            .collect();
+        for key in closed {
+            if let Some(funding) = self.funding.remove(&key) {
+                self.balances.release(funding.reservation);
+            }
+        }
+    }
+
+    /// Resize a funded order's reservation to its new price and size before the book applies an
+    /// amend, so that fills the amend causes settle against the new amount
+    ///
+    /// # Returns
+    /// * `Result<Option<(Funding, f64)>, String>` - The funding and reserved amount before the
+    ///   change, to restore if the book rejects it; Err if the account cannot cover the order at
+    ///   its new price and size
+    fn refund(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+        new_price: f64,
+        new_size: f64,
+    ) -> Result<Option<(Funding, f64)>, String> {
+        // The book rejects sizes that are not positive, and a reservation resized to nothing is gone
+        if new_size <= 0.0 {
+            return Ok(None);
         }
+        let funding = match self.funding.get_mut(&(trading_pair.clone(), id)) {
+            Some(funding) => funding,
+            None => return Ok(None),
+        };
+        let amount = match self.balances.reservation(funding.reservation) {
+            Some(reservation) => reservation.amount,
+            None => return Ok(None),
+        };
+        let previous = *funding;
+        let (_, rate) = payment(trading_pair, funding.side, new_price);
+        self.balances.resize(funding.reservation, new_size * rate)?;
+        funding.price = new_price;
+        Ok(Some((previous, amount)))
+    }
+
+    /// Put back the funding `refund` changed for an amend the book rejected
+    fn restore_funding(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+        previous: Option<(Funding, f64)>,
+    ) -> Result<(), String> {
+        let (funding, amount) = match previous {
+            Some(previous) => previous,
+            None => return Ok(()),
+        };
+        self.balances.resize(funding.reservation, amount)?;
+        self.funding.insert((trading_pair.clone(), id), funding);
+        Ok(())
+    }
+
+    /// Halt a market, applying its halt policy to resting orders
+    ///
+    /// # Returns
+    /// * `Result<Vec<OrderEvent>, String>` - The cancellations caused by the halt, Err if the orderbook does not exist
+    pub fn halt_market(&mut self, trading_pair: &TradingPair) -> Result<Vec<OrderEvent>, String> {
+        self.sequence += 1;
+        let events = match self.orderbooks.get_mut(trading_pair) {
+            Some(orderbook) => orderbook.halt(),
+            None => return Err("Orderbook does not exist".to_string()),
+        };
+        self.cancel_siblings(trading_pair, events.iter().filter_map(removed_order));
+        Ok(events)
+    }
+
+    /// Take a halted market into its re-opening auction
+    pub fn resume_market(&mut self, trading_pair: &TradingPair) -> Result<(), String> {
+        self.sequence += 1;
+        match self.orderbooks.get_mut(trading_pair) {
+            Some(orderbook) => {
+                orderbook.resume();
+                Ok(())
+            }
+            None => Err("Orderbook does not exist".to_string()),
+        }
+    }
+
+    /// Uncross every market whose auction has reached its end time
+    ///
+    /// # Returns
+    /// * `Vec<(TradingPair, Trade)>` - The auction trades, by market
+    pub fn poll_auctions(&mut self) -> Vec<(TradingPair, Trade)> {
+        self.sequence += 1;
+        let trades: Vec<(TradingPair, Trade)> = self
+            .orderbooks
+            .iter_mut()
+            .flat_map(|(pair, orderbook)| {
+                orderbook
+                    .poll_auction()
+                    .into_iter()
+                    .map(move |trade| (pair.clone(), trade))
+            })
+            .collect();
+        for (pair, trade) in &trades {
+            self.after_trades(pair, std::slice::from_ref(trade));
+        }
+        trades
+    }
+
+    /// Choose how a market's settlement price is computed
+    ///
+    /// Markets without a method settle on the VWAP of their last 15 minutes.
+    pub fn set_settlement_method(&mut self, trading_pair: TradingPair, method: SettlementMethod) {
+        self.settlement_methods.insert(trading_pair, method);
+    }
+
+    /// Compute the settlement price of every market that has traded
+    ///
+    /// When the method has no data (no recent trades, or no auction yet) the last traded price is
+    /// used and the settlement is flagged as a fallback. Settlements are kept in the engine's
+    /// history for margining and reporting.
+    ///
+    /// # Returns
+    /// * `Vec<Settlement>` - The settlements published by this run
+    pub fn settle(&mut self) -> Vec<Settlement> {
+        let mut published = Vec::new();
+        for (pair, orderbook) in self.orderbooks.iter_mut() {
+            let method = self
+                .settlement_methods
+                .get(pair)
+                .copied()
+                .unwrap_or(DEFAULT_SETTLEMENT);
+            let now = orderbook.now();
+            let price = match method {
+                SettlementMethod::Vwap { window } => {
+                    orderbook.stats().vwap(now.saturating_sub(window), now)
+                }
+                SettlementMethod::AuctionPrice => orderbook.last_auction_price().map(f64::from),
+            };
+            let (price, fallback) = match (price, orderbook.stats().last_price()) {
+                (Some(price), _) => (price, false),
+                (None, Some(last)) => (last, true),
+                (None, None) => continue,
+            };
+            published.push(Settlement {
+                pair: pair.clone(),
+                price,
+                method,
+                fallback,
+                at: now,
+            });
+        }
+        self.settlements.extend(published.iter().cloned());
+        published
+    }
+
+    /// Every settlement published so far, oldest first
+    pub fn settlements(&self) -> &[Settlement] {
+        &self.settlements
+    }
+
+    /// Remove every good-till-date order, on every market, whose expiry is at or before `now`
+    ///
+    /// Meant to be called periodically by whatever drives the engine.
+    ///
+    /// # Returns
+    /// * `Vec<(TradingPair, OrderEvent)>` - An `Expired` event per removed order, by market
+    pub fn expire_orders(&mut self, now: Timestamp) -> Vec<(TradingPair, OrderEvent)> {
+        self.sequence += 1;
+        let mut expired = Vec::new();
+        for (pair, orderbook) in self.orderbooks.iter_mut() {
+            expired.extend(
+                orderbook
+                    .expire_orders_at(now)
+                    .into_iter()
+                    .map(|event| (pair.clone(), event)),
+            );
+        }
+        for (pair, event) in &expired {
+            self.cancel_siblings(pair, removed_order(event));
+        }
+        expired
+    }
+
+    /// Choose how liquidity concentration is measured and how often it is sampled
+    pub fn set_concentration_config(&mut self, config: ConcentrationConfig) {
+        self.concentration_config = config;
+    }
+
+    /// Measure the liquidity concentration of every market, if a sample is due
+    ///
+    /// Meant to be called periodically by whatever drives the engine; calls between samples do
+    /// nothing.
+    ///
+    /// # Returns
+    /// * `bool` - Whether a sample was taken
+    pub fn sample_concentration(&mut self, now: Timestamp) -> bool {
+        if now < self.next_concentration_sample {
+            return false;
+        }
+        let config = self.concentration_config;
+        for (pair, orderbook) in self.orderbooks.iter() {
+            let depth = orderbook.depth(config.far_levels);
+            self.concentration.insert(
+                pair.clone(),
+                LiquidityConcentration::measure(&depth, config.near_levels, now),
+            );
+        }
+        self.next_concentration_sample = now.saturating_add(config.interval.max(1));
+        true
+    }
+
+    /// The latest liquidity concentration sample of a market
+    ///
+    /// # Returns
+    /// * `Option<&LiquidityConcentration>` - None if the market has not been sampled yet
+    pub fn liquidity_concentration(
+        &self,
+        trading_pair: &TradingPair,
+    ) -> Option<&LiquidityConcentration> {
+        self.concentration.get(trading_pair)
+    }
+
+    /// Simulate an order of a paper account against a market's real book, see [`PaperBook`]
+    ///
+    /// The order takes the liquidity the book shows on arrival without changing it. A limit
+    /// order's remainder rests as a paper order and fills off later real trades; those fills are
+    /// collected with [`Engine::drain_paper_fills`].
+    ///
+    /// # Arguments
+    /// * `trading_pair` - The market to trade on
+    /// * `price` - The limit price, None for a market order
+    /// * `order` - The order, from an account of class [`crate::accounts::AccountClass::Paper`]
+    ///
+    /// # Returns
+    /// * `Result<(OrderId, Vec<PaperFill>), OrderError>` - The paper order's id and its fills on arrival, Err if the
+    ///   orderbook does not exist, the account is not a paper account or the order breaks the market's rules
+    pub fn place_paper_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        price: Option<f64>,
+        order: Order,
+    ) -> Result<(OrderId, Vec<PaperFill>), OrderError> {
+        let orderbook = self
+            .orderbooks
+            .get(trading_pair)
+            .ok_or("Orderbook does not exist")?;
+        if !order
+            .account()
+            .is_some_and(|account| self.accounts.is_paper(account))
+        {
+            return Err("Paper orders need a paper account".into());
+        }
+        self.instruments
+            .validate(trading_pair, price, order.size())?;
+        let depth = orderbook.depth(usize::MAX);
+        let now = orderbook.now();
+        Ok(self.paper.entry(trading_pair.clone()).or_default().place(
+            &order,
+            price.map(Price::new),
+            &depth,
+            now,
+        ))
+    }
+
+    /// Cancel a resting paper order
+    ///
+    /// # Returns
+    /// * `Result<PaperOrder, String>` - The cancelled order, Err if it is not open
+    pub fn cancel_paper_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+    ) -> Result<PaperOrder, String> {
+        self.paper
+            .get_mut(trading_pair)
+            .and_then(|paper| paper.cancel(id))
+            .ok_or_else(|| format!("Paper order {} is not open", id))
+    }
+
+    /// The paper orders resting on a market
+    pub fn paper_book(&self, trading_pair: &TradingPair) -> Option<&PaperBook> {
+        self.paper.get(trading_pair)
+    }
+
+    /// Take the fills of resting paper orders since the last call, by market
+    pub fn drain_paper_fills(&mut self) -> Vec<(TradingPair, PaperFill)> {
+        std::mem::take(&mut self.paper_fills)
+    }
+
+    /// Choose how often sessions must send a heartbeat and how many they may miss
+    pub fn set_heartbeat_config(&mut self, config: HeartbeatConfig) {
+        self.heartbeats.set_config(config);
+    }
+
+    /// Start tracking the liveness of a client session trading for `account`
+    pub fn connect_session(&mut self, session: SessionId, account: AccountId, now: Timestamp) {
+        self.heartbeats.connect(session, account, now);
+    }
+
+    /// Record a heartbeat, or any other message, from a session
+    ///
+    /// # Returns
+    /// * `bool` - false if the session is not connected
+    pub fn heartbeat(&mut self, session: SessionId, now: Timestamp) -> bool {
+        self.heartbeats.heartbeat(session, now)
+    }
+
+    /// End a session, cancelling its account's orders on every market if it was the account's
+    /// last connected session
+    ///
+    /// Resting and stop orders are cancelled with reason `Pulled`, their one-cancels-other
+    /// siblings with them, and the events are queued on each market's book.
+    pub fn disconnect_session(&mut self, session: SessionId) {
+        if let Some(account) = self.heartbeats.disconnect(session) {
+            self.cancel_on_disconnect(account);
+        }
+    }
+
+    /// Disconnect every session that has missed too many heartbeats by `now`, see
+    /// [`Engine::disconnect_session`]
+    ///
+    /// Meant to be called periodically by whatever drives the engine, so a client that stops
+    /// responding is caught even while its connection stays open.
+    ///
+    /// # Returns
+    /// * `Vec<SessionId>` - The sessions disconnected
+    pub fn check_heartbeats(&mut self, now: Timestamp) -> Vec<SessionId> {
+        let dead = self.heartbeats.poll(now);
+        for (_, account) in &dead {
+            self.cancel_on_disconnect(*account);
+        }
+        dead.into_iter().map(|(session, _)| session).collect()
+    }
+
+    fn cancel_on_disconnect(&mut self, account: AccountId) {
+        if self.heartbeats.is_connected(account) {
+            return;
+        }
+        self.sequence += 1;
+        let pairs: Vec<TradingPair> = self.orderbooks.keys().cloned().collect();
+        for pair in pairs {
+            let orderbook = match self.orderbooks.get_mut(&pair) {
+                Some(orderbook) => orderbook,
+                None => continue,
+            };
+            let cancelled: Vec<OrderId> = orderbook
+                .order_ids_of(account)
+                .into_iter()
+                .filter(|id| orderbook.cancel_queued(*id, CancelReason::Pulled))
+                .collect();
+            self.cancel_siblings(&pair, cancelled);
+        }
+    }
+
+    /// Run the end-of-day process
+    ///
+    /// Expires DAY orders on every market, publishes settlement prices and builds a daily report
+    /// per market. `progress` is called as each step starts.
+    pub fn end_of_day(&mut self, mut progress: impl FnMut(EodStep)) -> EndOfDay {
+        self.sequence += 1;
+        let mut eod = EndOfDay::default();
+
+        progress(EodStep::ExpireDayOrders);
+        for (pair, orderbook) in self.orderbooks.iter_mut() {
+            eod.expired.extend(
+                orderbook
+                    .expire_day_orders()
+                    .into_iter()
+                    .map(|event| (pair.clone(), event)),
+            );
+        }
+        for (pair, event) in &eod.expired {
+            self.cancel_siblings(pair, removed_order(event));
+        }
+
+        progress(EodStep::Settle);
+        eod.settlements = self.settle();
+
+        progress(EodStep::Report);
+        for (pair, orderbook) in self.orderbooks.iter_mut() {
+            let now = orderbook.now();
+            let open_orders = orderbook.open_orders();
+            let stats = orderbook.stats();
+            eod.reports.push(DailyReport {
+                pair: pair.clone(),
+                volume: stats.volume(now),
+                last_price: stats.last_price(),
+                change: stats.change(now),
+                settlement: eod
+                    .settlements
+                    .iter()
+                    .find(|settlement| &settlement.pair == pair)
+                    .map(|settlement| settlement.price),
+                open_orders,
+            });
+        }
+
+        progress(EodStep::Done);
+        eod
+    }
+
+    /// The account registry used for attribution and limit checks
+    pub fn accounts(&self) -> &AccountRegistry {
+        &self.accounts
+    }
+
+    pub fn accounts_mut(&mut self) -> &mut AccountRegistry {
+        &mut self.accounts
+    }
+
+    /// Asset balances of each account, with the funds held for its open orders
+    pub fn balances(&self) -> &BalanceLedger {
+        &self.balances
+    }
+
+    /// Deposit and withdraw through this; once an account has a balance its orders are funded
+    /// from it
+    pub fn balances_mut(&mut self) -> &mut BalanceLedger {
+        &mut self.balances
+    }
+
+    /// An account's net position in a market, None if it has not traded there
+    pub fn position(&self, account: AccountId, trading_pair: &TradingPair) -> Option<&NetPosition> {
+        self.positions.get(account, trading_pair)
+    }
+
+    /// Net positions of every account, built from the fills of orders carrying an account
+    pub fn positions(&self) -> &PositionLedger {
+        &self.positions
+    }
+
+    /// Margin collateral posted by accounts; an account's open orders may not exceed its value
+    pub fn collateral(&self) -> &CollateralLedger {
+        &self.collateral
+    }
+
+    pub fn collateral_mut(&mut self) -> &mut CollateralLedger {
+        &mut self.collateral
+    }
+
+    /// The fund that covers liquidation shortfalls; its [`InsuranceFund::stats`] are public
+    pub fn insurance_fund(&self) -> &InsuranceFund {
+        &self.insurance
+    }
+
+    pub fn insurance_fund_mut(&mut self) -> &mut InsuranceFund {
+        &mut self.insurance
+    }
+
+    /// Notional an account can still open before reaching the value of its collateral
+    ///
+    /// # Returns
+    /// * `Option<f64>` - None if the account has not posted collateral
+    pub fn headroom(&self, account: AccountId) -> Option<f64> {
+        Some(self.collateral.value(account)? - self.open_notional(account))
+    }
+
+    /// The assets markets may be added for with [`Engine::add_market`]
+    pub fn assets(&self) -> &AssetRegistry {
+        &self.assets
+    }
+
+    pub fn assets_mut(&mut self) -> &mut AssetRegistry {
+        &mut self.assets
+    }
+
+    /// Combined notional of the open orders of an account and all of its sub-accounts, across every market
+    pub fn open_notional(&self, account: AccountId) -> f64 {
+        let mut members = self.accounts.descendants(account);
+        if members.is_empty() {
+            members.push(account);
+        }
+        exact_sum(
+            self.orderbooks
+                .values()
+                .map(|orderbook| orderbook.open_notional(&members)),
+        )
+    }
+
+    /// Move a virtual clock forward and run everything that became due
+    ///
+    /// Only available with the `test-mode` feature, so integration environments can exercise
+    /// expiries and auction/pause transitions without waiting for them. `clock` must be the clock
+    /// the engine's orderbooks were created with. Expiries go through [`Engine::expire_orders`],
+    /// so linked orders are cancelled and funds released as they would be in production.
+    ///
+    /// # Arguments
+    /// * `clock` - The shared clock of the engine's orderbooks
+    /// * `nanos` - How far to move it
+    #[cfg(feature = "test-mode")]
+    pub fn advance_clock(&mut self, clock: &super::clock::ManualClock, nanos: u64) -> ClockAdvance {
+        use super::clock::Clock;
+        self.sequence += 1;
+        clock.advance(nanos);
+        ClockAdvance {
+            expired: self.expire_orders(clock.now()),
+            auction_trades: self.poll_auctions(),
+        }
+    }
+
+    /// Add a pre-trade check run on every order before it reaches its book, and on the new price
+    /// and size of every amend and replace
+    ///
+    /// Checks run in the order they were added and the first rejection stops the order. Stop
+    /// orders are checked when placed and again when they trigger, since the market may have moved
+    /// in between; a stop turned away at its trigger is cancelled with [`CancelReason::Rejected`].
+    ///
+    /// [`CancelReason::Rejected`]: super::event::CancelReason::Rejected
+    pub fn add_risk_checker(&mut self, checker: Box<dyn RiskChecker>) {
+        self.risk_checkers.push(Arc::from(checker));
+        let pairs: Vec<TradingPair> = self.orderbooks.keys().cloned().collect();
+        for pair in pairs {
+            self.configure_orderbook(&pair);
+        }
+    }
+
+    /// Run an order through the pre-trade checks without placing it
+    ///
+    /// The place methods return the same rejection as [`OrderError::Risk`].
+    ///
+    /// # Arguments
+    /// * `trading_pair` - The market the order is for
+    /// * `order` - The order to check
+    /// * `price` - The order's limit price, None for a market order
+    pub fn check_risk(
+        &self,
+        trading_pair: &TradingPair,
+        order: &Order,
+        price: Option<f64>,
+    ) -> Result<(), RiskRejection> {
+        self.run_risk_checks(RiskContext {
+            pair: trading_pair,
+            side: order.order_type(),
+            price,
+            size: order.size(),
+            account: order.account(),
+            open_orders: self.open_orders_across_markets(order.account()),
+            last_trade: self.last_trade_price(trading_pair),
+        })
+    }
+
+    fn run_risk_checks(&self, context: RiskContext) -> Result<(), RiskRejection> {
+        self.risk_checkers
+            .iter()
+            .try_for_each(|checker| checker.check(&context))
+    }
+
+    /// Orders an account has open across every market, 0 without an account
+    fn open_orders_across_markets(&self, account: Option<AccountId>) -> usize {
+        match account {
+            Some(account) => self
+                .orderbooks
+                .values()
+                .map(|orderbook| orderbook.open_orders_of(account))
+                .sum(),
+            None => 0,
+        }
+    }
+
+    fn last_trade_price(&self, trading_pair: &TradingPair) -> Option<f64> {
+        self.orderbooks
+            .get(trading_pair)
+            .and_then(|orderbook| orderbook.last_trade_price())
+    }
+
+    /// Check a resting order's new price and size against the rules a new order would have to pass
+    fn check_amend(
+        &self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+        new_price: f64,
+        new_size: f64,
+    ) -> Result<(), OrderError> {
+        let orderbook = self
+            .orderbooks
+            .get(trading_pair)
+            .ok_or("Orderbook does not exist")?;
+        let (order, price) = match (orderbook.order(id), orderbook.order_price(id)) {
+            (Some(order), Some(price)) => (order, price),
+            _ => return Err(format!("Order {} is not open", id).into()),
+        };
+        let config = self.market_config(trading_pair);
+        config.validate(Some(new_price), new_size)?;
+        if let (Some(percent), false) = (config.price_collar, order.collar_override()) {
+            orderbook.check_price_collar(order.order_type(), new_price, percent)?;
+        }
+        // The order being amended is not one more open order
+        self.run_risk_checks(RiskContext {
+            pair: trading_pair,
+            side: order.order_type(),
+            price: Some(new_price),
+            size: new_size,
+            account: order.account(),
+            open_orders: self
+                .open_orders_across_markets(order.account())
+                .saturating_sub(1),
+            last_trade: self.last_trade_price(trading_pair),
+        })?;
+        if let Some(account) = order.account() {
+            let replaced = price * (order.size() + order.reserve());
+            self.check_account_limits(account, new_price * new_size, replaced)?;
+        }
+        Ok(())
+    }
+
+    /// Keep paper accounts' orders out of the real books
+    fn check_live(&self, order: &Order) -> Result<(), String> {
+        match order.account() {
+            Some(account) if self.accounts.is_paper(account) => Err(format!(
+                "Account {} is a paper account, its orders go through place_paper_order",
+                account
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a new order's notional against the collateral and limits of its account and each ancestor
+    ///
+    /// # Arguments
+    /// * `account` - The order's account
+    /// * `notional` - The order's notional
+    /// * `replaced` - Notional of the resting order being amended, which already counts toward
+    ///   open notional; 0 for a new order
+    fn check_account_limits(
+        &self,
+        account: AccountId,
+        notional: f64,
+        replaced: f64,
+    ) -> Result<(), String> {
+        let mut lineage = self.accounts.lineage(account);
+        if lineage.is_empty() {
+            lineage.push(account);
+        }
+        for id in lineage {
+            if let Some(headroom) = self.headroom(id) {
+                if notional - replaced > headroom {
+                    return Err(format!(
+                        "Order notional {} exceeds the collateral headroom of {} for account {}",
+                        notional, headroom, id
+                    ));
+                }
+            }
+            let limits = match self.accounts.get(id) {
+                Some(account) => account.limits,
+                None => continue,
+            };
+            if let Some(max) = limits.max_order_notional {
+                if notional > max {
+                    return Err(format!(
+                        "Order notional {} exceeds the limit of {} for account {}",
+                        notional, max, id
+                    ));
+                }
+            }
+            if let Some(max) = limits.max_open_notional {
+                let open = self.open_notional(id) - replaced;
+                if open + notional > max {
+                    return Err(format!(
+                        "Open notional {} would exceed the limit of {} for account {}",
+                        open + notional,
+                        max,
+                        id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Summarize every market, ranked by `metric` from highest to lowest
+    ///
+    /// Markets without a value for the metric (e.g. no trades yet) are listed last.
+    ///
+    /// # Arguments
+    /// * `metric` - The metric to rank by
+    /// * `limit` - Only return the top `limit` markets, if given
+    pub fn market_summary(
+        &mut self,
+        metric: SummaryMetric,
+        limit: Option<usize>,
+    ) -> Vec<MarketSummary> {
+        let mut summaries = self
+            .orderbooks
+            .iter_mut()
+            .map(|(pair, orderbook)| {
+                let now = orderbook.now();
+                let spread = orderbook.spread();
+                let stats = orderbook.stats();
+                MarketSummary {
+                    pair: pair.clone(),
+                    last_price: stats.last_price(),
+                    change_24h: stats.change(now),
+                    volume_24h: stats.volume(now),
+                    spread,
+                }
+            })
+            .collect::<Vec<MarketSummary>>();
+
+        summaries.sort_by(|a, b| match (a.metric(metric), b.metric(metric)) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        if let Some(limit) = limit {
+            summaries.truncate(limit);
+        }
+        summaries
+    }
+}
+
+/// The orders on either side of some trades
+fn traded_orders(trades: &[Trade]) -> Vec<OrderId> {
+    trades
+        .iter()
+        .flat_map(|trade| trade.maker_order_id.into_iter().chain(trade.taker_order_id))
+        .collect()
+}
+
+/// The order an event took out of the book, if any
+fn removed_order(event: &OrderEvent) -> Option<OrderId> {
+    match event {
+        OrderEvent::Cancelled { order_id, .. } | OrderEvent::Expired { order_id, .. } => {
+            Some(*order_id)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::{AccountClass, AccountLimits};
+    use crate::assets::{Asset, AssetKind};
+    use crate::matching::clock::ManualClock;
+    use crate::matching::duplicate::{DuplicateAction, DuplicateCheck};
+    use crate::matching::orderbook::{MarketStatus, OrderType, TimeInForce};
+    use crate::matching::peg::PegReference;
+    use crate::matching::risk::RiskLimits;
+    use crate::matching::stop::StopKind;
+    use std::sync::Arc;
+
+    fn pair() -> TradingPair {
+        TradingPair::new("BTC".to_string(), "USD".to_string())
+    }
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.add_orderbook(pair(), OrderBook::new());
+        engine
+    }
+
+    #[test]
+    fn firm_limits_aggregate_trader_orders() {
+        let mut engine = engine();
+        let firm_limits = AccountLimits {
+            max_order_notional: None,
+            max_open_notional: Some(1_000.0),
+        };
+        let trader_limits = AccountLimits {
+            max_order_notional: Some(800.0),
+            max_open_notional: None,
+        };
+        let accounts = engine.accounts_mut();
+        accounts.add_account(1, None, firm_limits).unwrap();
+        accounts.add_account(10, Some(1), trader_limits).unwrap();
+        accounts.add_account(11, Some(1), trader_limits).unwrap();
+
+        let bid = |account, size| Order::new(OrderType::Bid, size).with_account(account);
+        assert!(engine
+            .place_limit_order(pair(), 100.0, bid(10, 7.0))
+            .is_ok());
+        assert!(engine
+            .place_limit_order(pair(), 100.0, bid(11, 7.0))
+            .is_err());
+        assert!(engine
+            .place_limit_order(pair(), 100.0, bid(11, 3.0))
+            .is_ok());
+
+        assert_eq!(engine.open_notional(10), 700.0);
+        assert_eq!(engine.open_notional(1), 1_000.0);
+        assert!(engine.place_limit_order(pair(), 1.0, bid(10, 1.0)).is_err());
+    }
+
+    #[cfg(feature = "test-mode")]
+    #[test]
+    fn advancing_the_clock_expires_orders_and_ends_auctions() {
+        let clock = ManualClock::new(0);
+        let mut engine = Engine::new();
+        engine.add_orderbook(pair(), OrderBook::with_clock(Arc::new(clock.clone())));
+        let expiring = Order::new(OrderType::Bid, 1.0).with_expiry(1_000);
+        engine.place_limit_order(pair(), 99.0, expiring).unwrap();
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        engine.halt_market(&pair()).unwrap();
+        engine.resume_market(&pair()).unwrap();
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Bid, 1.0))
+            .unwrap();
+
+        let advance = engine.advance_clock(&clock, 500);
+        assert!(advance.expired.is_empty());
+        assert!(advance.auction_trades.is_empty());
+
+        let advance = engine.advance_clock(&clock, 10_000_000_000);
+        assert_eq!(advance.expired.len(), 1);
+        assert_eq!(advance.auction_trades.len(), 1);
+    }
+
+    #[cfg(feature = "test-mode")]
+    #[test]
+    fn advancing_the_clock_cancels_linked_orders_and_releases_funds() {
+        let clock = ManualClock::new(0);
+        let mut engine = Engine::new();
+        engine.add_orderbook(pair(), OrderBook::with_clock(Arc::new(clock.clone())));
+        engine.balances_mut().deposit(4, "USD", 1_000.0).unwrap();
+        let bid = |size| Order::new(OrderType::Bid, size).with_account(4);
+        let placement = engine
+            .place_oco(
+                &pair(),
+                OcoLeg::Limit {
+                    order: bid(2.0).with_expiry(1_000),
+                    price: 99.0,
+                },
+                OcoLeg::Stop {
+                    order: bid(1.0),
+                    stop_price: 105.0,
+                    kind: StopKind::Limit(106.0),
+                },
+            )
+            .unwrap();
+        let stop = placement.second.unwrap();
+        assert_eq!(engine.balances().reserved(4, "USD"), 304.0);
+
+        let advance = engine.advance_clock(&clock, 1_000);
+        assert_eq!(advance.expired.len(), 1);
+        let orderbook = engine.orderbook_mut(&pair()).unwrap();
+        assert!(orderbook.order(placement.first).is_none());
+        assert!(orderbook.stop_order(stop).is_none());
+        assert!(orderbook.drain_events().contains(&OrderEvent::Cancelled {
+            order_id: stop,
+            account: Some(4),
+            remaining: 1.0,
+            reason: CancelReason::Linked,
+        }));
+        assert_eq!(engine.balances().reserved(4, "USD"), 0.0);
+        assert_eq!(engine.balances().available(4, "USD"), 1_000.0);
+    }
+
+    #[test]
+    fn place_validated_orders() {
+        let mut engine = engine();
+        let ask = Order::builder()
+            .side(OrderType::Ask)
+            .price("100.25")
+            .size("2")
+            .build()
+            .unwrap();
+        assert_eq!(engine.place_order(pair(), ask), Ok(Vec::new()));
+
+        let bid = Order::builder()
+            .side(OrderType::Bid)
+            .size("0.5")
+            .build()
+            .unwrap();
+        let trades = engine.place_order(pair(), bid).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(f64::from(trades[0].price), 100.25);
+    }
+
+    #[test]
+    fn list_markets_with_metadata() {
+        let mut engine = engine();
+        let eth = TradingPair::new("ETH".to_string(), "USD".to_string());
+        let hidden = TradingPair::new("XYZ".to_string(), "USD".to_string());
+        engine.add_orderbook(eth.clone(), OrderBook::new());
+        engine.add_orderbook(hidden.clone(), OrderBook::new());
+        engine.set_market_config(
+            eth.clone(),
+            MarketConfig {
+                display_name: Some("Ether / US Dollar".to_string()),
+                price_precision: 2,
+                size_precision: 4,
+                tick_size: None,
+                lot_size: None,
+                min_size: None,
+                max_size: None,
+                min_notional: Some(10.0),
+                max_notional: None,
+                price_collar: None,
+                listed: true,
+            },
+        );
+        engine.set_market_config(
+            hidden,
+            MarketConfig {
+                listed: false,
+                ..MarketConfig::default()
+            },
+        );
+        engine.halt_market(&eth).unwrap();
+
+        let markets = engine.list_markets();
+        assert_eq!(markets.len(), 2);
+        assert_eq!(markets[0].pair, pair());
+        assert_eq!(markets[0].config, MarketConfig::default());
+        assert_eq!(markets[1].base, "ETH");
+        assert_eq!(markets[1].config.price_precision, 2);
+        assert_eq!(markets[1].status, MarketStatus::Halted);
+    }
+
+    #[test]
+    fn limit_orders_cross_before_resting() {
+        let mut engine = engine();
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Ask, 2.0))
+            .unwrap();
+        engine
+            .place_limit_order(pair(), 102.0, Order::new(OrderType::Ask, 2.0))
+            .unwrap();
+
+        let placement = engine
+            .place_limit_order(pair(), 101.0, Order::new(OrderType::Bid, 3.0))
+            .unwrap();
+        assert_eq!(placement.trades.len(), 1);
+        assert_eq!(placement.trades[0].size, 2.0);
+        assert_eq!(f64::from(placement.trades[0].price), 100.0);
+        assert_eq!(placement.trades[0].taker_order_id, Some(placement.id));
+
+        let orderbook = engine.orderbook(&pair()).unwrap();
+        assert_eq!(orderbook.order(placement.id).unwrap().size(), 1.0);
+        assert_eq!(orderbook.spread(), Some(1.0));
+        let top = engine.top_of_book(&pair()).unwrap();
+        let depth = engine.depth(&pair(), 1).unwrap();
+        assert_eq!(depth.bids, vec![top.bid.unwrap()]);
+        assert_eq!(depth.asks[0].order_count, 1);
+        let unknown = TradingPair::new("XYZ".to_string(), "USD".to_string());
+        assert!(engine.depth(&unknown, 1).is_err());
+        assert_eq!(top.bid.unwrap().volume, 1.0);
+        assert_eq!(top.ask.unwrap().volume, 2.0);
+        assert_eq!(top.mid, Some(101.5));
+
+        let filled = engine
+            .place_limit_order(pair(), 102.0, Order::new(OrderType::Bid, 2.0))
+            .unwrap();
+        assert_eq!(filled.trades.len(), 1);
+        assert!(engine
+            .orderbook(&pair())
+            .unwrap()
+            .order(filled.id)
+            .is_none());
+    }
+
+    #[test]
+    fn cancel_resting_orders() {
+        let mut engine = engine();
+        let id = engine
+            .orderbook_mut(&pair())
+            .unwrap()
+            .add(Order::new(OrderType::Bid, 2.0).with_account(4), 99.0);
+        assert_eq!(
+            engine.cancel_order(&pair(), id),
+            Ok(OrderEvent::Cancelled {
+                order_id: id,
+                account: Some(4),
+                remaining: 2.0,
+                reason: CancelReason::Requested,
+            })
+        );
+        assert!(engine.cancel_order(&pair(), id).is_err());
+        let eth = TradingPair::new("ETH".to_string(), "USD".to_string());
+        assert!(engine.cancel_order(&eth, id).is_err());
+        assert_eq!(engine.orderbook(&pair()).unwrap().open_orders(), 0);
+
+        let stop = engine
+            .place_stop_order(
+                &pair(),
+                Order::new(OrderType::Ask, 1.0),
+                95.0,
+                StopKind::Limit(94.0),
+            )
+            .unwrap();
+        assert!(engine.cancel_order(&pair(), stop).is_ok());
+        assert!(engine
+            .orderbook(&pair())
+            .unwrap()
+            .stop_order(stop)
+            .is_none());
+    }
+
+    #[test]
+    fn amends_and_replaces_pass_market_and_account_rules() {
+        let mut engine = engine();
+        engine.set_market_config(
+            pair(),
+            MarketConfig {
+                tick_size: Some(1.0),
+                max_size: Some(5.0),
+                max_notional: Some(1_000.0),
+                ..MarketConfig::default()
+            },
+        );
+        let limits = AccountLimits {
+            max_order_notional: None,
+            max_open_notional: Some(600.0),
+        };
+        engine.accounts_mut().add_account(4, None, limits).unwrap();
+        let bid = Order::new(OrderType::Bid, 5.0).with_account(4);
+        let id = engine.place_limit_order(pair(), 100.0, bid).unwrap().id;
+
+        for price in [100.5, f64::NAN, -5.0] {
+            assert!(matches!(
+                engine.amend_order(&pair(), id, price, 5.0),
+                Err(OrderError::Market(_))
+            ));
+        }
+        assert!(matches!(
+            engine.replace_order(&pair(), id, 100.0, 1_000.0),
+            Err(OrderError::Market(OrderRejection::SizeAboveMaximum { .. }))
+        ));
+        // The order's own notional is not counted twice against the open limit
+        assert!(engine.amend_order(&pair(), id, 110.0, 5.0).is_ok());
+        assert!(engine.replace_order(&pair(), id, 130.0, 5.0).is_err());
+        let orderbook = engine.orderbook(&pair()).unwrap();
+        assert_eq!(orderbook.order_price(id), Some(110.0));
+        assert_eq!(orderbook.order(id).unwrap().size(), 5.0);
+    }
+
+    #[test]
+    fn stop_limit_prices_follow_the_market_rules() {
+        let mut engine = engine();
+        engine.set_market_config(
+            pair(),
+            MarketConfig {
+                tick_size: Some(1.0),
+                max_notional: Some(1_000.0),
+                ..MarketConfig::default()
+            },
+        );
+        let mut stop =
+            |kind| engine.place_stop_order(&pair(), Order::new(OrderType::Bid, 1.0), 105.0, kind);
+        assert!(matches!(
+            stop(StopKind::Limit(105.37)),
+            Err(OrderError::Market(OrderRejection::OffTick { .. }))
+        ));
+        assert!(matches!(
+            stop(StopKind::Limit(f64::NAN)),
+            Err(OrderError::Market(OrderRejection::InvalidPrice { .. }))
+        ));
+        assert!(matches!(
+            stop(StopKind::Limit(1e9)),
+            Err(OrderError::Market(
+                OrderRejection::NotionalAboveMaximum { .. }
+            ))
+        ));
+        assert!(matches!(
+            stop(StopKind::Protected(0.5)),
+            Err(OrderError::Market(OrderRejection::OffTick { .. }))
+        ));
+        assert!(stop(StopKind::Limit(106.0)).is_ok());
+        assert!(stop(StopKind::Market).is_ok());
+    }
+
+    #[test]
+    fn sizes_must_be_whole_lots() {
+        let mut engine = engine();
+        engine.set_market_config(
+            pair(),
+            MarketConfig {
+                size_precision: 2,
+                ..MarketConfig::default()
+            },
+        );
+        let bid = |size| Order::new(OrderType::Bid, size);
+        let odd = engine.place_limit_order(pair(), 100.0, bid(0.015));
+        assert_eq!(
+            odd,
+            Err(OrderError::Market(OrderRejection::OffLot {
+                size: 0.015,
+                lot: 0.01
+            }))
+        );
+        assert!(matches!(
+            engine.place_limit_order(pair(), f64::NAN, bid(1.0)),
+            Err(OrderError::Market(OrderRejection::InvalidPrice { .. }))
+        ));
+        assert!(engine.place_limit_order(pair(), 100.0, bid(0.0)).is_err());
+        assert!(engine.place_market_order(&pair(), bid(-1.0)).is_err());
+
+        // Ten lots of 0.1 fill a resting size of 1 exactly
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        for _ in 0..10 {
+            engine.place_market_order(&pair(), bid(0.1)).unwrap();
+        }
+        assert!(engine.orderbook(&pair()).unwrap().best_ask().is_none());
+    }
+
+    #[test]
+    fn paper_accounts_trade_in_the_shadow_of_the_book() {
+        let mut engine = engine();
+        engine
+            .accounts_mut()
+            .add_account(9, None, AccountLimits::default())
+            .unwrap();
+        engine
+            .accounts_mut()
+            .set_class(9, AccountClass::Paper)
+            .unwrap();
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+
+        let paper = |size| Order::new(OrderType::Bid, size).with_account(9);
+        assert!(engine.place_limit_order(pair(), 100.0, paper(1.0)).is_err());
+        let unregistered = Order::new(OrderType::Bid, 1.0).with_account(3);
+        assert!(engine
+            .place_paper_order(&pair(), None, unregistered)
+            .is_err());
+
+        let (id, fills) = engine
+            .place_paper_order(&pair(), Some(100.0), paper(3.0))
+            .unwrap();
+        assert_eq!(fills[0].size, 1.0);
+        assert_eq!(engine.orderbook(&pair()).unwrap().best_ask(), Some(100.0));
+        assert_eq!(
+            engine
+                .paper_book(&pair())
+                .unwrap()
+                .order(id)
+                .unwrap()
+                .remaining,
+            2.0
+        );
+
+        // A real trade at the paper bid's price fills it
+        engine
+            .place_limit_order(pair(), 99.0, Order::new(OrderType::Ask, 0.5))
+            .unwrap();
+        engine
+            .place_market_order(&pair(), Order::new(OrderType::Bid, 1.5))
+            .unwrap();
+        let fills = engine.drain_paper_fills();
+        assert_eq!(fills.len(), 2);
+        assert_eq!(f64::from(fills[0].1.price), 100.0);
+        assert_eq!(fills[0].1.size + fills[1].1.size, 1.5);
+        assert!(engine.drain_paper_fills().is_empty());
+        assert_eq!(
+            engine.cancel_paper_order(&pair(), id).unwrap().remaining,
+            0.5
+        );
+        assert!(engine.cancel_paper_order(&pair(), id).is_err());
+    }
+
+    #[test]
+    fn orders_are_funded_from_account_balances() {
+        let mut engine = engine();
+        engine.balances_mut().deposit(4, "USD", 1_000.0).unwrap();
+        engine.balances_mut().deposit(5, "BTC", 10.0).unwrap();
+        let bid = |size| Order::new(OrderType::Bid, size).with_account(4);
+
+        let resting = engine.place_limit_order(pair(), 100.0, bid(6.0)).unwrap();
+        assert_eq!(engine.balances().reserved(4, "USD"), 600.0);
+        let over = engine.place_limit_order(pair(), 100.0, bid(5.0));
+        assert!(over
+            .unwrap_err()
+            .to_string()
+            .contains("Insufficient USD balance"));
+        assert!(engine
+            .amend_order(&pair(), resting.id, 100.0, 11.0)
+            .is_err());
+        assert_eq!(engine.balances().reserved(4, "USD"), 600.0);
+        engine.amend_order(&pair(), resting.id, 100.0, 8.0).unwrap();
+        assert_eq!(engine.balances().available(4, "USD"), 200.0);
+
+        // Fills settle both sides and release what the maker had reserved for them
+        let sell = Order::new(OrderType::Ask, 3.0).with_account(5);
+        engine.place_limit_order(pair(), 99.0, sell).unwrap();
+        let balances = engine.balances();
+        assert_eq!(balances.balance(4, "USD"), 700.0);
+        assert_eq!(balances.balance(4, "BTC"), 3.0);
+        assert_eq!(balances.reserved(4, "USD"), 500.0);
+        assert_eq!(balances.balance(5, "USD"), 300.0);
+        assert_eq!(balances.balance(5, "BTC"), 7.0);
+        assert_eq!(balances.reserved(5, "BTC"), 0.0);
+
+        engine.cancel_order(&pair(), resting.id).unwrap();
+        assert_eq!(engine.balances().available(4, "USD"), 700.0);
+        let sell = Order::new(OrderType::Ask, 8.0).with_account(5);
+        assert!(engine.place_market_order(&pair(), sell).is_err());
+    }
+
+    #[test]
+    fn stop_and_pegged_orders_hold_funds() {
+        let mut engine = engine();
+        engine.balances_mut().deposit(4, "USD", 1_000.0).unwrap();
+        engine.balances_mut().deposit(5, "BTC", 10.0).unwrap();
+        let anonymous = |side| Order::new(side, 1.0).with_account(6);
+        engine
+            .place_limit_order(pair(), 100.0, anonymous(OrderType::Bid))
+            .unwrap();
+        engine
+            .place_limit_order(pair(), 130.0, anonymous(OrderType::Ask))
+            .unwrap();
+        let bid = |size| Order::new(OrderType::Bid, size).with_account(4);
+
+        assert!(engine
+            .place_stop_order(&pair(), bid(1.0), 105.0, StopKind::Market)
+            .is_err());
+        let stop = engine
+            .place_stop_order(&pair(), bid(5.0), 105.0, StopKind::Limit(106.0))
+            .unwrap();
+        assert_eq!(engine.balances().reserved(4, "USD"), 530.0);
+        assert!(engine.place_limit_order(pair(), 100.0, bid(5.0)).is_err());
+        assert_eq!(engine.balances().reserved(4, "USD"), 530.0);
+
+        // Re-pegging holds funds at the new price, and cancels the order once they run out
+        let pegged = engine
+            .place_pegged_order(pair(), Peg::new(PegReference::Primary, 0.0), bid(4.0))
+            .unwrap()
+            .id;
+        engine
+            .place_limit_order(pair(), 101.0, anonymous(OrderType::Bid))
+            .unwrap();
+        assert_eq!(engine.balances().reserved(4, "USD"), 934.0);
+        engine
+            .place_limit_order(pair(), 120.0, anonymous(OrderType::Bid))
+            .unwrap();
+        let orderbook = engine.orderbook_mut(&pair()).unwrap();
+        assert!(orderbook.order(pegged).is_none());
+        assert!(orderbook.drain_events().contains(&OrderEvent::Cancelled {
+            order_id: pegged,
+            account: Some(4),
+            remaining: 4.0,
+            reason: CancelReason::Unfunded,
+        }));
+        assert_eq!(engine.balances().reserved(4, "USD"), 530.0);
+
+        // The triggered stop settles against what it reserved
+        engine
+            .place_market_order(&pair(), Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        assert_eq!(
+            engine.orderbook(&pair()).unwrap().order_price(stop),
+            Some(106.0)
+        );
+        let sell = Order::new(OrderType::Ask, 5.0).with_account(5);
+        engine.place_limit_order(pair(), 106.0, sell).unwrap();
+        let balances = engine.balances();
+        assert_eq!(balances.balance(4, "USD"), 470.0);
+        assert_eq!(balances.balance(4, "BTC"), 5.0);
+        assert_eq!(balances.reserved(4, "USD"), 0.0);
+    }
+
+    #[test]
+    fn fills_update_both_accounts_positions() {
+        let mut engine = engine();
+        let ask = |size| Order::new(OrderType::Ask, size).with_account(5);
+        engine.place_limit_order(pair(), 100.0, ask(2.0)).unwrap();
+        engine.place_limit_order(pair(), 110.0, ask(2.0)).unwrap();
+        let buy = Order::new(OrderType::Bid, 3.0).with_account(4);
+        engine.place_market_order(&pair(), buy).unwrap();
+
+        let long = engine.position(4, &pair()).unwrap();
+        assert_eq!(long.size(), 3.0);
+        assert!((long.entry_price - 310.0 / 3.0).abs() < 1e-9);
+        let short = engine.position(5, &pair()).unwrap();
+        assert_eq!((short.size(), short.entry_price), (-3.0, long.entry_price));
+
+        let sell = Order::new(OrderType::Ask, 3.0).with_account(4);
+        engine
+            .place_limit_order(pair(), 90.0, Order::new(OrderType::Bid, 3.0))
+            .unwrap();
+        engine.place_market_order(&pair(), sell).unwrap();
+        let closed = engine.position(4, &pair()).unwrap();
+        assert!(closed.is_flat());
+        assert!((closed.realized_pnl + 40.0).abs() < 1e-9);
+        assert!(engine.position(6, &pair()).is_none());
+    }
+
+    #[test]
+    fn risk_checkers_screen_orders_before_the_book() {
+        let mut engine = engine();
+        engine.add_risk_checker(Box::new(RiskLimits {
+            max_open_orders: Some(2),
+            collar_percent: Some(10.0),
+            ..RiskLimits::default()
+        }));
+        let bid = || Order::new(OrderType::Bid, 1.0).with_account(4);
+        engine.place_limit_order(pair(), 100.0, bid()).unwrap();
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Ask, 0.5))
+            .unwrap();
+        engine.place_limit_order(pair(), 95.0, bid()).unwrap();
+
+        assert_eq!(
+            engine.check_risk(&pair(), &bid(), Some(95.0)),
+            Err(RiskRejection::TooManyOpenOrders {
+                account: 4,
+                open: 2,
+                max: 2,
+            })
+        );
+        let rejected = engine.place_limit_order(pair(), 95.0, bid()).unwrap_err();
+        assert_eq!(
+            rejected.to_string(),
+            "Account 4 has 2 open orders, the limit is 2"
+        );
+        let far = Order::new(OrderType::Bid, 1.0);
+        assert!(matches!(
+            engine.check_risk(&pair(), &far, Some(111.0)),
+            Err(RiskRejection::OutsideCollar { .. })
+        ));
+        assert!(engine.place_limit_order(pair(), 111.0, far).is_err());
+        assert!(engine
+            .place_market_order(&pair(), Order::new(OrderType::Ask, 0.5))
+            .is_ok());
+
+        // Filled orders no longer count against the account
+        engine
+            .place_market_order(&pair(), Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        assert_eq!(engine.orderbook(&pair()).unwrap().open_orders_of(4), 0);
+        assert_eq!(engine.check_risk(&pair(), &bid(), Some(95.0)), Ok(()));
+    }
+
+    #[test]
+    fn each_risk_limit_rejects_with_its_own_error() {
+        let mut engine = engine();
+        engine.add_risk_checker(Box::new(RiskLimits {
+            max_order_size: Some(5.0),
+            max_notional: Some(1_000.0),
+            max_open_orders: Some(1),
+            collar_percent: Some(10.0),
+        }));
+        let bid = |size| Order::new(OrderType::Bid, size).with_account(4);
+        assert!(matches!(
+            engine.place_limit_order(pair(), 100.0, bid(6.0)),
+            Err(OrderError::Risk(RiskRejection::SizeAboveLimit { .. }))
+        ));
+        assert!(matches!(
+            engine.place_limit_order(pair(), 300.0, bid(4.0)),
+            Err(OrderError::Risk(RiskRejection::NotionalAboveLimit { .. }))
+        ));
+        assert!(matches!(
+            engine.place_market_order(&pair(), bid(6.0)),
+            Err(OrderError::Risk(RiskRejection::SizeAboveLimit { .. }))
+        ));
+        engine.place_limit_order(pair(), 100.0, bid(1.0)).unwrap();
+        assert!(matches!(
+            engine.place_limit_order(pair(), 100.0, bid(1.0)),
+            Err(OrderError::Risk(RiskRejection::TooManyOpenOrders {
+                account: 4,
+                open: 1,
+                max: 1,
+            }))
+        ));
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Ask, 0.5))
+            .unwrap();
+        assert!(matches!(
+            engine.place_limit_order(pair(), 89.0, Order::new(OrderType::Bid, 1.0)),
+            Err(OrderError::Risk(RiskRejection::OutsideCollar { .. }))
+        ));
+    }
+
+    #[test]
+    fn amends_and_replaces_pass_the_risk_checks() {
+        let mut engine = engine();
+        engine.add_risk_checker(Box::new(RiskLimits {
+            max_order_size: Some(5.0),
+            max_notional: Some(600.0),
+            max_open_orders: Some(1),
+            collar_percent: Some(10.0),
+        }));
+        let bid = Order::new(OrderType::Bid, 5.0).with_account(4);
+        let id = engine.place_limit_order(pair(), 100.0, bid).unwrap().id;
+        assert!(matches!(
+            engine.replace_order(&pair(), id, 100.0, 50.0),
+            Err(OrderError::Risk(RiskRejection::SizeAboveLimit { .. }))
+        ));
+        assert!(matches!(
+            engine.amend_order(&pair(), id, 130.0, 5.0),
+            Err(OrderError::Risk(RiskRejection::NotionalAboveLimit { .. }))
+        ));
+        assert!(engine.amend_order(&pair(), id, 100.0, 4.0).is_ok());
+
+        engine
+            .place_limit_order(pair(), 101.0, Order::new(OrderType::Ask, 5.0))
+            .unwrap();
+        engine
+            .place_market_order(&pair(), Order::new(OrderType::Bid, 1.0))
+            .unwrap();
+        assert!(matches!(
+            engine.replace_order(&pair(), id, 80.0, 4.0),
+            Err(OrderError::Risk(RiskRejection::OutsideCollar { .. }))
+        ));
+        assert_eq!(
+            engine.orderbook(&pair()).unwrap().order_price(id),
+            Some(100.0)
+        );
+    }
+
+    #[test]
+    fn stop_and_pegged_orders_pass_the_risk_checks() {
+        let mut engine = engine();
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Bid, 1.0))
+            .unwrap();
+        engine
+            .place_limit_order(pair(), 85.0, Order::new(OrderType::Bid, 2.0))
+            .unwrap();
+        engine
+            .place_limit_order(pair(), 101.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        engine.add_risk_checker(Box::new(RiskLimits {
+            max_order_size: Some(5.0),
+            collar_percent: Some(10.0),
+            ..RiskLimits::default()
+        }));
+
+        let pegged = engine.place_pegged_order(
+            pair(),
+            Peg::new(PegReference::Primary, 0.0),
+            Order::new(OrderType::Bid, 6.0),
+        );
+        assert!(matches!(
+            pegged,
+            Err(OrderError::Risk(RiskRejection::SizeAboveLimit { .. }))
+        ));
+        let stop = engine.place_stop_order(
+            &pair(),
+            Order::new(OrderType::Ask, 6.0),
+            95.0,
+            StopKind::Market,
+        );
+        assert!(matches!(
+            stop,
+            Err(OrderError::Risk(RiskRejection::SizeAboveLimit { .. }))
+        ));
+
+        // Within the collar of the last trade at 100 when placed, outside it once the market gaps
+        // down to 85 and triggers the stop
+        engine
+            .place_market_order(&pair(), Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        let stop = engine
+            .place_stop_order(
+                &pair(),
+                Order::new(OrderType::Ask, 1.0),
+                95.0,
+                StopKind::Limit(94.0),
+            )
+            .unwrap();
+        engine.orderbook_mut(&pair()).unwrap().drain_events();
+        engine
+            .place_market_order(&pair(), Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        let orderbook = engine.orderbook_mut(&pair()).unwrap();
+        assert!(orderbook.stop_order(stop).is_none());
+        assert!(orderbook.drain_events().contains(&OrderEvent::Cancelled {
+            order_id: stop,
+            account: None,
+            remaining: 1.0,
+            reason: CancelReason::Rejected,
+        }));
+        assert_eq!(orderbook.fillable_volume(OrderType::Ask, None), 1.0);
+    }
+
+    #[test]
+    fn collateral_caps_open_notional() {
+        let mut engine = engine();
+        let collateral = engine.collateral_mut();
+        collateral.post(4, "USD", 100.0).unwrap();
+        collateral.post(4, "ETH", 1.0).unwrap();
+        collateral.set_haircut("ETH", 50.0).unwrap();
+        collateral.update_mark("ETH", 200.0).unwrap();
+        assert_eq!(engine.headroom(4), Some(200.0));
+
+        let bid = |size| Order::new(OrderType::Bid, size).with_account(4);
+        engine.place_limit_order(pair(), 100.0, bid(1.5)).unwrap();
+        assert_eq!(engine.headroom(4), Some(50.0));
+        let over = engine.place_limit_order(pair(), 100.0, bid(1.0));
+        assert!(over
+            .unwrap_err()
+            .to_string()
+            .contains("collateral headroom"));
+
+        // A higher mark frees up headroom
+        engine.collateral_mut().update_mark("ETH", 300.0).unwrap();
+        assert!(engine.place_limit_order(pair(), 100.0, bid(1.0)).is_ok());
+        assert_eq!(engine.headroom(5), None);
+    }
+
+    #[test]
+    fn missed_heartbeats_cancel_orders() {
+        let mut engine = engine();
+        engine.set_heartbeat_config(HeartbeatConfig {
+            interval: 100,
+            miss_threshold: 2,
+        });
+        let ask = |account| Order::new(OrderType::Ask, 1.0).with_account(account);
+        engine.place_limit_order(pair(), 101.0, ask(1)).unwrap();
+        engine.place_limit_order(pair(), 102.0, ask(2)).unwrap();
+        engine.connect_session(10, 1, 0);
+        engine.connect_session(11, 1, 0);
+        engine.connect_session(20, 2, 0);
+
+        // Account 1 keeps one session alive, account 2 goes silent
+        assert!(engine.heartbeat(10, 150));
+        assert_eq!(engine.check_heartbeats(201), vec![11, 20]);
+        assert_eq!(engine.orderbook(&pair()).unwrap().open_orders(), 1);
+        assert_eq!(engine.orderbook(&pair()).unwrap().best_ask(), Some(101.0));
+
+        engine.disconnect_session(10);
+        assert_eq!(engine.orderbook(&pair()).unwrap().open_orders(), 0);
+        assert!(!engine.heartbeat(10, 300));
+    }
+
+    #[test]
+    fn disconnecting_cancels_stops_and_linked_orders() {
+        let mut engine = engine();
+        engine.balances_mut().deposit(4, "USD", 1_000.0).unwrap();
+        let bid = |size| Order::new(OrderType::Bid, size).with_account(4);
+        let placement = engine
+            .place_oco(
+                &pair(),
+                OcoLeg::Limit {
+                    order: bid(2.0),
+                    price: 99.0,
+                },
+                OcoLeg::Stop {
+                    order: bid(1.0),
+                    stop_price: 105.0,
+                    kind: StopKind::Limit(106.0),
+                },
+            )
+            .unwrap();
+        let other = Order::new(OrderType::Bid, 1.0).with_account(5);
+        engine.place_limit_order(pair(), 98.0, other).unwrap();
+        engine.connect_session(10, 4, 0);
+        engine.disconnect_session(10);
+
+        let orderbook = engine.orderbook_mut(&pair()).unwrap();
+        assert_eq!(orderbook.open_orders(), 1);
+        assert_eq!(orderbook.stop_orders(), 0);
+        let events = orderbook.drain_events();
+        for (id, remaining) in [(placement.first, 2.0), (placement.second.unwrap(), 1.0)] {
+            assert!(events.contains(&OrderEvent::Cancelled {
+                order_id: id,
+                account: Some(4),
+                remaining,
+                reason: CancelReason::Pulled,
+            }));
+        }
+        assert_eq!(engine.balances().reserved(4, "USD"), 0.0);
+    }
+
+    #[test]
+    fn market_notional_bounds() {
+        let mut engine = engine();
+        engine.set_market_config(
+            pair(),
+            MarketConfig {
+                min_notional: Some(10.0),
+                max_notional: Some(10_000.0),
+                ..MarketConfig::default()
+            },
+        );
+        let bid = |size| Order::new(OrderType::Bid, size);
+        let dust = engine.place_limit_order(pair(), 100.0, bid(0.05));
+        assert!(matches!(
+            dust,
+            Err(OrderError::Market(
+                OrderRejection::NotionalBelowMinimum { .. }
+            ))
+        ));
+        let fat_finger = engine.place_limit_order(pair(), 100.0, bid(500.0));
+        assert!(matches!(
+            fat_finger,
+            Err(OrderError::Market(
+                OrderRejection::NotionalAboveMaximum { .. }
+            ))
+        ));
+        assert!(engine.place_limit_order(pair(), 100.0, bid(1.0)).is_ok());
+    }
+
+    #[test]
+    fn price_collar_at_entry() {
+        let mut engine = engine();
+        engine.set_market_config(
+            pair(),
+            MarketConfig {
+                price_collar: Some(5.0),
+                ..MarketConfig::default()
+            },
+        );
+        let ask = |size| Order::new(OrderType::Ask, size);
+        let bid = |size| Order::new(OrderType::Bid, size);
+        assert!(engine.place_limit_order(pair(), 100.0, ask(5.0)).is_ok());
+        assert!(engine.place_limit_order(pair(), 90.0, bid(1.0)).is_ok());
+
+        assert!(engine.place_limit_order(pair(), 105.0, bid(1.0)).is_ok());
+        let through = engine.place_limit_order(pair(), 105.5, bid(1.0));
+        assert!(through
+            .unwrap_err()
+            .to_string()
+            .contains("through the opposite touch"));
+        assert!(engine
+            .place_limit_order(pair(), 105.5, bid(1.0).with_collar_override())
+            .is_ok());
+        assert!(engine.place_limit_order(pair(), 85.0, ask(1.0)).is_err());
+    }
+
+    #[test]
+    fn duplicate_orders() {
+        let clock = ManualClock::new(0);
+        let mut engine = Engine::new();
+        let mut orderbook = OrderBook::with_clock(Arc::new(clock.clone()));
+        orderbook.set_duplicate_check(Some(DuplicateCheck {
+            window: 1_000,
+            action: DuplicateAction::Reject,
+        }));
+        engine.add_orderbook(pair(), orderbook);
+        let bid = || Order::new(OrderType::Bid, 1.0).with_account(9);
+
+        assert!(engine.place_limit_order(pair(), 99.0, bid()).is_ok());
+        assert!(engine.place_limit_order(pair(), 99.0, bid()).is_err());
+        let tagged = bid().with_client_order_id("retry-2".to_string());
+        assert!(engine.place_limit_order(pair(), 99.0, tagged).is_ok());
+        clock.advance(2_000);
+        assert!(engine.place_limit_order(pair(), 99.0, bid()).is_ok());
+
+        let orderbook = engine.orderbook_mut(&pair()).unwrap();
+        orderbook.set_duplicate_check(Some(DuplicateCheck {
+            window: 1_000,
+            action: DuplicateAction::Flag,
+        }));
+        orderbook.place_limit_order(bid(), 99.0).unwrap();
+        let id = orderbook.place_limit_order(bid(), 99.0).unwrap().id;
+        assert_eq!(
+            orderbook.drain_events(),
+            vec![OrderEvent::SuspectedDuplicate {
+                order_id: id,
+                account: Some(9),
+            }]
+        );
+    }
+
+    #[test]
+    fn markets_use_registered_assets() {
+        let mut engine = Engine::new();
+        for (symbol, kind) in [
+            ("BTC", AssetKind::Crypto),
+            ("ETH", AssetKind::Crypto),
+            ("USD", AssetKind::Fiat),
+        ] {
+            engine
+                .assets_mut()
+                .register(Asset {
+                    symbol: symbol.to_string(),
+                    precision: 8,
+                    kind,
+                    network: None,
+                })
+                .unwrap();
+        }
+        let eth_btc = TradingPair::new("ETH".to_string(), "BTC".to_string());
+        assert!(engine.add_market(pair(), OrderBook::new()).is_ok());
+        assert!(engine.add_market(pair(), OrderBook::new()).is_err());
+        assert!(engine.add_market(eth_btc.clone(), OrderBook::new()).is_ok());
+        assert!(engine
+            .add_market(
+                TradingPair::new("SOL".to_string(), "USD".to_string()),
+                OrderBook::new()
+            )
+            .is_err());
+
+        assert_eq!(engine.markets_for_asset("BTC"), vec![pair(), eth_btc]);
+        assert!(engine.markets_for_asset("SOL").is_empty());
+    }
+
+    #[test]
+    fn settlement_prices() {
+        let clock = ManualClock::new(0);
+        let mut engine = Engine::new();
+        let eth = TradingPair::new("ETH".to_string(), "USD".to_string());
+        let quiet = TradingPair::new("SOL".to_string(), "USD".to_string());
+        engine.add_orderbook(pair(), OrderBook::with_clock(Arc::new(clock.clone())));
+        engine.add_orderbook(eth.clone(), OrderBook::with_clock(Arc::new(clock.clone())));
+        engine.add_orderbook(quiet, OrderBook::with_clock(Arc::new(clock.clone())));
+        engine.set_settlement_method(pair(), SettlementMethod::Vwap { window: 200 });
+        engine.set_settlement_method(eth.clone(), SettlementMethod::AuctionPrice);
+
+        for (pair, price, size) in [
+            (pair(), 100.0, 1.0),
+            (pair(), 110.0, 3.0),
+            (eth.clone(), 10.0, 1.0),
+        ] {
+            engine
+                .place_limit_order(pair.clone(), price, Order::new(OrderType::Ask, size))
+                .unwrap();
+            let mut buy = Order::new(OrderType::Bid, size);
+            engine
+                .orderbooks
+                .get_mut(&pair)
+                .unwrap()
+                .place_market_order(&mut buy);
+            clock.advance(50);
+        }
+
+        let mut settlements = engine.settle();
+        settlements.sort_by(|a, b| a.price.total_cmp(&b.price));
+        assert_eq!(settlements.len(), 2);
+        assert_eq!(settlements[0].pair, eth);
+        assert_eq!(settlements[0].price, 10.0);
+        assert!(settlements[0].fallback);
+        assert_eq!(settlements[1].price, 107.5);
+        assert!(!settlements[1].fallback);
+        assert_eq!(engine.settlements().len(), 2);
+    }
+
+    #[test]
+    fn end_of_day_expires_day_orders_and_reports() {
+        let mut engine = engine();
+        let day = Order::new(OrderType::Bid, 1.0).with_time_in_force(TimeInForce::Day);
+        engine.place_limit_order(pair(), 99.0, day).unwrap();
+        engine
+            .place_limit_order(pair(), 98.0, Order::new(OrderType::Bid, 1.0))
+            .unwrap();
+        engine
+            .place_limit_order(pair(), 101.0, Order::new(OrderType::Ask, 2.0))
+            .unwrap();
+        let mut buy = Order::new(OrderType::Bid, 1.0);
+        engine
+            .orderbooks
+            .get_mut(&pair())
+            .unwrap()
+            .place_market_order(&mut buy);
+
+        let mut steps = Vec::new();
+        let eod = engine.end_of_day(|step| steps.push(step));
+
+        assert_eq!(
+            steps,
+            vec![
+                EodStep::ExpireDayOrders,
+                EodStep::Settle,
+                EodStep::Report,
+                EodStep::Done
+            ]
+        );
+        assert_eq!(eod.expired.len(), 1);
+        assert!(matches!(
+            eod.expired[0].1,
+            OrderEvent::Cancelled {
+                reason: CancelReason::EndOfDay,
+                ..
+            }
+        ));
+        assert_eq!(eod.settlements.len(), 1);
+        assert_eq!(eod.reports.len(), 1);
+        assert_eq!(eod.reports[0].volume, 1.0);
+        assert_eq!(eod.reports[0].settlement, Some(101.0));
+        assert_eq!(eod.reports[0].open_orders, 2);
+    }
+
+    #[test]
+    fn removing_a_market_releases_funds_and_links() {
+        let mut engine = engine();
+        engine.balances_mut().deposit(4, "USD", 1_000.0).unwrap();
+        let bid = |size| Order::new(OrderType::Bid, size).with_account(4);
+        engine
+            .place_oco(
+                &pair(),
+                OcoLeg::Limit {
+                    order: bid(2.0),
+                    price: 99.0,
+                },
+                OcoLeg::Stop {
+                    order: bid(1.0),
+                    stop_price: 105.0,
+                    kind: StopKind::Limit(106.0),
+                },
+            )
+            .unwrap();
+        assert_eq!(engine.balances().reserved(4, "USD"), 304.0);
+
+        assert!(engine.remove_market(&pair()).is_some());
+        assert_eq!(engine.balances().reserved(4, "USD"), 0.0);
+        assert_eq!(engine.balances().available(4, "USD"), 1_000.0);
+        assert!(engine.oco.is_empty());
+        assert!(engine.remove_market(&pair()).is_none());
+    }
+
+    #[test]
+    fn snapshots_carry_stop_orders() {
+        let mut source = engine();
+        source
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        let stop = source
+            .place_stop_order(
+                &pair(),
+                Order::new(OrderType::Bid, 1.0).with_tag("breakout".to_string()),
+                105.0,
+                StopKind::Protected(2.0),
+            )
+            .unwrap();
+
+        let snapshot = source.snapshot();
+        assert_eq!(snapshot.markets[0].stops.len(), 1);
+        assert_eq!(snapshot.markets[0].stops[0].id, stop);
+
+        let mut recovered = engine();
+        recovered.restore_snapshot(&snapshot).unwrap();
+        let orderbook = recovered.orderbook(&pair()).unwrap();
+        assert_eq!(orderbook.stop_orders(), 1);
+        let restored = orderbook.stop_order(stop).unwrap();
+        assert_eq!(restored.limit_price(), Some(107.0));
+        assert_eq!(restored.order.tag(), Some("breakout"));
+        assert_eq!(recovered.snapshot().markets, snapshot.markets);
+        assert!(recovered.restore_snapshot(&snapshot).is_err());
+
+        let mut moved = Engine::new();
+        moved.import_market(&snapshot.markets[0]).unwrap();
+        assert_eq!(moved.orderbook(&pair()).unwrap().stop_orders(), 1);
+    }
+
+    #[test]
+    fn snapshot_all_markets_at_one_sequence() {
+        let mut engine = engine();
+        let eth = TradingPair::new("ETH".to_string(), "USD".to_string());
+        engine.add_orderbook(eth.clone(), OrderBook::new());
+        engine
+            .place_limit_order(pair(), 100.0, Order::new(OrderType::Ask, 2.0))
+            .unwrap();
+        engine
+            .place_limit_order(eth.clone(), 10.0, Order::new(OrderType::Bid, 3.0))
+            .unwrap();
+        engine
+            .place_market_order(&pair(), Order::new(OrderType::Bid, 0.5))
+            .unwrap();
+
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.sequence, engine.sequence());
+        assert_eq!(snapshot.markets.len(), 2);
+        assert_eq!(snapshot.markets[0].pair, pair());
+        assert_eq!(snapshot.markets[0].orders.len(), 1);
+        assert_eq!(snapshot.markets[0].orders[0].size, 1.5);
+        assert_eq!(snapshot.markets[1].pair, eth);
+
+        engine.cancel_order(&eth, 1).unwrap();
+        assert!(engine.sequence() > snapshot.sequence);
+
+        let mut recovered = Engine::new();
+        assert!(recovered.restore_snapshot(&snapshot).is_err());
+        recovered.add_orderbook(pair(), OrderBook::new());
+        recovered.add_orderbook(eth.clone(), OrderBook::new());
+        recovered.restore_snapshot(&snapshot).unwrap();
+        assert_eq!(recovered.sequence(), snapshot.sequence);
+        assert_eq!(recovered.snapshot().markets, snapshot.markets);
+    }
+
+    #[test]
+    fn client_order_ids_survive_a_restart() {
+        let mut engine = engine();
+        let ask = |client: &str| {
+            Order::new(OrderType::Ask, 1.0)
+                .with_account(3)
+                .with_client_order_id(client.to_string())
+        };
+        let bid = |client: &str| {
+            Order::new(OrderType::Bid, 1.0)
+                .with_account(4)
+                .with_client_order_id(client.to_string())
+        };
+        let first = engine.place_limit_order(pair(), 100.0, ask("a-1")).unwrap();
+        engine.place_market_order(&pair(), bid("b-1")).unwrap();
+        assert!(engine.place_limit_order(pair(), 100.0, ask("a-1")).is_err());
+        // Ids are per account
+        engine.place_limit_order(pair(), 100.0, bid("a-1")).unwrap();
+
+        // The filled order has left the book, but its id is still taken after a restart
+        let snapshot = engine.snapshot();
+        let mut recovered = Engine::new();
+        recovered.add_orderbook(pair(), OrderBook::new());
+        recovered.restore_snapshot(&snapshot).unwrap();
+        let retry = recovered.place_limit_order(pair(), 100.0, ask("a-1"));
+        assert_eq!(
+            retry.unwrap_err().to_string(),
+            format!(
+                "Client order id \"a-1\" was already used for order {}",
+                first.id
+            )
+        );
+        assert!(recovered.place_market_order(&pair(), bid("b-1")).is_err());
+        assert!(recovered
+            .place_limit_order(pair(), 100.0, ask("a-2"))
+            .is_ok());
+        assert_eq!(recovered.snapshot().markets[0].client_orders.len(), 4);
+    }
+
+    #[test]
+    fn expire_good_till_date_orders() {
+        let clock = Arc::new(ManualClock::new(0));
+        let mut engine = Engine::new();
+        let eth = TradingPair::new("ETH".to_string(), "USD".to_string());
+        engine.add_orderbook(pair(), OrderBook::with_clock(clock.clone()));
+        engine.add_orderbook(eth.clone(), OrderBook::with_clock(clock));
+        let gtd = |expires_at| {
+            Order::new(OrderType::Bid, 1.0)
+                .with_time_in_force(TimeInForce::Gtd)
+                .with_expiry(expires_at)
+        };
+        engine.place_limit_order(pair(), 99.0, gtd(1_000)).unwrap();
+        engine
+            .place_limit_order(eth.clone(), 9.0, gtd(2_000))
+            .unwrap();
+        assert!(engine
+            .place_limit_order(
+                pair(),
+                99.0,
+                Order::new(OrderType::Bid, 1.0).with_time_in_force(TimeInForce::Gtd)
+            )
+            .is_err());
+
+        assert!(engine.expire_orders(999).is_empty());
+        let expired = engine.expire_orders(1_000);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0, pair());
+        assert!(matches!(
+            expired[0].1,
+            OrderEvent::Expired { at: 1_000, .. }
+        ));
+        assert_eq!(engine.expire_orders(5_000).len(), 1);
+        assert_eq!(engine.orderbook(&eth).unwrap().open_orders(), 0);
+    }
+
+    #[test]
+    fn relative_prices_resolve_at_placement() {
+        let mut engine = engine();
+        engine.set_market_config(
+            pair(),
+            MarketConfig {
+                price_precision: 2,
+                ..MarketConfig::default()
+            },
+        );
+        assert!(engine
+            .place_relative_order(pair(), RelativePrice::Join, Order::new(OrderType::Bid, 1.0))
+            .is_err());
+        engine
+            .place_limit_order(pair(), 99.0, Order::new(OrderType::Bid, 1.0))
+            .unwrap();
+        engine
+            .place_limit_order(pair(), 101.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+
+        let improved = engine
+            .place_relative_order(
+                pair(),
+                RelativePrice::Improve,
+                Order::new(OrderType::Bid, 1.0),
+            )
+            .unwrap();
+        assert!(improved.trades.is_empty());
+        assert!(engine
+            .place_limit_order(pair(), 99.005, Order::new(OrderType::Bid, 1.0))
+            .is_err());
+        assert_eq!(engine.orderbook(&pair()).unwrap().best_bid(), Some(99.01));
+
+        let crossed = engine
+            .place_relative_order(
+                pair(),
+                RelativePrice::CrossToTouch,
+                Order::new(OrderType::Bid, 1.0),
+            )
+            .unwrap();
+        assert_eq!(crossed.trades.len(), 1);
+        assert_eq!(f64::from(crossed.trades[0].price), 101.0);
+    }
+
+    #[test]
+    fn one_cancels_other_groups() {
+        let mut engine = engine();
+        let placement = engine
+            .place_oco(
+                &pair(),
+                OcoLeg::Limit {
+                    order: Order::new(OrderType::Ask, 1.0),
+                    price: 110.0,
+                },
+                OcoLeg::Stop {
+                    order: Order::new(OrderType::Ask, 1.0),
+                    stop_price: 90.0,
+                    kind: StopKind::Market,
+                },
+            )
+            .unwrap();
+        let stop = placement.second.unwrap();
+        assert!(engine
+            .orderbook(&pair())
+            .unwrap()
+            .stop_order(stop)
+            .is_some());
+
+        let taken = engine
+            .place_limit_order(pair(), 110.0, Order::new(OrderType::Bid, 1.0))
+            .unwrap();
+        assert_eq!(taken.trades.len(), 1);
+        let orderbook = engine.orderbook_mut(&pair()).unwrap();
+        assert!(orderbook.stop_order(stop).is_none());
+        assert!(orderbook.drain_events().iter().any(|event| matches!(
+            event,
+            OrderEvent::Cancelled {
+                order_id,
+                reason: CancelReason::Linked,
+                ..
+            } if *order_id == stop
+        )));
+
+        // Cancelling either order takes its sibling out of the book
+        let placement = engine
+            .place_oco(
+                &pair(),
+                OcoLeg::Limit {
+                    order: Order::new(OrderType::Bid, 1.0),
+                    price: 95.0,
+                },
+                OcoLeg::Limit {
+                    order: Order::new(OrderType::Ask, 1.0),
+                    price: 120.0,
+                },
+            )
+            .unwrap();
+        engine.cancel_order(&pair(), placement.first).unwrap();
+        let orderbook = engine.orderbook(&pair()).unwrap();
+        assert_eq!(orderbook.open_orders(), 0);
+
+        // A rejected second order leaves nothing behind
+        assert!(engine
+            .place_oco(
+                &pair(),
+                OcoLeg::Limit {
+                    order: Order::new(OrderType::Bid, 1.0),
+                    price: 95.0,
+                },
+                OcoLeg::Limit {
+                    order: Order::new(OrderType::Ask, 1.0).with_time_in_force(TimeInForce::Gtd),
+                    price: 120.0,
+                },
+            )
+            .is_err());
+        assert_eq!(engine.orderbook(&pair()).unwrap().open_orders(), 0);
+    }
+
+    #[test]
+    fn liquidity_concentration_is_sampled_on_an_interval() {
+        let mut engine = engine();
+        engine.set_concentration_config(ConcentrationConfig {
+            near_levels: 1,
+            far_levels: 2,
+            interval: 100,
+        });
+        for price in [99.0, 98.0, 97.0] {
+            engine
+                .place_limit_order(pair(), price, Order::new(OrderType::Bid, 1.0))
+                .unwrap();
+        }
+        assert!(engine.liquidity_concentration(&pair()).is_none());
+
+        assert!(engine.sample_concentration(1_000));
+        let sample = *engine.liquidity_concentration(&pair()).unwrap();
+        assert_eq!(sample.at, 1_000);
+        assert_eq!(sample.bids.near_share, Some(0.5));
+        assert_eq!(sample.asks.near_share, None);
+
+        engine
+            .place_limit_order(pair(), 99.0, Order::new(OrderType::Bid, 2.0))
+            .unwrap();
+        assert!(!engine.sample_concentration(1_050));
+        assert_eq!(engine.liquidity_concentration(&pair()), Some(&sample));
+        assert!(engine.sample_concentration(1_100));
+        let sample = engine.liquidity_concentration(&pair()).unwrap();
+        assert_eq!(sample.bids.near_share, Some(0.75));
+    }
+
+    #[test]
+    fn pegged_orders_follow_the_touch() {
+        let mut engine = engine();
+        assert!(engine
+            .place_pegged_order(
+                pair(),
+                Peg::new(PegReference::Primary, 0.0),
+                Order::new(OrderType::Bid, 1.0)
+            )
+            .is_err());
+        engine
+            .place_limit_order(pair(), 99.0, Order::new(OrderType::Bid, 1.0))
+            .unwrap();
+        engine
+            .place_limit_order(pair(), 101.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        let pegged = engine
+            .place_pegged_order(
+                pair(),
+                Peg::new(PegReference::Midpoint, -0.5),
+                Order::new(OrderType::Bid, 1.0),
+            )
+            .unwrap()
+            .id;
+        assert_eq!(engine.orderbook(&pair()).unwrap().best_bid(), Some(99.5));
+
+        engine
+            .place_limit_order(pair(), 103.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        engine.cancel_order(&pair(), 2).unwrap();
+        let orderbook = engine.orderbook(&pair()).unwrap();
+        assert_eq!(orderbook.best_bid(), Some(100.5));
+        assert!(orderbook.peg(pegged).is_some());
+
+        // Pegs land on the market's tick grid, rounded away from the other side
+        engine.set_market_config(
+            pair(),
+            MarketConfig {
+                tick_size: Some(2.0),
+                ..MarketConfig::default()
+            },
+        );
+        assert_eq!(engine.orderbook(&pair()).unwrap().best_bid(), Some(100.0));
+        let ask = engine
+            .place_pegged_order(
+                pair(),
+                Peg::new(PegReference::Midpoint, 0.25),
+                Order::new(OrderType::Ask, 1.0),
+            )
+            .unwrap()
+            .id;
+        let orderbook = engine.orderbook(&pair()).unwrap();
+        assert_eq!(orderbook.order_price(ask), Some(102.0));
     }
 }
@@ -0,0 +1,34 @@
+use super::event::OrderEvent;
+use super::orderbook::TradingPair;
+use super::settlement::Settlement;
+
+/// Stages of the end-of-day process, reported as they start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EodStep {
+    ExpireDayOrders,
+    Settle,
+    Report,
+    Done,
+}
+
+/// Daily summary of one market
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyReport {
+    pub pair: TradingPair,
+    pub volume: f64,
+    pub last_price: Option<f64>,
+    /// Percentage change over the last 24 hours
+    pub change: Option<f64>,
+    pub settlement: Option<f64>,
+    /// Open orders left in the book after DAY orders expired
+    pub open_orders: usize,
+}
+
+/// Everything the end-of-day process produced
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EndOfDay {
+    /// A `Cancelled` event for every DAY order that expired, by market
+    pub expired: Vec<(TradingPair, OrderEvent)>,
+    pub settlements: Vec<Settlement>,
+    pub reports: Vec<DailyReport>,
+}
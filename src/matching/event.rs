@@ -0,0 +1,72 @@
+use super::clock::Timestamp;
+use super::orderbook::{AccountId, OrderId};
+
+/// Why an order was cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    /// The owner asked for the order to be cancelled
+    Requested,
+    /// The market halted under a cancelling halt policy
+    Halt,
+    /// A DAY order reached the end of the trading day
+    EndOfDay,
+    /// An IOC or FOK order could not fill in full on arrival
+    Unfilled,
+    /// The other order of its one-cancels-other group executed or was cancelled
+    Linked,
+    /// Its owner's quotes were pulled, by market maker protection or a lost session
+    Pulled,
+    /// A pre-trade risk check turned the stop order away as it triggered
+    Rejected,
+    /// Its account could no longer fund it at the price it was re-pegged to
+    Unfunded,
+}
+
+/// Something that happened to a resting order outside of a match
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderEvent {
+    /// The order reached its expiry time and was removed from the book
+    Expired {
+        order_id: OrderId,
+        account: Option<AccountId>,
+        remaining: f64,
+        at: Timestamp,
+    },
+    /// The order was removed from the book before it filled
+    Cancelled {
+        order_id: OrderId,
+        account: Option<AccountId>,
+        remaining: f64,
+        reason: CancelReason,
+    },
+    /// The order's size was reduced in place, keeping its place in the queue
+    Amended {
+        order_id: OrderId,
+        account: Option<AccountId>,
+        /// The order's version after the amend
+        version: u32,
+        price: f64,
+        size: f64,
+    },
+    /// The order was replaced with a new price and size, losing its place in the queue
+    Replaced {
+        order_id: OrderId,
+        account: Option<AccountId>,
+        /// The order's version after the replace
+        version: u32,
+        price: f64,
+        size: f64,
+    },
+    /// The order repeats one placed moments before and may be an accidental resubmission
+    SuspectedDuplicate {
+        order_id: OrderId,
+        account: Option<AccountId>,
+    },
+    /// The size queued ahead of a watched order changed by more than its threshold
+    QueueAhead {
+        order_id: OrderId,
+        account: Option<AccountId>,
+        orders_ahead: usize,
+        size_ahead: f64,
+    },
+}
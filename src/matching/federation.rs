@@ -0,0 +1,558 @@
+use super::engine::{Engine, OrderError};
+use super::event::OrderEvent;
+use super::orderbook::{MarketFill, MarketStatus, Order, OrderId, Placement, TradingPair};
+use super::snapshot::MarketSnapshot;
+use std::collections::HashMap;
+
+/// An engine instance that takes orders for the markets it hosts
+///
+/// [`Engine`] implements it for peers in the same process. A client for a remote instance
+/// implements it by forwarding each call over its transport and returning the instance's response.
+pub trait EnginePeer {
+    fn hosts(&self, trading_pair: &TradingPair) -> bool;
+
+    fn place_limit_order(
+        &mut self,
+        trading_pair: TradingPair,
+        price: f64,
+        order: Order,
+    ) -> Result<Placement, OrderError>;
+
+    fn place_market_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        order: Order,
+    ) -> Result<MarketFill, OrderError>;
+
+    fn cancel_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+    ) -> Result<OrderEvent, String>;
+
+    /// Start hosting a market moved from another instance
+    fn import_market(&mut self, snapshot: &MarketSnapshot) -> Result<(), String>;
+}
+
+impl EnginePeer for Engine {
+    fn hosts(&self, trading_pair: &TradingPair) -> bool {
+        self.orderbook(trading_pair).is_some()
+    }
+
+    fn place_limit_order(
+        &mut self,
+        trading_pair: TradingPair,
+        price: f64,
+        order: Order,
+    ) -> Result<Placement, OrderError> {
+        Engine::place_limit_order(self, trading_pair, price, order)
+    }
+
+    fn place_market_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        order: Order,
+    ) -> Result<MarketFill, OrderError> {
+        Engine::place_market_order(self, trading_pair, order)
+    }
+
+    fn cancel_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+    ) -> Result<OrderEvent, String> {
+        Engine::cancel_order(self, trading_pair, id)
+    }
+
+    fn import_market(&mut self, snapshot: &MarketSnapshot) -> Result<(), String> {
+        Engine::import_market(self, snapshot)
+    }
+}
+
+/// Stages of moving a market to another instance, reported as they complete
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationStatus {
+    /// New orders for the market are rejected; cancels are still accepted
+    Frozen,
+    /// The market's resting orders were copied to the new owner
+    Transferred,
+    /// Cancels accepted since the transfer were applied on the new owner
+    Replayed { cancels: usize },
+    /// Orders for the market now go to the new owner
+    Routed { peer: String },
+    /// The market accepts new orders again
+    Resumed,
+}
+
+/// A client-visible change in the status of a market being migrated
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationEvent {
+    pub pair: TradingPair,
+    pub status: MigrationStatus,
+}
+
+/// A market on its way to a peer
+struct Migration {
+    peer: String,
+    /// Orders cancelled locally since the transfer, to cancel on the peer as well
+    cancels: Vec<OrderId>,
+    /// Number of cancels already applied on the peer
+    replayed: usize,
+}
+
+/// A local engine that forwards orders for markets it does not host to the peer that does
+///
+/// Markets can be routed to a peer up front with [`Federation::route`]. Orders for a market that
+/// is neither hosted locally nor routed go to the first peer, by name, that hosts it, and the
+/// route is remembered.
+pub struct Federation {
+    local: Engine,
+    peers: HashMap<String, Box<dyn EnginePeer>>,
+    routes: HashMap<TradingPair, String>,
+    migrations: HashMap<TradingPair, Migration>,
+    events: Vec<MigrationEvent>,
+}
+
+impl Federation {
+    pub fn new(local: Engine) -> Self {
+        Federation {
+            local,
+            peers: HashMap::new(),
+            routes: HashMap::new(),
+            migrations: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Add a peer under a name, replacing any peer of the same name
+    pub fn add_peer(&mut self, name: String, peer: Box<dyn EnginePeer>) {
+        self.peers.insert(name, peer);
+    }
+
+    /// Send orders for a market to a peer
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if there is no peer with that name
+    pub fn route(&mut self, trading_pair: TradingPair, peer: &str) -> Result<(), String> {
+        if !self.peers.contains_key(peer) {
+            return Err(format!("Unknown peer {}", peer));
+        }
+        self.routes.insert(trading_pair, peer.to_string());
+        Ok(())
+    }
+
+    /// The name of the peer a market is routed to, None if it is hosted locally or unknown
+    pub fn peer_of(&self, trading_pair: &TradingPair) -> Option<&str> {
+        self.routes.get(trading_pair).map(String::as_str)
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.local
+    }
+
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        &mut self.local
+    }
+
+    /// See [`Engine::place_limit_order`]
+    pub fn place_limit_order(
+        &mut self,
+        trading_pair: TradingPair,
+        price: f64,
+        order: Order,
+    ) -> Result<Placement, OrderError> {
+        self.check_not_migrating(&trading_pair)?;
+        self.owner(&trading_pair)?
+            .place_limit_order(trading_pair, price, order)
+    }
+
+    /// See [`Engine::place_market_order`]
+    pub fn place_market_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        order: Order,
+    ) -> Result<MarketFill, OrderError> {
+        self.check_not_migrating(trading_pair)?;
+        self.owner(trading_pair)?
+            .place_market_order(trading_pair, order)
+    }
+
+    /// See [`Engine::cancel_order`]
+    ///
+    /// Cancels for a market being migrated are applied locally and replayed on the new owner when
+    /// the migration finishes.
+    pub fn cancel_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        id: OrderId,
+    ) -> Result<OrderEvent, String> {
+        let event = self.owner(trading_pair)?.cancel_order(trading_pair, id)?;
+        if let Some(migration) = self.migrations.get_mut(trading_pair) {
+            migration.cancels.push(id);
+        }
+        Ok(event)
+    }
+
+    /// Freeze a local market and copy it to a peer, the first half of a migration
+    ///
+    /// From here until [`Federation::finish_migration`], new orders for the market are rejected
+    /// while cancels still go through.
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the market is not hosted here and open, the peer is unknown or it rejected the
+    ///   market, in which case the market is left unfrozen
+    pub fn start_migration(
+        &mut self,
+        trading_pair: &TradingPair,
+        peer: &str,
+    ) -> Result<(), String> {
+        if self.migrations.contains_key(trading_pair) {
+            return Err(format!("{} is already migrating", pair_name(trading_pair)));
+        }
+        if !self.peers.contains_key(peer) {
+            return Err(format!("Unknown peer {}", peer));
+        }
+        let orderbook = self
+            .local
+            .orderbook(trading_pair)
+            .ok_or("Orderbook does not exist")?;
+        if orderbook.status() != MarketStatus::Open {
+            return Err("Only open markets can be migrated".to_string());
+        }
+
+        self.migrations.insert(
+            trading_pair.clone(),
+            Migration {
+                peer: peer.to_string(),
+                cancels: Vec::new(),
+                replayed: 0,
+            },
+        );
+        self.report(trading_pair, MigrationStatus::Frozen);
+        let snapshot = self
+            .local
+            .market_snapshot(trading_pair)
+            .ok_or("Orderbook does not exist")?;
+        let imported = match self.peers.get_mut(peer) {
+            Some(peer) => peer.import_market(&snapshot),
+            None => Err(format!("Unknown peer {}", peer)),
+        };
+        if let Err(error) = imported {
+            self.migrations.remove(trading_pair);
+            self.report(trading_pair, MigrationStatus::Resumed);
+            return Err(error);
+        }
+        self.report(trading_pair, MigrationStatus::Transferred);
+        Ok(())
+    }
+
+    /// Replay cancels on the new owner, route the market to it and resume order entry
+    ///
+    /// The local copy of the market is removed, releasing the funds reserved for its orders.
+    ///
+    /// If a cancel cannot be replayed the market stays frozen and local, and calling this again
+    /// carries on from that cancel.
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if the market is not migrating or a cancel could not be replayed
+    pub fn finish_migration(&mut self, trading_pair: &TradingPair) -> Result<(), String> {
+        let migration = self
+            .migrations
+            .get_mut(trading_pair)
+            .ok_or_else(|| format!("{} is not migrating", pair_name(trading_pair)))?;
+        let peer = self
+            .peers
+            .get_mut(&migration.peer)
+            .ok_or_else(|| format!("Unknown peer {}", migration.peer))?;
+        while let Some(id) = migration.cancels.first().copied() {
+            peer.cancel_order(trading_pair, id)?;
+            migration.cancels.remove(0);
+            migration.replayed += 1;
+        }
+        let migration = self
+            .migrations
+            .remove(trading_pair)
+            .ok_or_else(|| format!("{} is not migrating", pair_name(trading_pair)))?;
+        self.report(
+            trading_pair,
+            MigrationStatus::Replayed {
+                cancels: migration.replayed,
+            },
+        );
+        self.local.remove_market(trading_pair);
+        self.routes
+            .insert(trading_pair.clone(), migration.peer.clone());
+        self.report(
+            trading_pair,
+            MigrationStatus::Routed {
+                peer: migration.peer,
+            },
+        );
+        self.report(trading_pair, MigrationStatus::Resumed);
+        Ok(())
+    }
+
+    /// Move a local market to a peer in one go, see [`Federation::start_migration`]
+    pub fn migrate(&mut self, trading_pair: &TradingPair, peer: &str) -> Result<(), String> {
+        self.start_migration(trading_pair, peer)?;
+        self.finish_migration(trading_pair)
+    }
+
+    /// Take the migration status changes since the last call
+    pub fn drain_migration_events(&mut self) -> Vec<MigrationEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn report(&mut self, trading_pair: &TradingPair, status: MigrationStatus) {
+        self.events.push(MigrationEvent {
+            pair: trading_pair.clone(),
+            status,
+        });
+    }
+
+    fn check_not_migrating(&self, trading_pair: &TradingPair) -> Result<(), String> {
+        match self.migrations.contains_key(trading_pair) {
+            true => Err(format!(
+                "{} is migrating and not accepting new orders",
+                pair_name(trading_pair)
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// The engine hosting a market: the local one, the peer it is routed to, or the first peer
+    /// found hosting it
+    fn owner(&mut self, trading_pair: &TradingPair) -> Result<&mut dyn EnginePeer, String> {
+        if self.local.hosts(trading_pair) {
+            return Ok(&mut self.local);
+        }
+        if !self.routes.contains_key(trading_pair) {
+            let mut names: Vec<&String> = self.peers.keys().collect();
+            names.sort();
+            let name = names
+                .into_iter()
+                .find(|name| self.peers[*name].hosts(trading_pair))
+                .ok_or_else(|| format!("No engine hosts {}", pair_name(trading_pair)))?;
+            self.routes.insert(trading_pair.clone(), name.clone());
+        }
+        let name = &self.routes[trading_pair];
+        self.peers
+            .get_mut(name)
+            .map(|peer| peer.as_mut() as &mut dyn EnginePeer)
+            .ok_or_else(|| format!("Unknown peer {}", name))
+    }
+}
+
+fn pair_name(trading_pair: &TradingPair) -> String {
+    String::from(trading_pair.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::{OrderBook, OrderType};
+    use crate::matching::stop::StopKind;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn engine(pair: &TradingPair) -> Engine {
+        let mut engine = Engine::new();
+        engine.add_orderbook(pair.clone(), OrderBook::new());
+        engine
+    }
+
+    /// A peer whose cancels fail while `down` is set
+    struct Flaky {
+        engine: Engine,
+        down: Rc<Cell<bool>>,
+    }
+
+    impl EnginePeer for Flaky {
+        fn hosts(&self, trading_pair: &TradingPair) -> bool {
+            self.engine.hosts(trading_pair)
+        }
+
+        fn place_limit_order(
+            &mut self,
+            trading_pair: TradingPair,
+            price: f64,
+            order: Order,
+        ) -> Result<Placement, OrderError> {
+            self.engine.place_limit_order(trading_pair, price, order)
+        }
+
+        fn place_market_order(
+            &mut self,
+            trading_pair: &TradingPair,
+            order: Order,
+        ) -> Result<MarketFill, OrderError> {
+            self.engine.place_market_order(trading_pair, order)
+        }
+
+        fn cancel_order(
+            &mut self,
+            trading_pair: &TradingPair,
+            id: OrderId,
+        ) -> Result<OrderEvent, String> {
+            match self.down.get() {
+                true => Err("Peer is down".to_string()),
+                false => self.engine.cancel_order(trading_pair, id),
+            }
+        }
+
+        fn import_market(&mut self, snapshot: &MarketSnapshot) -> Result<(), String> {
+            self.engine.import_market(snapshot)
+        }
+    }
+
+    #[test]
+    fn orders_for_remote_markets_go_to_their_peer() {
+        let btc = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let eth = TradingPair::new("ETH".to_string(), "USD".to_string());
+        let mut federation = Federation::new(engine(&btc));
+        federation.add_peer("eth".to_string(), Box::new(engine(&eth)));
+        assert!(federation.route(eth.clone(), "sol").is_err());
+
+        federation
+            .place_limit_order(btc.clone(), 100.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap();
+        assert_eq!(federation.peer_of(&btc), None);
+
+        let resting = federation
+            .place_limit_order(eth.clone(), 10.0, Order::new(OrderType::Ask, 2.0))
+            .unwrap();
+        assert_eq!(federation.peer_of(&eth), Some("eth"));
+        assert!(federation.engine().orderbook(&eth).is_none());
+        let fill = federation
+            .place_market_order(&eth, Order::new(OrderType::Bid, 1.0))
+            .unwrap();
+        assert_eq!(fill.trades.len(), 1);
+        assert!(federation.cancel_order(&eth, resting.id).is_ok());
+
+        let sol = TradingPair::new("SOL".to_string(), "USD".to_string());
+        assert!(federation
+            .place_market_order(&sol, Order::new(OrderType::Bid, 1.0))
+            .is_err());
+    }
+
+    #[test]
+    fn markets_migrate_to_a_peer() {
+        let btc = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut federation = Federation::new(engine(&btc));
+        federation.add_peer("east".to_string(), Box::new(Engine::new()));
+        let first = federation
+            .place_limit_order(btc.clone(), 100.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap()
+            .id;
+        federation
+            .place_limit_order(btc.clone(), 101.0, Order::new(OrderType::Ask, 2.0))
+            .unwrap();
+        assert!(federation.start_migration(&btc, "west").is_err());
+
+        federation.start_migration(&btc, "east").unwrap();
+        assert!(federation
+            .place_market_order(&btc, Order::new(OrderType::Bid, 1.0))
+            .is_err());
+        federation.cancel_order(&btc, first).unwrap();
+        federation.finish_migration(&btc).unwrap();
+
+        assert!(federation.engine().orderbook(&btc).is_none());
+        assert_eq!(federation.peer_of(&btc), Some("east"));
+        let fill = federation
+            .place_market_order(&btc, Order::new(OrderType::Bid, 3.0))
+            .unwrap();
+        assert_eq!(fill.trades.len(), 1);
+        assert_eq!(f64::from(fill.trades[0].price), 101.0);
+        assert_eq!(fill.remaining, 1.0);
+
+        let statuses: Vec<MigrationStatus> = federation
+            .drain_migration_events()
+            .into_iter()
+            .map(|event| event.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                MigrationStatus::Frozen,
+                MigrationStatus::Transferred,
+                MigrationStatus::Replayed { cancels: 1 },
+                MigrationStatus::Routed {
+                    peer: "east".to_string()
+                },
+                MigrationStatus::Resumed,
+            ]
+        );
+        assert!(federation.finish_migration(&btc).is_err());
+    }
+
+    #[test]
+    fn migrating_a_market_releases_its_funds() {
+        let btc = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut local = engine(&btc);
+        local.balances_mut().deposit(4, "USD", 1_000.0).unwrap();
+        let mut federation = Federation::new(local);
+        federation.add_peer("east".to_string(), Box::new(Engine::new()));
+        federation
+            .place_limit_order(
+                btc.clone(),
+                100.0,
+                Order::new(OrderType::Bid, 5.0).with_account(4),
+            )
+            .unwrap();
+        federation
+            .engine_mut()
+            .place_stop_order(
+                &btc,
+                Order::new(OrderType::Bid, 1.0).with_account(4),
+                105.0,
+                StopKind::Limit(106.0),
+            )
+            .unwrap();
+        assert_eq!(federation.engine().balances().available(4, "USD"), 394.0);
+
+        federation.migrate(&btc, "east").unwrap();
+        assert_eq!(federation.engine().balances().available(4, "USD"), 1_000.0);
+        assert_eq!(federation.engine().balances().reserved(4, "USD"), 0.0);
+        let fill = federation
+            .place_market_order(&btc, Order::new(OrderType::Ask, 5.0))
+            .unwrap();
+        assert_eq!(fill.trades.len(), 1);
+    }
+
+    #[test]
+    fn a_failed_replay_keeps_the_market_migrating() {
+        let btc = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut federation = Federation::new(engine(&btc));
+        let down = Rc::new(Cell::new(false));
+        federation.add_peer(
+            "east".to_string(),
+            Box::new(Flaky {
+                engine: Engine::new(),
+                down: down.clone(),
+            }),
+        );
+        let first = federation
+            .place_limit_order(btc.clone(), 100.0, Order::new(OrderType::Ask, 1.0))
+            .unwrap()
+            .id;
+        federation.start_migration(&btc, "east").unwrap();
+        federation.cancel_order(&btc, first).unwrap();
+
+        down.set(true);
+        assert!(federation.finish_migration(&btc).is_err());
+        assert!(federation.engine().orderbook(&btc).is_some());
+        assert_eq!(federation.peer_of(&btc), None);
+        assert!(federation
+            .place_limit_order(btc.clone(), 101.0, Order::new(OrderType::Ask, 1.0))
+            .is_err());
+
+        down.set(false);
+        federation.finish_migration(&btc).unwrap();
+        assert!(federation.engine().orderbook(&btc).is_none());
+        assert_eq!(federation.peer_of(&btc), Some("east"));
+        assert!(federation
+            .drain_migration_events()
+            .iter()
+            .any(|event| event.status == MigrationStatus::Replayed { cancels: 1 }));
+    }
+}
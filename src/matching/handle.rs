@@ -0,0 +1,300 @@
+use super::depth::{Depth, DepthUpdate};
+use super::engine::Engine;
+use super::eod::{EndOfDay, EodStep};
+use super::event::OrderEvent;
+use super::orderbook::{MarketFill, Order, OrderBook, Placement, TradingPair};
+use super::settlement::Settlement;
+use super::trade::Trade;
+use std::collections::HashMap;
+
+/// Levels per side compared when computing book updates
+const BOOK_UPDATE_LEVELS: usize = 10;
+
+/// Callbacks for applications embedding the engine
+///
+/// Every method has an empty default so listeners only implement what they need. Callbacks run
+/// synchronously on the thread driving the [`EngineHandle`], after the operation that caused them.
+pub trait EngineListener {
+    fn on_trade(&mut self, _trading_pair: &TradingPair, _trade: &Trade) {}
+
+    fn on_order_event(&mut self, _trading_pair: &TradingPair, _event: &OrderEvent) {}
+
+    fn on_book_update(&mut self, _trading_pair: &TradingPair, _update: &DepthUpdate) {}
+
+    fn on_settlement(&mut self, _settlement: &Settlement) {}
+}
+
+/// An [`Engine`] that reports what happens to registered listeners
+///
+/// Operations mirror the ones on `Engine`. Reads go through [`EngineHandle::engine`]; mutating the
+/// engine directly bypasses the listeners.
+///
+/// # Example
+/// ```
+/// use orderbook::matching::engine::Engine;
+/// use orderbook::matching::handle::{EngineHandle, EngineListener};
+/// use orderbook::matching::orderbook::{Order, OrderBook, OrderType, TradingPair};
+/// use orderbook::matching::trade::Trade;
+///
+/// struct Printer;
+///
+/// impl EngineListener for Printer {
+///     fn on_trade(&mut self, trading_pair: &TradingPair, trade: &Trade) {
+///         println!("{:?} traded {}", trading_pair, trade.size);
+///     }
+/// }
+///
+/// let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+/// let mut handle = EngineHandle::new(Engine::new());
+/// handle.register(Box::new(Printer));
+/// handle.add_orderbook(pair.clone(), OrderBook::new());
+/// handle.place_limit_order(pair.clone(), 100.0, Order::new(OrderType::Ask, 1.0)).unwrap();
+/// let fill = handle.place_market_order(&pair, Order::new(OrderType::Bid, 1.0)).unwrap();
+/// assert_eq!(fill.trades.len(), 1);
+/// ```
+pub struct EngineHandle {
+    engine: Engine,
+    listeners: Vec<Box<dyn EngineListener>>,
+    /// Depth last reported to the listeners, by market
+    depths: HashMap<TradingPair, Depth>,
+}
+
+impl EngineHandle {
+    pub fn new(engine: Engine) -> Self {
+        EngineHandle {
+            engine,
+            listeners: Vec::new(),
+            depths: HashMap::new(),
+        }
+    }
+
+    /// Add a listener, called after those registered before it
+    pub fn register(&mut self, listener: Box<dyn EngineListener>) {
+        self.listeners.push(listener);
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Give back the engine, dropping the listeners
+    pub fn into_engine(self) -> Engine {
+        self.engine
+    }
+
+    pub fn add_orderbook(&mut self, trading_pair: TradingPair, orderbook: OrderBook) {
+        self.engine.add_orderbook(trading_pair.clone(), orderbook);
+        self.publish(&trading_pair, &[], Vec::new());
+    }
+
+    /// See [`Engine::place_limit_order`]
+    pub fn place_limit_order(
+        &mut self,
+        trading_pair: TradingPair,
+        price: f64,
+        order: Order,
+    ) -> Result<Placement, String> {
+        let placement = self
+            .engine
+            .place_limit_order(trading_pair.clone(), price, order)?;
+        self.publish(&trading_pair, &placement.trades, Vec::new());
+        Ok(placement)
+    }
+
+    /// See [`Engine::place_market_order`]
+    pub fn place_market_order(
+        &mut self,
+        trading_pair: &TradingPair,
+        order: Order,
+    ) -> Result<MarketFill, String> {
+        let fill = self.engine.place_market_order(trading_pair, order)?;
+        self.publish(trading_pair, &fill.trades, Vec::new());
+        Ok(fill)
+    }
+
+    /// See [`Engine::halt_market`]
+    pub fn halt_market(&mut self, trading_pair: &TradingPair) -> Result<Vec<OrderEvent>, String> {
+        let events = self.engine.halt_market(trading_pair)?;
+        self.publish(trading_pair, &[], events.clone());
+        Ok(events)
+    }
+
+    /// See [`Engine::resume_market`]
+    pub fn resume_market(&mut self, trading_pair: &TradingPair) -> Result<(), String> {
+        self.engine.resume_market(trading_pair)?;
+        self.publish(trading_pair, &[], Vec::new());
+        Ok(())
+    }
+
+    /// See [`Engine::poll_auctions`]
+    pub fn poll_auctions(&mut self) -> Vec<(TradingPair, Trade)> {
+        let trades = self.engine.poll_auctions();
+        let mut pairs: Vec<TradingPair> = trades.iter().map(|(pair, _)| pair.clone()).collect();
+        pairs.dedup();
+        for pair in pairs {
+            let market_trades: Vec<Trade> = trades
+                .iter()
+                .filter(|(trade_pair, _)| *trade_pair == pair)
+                .map(|(_, trade)| trade.clone())
+                .collect();
+            self.publish(&pair, &market_trades, Vec::new());
+        }
+        trades
+    }
+
+    /// See [`Engine::settle`]
+    pub fn settle(&mut self) -> Vec<Settlement> {
+        let settlements = self.engine.settle();
+        self.publish_settlements(&settlements);
+        settlements
+    }
+
+    /// See [`Engine::end_of_day`]
+    pub fn end_of_day(&mut self, progress: impl FnMut(EodStep)) -> EndOfDay {
+        let eod = self.engine.end_of_day(progress);
+        let mut pairs: Vec<TradingPair> = eod
+            .reports
+            .iter()
+            .map(|report| report.pair.clone())
+            .collect();
+        pairs.sort_by_key(|pair| String::from(pair.clone()));
+        for pair in pairs {
+            let events = eod
+                .expired
+                .iter()
+                .filter(|(event_pair, _)| *event_pair == pair)
+                .map(|(_, event)| event.clone())
+                .collect();
+            self.publish(&pair, &[], events);
+        }
+        self.publish_settlements(&eod.settlements);
+        eod
+    }
+
+    fn publish_settlements(&mut self, settlements: &[Settlement]) {
+        for listener in self.listeners.iter_mut() {
+            for settlement in settlements {
+                listener.on_settlement(settlement);
+            }
+        }
+    }
+
+    /// Report an operation's trades and events, the book's queued events and the resulting depth
+    /// changes for one market
+    fn publish(
+        &mut self,
+        trading_pair: &TradingPair,
+        trades: &[Trade],
+        mut events: Vec<OrderEvent>,
+    ) {
+        let (queued, depth) = match self.engine.orderbook_mut(trading_pair) {
+            Some(orderbook) => (
+                orderbook.drain_events(),
+                orderbook.depth(BOOK_UPDATE_LEVELS),
+            ),
+            None => return,
+        };
+        events.extend(queued);
+        let previous = self.depths.remove(trading_pair).unwrap_or_default();
+        let updates = depth.diff(&previous);
+        self.depths.insert(trading_pair.clone(), depth);
+
+        for listener in self.listeners.iter_mut() {
+            for trade in trades {
+                listener.on_trade(trading_pair, trade);
+            }
+            for event in &events {
+                listener.on_order_event(trading_pair, event);
+            }
+            for update in &updates {
+                listener.on_book_update(trading_pair, update);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::{HaltPolicy, OrderType};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl EngineListener for Recorder {
+        fn on_trade(&mut self, _trading_pair: &TradingPair, trade: &Trade) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("trade {}", trade.size));
+        }
+
+        fn on_order_event(&mut self, _trading_pair: &TradingPair, event: &OrderEvent) {
+            let name = match event {
+                OrderEvent::Expired { .. } => "expired",
+                OrderEvent::Cancelled { .. } => "cancelled",
+                OrderEvent::Amended { .. } => "amended",
+                OrderEvent::Replaced { .. } => "replaced",
+                OrderEvent::QueueAhead { .. } => "queue",
+                OrderEvent::SuspectedDuplicate { .. } => "duplicate",
+            };
+            self.calls.lock().unwrap().push(name.to_string());
+        }
+
+        fn on_book_update(&mut self, _trading_pair: &TradingPair, update: &DepthUpdate) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("book {:?} {}", update.side, update.level.volume));
+        }
+
+        fn on_settlement(&mut self, settlement: &Settlement) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("settle {}", settlement.price));
+        }
+    }
+
+    #[test]
+    fn listeners_see_trades_events_and_book_updates() {
+        let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let recorder = Recorder::default();
+        let calls = recorder.calls.clone();
+        let mut handle = EngineHandle::new(Engine::new());
+        handle.register(Box::new(recorder));
+        let mut orderbook = OrderBook::new();
+        orderbook.set_halt_policy(HaltPolicy::CancelAll);
+        handle.add_orderbook(pair.clone(), orderbook);
+
+        handle
+            .place_limit_order(pair.clone(), 100.0, Order::new(OrderType::Ask, 2.0))
+            .unwrap();
+        handle
+            .place_market_order(&pair, Order::new(OrderType::Bid, 1.5))
+            .unwrap();
+        handle.halt_market(&pair).unwrap();
+        assert_eq!(handle.settle().len(), 1);
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "book Ask 2",
+                "trade 1.5",
+                "book Ask 0.5",
+                "cancelled",
+                "book Ask 0",
+                "settle 100"
+            ]
+        );
+        assert!(handle
+            .place_market_order(
+                &TradingPair::new("ETH".to_string(), "USD".to_string()),
+                Order::new(OrderType::Bid, 1.0)
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use super::clock::Timestamp;
+use super::orderbook::AccountId;
+use std::collections::BTreeMap;
+
+pub type SessionId = u64;
+
+/// How often connected clients must send a heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    /// Nanoseconds between heartbeats
+    pub interval: u64,
+    /// Heartbeats a session may miss in a row before it is treated as disconnected
+    pub miss_threshold: u32,
+}
+
+impl Default for HeartbeatConfig {
+    /// A heartbeat every second, three missed in a row disconnect the session
+    fn default() -> Self {
+        HeartbeatConfig {
+            interval: 1_000_000_000,
+            miss_threshold: 3,
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    /// Nanoseconds of silence after which a session is treated as disconnected
+    pub fn timeout(&self) -> u64 {
+        self.interval
+            .saturating_mul(self.miss_threshold.max(1) as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Session {
+    account: AccountId,
+    last_seen: Timestamp,
+}
+
+/// Application-level liveness of client sessions
+///
+/// A connection can stay open at the TCP level long after the client behind it has stopped
+/// responding. Every message a session sends counts as a heartbeat; a session silent for longer
+/// than its config's timeout is dropped and reported as disconnected.
+#[derive(Debug, Default)]
+pub struct HeartbeatMonitor {
+    config: HeartbeatConfig,
+    sessions: BTreeMap<SessionId, Session>,
+}
+
+impl HeartbeatMonitor {
+    pub fn new(config: HeartbeatConfig) -> Self {
+        HeartbeatMonitor {
+            config,
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> HeartbeatConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: HeartbeatConfig) {
+        self.config = config;
+    }
+
+    /// Start tracking a session trading for `account`, replacing any session with the same id
+    pub fn connect(&mut self, session: SessionId, account: AccountId, now: Timestamp) {
+        self.sessions.insert(
+            session,
+            Session {
+                account,
+                last_seen: now,
+            },
+        );
+    }
+
+    /// Record a heartbeat, or any other message, from a session
+    ///
+    /// # Returns
+    /// * `bool` - false if the session is not connected
+    pub fn heartbeat(&mut self, session: SessionId, now: Timestamp) -> bool {
+        match self.sessions.get_mut(&session) {
+            Some(session) => {
+                session.last_seen = session.last_seen.max(now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop tracking a session, returning the account it traded for
+    pub fn disconnect(&mut self, session: SessionId) -> Option<AccountId> {
+        self.sessions
+            .remove(&session)
+            .map(|session| session.account)
+    }
+
+    /// Drop every session that has missed too many heartbeats by `now`
+    ///
+    /// # Returns
+    /// * `Vec<(SessionId, AccountId)>` - The dropped sessions and their accounts, by session id
+    pub fn poll(&mut self, now: Timestamp) -> Vec<(SessionId, AccountId)> {
+        let timeout = self.config.timeout();
+        let dead: Vec<(SessionId, AccountId)> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| now.saturating_sub(session.last_seen) > timeout)
+            .map(|(id, session)| (*id, session.account))
+            .collect();
+        for (id, _) in &dead {
+            self.sessions.remove(id);
+        }
+        dead
+    }
+
+    /// Whether the account still has a connected session
+    pub fn is_connected(&self, account: AccountId) -> bool {
+        self.sessions
+            .values()
+            .any(|session| session.account == account)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_sessions_that_miss_heartbeats() {
+        let mut monitor = HeartbeatMonitor::new(HeartbeatConfig {
+            interval: 100,
+            miss_threshold: 3,
+        });
+        monitor.connect(1, 7, 0);
+        monitor.connect(2, 8, 0);
+        assert!(monitor.heartbeat(1, 250));
+        assert!(!monitor.heartbeat(3, 250));
+
+        assert!(monitor.poll(300).is_empty());
+        assert_eq!(monitor.poll(301), vec![(2, 8)]);
+        assert!(!monitor.is_connected(8));
+        assert!(monitor.is_connected(7));
+        assert!(!monitor.heartbeat(2, 302));
+
+        assert_eq!(monitor.poll(551), vec![(1, 7)]);
+        assert_eq!(monitor.disconnect(1), None);
+    }
+}
@@ -0,0 +1,120 @@
+use super::clock::Timestamp;
+use super::depth::Depth;
+use super::numeric::exact_sum;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Resting size per price bucket for one time bucket, split by side
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Column {
+    bids: BTreeMap<i64, f64>,
+    asks: BTreeMap<i64, f64>,
+}
+
+/// Records liquidity-at-price over time for heatmap visualisation
+///
+/// Depth snapshots are bucketed by time (`interval` nanoseconds) and price (`price_step`). Within a
+/// time bucket the latest snapshot wins, so sampling more often than the interval is harmless.
+#[derive(Debug)]
+pub struct HeatmapRecorder {
+    interval: u64,
+    price_step: f64,
+    columns: BTreeMap<Timestamp, Column>,
+}
+
+impl HeatmapRecorder {
+    pub fn new(interval: u64, price_step: f64) -> Self {
+        HeatmapRecorder {
+            interval: interval.max(1),
+            price_step,
+            columns: BTreeMap::new(),
+        }
+    }
+
+    /// Record a depth snapshot taken at `now`
+    pub fn record(&mut self, depth: &Depth, now: Timestamp) {
+        let bucket = now - now % self.interval;
+        let mut column = Column::default();
+        for (levels, cells) in [
+            (&depth.bids, &mut column.bids),
+            (&depth.asks, &mut column.asks),
+        ] {
+            for level in levels {
+                let price: f64 = level.price.into();
+                let cell = (price / self.price_step).floor() as i64;
+                let size = cells.entry(cell).or_insert(0.0);
+                *size = exact_sum([*size, level.volume]);
+            }
+        }
+        self.columns.insert(bucket, column);
+    }
+
+    /// Number of recorded time buckets
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Resting size in the cell containing `price` at the time bucket containing `at`
+    pub fn size_at(&self, at: Timestamp, price: f64) -> f64 {
+        let bucket = at - at % self.interval;
+        let cell = (price / self.price_step).floor() as i64;
+        self.columns.get(&bucket).map_or(0.0, |column| {
+            column.bids.get(&cell).copied().unwrap_or(0.0)
+                + column.asks.get(&cell).copied().unwrap_or(0.0)
+        })
+    }
+
+    /// Write the matrix as CSV rows of `time,price,side,size`, one per non-empty cell
+    ///
+    /// `price` is the lower bound of the price bucket.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "time,price,side,size")?;
+        for (time, column) in &self.columns {
+            for (side, cells) in [("bid", &column.bids), ("ask", &column.asks)] {
+                for (cell, size) in cells {
+                    let price = *cell as f64 * self.price_step;
+                    writeln!(writer, "{},{},{},{}", time, price, side, size)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::{Order, OrderBook, OrderType};
+
+    #[test]
+    fn buckets_liquidity_by_time_and_price() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 99.2);
+        orderbook.add(Order::new(OrderType::Bid, 2.0), 99.7);
+        orderbook.add(Order::new(OrderType::Ask, 3.0), 101.0);
+
+        let mut recorder = HeatmapRecorder::new(1_000, 1.0);
+        recorder.record(&orderbook.depth(10), 1_100);
+        orderbook.add(Order::new(OrderType::Ask, 4.0), 101.5);
+        recorder.record(&orderbook.depth(10), 1_900);
+        recorder.record(&orderbook.depth(10), 2_000);
+
+        assert_eq!(recorder.len(), 2);
+        assert_eq!(recorder.size_at(1_000, 99.0), 3.0);
+        assert_eq!(recorder.size_at(1_500, 101.9), 7.0);
+        assert_eq!(recorder.size_at(3_000, 99.0), 0.0);
+
+        let mut csv = Vec::new();
+        recorder.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines = csv.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "time,price,side,size");
+        assert_eq!(lines[1], "1000,99,bid,3");
+        assert_eq!(lines[2], "1000,101,ask,7");
+        assert_eq!(lines.len(), 5);
+    }
+}
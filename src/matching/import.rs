@@ -0,0 +1,160 @@
+use super::orderbook::{AccountId, Order, OrderType};
+use std::io::{BufRead, Lines};
+use std::iter::Enumerate;
+
+/// A single order read from a CSV batch file
+#[derive(Debug, PartialEq)]
+pub struct ImportRow {
+    /// 1-based line number in the source file
+    pub line: usize,
+    pub side: OrderType,
+    pub price: f64,
+    pub size: f64,
+    pub account: Option<AccountId>,
+}
+
+impl ImportRow {
+    pub fn to_order(&self) -> Order {
+        let order = Order::new(self.side, self.size);
+        match self.account {
+            Some(account) => order.with_account(account),
+            None => order,
+        }
+    }
+}
+
+/// Parse a CSV batch of orders
+///
+/// Each row is `side,price,size[,account]` where side is `bid`/`buy` or `ask`/`sell`. A header
+/// row starting with `side`, blank lines and lines starting with `#` are skipped.
+///
+/// Rows are read from `reader` as they are iterated, so files of any size are parsed in constant
+/// memory.
+///
+/// # Returns
+/// * `ImportRows<R>` - One entry per order row, with the reason for every rejected row
+///
+/// # Example
+/// ```
+/// use orderbook::matching::import::parse_orders_csv;
+/// let rows: Vec<_> = parse_orders_csv("side,price,size\nbid,100.5,2\n".as_bytes()).collect();
+/// assert_eq!(rows.len(), 1);
+/// assert!(rows[0].is_ok());
+/// ```
+pub fn parse_orders_csv<R: BufRead>(reader: R) -> ImportRows<R> {
+    ImportRows {
+        lines: reader.lines().enumerate(),
+        failed: false,
+    }
+}
+
+/// The rows of a CSV batch of orders, parsed one line at a time, see [`parse_orders_csv`]
+pub struct ImportRows<R> {
+    lines: Enumerate<Lines<R>>,
+    /// Set once the reader fails, which ends the rows
+    failed: bool,
+}
+
+impl<R: BufRead> Iterator for ImportRows<R> {
+    type Item = Result<ImportRow, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        for (index, line) in self.lines.by_ref() {
+            let line_number = index + 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(format!("line {}: {}", line_number, e)));
+                }
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if index == 0 && trimmed.to_ascii_lowercase().starts_with("side") {
+                continue;
+            }
+            return Some(
+                parse_row(line_number, trimmed).map_err(|e| format!("line {}: {}", line_number, e)),
+            );
+        }
+        None
+    }
+}
+
+fn parse_row(line: usize, row: &str) -> Result<ImportRow, String> {
+    let fields = row.split(',').map(str::trim).collect::<Vec<&str>>();
+    if fields.len() < 3 || fields.len() > 4 {
+        return Err(format!("expected 3 or 4 fields, found {}", fields.len()));
+    }
+
+    let side = match fields[0].to_ascii_lowercase().as_str() {
+        "bid" | "buy" => OrderType::Bid,
+        "ask" | "sell" => OrderType::Ask,
+        other => return Err(format!("unknown side {:?}", other)),
+    };
+    let price = parse_positive(fields[1], "price")?;
+    let size = parse_positive(fields[2], "size")?;
+    let account = match fields.get(3) {
+        Some(account) if !account.is_empty() => Some(
+            account
+                .parse::<AccountId>()
+                .map_err(|_| format!("invalid account {:?}", account))?,
+        ),
+        _ => None,
+    };
+
+    Ok(ImportRow {
+        line,
+        side,
+        price,
+        size,
+        account,
+    })
+}
+
+fn parse_positive(field: &str, name: &str) -> Result<f64, String> {
+    match field.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!("invalid {} {:?}", name, field)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rows_and_reports_rejections() {
+        let csv = "side,price,size,account\n\
+                   bid,100.5,2,7\n\
+                   \n\
+                   # comment\n\
+                   SELL,101,1.5\n\
+                   hold,1,1\n\
+                   ask,-1,1\n\
+                   ask,1\n";
+        let rows: Vec<_> = parse_orders_csv(csv.as_bytes()).collect();
+
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows[0],
+            Ok(ImportRow {
+                line: 2,
+                side: OrderType::Bid,
+                price: 100.5,
+                size: 2.0,
+                account: Some(7),
+            })
+        );
+        assert_eq!(rows[1].as_ref().unwrap().side, OrderType::Ask);
+        assert_eq!(rows[1].as_ref().unwrap().line, 5);
+        assert!(rows[2].as_ref().unwrap_err().starts_with("line 6"));
+        assert!(rows[3].as_ref().unwrap_err().contains("price"));
+        assert!(rows[4].as_ref().unwrap_err().contains("3 or 4 fields"));
+    }
+}
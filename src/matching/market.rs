@@ -0,0 +1,400 @@
+use super::orderbook::{
+    from_lots, to_lots, MarketStatus, Price, TradingPair, PRICE_DECIMALS, SIZE_DECIMALS,
+};
+use std::{collections::HashMap, fmt};
+
+/// Static description of a market for clients and UIs, and the rules its orders must meet
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketConfig {
+    /// Human-readable name, e.g. "Bitcoin / US Dollar"
+    pub display_name: Option<String>,
+    /// Decimal places prices are quoted in
+    pub price_precision: u32,
+    /// Decimal places order sizes are given in
+    pub size_precision: u32,
+    /// Smallest price increment, e.g. 0.5; None for one unit in the last place of `price_precision`
+    pub tick_size: Option<f64>,
+    /// Smallest size increment, e.g. 25; None for one unit in the last place of `size_precision`
+    pub lot_size: Option<f64>,
+    /// Smallest order size in the base currency
+    pub min_size: Option<f64>,
+    /// Largest order size in the base currency
+    pub max_size: Option<f64>,
+    /// Smallest order notional (price × size) in the quote currency
+    pub min_notional: Option<f64>,
+    /// Largest order notional in the quote currency
+    pub max_notional: Option<f64>,
+    /// Percentage a limit order may be priced through the opposite touch at entry; orders beyond
+    /// it are rejected unless they carry a collar override
+    pub price_collar: Option<f64>,
+    /// Whether the market is listed to clients
+    pub listed: bool,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        MarketConfig {
+            display_name: None,
+            price_precision: 5,
+            size_precision: 8,
+            tick_size: None,
+            lot_size: None,
+            min_size: None,
+            max_size: None,
+            min_notional: None,
+            max_notional: None,
+            price_collar: None,
+            listed: true,
+        }
+    }
+}
+
+impl MarketConfig {
+    /// Smallest price increment in effect, the configured tick size or one unit in the last
+    /// displayed decimal place
+    pub fn tick_size(&self) -> f64 {
+        self.tick().into()
+    }
+
+    /// The tick size as a [`Price`], never finer than the precision prices are stored with
+    pub fn tick(&self) -> Price {
+        let ticks = match self.tick_size {
+            Some(tick_size) => Price::new(tick_size).ticks(),
+            None => 10i64.pow(PRICE_DECIMALS.saturating_sub(self.price_precision)),
+        };
+        Price::from_ticks(ticks.max(1))
+    }
+
+    /// Check that a price is a positive number on the market's tick grid
+    pub fn check_tick(&self, price: f64) -> Result<(), OrderRejection> {
+        if !price.is_finite() || price <= 0.0 {
+            return Err(OrderRejection::InvalidPrice { price });
+        }
+        match Price::new(price).is_multiple_of(self.tick()) {
+            true => Ok(()),
+            false => Err(OrderRejection::OffTick {
+                price,
+                tick: self.tick(),
+            }),
+        }
+    }
+
+    /// The lot size in lots of 10^-8, never finer than the precision sizes are stored with
+    pub fn lot(&self) -> u64 {
+        let lots = match self.lot_size {
+            Some(lot_size) => to_lots(lot_size),
+            None => 10u64.pow(SIZE_DECIMALS.saturating_sub(self.size_precision)),
+        };
+        lots.max(1)
+    }
+
+    /// Check that an order size is a positive whole number of the market's lots within the
+    /// market's size bounds
+    pub fn check_size(&self, size: f64) -> Result<(), OrderRejection> {
+        let lots = to_lots(size);
+        if !size.is_finite() || lots == 0 {
+            return Err(OrderRejection::NonPositiveSize { size });
+        }
+        if !lots.is_multiple_of(self.lot()) {
+            return Err(OrderRejection::OffLot {
+                size,
+                lot: from_lots(self.lot()),
+            });
+        }
+        if let Some(min) = self.min_size.filter(|min| size < *min) {
+            return Err(OrderRejection::SizeBelowMinimum { size, min });
+        }
+        if let Some(max) = self.max_size.filter(|max| size > *max) {
+            return Err(OrderRejection::SizeAboveMaximum { size, max });
+        }
+        Ok(())
+    }
+
+    /// Check an order's notional against the market's bounds
+    pub fn check_notional(&self, notional: f64) -> Result<(), OrderRejection> {
+        if let Some(min) = self.min_notional.filter(|min| notional < *min) {
+            return Err(OrderRejection::NotionalBelowMinimum { notional, min });
+        }
+        if let Some(max) = self.max_notional.filter(|max| notional > *max) {
+            return Err(OrderRejection::NotionalAboveMaximum { notional, max });
+        }
+        Ok(())
+    }
+
+    /// Check an incoming order against every rule of the market
+    ///
+    /// # Arguments
+    /// * `price` - The order's limit price, None for a market order, whose price and notional
+    ///   are not known up front
+    /// * `size` - The order's size
+    pub fn validate(&self, price: Option<f64>, size: f64) -> Result<(), OrderRejection> {
+        if let Some(price) = price {
+            self.check_tick(price)?;
+        }
+        self.check_size(size)?;
+        match price {
+            Some(price) => self.check_notional(price * size),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Why an order breaks its market's rules
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderRejection {
+    /// The price is not a positive, finite number
+    InvalidPrice {
+        price: f64,
+    },
+    OffTick {
+        price: f64,
+        tick: Price,
+    },
+    NonPositiveSize {
+        size: f64,
+    },
+    OffLot {
+        size: f64,
+        lot: f64,
+    },
+    SizeBelowMinimum {
+        size: f64,
+        min: f64,
+    },
+    SizeAboveMaximum {
+        size: f64,
+        max: f64,
+    },
+    NotionalBelowMinimum {
+        notional: f64,
+        min: f64,
+    },
+    NotionalAboveMaximum {
+        notional: f64,
+        max: f64,
+    },
+}
+
+impl fmt::Display for OrderRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderRejection::InvalidPrice { price } => {
+                write!(f, "Price must be a positive number, got {}", price)
+            }
+            OrderRejection::OffTick { price, tick } => write!(
+                f,
+                "Price {} is not a multiple of the tick size {}",
+                price, tick
+            ),
+            OrderRejection::NonPositiveSize { size } => {
+                write!(f, "Order size must be positive, got {}", size)
+            }
+            OrderRejection::OffLot { size, lot } => write!(
+                f,
+                "Order size {} is not a multiple of the lot size {}",
+                size, lot
+            ),
+            OrderRejection::SizeBelowMinimum { size, min } => write!(
+                f,
+                "Order size {} is below the market minimum of {}",
+                size, min
+            ),
+            OrderRejection::SizeAboveMaximum { size, max } => write!(
+                f,
+                "Order size {} exceeds the market maximum of {}",
+                size, max
+            ),
+            OrderRejection::NotionalBelowMinimum { notional, min } => write!(
+                f,
+                "Order notional {} is below the market minimum of {}",
+                notional, min
+            ),
+            OrderRejection::NotionalAboveMaximum { notional, max } => write!(
+                f,
+                "Order notional {} exceeds the market maximum of {}",
+                notional, max
+            ),
+        }
+    }
+}
+
+impl From<OrderRejection> for String {
+    fn from(rejection: OrderRejection) -> Self {
+        rejection.to_string()
+    }
+}
+
+/// Per-market trading rules, keyed by trading pair
+///
+/// Markets without an entry trade under [`MarketConfig::default`].
+#[derive(Debug, Clone, Default)]
+pub struct InstrumentRegistry {
+    instruments: HashMap<TradingPair, MarketConfig>,
+}
+
+impl InstrumentRegistry {
+    pub fn new() -> Self {
+        InstrumentRegistry::default()
+    }
+
+    /// Set the rules of a market, replacing any it had
+    pub fn register(&mut self, trading_pair: TradingPair, config: MarketConfig) {
+        self.instruments.insert(trading_pair, config);
+    }
+
+    pub fn get(&self, trading_pair: &TradingPair) -> Option<&MarketConfig> {
+        self.instruments.get(trading_pair)
+    }
+
+    /// The rules of a market, the defaults if it has not been registered
+    pub fn config(&self, trading_pair: &TradingPair) -> MarketConfig {
+        self.get(trading_pair).cloned().unwrap_or_default()
+    }
+
+    /// Parse a trading pair sent by a client and check that its market is registered
+    ///
+    /// # Arguments
+    /// * `pair` - The pair as text, e.g. `BTC/USD` or `btc-usd`
+    ///
+    /// # Returns
+    /// * `Result<TradingPair, String>` - Err if the text is not a trading pair or its market is not registered
+    pub fn resolve(&self, pair: &str) -> Result<TradingPair, String> {
+        let trading_pair: TradingPair = pair.parse()?;
+        match self.instruments.contains_key(&trading_pair) {
+            true => Ok(trading_pair),
+            false => Err(format!(
+                "Market {} is not registered",
+                String::from(trading_pair)
+            )),
+        }
+    }
+
+    /// Check an incoming order against its market's rules, see [`MarketConfig::validate`]
+    pub fn validate(
+        &self,
+        trading_pair: &TradingPair,
+        price: Option<f64>,
+        size: f64,
+    ) -> Result<(), OrderRejection> {
+        match self.get(trading_pair) {
+            Some(config) => config.validate(price, size),
+            None => MarketConfig::default().validate(price, size),
+        }
+    }
+}
+
+/// A market as reported by `Engine::list_markets`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketInfo {
+    pub pair: TradingPair,
+    pub base: String,
+    pub quote: String,
+    pub config: MarketConfig,
+    pub status: MarketStatus,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_validates_orders_against_their_market() {
+        let btc = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut registry = InstrumentRegistry::new();
+        registry.register(
+            btc.clone(),
+            MarketConfig {
+                price_precision: 2,
+                size_precision: 3,
+                min_size: Some(0.01),
+                max_size: Some(100.0),
+                min_notional: Some(5.0),
+                ..MarketConfig::default()
+            },
+        );
+
+        assert_eq!(registry.validate(&btc, Some(100.25), 0.5), Ok(()));
+        assert_eq!(
+            registry.validate(&btc, Some(100.255), 0.5),
+            Err(OrderRejection::OffTick {
+                price: 100.255,
+                tick: Price::new(0.01)
+            })
+        );
+        assert_eq!(
+            registry.validate(&btc, None, 0.0005),
+            Err(OrderRejection::OffLot {
+                size: 0.0005,
+                lot: 0.001
+            })
+        );
+        assert_eq!(
+            registry.validate(&btc, None, 0.005),
+            Err(OrderRejection::SizeBelowMinimum {
+                size: 0.005,
+                min: 0.01
+            })
+        );
+        assert_eq!(
+            registry.validate(&btc, None, 101.0),
+            Err(OrderRejection::SizeAboveMaximum {
+                size: 101.0,
+                max: 100.0
+            })
+        );
+        let dust = registry.validate(&btc, Some(100.0), 0.02).unwrap_err();
+        assert_eq!(
+            String::from(dust),
+            "Order notional 2 is below the market minimum of 5"
+        );
+
+        assert_eq!(registry.resolve("btc-usd"), Ok(btc.clone()));
+        assert_eq!(
+            registry.resolve("eth/usd").unwrap_err(),
+            "Market ETH/USD is not registered"
+        );
+        assert!(registry.resolve("BTCUSD").is_err());
+
+        // Unregistered markets trade under the defaults
+        let eth = TradingPair::new("ETH".to_string(), "USD".to_string());
+        assert!(registry.get(&eth).is_none());
+        assert_eq!(registry.validate(&eth, Some(100.255), 0.0005), Ok(()));
+        assert_eq!(
+            registry.validate(&eth, None, -1.0),
+            Err(OrderRejection::NonPositiveSize { size: -1.0 })
+        );
+        for price in [f64::NAN, f64::INFINITY, 0.0, -5.0] {
+            assert!(matches!(
+                registry.validate(&eth, Some(price), 1.0),
+                Err(OrderRejection::InvalidPrice { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn explicit_tick_and_lot_sizes() {
+        let config = MarketConfig {
+            price_precision: 1,
+            size_precision: 0,
+            tick_size: Some(0.5),
+            lot_size: Some(25.0),
+            ..MarketConfig::default()
+        };
+        assert_eq!(config.tick_size(), 0.5);
+        assert_eq!(config.validate(Some(100.5), 50.0), Ok(()));
+        assert_eq!(
+            config.validate(Some(100.2), 50.0),
+            Err(OrderRejection::OffTick {
+                price: 100.2,
+                tick: Price::new(0.5)
+            })
+        );
+        assert_eq!(
+            config.validate(Some(100.0), 30.0),
+            Err(OrderRejection::OffLot {
+                size: 30.0,
+                lot: 25.0
+            })
+        );
+    }
+}
@@ -1,2 +1,39 @@
+pub mod async_engine;
+pub mod auction;
+pub mod backend;
+pub mod bands;
+pub mod builder;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod clock;
+pub mod concentration;
+pub mod config;
+pub mod conformance;
+pub mod dark;
+pub mod depth;
+pub mod duplicate;
 pub mod engine;
+pub mod eod;
+pub mod event;
+pub mod federation;
+pub mod handle;
+pub mod heartbeat;
+pub mod heatmap;
+pub mod import;
+pub mod market;
+pub mod numeric;
+pub mod oco;
 pub mod orderbook;
+pub mod paper;
+pub mod peg;
+pub mod pricing;
+pub mod protection;
+pub mod quality;
+pub mod risk;
+pub mod scenario;
+pub mod settlement;
+pub mod snapshot;
+pub mod stats;
+pub mod stop;
+pub mod timer;
+pub mod trade;
@@ -0,0 +1,39 @@
+/// Sum floating point values with Neumaier's compensated summation
+///
+/// Aggregating many order sizes with plain `+` lets rounding error build up (ten orders of 0.1 do
+/// not add up to 1.0). Carrying the lost low-order bits separately keeps the total as close to the
+/// exact decimal sum as f64 allows.
+///
+/// # Example
+/// ```
+/// use orderbook::matching::numeric::exact_sum;
+/// assert_eq!(exact_sum(std::iter::repeat_n(0.1, 10)), 1.0);
+/// ```
+pub fn exact_sum<I: IntoIterator<Item = f64>>(values: I) -> f64 {
+    let mut sum = 0.0_f64;
+    let mut compensation = 0.0_f64;
+    for value in values {
+        let total = sum + value;
+        if sum.abs() >= value.abs() {
+            compensation += (sum - total) + value;
+        } else {
+            compensation += (value - total) + sum;
+        }
+        sum = total;
+    }
+    sum + compensation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compensates_rounding_error() {
+        let naive: f64 = std::iter::repeat_n(0.1, 10).sum();
+        assert_ne!(naive, 1.0);
+        assert_eq!(exact_sum(std::iter::repeat_n(0.1, 10)), 1.0);
+        assert_eq!(exact_sum([1e16, 1.0, -1e16]), 1.0);
+        assert_eq!(exact_sum(Vec::new()), 0.0);
+    }
+}
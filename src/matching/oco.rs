@@ -0,0 +1,69 @@
+use super::orderbook::{Order, OrderId, TradingPair};
+use super::stop::StopKind;
+use super::trade::Trade;
+use std::collections::HashMap;
+
+/// One order of a one-cancels-other group
+#[derive(Debug)]
+pub enum OcoLeg {
+    Limit {
+        order: Order,
+        price: f64,
+    },
+    Stop {
+        order: Order,
+        stop_price: f64,
+        kind: StopKind,
+    },
+}
+
+/// The outcome of placing a one-cancels-other group with `Engine::place_oco`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcoPlacement {
+    pub first: OrderId,
+    /// None if the first order traded on arrival, in which case the second is never placed
+    pub second: Option<OrderId>,
+    /// Fills of either order on arrival
+    pub trades: Vec<Trade>,
+}
+
+/// Links between the two orders of each one-cancels-other group, by market
+#[derive(Debug, Default)]
+pub struct OcoGroups {
+    siblings: HashMap<(TradingPair, OrderId), OrderId>,
+}
+
+impl OcoGroups {
+    pub fn new() -> Self {
+        OcoGroups::default()
+    }
+
+    /// Link two orders on a market so that either one ending cancels the other
+    pub fn link(&mut self, trading_pair: &TradingPair, first: OrderId, second: OrderId) {
+        self.siblings.insert((trading_pair.clone(), first), second);
+        self.siblings.insert((trading_pair.clone(), second), first);
+    }
+
+    pub fn sibling(&self, trading_pair: &TradingPair, id: OrderId) -> Option<OrderId> {
+        self.siblings.get(&(trading_pair.clone(), id)).copied()
+    }
+
+    /// Dissolve the group of an order that executed or left the book
+    ///
+    /// # Returns
+    /// * `Option<OrderId>` - The order's sibling, which should now be cancelled
+    pub fn unlink(&mut self, trading_pair: &TradingPair, id: OrderId) -> Option<OrderId> {
+        let sibling = self.siblings.remove(&(trading_pair.clone(), id))?;
+        self.siblings.remove(&(trading_pair.clone(), sibling));
+        Some(sibling)
+    }
+
+    /// Dissolve every group on a market, e.g. once the market has left the engine
+    pub fn remove_market(&mut self, trading_pair: &TradingPair) {
+        self.siblings.retain(|(pair, _), _| pair != trading_pair);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.siblings.is_empty()
+    }
+}
@@ -1,332 +1,3363 @@
+use super::auction::{indicative_price, IndicativePrice, RandomClose};
+use super::bands::{BandConfig, PriceBands};
+use super::clock::{Clock, SystemClock, Timestamp};
+use super::dark::DarkPool;
+use super::depth::{Depth, DepthLevel, FillCost, QueuePosition, TopOfBook};
+use super::duplicate::{DuplicateAction, DuplicateCheck, DuplicateDetector};
+use super::event::{CancelReason, OrderEvent};
+use super::numeric::exact_sum;
+use super::peg::Peg;
+use super::protection::{MmProtection, MmProtectionConfig};
+use super::risk::{RiskChecker, RiskContext, RiskRejection};
+use super::snapshot::{ClientOrder, RestingOrder, SnapshotChunk, SnapshotCursor, WaitingStop};
+use super::stats::TradeStats;
+use super::stop::{StopBook, StopKind, StopOrder};
+use super::timer::TimerWheel;
+use super::trade::{Trade, TradeId};
 use std::{
-    cmp::Ordering,
     collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+    sync::Arc,
 };
 
-#[derive(Debug)]
+pub type AccountId = u64;
+pub type OrderId = u64;
+
+/// Longest client tag an order may carry, in bytes
+pub const MAX_TAG_LEN: usize = 64;
+
+/// How long an order stays in the book
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeInForce {
+    /// Good till cancelled
+    #[default]
+    Gtc,
+    /// Expires at the end of the trading day
+    Day,
+    /// Immediate or cancel: fills what it can on arrival, the rest is cancelled instead of resting
+    Ioc,
+    /// Fill or kill: fills in full on arrival or is cancelled without trading
+    Fok,
+    /// Good till date: rests until its expiry time, set with [`Order::with_expiry`]
+    Gtd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OrderType {
     Bid,
     Ask,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Decimal places a [`Price`] is stored with
+pub const PRICE_DECIMALS: u32 = 5;
+/// Ticks in one unit of the quote currency
+pub const PRICE_SCALE: i64 = 10i64.pow(PRICE_DECIMALS);
+
+/// A price as a whole number of ticks of 10^-5 of the quote currency
+///
+/// Prices are exact and ordered by their tick count, so they can key price levels. Conversions
+/// from `f64` round to the nearest tick; markets with a coarser tick size check prices with
+/// [`Price::is_multiple_of`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Price {
-    integral: u64,
-    fractional: u64,
-    scalar: u64,
+    ticks: i64,
+}
+
+impl Price {
+    /// The nearest price to `price`, saturating at the representable range
+    pub fn new(price: f64) -> Price {
+        Price {
+            ticks: (price * PRICE_SCALE as f64).round() as i64,
+        }
+    }
+
+    pub fn from_ticks(ticks: i64) -> Price {
+        Price { ticks }
+    }
+
+    pub fn ticks(&self) -> i64 {
+        self.ticks
+    }
+
+    /// # Returns
+    /// * `Result<Price, String>` - Err if the sum is out of range
+    pub fn checked_add(self, other: Price) -> Result<Price, String> {
+        self.ticks
+            .checked_add(other.ticks)
+            .map(Price::from_ticks)
+            .ok_or_else(|| overflow(&self))
+    }
+
+    /// # Returns
+    /// * `Result<Price, String>` - Err if the difference is out of range
+    pub fn checked_sub(self, other: Price) -> Result<Price, String> {
+        self.ticks
+            .checked_sub(other.ticks)
+            .map(Price::from_ticks)
+            .ok_or_else(|| overflow(&self))
+    }
+
+    /// Scale the price, e.g. a tick size by a number of ticks
+    ///
+    /// # Returns
+    /// * `Result<Price, String>` - Err if the product is out of range
+    pub fn checked_mul(self, factor: i64) -> Result<Price, String> {
+        self.ticks
+            .checked_mul(factor)
+            .map(Price::from_ticks)
+            .ok_or_else(|| overflow(&self))
+    }
+
+    /// Whether the price lies on the grid of a market with tick size `tick`
+    pub fn is_multiple_of(&self, tick: Price) -> bool {
+        tick.ticks > 0 && self.ticks % tick.ticks == 0
+    }
+
+    /// The nearest price on the grid of tick size `tick`, halfway prices rounding away from zero
+    ///
+    /// # Returns
+    /// * `Result<Price, String>` - Err if `tick` is not positive
+    pub fn round_to(&self, tick: Price) -> Result<Price, String> {
+        let floor = self.floor_to(tick)?;
+        let remainder = self.ticks - floor.ticks;
+        let away_from_zero = match self.ticks < 0 {
+            true => remainder * 2 > tick.ticks,
+            false => remainder * 2 >= tick.ticks,
+        };
+        match away_from_zero {
+            true => floor.checked_add(tick),
+            false => Ok(floor),
+        }
+    }
+
+    /// The highest price on the grid of tick size `tick` at or below this one, e.g. for a bid
+    ///
+    /// # Returns
+    /// * `Result<Price, String>` - Err if `tick` is not positive
+    pub fn floor_to(&self, tick: Price) -> Result<Price, String> {
+        if tick.ticks <= 0 {
+            return Err(format!("Tick size must be positive, got {}", tick));
+        }
+        Ok(Price::from_ticks(
+            self.ticks.div_euclid(tick.ticks) * tick.ticks,
+        ))
+    }
+
+    /// The lowest price on the grid of tick size `tick` at or above this one, e.g. for an ask
+    ///
+    /// # Returns
+    /// * `Result<Price, String>` - Err if `tick` is not positive
+    pub fn ceil_to(&self, tick: Price) -> Result<Price, String> {
+        let floor = self.floor_to(tick)?;
+        match floor == *self {
+            true => Ok(floor),
+            false => floor.checked_add(tick),
+        }
+    }
+
+    /// The price halfway to `other` on the grid of tick size `tick`, rounded toward this one
+    ///
+    /// Neither price is passed, so a midpoint that falls between ticks goes to the tick on this
+    /// price's side, and to this price itself if they are less than a tick apart.
+    ///
+    /// # Returns
+    /// * `Result<Price, String>` - Err if `tick` is not positive
+    pub fn midpoint_toward(self, other: Price, tick: Price) -> Result<Price, String> {
+        if tick.ticks <= 0 {
+            return Err(format!("Tick size must be positive, got {}", tick));
+        }
+        // Widened so the gap cannot overflow; the midpoint itself lies between two i64 prices
+        let (from, to, tick) = (self.ticks as i128, other.ticks as i128, tick.ticks as i128);
+        let midpoint = from + (to - from) / 2;
+        let on_grid = match to >= from {
+            true => (midpoint.div_euclid(tick) * tick).max(from),
+            false => (-(-midpoint).div_euclid(tick) * tick).min(from),
+        };
+        i64::try_from(on_grid)
+            .map(Price::from_ticks)
+            .map_err(|_| overflow(&self))
+    }
+
+    /// The price moved by `percent` of itself, rounded to the nearest tick: 5 for 5% higher, -5
+    /// for 5% lower
+    ///
+    /// # Returns
+    /// * `Result<Price, String>` - Err if the percentage is not finite or the result is out of range
+    pub fn offset_percent(&self, percent: f64) -> Result<Price, String> {
+        if !percent.is_finite() {
+            return Err(format!("Percentage must be finite, got {}", percent));
+        }
+        let offset = (self.ticks as f64 * percent / 100.0).round();
+        if offset.abs() >= i64::MAX as f64 {
+            return Err(overflow(self));
+        }
+        self.ticks
+            .checked_add(offset as i64)
+            .map(Price::from_ticks)
+            .ok_or_else(|| overflow(self))
+    }
+}
+
+fn overflow(price: &Price) -> String {
+    format!("Price {} is out of range", price)
 }
 
-impl PartialEq for Price {
-    fn eq(&self, other: &Self) -> bool {
-        self.integral == other.integral && self.fractional == other.fractional
+impl From<f64> for Price {
+    fn from(price: f64) -> Self {
+        Price::new(price)
     }
 }
 
-impl Default for Price {
-    fn default() -> Self {
-        Price {
-            integral: 0,
-            fractional: 0,
-            scalar: 100000,
+impl From<Price> for f64 {
+    fn from(price: Price) -> Self {
+        price.ticks as f64 / PRICE_SCALE as f64
+    }
+}
+
+impl fmt::Display for Price {
+    /// The price in decimal notation without trailing zeros, e.g. `101.25` or `-3`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.ticks < 0 { "-" } else { "" };
+        let ticks = self.ticks.unsigned_abs();
+        let scale = PRICE_SCALE as u64;
+        let fraction = format!("{:0width$}", ticks % scale, width = PRICE_DECIMALS as usize);
+        match fraction.trim_end_matches('0') {
+            "" => write!(f, "{}{}", sign, ticks / scale),
+            fraction => write!(f, "{}{}.{}", sign, ticks / scale, fraction),
         }
     }
 }
 
-impl Eq for Price {}
+impl FromStr for Price {
+    type Err = String;
 
-impl PartialOrd for Price {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// Parse a decimal price exactly, without going through `f64`
+    fn from_str(price: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid price {:?}", price);
+        let (negative, digits) = match price.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, price),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(invalid());
+        }
+        if fraction.len() > PRICE_DECIMALS as usize {
+            return Err(format!(
+                "Price {} has more than {} decimal places",
+                price, PRICE_DECIMALS
+            ));
+        }
+        let whole: i64 = whole.parse().map_err(|_| invalid())?;
+        let fraction: i64 = match fraction.is_empty() {
+            true => 0,
+            false => {
+                fraction.parse::<i64>().map_err(|_| invalid())?
+                    * 10i64.pow(PRICE_DECIMALS - fraction.len() as u32)
+            }
+        };
+        let ticks = whole
+            .checked_mul(PRICE_SCALE)
+            .and_then(|ticks| ticks.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        Ok(Price::from_ticks(if negative { -ticks } else { ticks }))
     }
 }
 
-impl Ord for Price {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.integral < other.integral {
-            Ordering::Less
-        } else if self.integral > other.integral {
-            Ordering::Greater
-        } else {
-            if self.fractional < other.fractional {
-                Ordering::Less
-            } else if self.fractional > other.fractional {
-                Ordering::Greater
-            } else {
-                Ordering::Equal
+/// Decimal places an order size is stored with
+pub const SIZE_DECIMALS: u32 = 8;
+/// Lots in one unit of the base currency
+pub const LOT_SCALE: u64 = 10u64.pow(SIZE_DECIMALS);
+
+/// The nearest whole number of lots of 10^-8 to `size`
+///
+/// Negative and NaN sizes come out as zero lots, sizes too large to represent as `u64::MAX`.
+pub fn to_lots(size: f64) -> u64 {
+    (size * LOT_SCALE as f64).round() as u64
+}
+
+/// A number of lots as a size in the base currency
+pub fn from_lots(lots: u64) -> f64 {
+    lots as f64 / LOT_SCALE as f64
+}
+
+/// The orders resting at one price level, in time priority
+///
+/// Orders keep the slot they were queued in, so removing one by id is a hash lookup that leaves
+/// the slot vacant instead of shifting every order behind it. Vacant slots are reclaimed once
+/// they outnumber the orders.
+#[derive(Debug, Default)]
+struct OrderQueue {
+    slots: Vec<Option<Order>>,
+    slot_of: HashMap<OrderId, usize>,
+    len: usize,
+}
+
+impl OrderQueue {
+    /// Queue an order behind every order with an earlier or equal sequence number
+    ///
+    /// Orders normally arrive in sequence and go straight to the back; an earlier one (e.g. from
+    /// a snapshot) is slotted in place, which re-indexes the queue.
+    fn add(&mut self, order: Order) {
+        let in_sequence = self
+            .iter()
+            .next_back()
+            .is_none_or(|last| last.sequence <= order.sequence);
+        if in_sequence {
+            if let Some(id) = order.id {
+                self.slot_of.insert(id, self.slots.len());
             }
+            self.slots.push(Some(order));
+            self.len += 1;
+            return;
         }
+        self.compact();
+        let slot = self.slots.partition_point(|queued| {
+            queued
+                .as_ref()
+                .is_some_and(|queued| queued.sequence <= order.sequence)
+        });
+        self.slots.insert(slot, Some(order));
+        self.len += 1;
+        self.reindex();
     }
-}
 
-impl Price {
-    pub fn new(price: f64) -> Price {
-        let scalar = 100000;
-        let integral = price as u64;
-        let fractional = ((price % 1.0) * scalar as f64) as u64;
+    fn get(&self, id: OrderId) -> Option<&Order> {
+        self.slots[*self.slot_of.get(&id)?].as_ref()
+    }
 
-        Price {
-            integral,
-            fractional,
-            scalar,
+    fn get_mut(&mut self, id: OrderId) -> Option<&mut Order> {
+        self.slots[*self.slot_of.get(&id)?].as_mut()
+    }
+
+    /// Remove an order by id, reclaiming vacant slots if they have piled up
+    fn remove(&mut self, id: OrderId) -> Option<Order> {
+        let order = self.take(*self.slot_of.get(&id)?);
+        self.compact_if_sparse();
+        order
+    }
+
+    /// Empty a slot without moving any other order
+    fn take(&mut self, slot: usize) -> Option<Order> {
+        let order = self.slots[slot].take()?;
+        if let Some(id) = order.id {
+            self.slot_of.remove(&id);
+        }
+        self.len -= 1;
+        Some(order)
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&Order) -> bool) {
+        self.slots
+            .retain(|slot| slot.as_ref().is_some_and(&mut keep));
+        self.len = self.slots.len();
+        self.reindex();
+    }
+
+    fn iter(&self) -> impl DoubleEndedIterator<Item = &Order> {
+        self.slots.iter().flatten()
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Order> {
+        self.slots.iter_mut().flatten()
+    }
+
+    fn compact_if_sparse(&mut self) {
+        if self.slots.len() > 2 * self.len {
+            self.compact();
+        }
+    }
+
+    fn compact(&mut self) {
+        if self.slots.len() != self.len {
+            self.slots.retain(Option::is_some);
+            self.reindex();
         }
     }
+
+    fn reindex(&mut self) {
+        self.slot_of = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, order)| Some((order.as_ref()?.id?, slot)))
+            .collect();
+    }
 }
 
-impl From<f64> for Price {
-    fn from(price: f64) -> Self {
-        Price::new(price)
+impl IntoIterator for OrderQueue {
+    type Item = Order;
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<Option<Order>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slots.into_iter().flatten()
     }
 }
 
-impl Into<f64> for Price {
-    fn into(self) -> f64 {
-        self.integral as f64 + (self.fractional as f64 / self.scalar as f64)
+impl Extend<Order> for OrderQueue {
+    fn extend<T: IntoIterator<Item = Order>>(&mut self, orders: T) {
+        for order in orders {
+            self.add(order);
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct Limit {
     price: Price,
-    orders: Vec<Order>,
+    orders: OrderQueue,
 }
 
 impl Limit {
     pub fn new(price: f64) -> Limit {
         Limit {
             price: Price::new(price),
-            orders: Vec::new(),
+            orders: OrderQueue::default(),
         }
     }
 
+    /// Queue an order behind every order with an earlier or equal sequence number
     fn add(&mut self, order: Order) {
-        self.orders.push(order)
+        self.orders.add(order)
     }
 
-    fn volume(&self) -> f64 {
+    /// Visible lots at this level
+    fn volume(&self) -> u64 {
+        self.orders.iter().map(|order| order.size).sum()
+    }
+
+    /// Lots that can trade at this level, including the hidden reserve of iceberg orders
+    fn executable_volume(&self) -> u64 {
+        self.orders.iter().map(Order::remaining).sum()
+    }
+
+    /// Number of open orders at this level
+    fn order_count(&self) -> usize {
         self.orders
             .iter()
-            .map(|order| order.size)
-            .reduce(|a, b| a + b)
-            .unwrap_or(0.0)
+            .filter(|order| !order.is_filled())
+            .count()
+    }
+
+    fn depth_level(&self) -> DepthLevel {
+        DepthLevel {
+            price: self.price,
+            volume: from_lots(self.volume()),
+            order_count: self.order_count(),
+        }
     }
 
     /// Used for filling orders at a certain limit
-    fn fill(&mut self, market_order: &mut Order) {
-        for limit_order in self.orders.iter_mut() {
-            match market_order.size >= limit_order.size {
-                true => {
-                    market_order.size -= limit_order.size;
-                    limit_order.size = 0.0;
+    ///
+    /// Orders fill in time priority: the queue is kept sorted by sequence number. Filled orders
+    /// leave the level; an iceberg whose visible slice fills shows its next slice at the back of
+    /// the queue instead, numbered from `next_sequence`. The trades are left unstamped; the order book sets their id and
+    /// transaction time.
+    fn fill(&mut self, market_order: &mut Order, next_sequence: &mut u64) -> Vec<Trade> {
+        let mut trades = Vec::new();
+        let mut slot = 0;
+        while slot < self.orders.slots.len() && !market_order.is_filled() {
+            let limit_order = match self.orders.slots[slot].as_mut() {
+                Some(order) if !order.is_filled() => order,
+                _ => {
+                    slot += 1;
+                    continue;
                 }
-                false => {
-                    limit_order.size -= market_order.size;
-                    market_order.size = 0.0;
+            };
+
+            let size = market_order.size.min(limit_order.size);
+            market_order.size -= size;
+            limit_order.size -= size;
+
+            trades.push(Trade {
+                id: 0,
+                price: self.price,
+                size: from_lots(size),
+                aggressor_side: market_order.order_type,
+                maker_order_id: limit_order.id,
+                taker_order_id: market_order.id,
+                maker_account: limit_order.account,
+                taker_account: market_order.account,
+                maker_tag: limit_order.tag.clone(),
+                taker_tag: market_order.tag.clone(),
+                maker_version: limit_order.version,
+                taker_version: market_order.version,
+                transaction_time: 0,
+                triggered_stops: false,
+                triggered_band: false,
+                dark: false,
+            });
+            if limit_order.is_filled() {
+                match limit_order.reserve > 0 {
+                    true => self.replenish(slot, next_sequence),
+                    false => {
+                        self.orders.take(slot);
+                    }
                 }
             }
+            slot += 1;
+        }
+        self.orders.compact_if_sparse();
+        trades
+    }
 
-            if market_order.is_filled() {
-                break;
+    /// Show the next slice of the iceberg order in `slot` at the back of the queue
+    fn replenish(&mut self, slot: usize, next_sequence: &mut u64) {
+        if let Some(mut order) = self.orders.take(slot) {
+            order.show_slice();
+            order.sequence = *next_sequence;
+            *next_sequence += 1;
+            self.orders.add(order);
+        }
+    }
+
+    /// Show the next slice of the first iceberg order whose visible slice has filled
+    fn replenish_exhausted(&mut self, next_sequence: &mut u64) {
+        let exhausted = self.orders.slots.iter().position(|slot| {
+            slot.as_ref()
+                .is_some_and(|order| order.is_filled() && order.reserve > 0)
+        });
+        if let Some(slot) = exhausted {
+            self.replenish(slot, next_sequence);
+            self.orders.compact_if_sparse();
+        }
+    }
+
+    /// Take out every filled order that has no hidden reserve left, returning their ids
+    fn remove_filled(&mut self) -> Vec<OrderId> {
+        let mut removed = Vec::new();
+        self.orders.retain(|order| {
+            let keep = !order.is_filled() || order.reserve > 0;
+            if !keep {
+                removed.extend(order.id);
             }
+            keep
+        });
+        removed
+    }
+
+    /// Queue position of an open order at this level
+    fn queue_position(&self, id: OrderId) -> Option<QueuePosition> {
+        let slot = *self.orders.slot_of.get(&id)?;
+        if self.orders.get(id)?.is_filled() {
+            return None;
+        }
+        let ahead = self.orders.slots[..slot]
+            .iter()
+            .flatten()
+            .filter(|order| !order.is_filled());
+        Some(QueuePosition {
+            orders_ahead: ahead.clone().count(),
+            size_ahead: from_lots(ahead.map(|order| order.size).sum()),
+        })
+    }
+
+    /// Remove a resting order, returning it if it was still open
+    fn remove(&mut self, id: OrderId) -> Option<Order> {
+        let order = self.orders.remove(id)?;
+        match order.is_filled() {
+            true => None,
+            false => Some(order),
         }
     }
+
+    /// Remove every open order belonging to an account
+    fn remove_account(&mut self, account: AccountId) -> Vec<Order> {
+        let ids: Vec<OrderId> = self
+            .orders
+            .iter()
+            .filter(|order| order.account == Some(account) && !order.is_filled())
+            .filter_map(|order| order.id)
+            .collect();
+        let removed = ids
+            .into_iter()
+            .filter_map(|id| self.orders.take(*self.orders.slot_of.get(&id)?))
+            .collect();
+        self.orders.compact_if_sparse();
+        removed
+    }
 }
 
 #[derive(Debug)]
 pub struct Order {
-    size: f64,
+    id: Option<OrderId>,
+    /// Visible size in lots of 10^-8
+    size: u64,
     order_type: OrderType,
+    account: Option<AccountId>,
+    expires_at: Option<Timestamp>,
+    time_in_force: TimeInForce,
+    collar_override: bool,
+    client_order_id: Option<String>,
+    tag: Option<String>,
+    /// Time priority within a price level, assigned by the book each time the order is queued
+    sequence: u64,
+    /// Starts at 1 and goes up each time the order is amended or replaced
+    version: u32,
+    /// Largest slice of an iceberg order shown in the book, in lots
+    display_size: Option<u64>,
+    /// Hidden lots of an iceberg order, shown a slice at a time as the visible size fills
+    reserve: u64,
 }
 
 impl Order {
+    /// A new order for `size`, rounded to the nearest lot of 10^-8
     pub fn new(order_type: OrderType, size: f64) -> Order {
-        Order { order_type, size }
+        Order {
+            id: None,
+            order_type,
+            size: to_lots(size),
+            account: None,
+            expires_at: None,
+            time_in_force: TimeInForce::default(),
+            collar_override: false,
+            client_order_id: None,
+            tag: None,
+            sequence: 0,
+            version: 1,
+            display_size: None,
+            reserve: 0,
+        }
     }
 
-    pub fn is_filled(&self) -> bool {
-        self.size == 0.0
+    /// The id assigned by the order book, if the order has been submitted to one
+    pub fn id(&self) -> Option<OrderId> {
+        self.id
     }
-}
 
-#[derive(Debug)]
-pub struct OrderBook {
-    asks: BTreeMap<Price, Limit>,
-    bids: BTreeMap<Price, Limit>,
-}
+    /// Attribute the order to an account
+    pub fn with_account(mut self, account: AccountId) -> Order {
+        self.account = Some(account);
+        self
+    }
 
-impl OrderBook {
-    /// Create a new order book
-    ///
-    /// # Example
-    /// ```
-    /// use matching::orderbook::OrderBook;
-    /// let order_book = OrderBook::new();
-    /// ```
-    pub fn new() -> OrderBook {
-        OrderBook {
-            asks: BTreeMap::new(),
-            bids: BTreeMap::new(),
-        }
+    pub fn account(&self) -> Option<AccountId> {
+        self.account
     }
 
-    pub fn place_market_order(&mut self, order: &mut Order) {
-        let limits = match order.order_type {
-            OrderType::Ask => self.bid_limits(), // If we are selling, we need the buyers
-            OrderType::Bid => self.ask_limits(), // Vice Versa
-        };
-        for limit_order in limits {
-            limit_order.fill(order);
-        }
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Order {
+        self.time_in_force = time_in_force;
+        self
     }
 
-    /// Returns the ask limits sorted by price of each limit
-    pub fn ask_limits(&mut self) -> Vec<&mut Limit> {
-        let mut limits = self.asks.values_mut().collect::<Vec<&mut Limit>>();
-        limits.sort_by(|a, b| a.price.cmp(&b.price));
-        limits
+    pub fn time_in_force(&self) -> TimeInForce {
+        self.time_in_force
     }
 
-    /// Collects the BTree of the Bids and collects it into a Vec and sorts by highest price
-    pub fn bid_limits(&mut self) -> Vec<&mut Limit> {
-        let mut limits = self.bids.values_mut().collect::<Vec<&mut Limit>>();
-        limits.sort_by(|a, b| b.price.cmp(&a.price));
-        limits
+    /// Make the order good-till-time: it is removed from the book once `expires_at` is reached
+    pub fn with_expiry(mut self, expires_at: Timestamp) -> Order {
+        self.expires_at = Some(expires_at);
+        self
     }
 
-    pub fn spread(&self) -> Option<f64> {
-        let lowest_ask = match self.asks.keys().next() {
-            Some(ask) => ask,
-            None => return None,
-        };
-        let highest_bid = match self.bids.keys().next_back() {
-            Some(bid) => bid,
-            None => return None,
-        };
+    pub fn expires_at(&self) -> Option<Timestamp> {
+        self.expires_at
+    }
 
-        let hb: f64 = highest_bid.clone().into();
-        let la: f64 = lowest_ask.clone().into();
+    /// Attach the client's own id for the order; an account can use each id once per market
+    pub fn with_client_order_id(mut self, client_order_id: String) -> Order {
+        self.client_order_id = Some(client_order_id);
+        self
+    }
 
-        Some(hb - la)
+    pub fn client_order_id(&self) -> Option<&str> {
+        self.client_order_id.as_deref()
     }
 
-    /// Add an order to the order book
-    ///
-    /// # Arguments
-    /// * `order` - The order to add to the order book
-    /// * `price` - The price of the order
+    /// Attach an opaque client tag, echoed on the order's trades
     ///
-    /// # Example
-    /// ```
-    /// use matching::orderbook::{OrderBook, Order, OrderType};
-    /// let mut order_book = OrderBook::new();
-    /// let order = Order::new(OrderType::Bid, 100.0);
-    /// order_book.add(order, 1000.00);
-    /// ```
-    pub fn add(&mut self, order: Order, price: f64) {
-        match order.order_type {
-            OrderType::Ask => {
-                let limit = self
-                    .asks
-                    .entry(Price::new(price))
-                    .or_insert(Limit::new(price));
-                limit.add(order);
-            }
-            OrderType::Bid => {
-                let limit = self
-                    .bids
-                    .entry(Price::new(price))
-                    .or_insert(Limit::new(price));
-                limit.add(order);
-            }
-        }
+    /// Tags longer than [`MAX_TAG_LEN`] are rejected when the order is placed.
+    pub fn with_tag(mut self, tag: String) -> Order {
+        self.tag = Some(tag);
+        self
     }
-}
-
-#[derive(Debug, Hash, Eq, PartialEq, Clone)]
-pub struct TradingPair {
-    base: String,
-    quote: String,
-}
 
-impl TradingPair {
-    pub fn new(base: String, quote: String) -> Self {
-        TradingPair { base, quote }
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
     }
-}
 
-impl From<(String, String)> for TradingPair {
-    fn from(pair: (String, String)) -> Self {
-        TradingPair {
-            base: pair.0,
-            quote: pair.1,
+    /// Check the order's tag against [`MAX_TAG_LEN`]
+    pub fn check_tag(&self) -> Result<(), String> {
+        match &self.tag {
+            Some(tag) if tag.len() > MAX_TAG_LEN => Err(format!(
+                "Order tag is {} bytes, the limit is {}",
+                tag.len(),
+                MAX_TAG_LEN
+            )),
+            _ => Ok(()),
         }
     }
-}
 
-impl Into<String> for TradingPair {
-    fn into(self) -> String {
-        format!("{}/{}", self.base, self.quote)
+    /// Let the order through a market's entry price collar
+    pub fn with_collar_override(mut self) -> Order {
+        self.collar_override = true;
+        self
     }
-}
 
-#[cfg(test)]
-pub mod tests {
-    use std::fmt::Debug;
+    pub fn collar_override(&self) -> bool {
+        self.collar_override
+    }
 
-    use super::*;
+    pub fn size(&self) -> f64 {
+        from_lots(self.size)
+    }
 
-    #[test]
-    fn limit_order_single_fill() {
-        let mut limit = Limit::new(1000.00);
-        let buy_limit_order = Order::new(OrderType::Bid, 100.0);
+    /// The visible size as a whole number of lots of 10^-8
+    pub fn lots(&self) -> u64 {
+        self.size
+    }
 
-        limit.add(buy_limit_order);
+    /// The order's time priority in its level, lower sequence numbers fill first
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
 
-        let mut market_sell_order = Order::new(OrderType::Ask, 99.0);
-        limit.fill(&mut market_sell_order);
-        println!("{:?}", limit);
-        assert!(market_sell_order.is_filled());
-        assert_eq!(limit.orders.get(0).unwrap().size, 1.0);
+    pub fn version(&self) -> u32 {
+        self.version
     }
 
-    #[test]
-    fn limit_order_multi_fill() {
-        let price = Price::new(1000.00);
-        let mut limit = Limit::new(1000.00);
-        let buy_limit_order_a = Order::new(OrderType::Bid, 50.0);
-        let buy_limit_order_b = Order::new(OrderType::Bid, 50.0);
+    /// Make the order an iceberg: once resting, only `display_size` of it is shown in the book and
+    /// the rest is revealed a slice at a time
+    pub fn with_display_size(mut self, display_size: f64) -> Order {
+        self.display_size = Some(to_lots(display_size));
+        self
+    }
 
-        limit.add(buy_limit_order_a);
-        limit.add(buy_limit_order_b);
+    pub fn display_size(&self) -> Option<f64> {
+        self.display_size.map(from_lots)
+    }
 
-        let mut market_sell_order = Order::new(OrderType::Ask, 99.0);
-        limit.fill(&mut market_sell_order);
-        println!("{:?}", limit);
-        assert!(market_sell_order.is_filled());
-        assert!(limit.orders.get(0).unwrap().is_filled());
-        assert!(!limit.orders.get(1).unwrap().is_filled())
+    /// Size of a resting iceberg order that is not shown in the book
+    pub fn reserve(&self) -> f64 {
+        from_lots(self.reserve)
     }
 
-    #[test]
-    fn limit_total_volume() {
-        let mut limit = Limit::new(1000.00);
+    /// Visible and hidden lots together
+    fn remaining(&self) -> u64 {
+        self.size + self.reserve
+    }
 
-        let buy_limit_order_a = Order::new(OrderType::Bid, 50.0);
-        let buy_limit_order_b = Order::new(OrderType::Bid, 50.0);
+    /// Split the remaining size into a visible slice and a hidden reserve
+    fn show_slice(&mut self) {
+        if let Some(display_size) = self.display_size {
+            let remaining = self.remaining();
+            self.size = display_size.min(remaining);
+            self.reserve = remaining - self.size;
+        }
+    }
 
-        limit.add(buy_limit_order_a);
-        limit.add(buy_limit_order_b);
+    pub fn order_type(&self) -> OrderType {
+        self.order_type
+    }
 
-        assert_eq!(limit.volume(), 100.0);
+    pub fn is_filled(&self) -> bool {
+        self.size == 0
+    }
 
-        let mut market_sell_order = Order::new(OrderType::Ask, 99.0);
+    pub(crate) fn reduce(&mut self, lots: u64) {
+        self.size -= lots;
+    }
+}
 
-        limit.fill(&mut market_sell_order);
+/// The outcome of placing a limit order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub id: OrderId,
+    /// Fills against the opposite side before the remainder (if any) rested
+    pub trades: Vec<Trade>,
+}
 
-        assert_eq!(limit.volume(), 1.0);
-    }
+/// The outcome of a market order
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketFill {
+    pub trades: Vec<Trade>,
+    /// Size left unfilled once the opposite side ran out; market orders never rest
+    pub remaining: f64,
+}
 
-    #[test]
-    fn orderbook_fill_market_order() {
+/// Which price a match executes at when an incoming order crosses a resting one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionPrice {
+    /// The resting order's price, so any price improvement goes to the taker
+    #[default]
+    Resting,
+    /// Halfway between the resting price and the taker's limit price, splitting the improvement.
+    /// A midpoint between two of the market's ticks is rounded onto the tick on the resting
+    /// price's side. Takers without a limit price (market orders) execute at the resting price.
+    Midpoint,
+}
+
+impl ExecutionPrice {
+    /// The price a resting order at `resting` trades at with a taker limited to `taker_limit`,
+    /// on a market of tick size `tick`
+    fn select(&self, resting: Price, taker_limit: Option<Price>, tick: Price) -> Price {
+        match (self, taker_limit) {
+            (ExecutionPrice::Midpoint, Some(limit)) => {
+                resting.midpoint_toward(limit, tick).unwrap_or(resting)
+            }
+            _ => resting,
+        }
+    }
+}
+
+/// Whether a market is matching
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarketStatus {
+    #[default]
+    Open,
+    /// Orders may still rest, but nothing matches until the market resumes
+    Halted,
+    /// Orders are collected without matching and uncross at a single price when the auction ends
+    Auction,
+}
+
+/// What happens to resting orders when a market halts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HaltPolicy {
+    /// Resting orders stay in the book through the halt
+    #[default]
+    KeepOrders,
+    /// Every resting order, and every stop order, is cancelled
+    CancelAll,
+    /// Stop and pegged orders are cancelled; other resting limit orders stay in the book
+    CancelConditional,
+}
+
+/// Default length of the re-opening auction after a halt (5 seconds)
+const REOPENING_AUCTION: u64 = 5_000_000_000;
+
+/// Width of a slot in the expiry timer wheel (1ms)
+const EXPIRY_RESOLUTION: u64 = 1_000_000;
+/// Slots in the expiry timer wheel, one turn covers about a second
+const EXPIRY_SLOTS: usize = 1024;
+
+#[derive(Debug)]
+pub struct OrderBook {
+    asks: BTreeMap<Price, Limit>,
+    bids: BTreeMap<Price, Limit>,
+    clock: Arc<dyn Clock>,
+    protection: MmProtection,
+    stats: TradeStats,
+    execution_price: ExecutionPrice,
+    dark_pool: Option<DarkPool>,
+    expiries: TimerWheel<OrderId>,
+    /// Watched orders with their notification threshold and last reported size ahead
+    queue_watches: HashMap<OrderId, (f64, f64)>,
+    status: MarketStatus,
+    halt_policy: HaltPolicy,
+    reopening_auction: u64,
+    auction_end: Option<Timestamp>,
+    random_close: Option<RandomClose>,
+    bands: Option<PriceBands>,
+    halt_end: Option<Timestamp>,
+    last_auction_price: Option<Price>,
+    events: Vec<OrderEvent>,
+    stops: StopBook,
+    pegs: BTreeMap<OrderId, Peg>,
+    /// The market's tick size, which pegged prices are rounded onto
+    tick_size: Price,
+    /// Side and price level of every resting order
+    index: HashMap<OrderId, (OrderType, Price)>,
+    duplicates: Option<DuplicateDetector>,
+    /// Order id given to each client order id placed, by account
+    client_orders: HashMap<(Option<AccountId>, String), OrderId>,
+    /// The market's pair and the pre-trade checks triggered stops must pass
+    risk_checkers: Option<(TradingPair, Vec<Arc<dyn RiskChecker>>)>,
+    #[cfg(feature = "chaos")]
+    faults: Option<super::chaos::FaultInjector<OrderEvent>>,
+    next_order_id: OrderId,
+    next_trade_id: TradeId,
+    next_sequence: u64,
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        OrderBook::new()
+    }
+}
+
+impl OrderBook {
+    /// Create a new order book
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::OrderBook;
+    /// let order_book = OrderBook::new();
+    /// ```
+    pub fn new() -> OrderBook {
+        OrderBook::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Create a new order book that reads the time from `clock`
+    pub fn with_clock(clock: Arc<dyn Clock>) -> OrderBook {
+        let now = clock.now();
+        OrderBook {
+            asks: BTreeMap::new(),
+            bids: BTreeMap::new(),
+            clock,
+            protection: MmProtection::new(),
+            stats: TradeStats::new(),
+            execution_price: ExecutionPrice::default(),
+            dark_pool: None,
+            expiries: TimerWheel::new(EXPIRY_RESOLUTION, EXPIRY_SLOTS, now),
+            queue_watches: HashMap::new(),
+            status: MarketStatus::default(),
+            halt_policy: HaltPolicy::default(),
+            reopening_auction: REOPENING_AUCTION,
+            auction_end: None,
+            random_close: None,
+            bands: None,
+            halt_end: None,
+            last_auction_price: None,
+            events: Vec::new(),
+            stops: StopBook::new(),
+            pegs: BTreeMap::new(),
+            tick_size: Price::from_ticks(1),
+            index: HashMap::new(),
+            duplicates: None,
+            client_orders: HashMap::new(),
+            risk_checkers: None,
+            #[cfg(feature = "chaos")]
+            faults: None,
+            next_order_id: 1,
+            next_trade_id: 1,
+            next_sequence: 1,
+        }
+    }
+
+    /// Enable market-maker protection for an account on this book
+    pub fn set_mm_protection(&mut self, account: AccountId, config: MmProtectionConfig) {
+        self.protection.configure(account, config);
+    }
+
+    /// Disable market-maker protection for an account on this book
+    pub fn clear_mm_protection(&mut self, account: AccountId) {
+        self.protection.remove(account);
+    }
+
+    /// Choose the price matches execute at
+    pub fn set_execution_price(&mut self, execution_price: ExecutionPrice) {
+        self.execution_price = execution_price;
+    }
+
+    /// Enable a hidden midpoint pool on this book for orders of at least `min_size`
+    pub fn enable_dark_pool(&mut self, min_size: f64) {
+        if self.dark_pool.is_none() {
+            self.dark_pool = Some(DarkPool::new(min_size));
+        }
+    }
+
+    pub fn dark_pool(&self) -> Option<&DarkPool> {
+        self.dark_pool.as_ref()
+    }
+
+    /// Place a midpoint-pegged order in the book's dark pool
+    ///
+    /// The order rests invisibly and matches other dark orders at the lit book's midprice.
+    ///
+    /// # Returns
+    /// * `Result<Vec<Trade>, String>` - The dark trades generated, Err if the book has no dark pool or the order is too small
+    pub fn place_dark_order(&mut self, mut order: Order) -> Result<Vec<Trade>, String> {
+        order.check_tag()?;
+        self.assign_id(&mut order);
+        match self.dark_pool.as_mut() {
+            Some(pool) => pool.add(order)?,
+            None => return Err("Dark pool is not enabled".to_string()),
+        }
+        Ok(self.cross_dark_pool())
+    }
+
+    /// Cross the dark pool at the current lit midprice
+    fn cross_dark_pool(&mut self) -> Vec<Trade> {
+        if self.status != MarketStatus::Open {
+            return Vec::new();
+        }
+        // Dark crosses split the spread exactly, so the midpoint may fall between the market's
+        // ticks; an odd spread at the storage scale leaves the spare tick with the bid
+        let midpoint = match (self.bids.keys().next_back(), self.asks.keys().next()) {
+            (Some(bid), Some(ask)) => bid.midpoint_toward(*ask, Price::from_ticks(1)),
+            _ => return Vec::new(),
+        };
+        let midpoint = match midpoint {
+            Ok(midpoint) => midpoint,
+            Err(_) => return Vec::new(),
+        };
+        let now = self.clock.now();
+        let mut trades = match self.dark_pool.as_mut() {
+            Some(pool) => pool.cross(midpoint, now),
+            None => return Vec::new(),
+        };
+        self.record_trades(&mut trades);
+        trades
+    }
+
+    /// Number the trades and add them to the book's statistics
+    fn record_trades(&mut self, trades: &mut [Trade]) {
+        for trade in trades.iter_mut() {
+            trade.id = self.next_trade_id;
+            self.next_trade_id += 1;
+            self.stats.record(trade);
+            for id in [trade.maker_order_id, trade.taker_order_id]
+                .into_iter()
+                .flatten()
+            {
+                if self.order(id).is_none() {
+                    self.index.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// Fill an order against the opposite side of the book
+    ///
+    /// Levels that are completely consumed are removed. Any maker whose fills breach their
+    /// market-maker protection has its remaining quotes pulled from the book.
+    ///
+    /// # Returns
+    /// * `Vec<Trade>` - The trades generated, in execution order
+    pub fn place_market_order(&mut self, order: &mut Order) -> Vec<Trade> {
+        let mut trades = self.match_order(order, None);
+        if let (Some(client), Some(id)) = (&order.client_order_id, order.id) {
+            self.client_orders
+                .insert((order.account, client.clone()), id);
+        }
+        self.trigger_stops(&mut trades);
+        trades
+    }
+
+    /// Match an order against the opposite side, best price first, without crossing `limit_price`
+    fn match_order(&mut self, order: &mut Order, limit_price: Option<Price>) -> Vec<Trade> {
+        self.assign_id(order);
+        if self.status != MarketStatus::Open {
+            return Vec::new();
+        }
+        let now = self.clock.now();
+        let execution_price = self.execution_price;
+        let tick_size = self.tick_size;
+        let mut bands = self.bands.take();
+        let mut band_breached = false;
+        let mut next_sequence = self.next_sequence;
+        // Walk the opposite side lazily so matching only visits the levels it touches
+        let limits: Box<dyn Iterator<Item = &mut Limit>> = match order.order_type {
+            OrderType::Ask => Box::new(self.bids.values_mut().rev()), // If we are selling, we need the buyers
+            OrderType::Bid => Box::new(self.asks.values_mut()),       // Vice Versa
+        };
+        let mut trades = Vec::new();
+        let mut emptied = Vec::new();
+        let mut filled = Vec::new();
+        for limit_order in limits {
+            if order.is_filled() {
+                break;
+            }
+            let crosses = match (order.order_type, limit_price) {
+                (_, None) => true,
+                (OrderType::Bid, Some(limit)) => limit_order.price <= limit,
+                (OrderType::Ask, Some(limit)) => limit_order.price >= limit,
+            };
+            if !crosses {
+                break;
+            }
+            let price = execution_price.select(limit_order.price, limit_price, tick_size);
+            if let Some(bands) = bands.as_mut() {
+                if !bands.allows(price.into(), now) {
+                    band_breached = true;
+                    break;
+                }
+                bands.record(price.into(), now);
+            }
+            for mut trade in limit_order.fill(order, &mut next_sequence) {
+                trade.price = price;
+                trade.transaction_time = now;
+                filled.extend(
+                    trade
+                        .maker_order_id
+                        .filter(|id| limit_order.orders.get(*id).is_none()),
+                );
+                trades.push(trade);
+            }
+            if limit_order.volume() == 0 {
+                emptied.push(limit_order.price);
+            }
+        }
+
+        let side = match order.order_type {
+            OrderType::Ask => &mut self.bids,
+            OrderType::Bid => &mut self.asks,
+        };
+        for price in emptied {
+            side.remove(&price);
+        }
+        for id in filled {
+            self.index.remove(&id);
+        }
+
+        self.next_sequence = next_sequence;
+        self.bands = bands;
+        if band_breached {
+            if let Some(last) = trades.last_mut() {
+                last.triggered_band = true;
+            }
+        }
+        self.record_trades(&mut trades);
+        self.apply_mm_protection(&trades);
+        if band_breached {
+            self.pause();
+        }
+        self.book_changed();
+        trades
+    }
+
+    /// Where an open order stands in the queue at its price level
+    pub fn queue_position(&self, id: OrderId) -> Option<QueuePosition> {
+        self.limit_of(id)?.queue_position(id)
+    }
+
+    /// The level an order was resting at, via the order index
+    fn limit_of(&self, id: OrderId) -> Option<&Limit> {
+        let (side, price) = self.index.get(&id)?;
+        match side {
+            OrderType::Ask => self.asks.get(price),
+            OrderType::Bid => self.bids.get(price),
+        }
+    }
+
+    /// Notify the order's owner whenever the size queued ahead of it moves by more than `threshold`
+    ///
+    /// Notifications are `QueueAhead` events, collected with [`OrderBook::drain_events`]. The watch
+    /// ends when the order leaves the book.
+    ///
+    /// # Returns
+    /// * `Option<QueuePosition>` - The current position, None (and no watch) if the order is not open
+    pub fn watch_queue_position(&mut self, id: OrderId, threshold: f64) -> Option<QueuePosition> {
+        let position = self.queue_position(id)?;
+        self.queue_watches
+            .insert(id, (threshold, position.size_ahead));
+        Some(position)
+    }
+
+    /// Re-peg pegged orders and notify queue watches after the book changed
+    fn book_changed(&mut self) {
+        self.repeg();
+        self.refresh_queue_watches();
+    }
+
+    fn refresh_queue_watches(&mut self) {
+        let mut watches = std::mem::take(&mut self.queue_watches);
+        watches.retain(|id, (threshold, last_size_ahead)| {
+            let position = match self.queue_position(*id) {
+                Some(position) => position,
+                None => return false,
+            };
+            if (position.size_ahead - *last_size_ahead).abs() > *threshold {
+                *last_size_ahead = position.size_ahead;
+                let account = self.order(*id).and_then(|order| order.account);
+                self.events.push(OrderEvent::QueueAhead {
+                    order_id: *id,
+                    account,
+                    orders_ahead: position.orders_ahead,
+                    size_ahead: position.size_ahead,
+                });
+            }
+            true
+        });
+        self.queue_watches = watches;
+    }
+
+    /// The price an open order rests at
+    pub fn order_price(&self, id: OrderId) -> Option<f64> {
+        self.order(id)?;
+        self.index.get(&id).map(|(_, price)| (*price).into())
+    }
+
+    /// Look up an open order
+    pub fn order(&self, id: OrderId) -> Option<&Order> {
+        self.limit_of(id)?
+            .orders
+            .get(id)
+            .filter(|order| !order.is_filled())
+    }
+
+    /// Screen orders placed with [`OrderBook::place_limit_order`] for duplicates
+    pub fn set_duplicate_check(&mut self, check: Option<DuplicateCheck>) {
+        self.duplicates = check.map(DuplicateDetector::new);
+    }
+
+    /// Place a limit order: match it against the opposite side up to its price, then rest whatever
+    /// is left
+    ///
+    /// Orders are screened by the duplicate check first, if one is set. While the market is not
+    /// open the whole order rests without matching. Whatever an IOC order leaves unfilled is
+    /// cancelled with [`CancelReason::Unfilled`] rather than rested; a FOK order that cannot fill
+    /// in full is cancelled the same way before it trades.
+    ///
+    /// # Arguments
+    /// * `order` - The order to place
+    /// * `price` - The order's limit price
+    ///
+    /// # Returns
+    /// * `Result<Placement, String>` - The order's id and its immediate fills, Err if its tag is too long, it is a
+    ///   GTD order without an expiry time, its display size is not positive, it was rejected as a duplicate or its
+    ///   account already used its client order id on this market
+    pub fn place_limit_order(&mut self, order: Order, price: f64) -> Result<Placement, String> {
+        order.check_tag()?;
+        self.check_client_order_id(&order)?;
+        if order.time_in_force == TimeInForce::Gtd && order.expires_at.is_none() {
+            return Err("Good-till-date orders need an expiry time".to_string());
+        }
+        if order.display_size == Some(0) {
+            return Err("Display size must be at least one lot".to_string());
+        }
+        let now = self.clock.now();
+        let duplicate = match (self.duplicates.as_mut(), &order.client_order_id) {
+            (Some(detector), None) => {
+                let duplicate = detector.observe(
+                    order.account,
+                    order.order_type,
+                    Price::new(price),
+                    order.size(),
+                    now,
+                );
+                match (duplicate, detector.check().action) {
+                    (true, DuplicateAction::Reject) => {
+                        return Err(format!(
+                            "Order repeats one placed within the last {}ns",
+                            detector.check().window
+                        ))
+                    }
+                    (duplicate, _) => duplicate,
+                }
+            }
+            _ => false,
+        };
+        let account = order.account;
+        let client_order = order
+            .client_order_id
+            .clone()
+            .map(|client| (account, client));
+        let (id, mut trades) = self.execute_limit(order, price);
+        if let Some(key) = client_order {
+            self.client_orders.insert(key, id);
+        }
+        if duplicate {
+            self.events.push(OrderEvent::SuspectedDuplicate {
+                order_id: id,
+                account,
+            });
+        }
+        self.trigger_stops(&mut trades);
+        Ok(Placement { id, trades })
+    }
+
+    /// Match a limit order and rest, or cancel, whatever its time in force leaves
+    fn execute_limit(&mut self, mut order: Order, price: f64) -> (OrderId, Vec<Trade>) {
+        let trades = match order.time_in_force {
+            TimeInForce::Fok if !self.can_fill(&order, price) => {
+                self.assign_id(&mut order);
+                Vec::new()
+            }
+            _ => self.match_order(&mut order, Some(Price::new(price))),
+        };
+        let id = match (order.is_filled(), order.time_in_force) {
+            (true, _) => self.assign_id(&mut order),
+            (false, TimeInForce::Ioc | TimeInForce::Fok) => {
+                let id = self.assign_id(&mut order);
+                self.cancel_unfilled(id, &order);
+                id
+            }
+            (false, _) => self.add(order, price),
+        };
+        (id, trades)
+    }
+
+    fn cancel_unfilled(&mut self, id: OrderId, order: &Order) {
+        self.events.push(OrderEvent::Cancelled {
+            order_id: id,
+            account: order.account,
+            remaining: order.size(),
+            reason: CancelReason::Unfilled,
+        });
+    }
+
+    /// Hold an order until a trade prints at its stop price, then place it as a market or limit
+    /// order
+    ///
+    /// Buy stops trigger on a trade at or above the stop price, sell stops on one at or below it.
+    /// Triggered stops are placed straight after the trade that set them off, and their fills are
+    /// returned along with that trade's.
+    ///
+    /// # Arguments
+    /// * `order` - The order to place once triggered
+    /// * `stop_price` - The trade price that triggers the order
+    /// * `kind` - Whether it becomes a market order, a limit order, or a limit order offset from
+    ///   the stop price
+    ///
+    /// # Returns
+    /// * `Result<OrderId, String>` - The order's id, Err if its tag is too long or the last trade is
+    ///   already through the stop price
+    pub fn place_stop_order(
+        &mut self,
+        mut order: Order,
+        stop_price: f64,
+        kind: StopKind,
+    ) -> Result<OrderId, String> {
+        order.check_tag()?;
+        if let Some(last) = self.stats.last_price() {
+            let triggered = match order.order_type {
+                OrderType::Bid => last >= stop_price,
+                OrderType::Ask => last <= stop_price,
+            };
+            if triggered {
+                return Err(format!(
+                    "Stop price {} is already through the last trade at {}",
+                    stop_price, last
+                ));
+            }
+        }
+        let id = self.assign_id(&mut order);
+        self.stops.insert(StopOrder {
+            order,
+            stop_price: Price::new(stop_price),
+            kind,
+        });
+        Ok(id)
+    }
+
+    /// A stop order that has not triggered yet
+    pub fn stop_order(&self, id: OrderId) -> Option<&StopOrder> {
+        self.stops.get(id)
+    }
+
+    /// Number of stop orders that have not triggered yet
+    pub fn stop_orders(&self) -> usize {
+        self.stops.len()
+    }
+
+    /// Place the stop orders set off by the last of `trades`, appending their fills
+    ///
+    /// Fills of a triggered stop can set off further stops. Trades that triggered a stop are
+    /// flagged with `triggered_stops`. Stops stop triggering if the market leaves the open state.
+    fn trigger_stops(&mut self, trades: &mut Vec<Trade>) {
+        while self.status == MarketStatus::Open {
+            let last = match trades.last_mut() {
+                Some(trade) => trade,
+                None => return,
+            };
+            let stop = match self.stops.pop_triggered(last.price) {
+                Some(stop) => stop,
+                None => return,
+            };
+            last.triggered_stops = true;
+            if self.check_triggered_stop(&stop).is_err() {
+                self.events.push(OrderEvent::Cancelled {
+                    order_id: stop.order.id.unwrap_or_default(),
+                    account: stop.order.account,
+                    remaining: from_lots(stop.order.remaining()),
+                    reason: CancelReason::Rejected,
+                });
+                continue;
+            }
+            let limit_price = stop.limit_price();
+            let mut order = stop.order;
+            let fills = match limit_price {
+                Some(price) => self.execute_limit(order, price).1,
+                None => {
+                    let fills = self.match_order(&mut order, None);
+                    if !order.is_filled() {
+                        self.cancel_unfilled(order.id.unwrap_or_default(), &order);
+                    }
+                    fills
+                }
+            };
+            trades.extend(fills);
+        }
+    }
+
+    /// Set the pre-trade checks stop orders must pass as they trigger, see
+    /// `Engine::add_risk_checker`
+    pub fn set_risk_checkers(
+        &mut self,
+        trading_pair: TradingPair,
+        checkers: Vec<Arc<dyn RiskChecker>>,
+    ) {
+        self.risk_checkers = Some((trading_pair, checkers));
+    }
+
+    /// Run a triggered stop through the pre-trade checks
+    fn check_triggered_stop(&self, stop: &StopOrder) -> Result<(), RiskRejection> {
+        let (pair, checkers) = match &self.risk_checkers {
+            Some(risk) => risk,
+            None => return Ok(()),
+        };
+        let context = RiskContext {
+            pair,
+            side: stop.order.order_type,
+            price: stop.limit_price(),
+            size: stop.order.size(),
+            account: stop.order.account,
+            open_orders: stop
+                .order
+                .account
+                .map_or(0, |account| self.open_orders_of(account)),
+            last_trade: self.stats.last_price(),
+        };
+        checkers
+            .iter()
+            .try_for_each(|checker| checker.check(&context))
+    }
+
+    /// Volume the opposite side offers an order on `side` without crossing `limit_price`, hidden
+    /// iceberg reserve included
+    ///
+    /// # Arguments
+    /// * `side` - The side of the incoming order
+    /// * `limit_price` - The incoming order's limit, None for a market order
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::{Order, OrderBook, OrderType};
+    /// let mut order_book = OrderBook::new();
+    /// order_book.add(Order::new(OrderType::Ask, 1.0), 100.0);
+    /// order_book.add(Order::new(OrderType::Ask, 2.0), 101.0);
+    /// assert_eq!(order_book.fillable_volume(OrderType::Bid, Some(100.5)), 1.0);
+    /// assert_eq!(order_book.fillable_volume(OrderType::Bid, None), 3.0);
+    /// ```
+    pub fn fillable_volume(&self, side: OrderType, limit_price: Option<f64>) -> f64 {
+        from_lots(self.fillable_lots(side, limit_price))
+    }
+
+    fn fillable_lots(&self, side: OrderType, limit_price: Option<f64>) -> u64 {
+        let limit_price = limit_price.map(Price::new);
+        self.opposite_levels(side)
+            .into_iter()
+            .take_while(|limit| match (side, limit_price) {
+                (_, None) => true,
+                (OrderType::Bid, Some(price)) => limit.price <= price,
+                (OrderType::Ask, Some(price)) => limit.price >= price,
+            })
+            .map(Limit::executable_volume)
+            .sum()
+    }
+
+    /// Estimate the slippage of a market order before sending it
+    ///
+    /// Walks the opposite side best price first, hidden iceberg reserve included, without
+    /// changing the book.
+    ///
+    /// # Arguments
+    /// * `side` - The side of the market order
+    /// * `quantity` - Its size
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::{Order, OrderBook, OrderType};
+    /// let mut order_book = OrderBook::new();
+    /// order_book.add(Order::new(OrderType::Ask, 1.0), 100.0);
+    /// order_book.add(Order::new(OrderType::Ask, 3.0), 102.0);
+    /// let cost = order_book.cost_to_fill(OrderType::Bid, 2.0);
+    /// assert_eq!(cost.average_price, Some(101.0));
+    /// assert_eq!(cost.worst_price, Some(102.0));
+    /// assert!(cost.sufficient);
+    /// ```
+    pub fn cost_to_fill(&self, side: OrderType, quantity: f64) -> FillCost {
+        let mut remaining = to_lots(quantity);
+        let mut fills = Vec::new();
+        for limit in self.opposite_levels(side) {
+            if remaining == 0 {
+                break;
+            }
+            let lots = remaining.min(limit.executable_volume());
+            if lots > 0 {
+                remaining -= lots;
+                fills.push((f64::from(limit.price), from_lots(lots)));
+            }
+        }
+        let filled = exact_sum(fills.iter().map(|(_, size)| *size));
+        let notional = exact_sum(fills.iter().map(|(price, size)| price * size));
+        FillCost {
+            filled,
+            average_price: (filled > 0.0).then(|| notional / filled),
+            worst_price: fills.last().map(|(price, _)| *price),
+            sufficient: remaining == 0,
+        }
+    }
+
+    /// The levels an order on `side` matches against, best price first
+    fn opposite_levels(&self, side: OrderType) -> Vec<&Limit> {
+        match side {
+            OrderType::Ask => self.bids.values().rev().collect(),
+            OrderType::Bid => self.asks.values().collect(),
+        }
+    }
+
+    /// Whether a limit order would fill in full right now, without stopping at a price band
+    fn can_fill(&self, order: &Order, price: f64) -> bool {
+        if self.status != MarketStatus::Open
+            || self.fillable_lots(order.order_type, Some(price)) < order.size
+        {
+            return false;
+        }
+        let mut bands = match self.bands.clone() {
+            Some(bands) => bands,
+            None => return true,
+        };
+        let now = self.clock.now();
+        let mut remaining = order.size;
+        for limit in self.opposite_levels(order.order_type) {
+            if remaining == 0 {
+                break;
+            }
+            let trade_price: f64 = self
+                .execution_price
+                .select(limit.price, Some(Price::new(price)), self.tick_size)
+                .into();
+            if !bands.allows(trade_price, now) {
+                return false;
+            }
+            bands.record(trade_price, now);
+            remaining = remaining.saturating_sub(limit.executable_volume());
+        }
+        true
+    }
+
+    /// Check that a limit price is no more than `percent` through the opposite touch
+    ///
+    /// Orders on a side whose opposite side is empty always pass.
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err describing the breach
+    pub fn check_price_collar(
+        &self,
+        side: OrderType,
+        price: f64,
+        percent: f64,
+    ) -> Result<(), String> {
+        let (touch, limit) = match side {
+            OrderType::Bid => match self.asks.keys().next() {
+                Some(ask) => (*ask, ask.offset_percent(percent)?),
+                None => return Ok(()),
+            },
+            OrderType::Ask => match self.bids.keys().next_back() {
+                Some(bid) => (*bid, bid.offset_percent(-percent)?),
+                None => return Ok(()),
+            },
+        };
+        let breached = match side {
+            OrderType::Bid => Price::new(price) > limit,
+            OrderType::Ask => Price::new(price) < limit,
+        };
+        match breached {
+            true => Err(format!(
+                "Order price {} is more than {}% through the opposite touch of {}",
+                price, percent, touch
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Cancel a resting order
+    ///
+    /// # Arguments
+    /// * `id` - The id returned when the order was added
+    ///
+    /// # Returns
+    /// * `Option<OrderEvent>` - A `Cancelled` event, None if the order is not open in this book
+    pub fn cancel(&mut self, id: OrderId) -> Option<OrderEvent> {
+        self.cancel_with_reason(id, CancelReason::Requested)
+    }
+
+    /// Cancel an order because an order linked to it executed or was cancelled
+    ///
+    /// The `Cancelled` event is queued for [`OrderBook::drain_events`].
+    ///
+    /// # Returns
+    /// * `bool` - Whether the order was open, as a resting or stop order
+    pub fn cancel_linked(&mut self, id: OrderId) -> bool {
+        self.cancel_queued(id, CancelReason::Linked)
+    }
+
+    /// Cancel an order for `reason`, queueing the `Cancelled` event for
+    /// [`OrderBook::drain_events`]
+    ///
+    /// # Returns
+    /// * `bool` - Whether the order was open, as a resting or stop order
+    pub fn cancel_queued(&mut self, id: OrderId, reason: CancelReason) -> bool {
+        match self.cancel_with_reason(id, reason) {
+            Some(event) => {
+                self.events.push(event);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn cancel_with_reason(&mut self, id: OrderId, reason: CancelReason) -> Option<OrderEvent> {
+        let order = match self.remove_order(id) {
+            Some(order) => order,
+            None => self.stops.remove(id)?.order,
+        };
+        self.book_changed();
+        Some(OrderEvent::Cancelled {
+            order_id: id,
+            account: order.account,
+            remaining: from_lots(order.remaining()),
+            reason,
+        })
+    }
+
+    /// Place a limit order whose price follows the book, see [`Peg`]
+    ///
+    /// The order is placed at its pegged price like any other limit order and may trade on
+    /// arrival. Whatever rests is moved whenever its reference price changes.
+    ///
+    /// # Returns
+    /// * `Result<Placement, String>` - The order's id and its immediate fills, Err if the reference side of the book
+    ///   is empty or the order is rejected
+    pub fn place_pegged_order(&mut self, order: Order, peg: Peg) -> Result<Placement, String> {
+        let price = self
+            .peg_price(order.order_type, &peg)
+            .ok_or_else(|| "No reference price to peg the order to".to_string())?;
+        let placement = self.place_limit_order(order, price)?;
+        self.peg_order(placement.id, peg);
+        Ok(placement)
+    }
+
+    /// The price an order on `side` would be pegged to now, on the market's tick grid; None if the
+    /// reference side is empty
+    pub fn peg_price(&self, side: OrderType, peg: &Peg) -> Option<f64> {
+        let price = peg.price(
+            side,
+            self.unpegged_touch(OrderType::Bid),
+            self.unpegged_touch(OrderType::Ask),
+        )?;
+        self.round_passive(side, price).map(f64::from)
+    }
+
+    /// Set the tick size pegged prices are rounded onto, see `Engine::set_market_config`
+    pub fn set_tick_size(&mut self, tick_size: Price) {
+        self.tick_size = tick_size;
+        self.repeg();
+    }
+
+    /// Round a price onto the tick grid away from the opposite side, down for a bid and up for an
+    /// ask, so rounding never makes an order more aggressive than asked for
+    fn round_passive(&self, side: OrderType, price: f64) -> Option<Price> {
+        let rounded = match side {
+            OrderType::Bid => Price::new(price).floor_to(self.tick_size),
+            OrderType::Ask => Price::new(price).ceil_to(self.tick_size),
+        };
+        rounded.ok()
+    }
+
+    /// Peg a resting order, moving it to its pegged price straight away
+    ///
+    /// # Returns
+    /// * `bool` - Whether the order was resting in the book
+    pub fn peg_order(&mut self, id: OrderId, peg: Peg) -> bool {
+        if !self.index.contains_key(&id) {
+            return false;
+        }
+        self.pegs.insert(id, peg);
+        self.book_changed();
+        true
+    }
+
+    /// The pegging instructions of a resting order, None if it is not pegged
+    pub fn peg(&self, id: OrderId) -> Option<&Peg> {
+        self.pegs.get(&id)
+    }
+
+    /// The best price on a side among the open orders that are not pegged
+    fn unpegged_touch(&self, side: OrderType) -> Option<f64> {
+        let unpegged = |limit: &&Limit| {
+            limit.orders.iter().any(|order| {
+                !order.is_filled() && !order.id.is_some_and(|id| self.pegs.contains_key(&id))
+            })
+        };
+        let limit = match side {
+            OrderType::Bid => self.bids.values().rev().find(unpegged),
+            OrderType::Ask => self.asks.values().find(unpegged),
+        };
+        limit.map(|limit| limit.price.into())
+    }
+
+    /// Move each pegged order whose reference price changed to the back of its new level
+    ///
+    /// Only runs while the market is open. A pegged order whose new price would cross the
+    /// opposite side stays where it is, so re-pegging never trades. Pegs are moved in passes until
+    /// none can move, since moving one can clear the way for another.
+    fn repeg(&mut self) {
+        if self.pegs.is_empty() || self.status != MarketStatus::Open {
+            return;
+        }
+        let best_bid = self.unpegged_touch(OrderType::Bid);
+        let best_ask = self.unpegged_touch(OrderType::Ask);
+        while self.repeg_pass(best_bid, best_ask) {}
+    }
+
+    /// Move the pegged orders that can move to their pegged prices
+    ///
+    /// # Returns
+    /// * `bool` - Whether any order moved
+    fn repeg_pass(&mut self, best_bid: Option<f64>, best_ask: Option<f64>) -> bool {
+        let mut moved = false;
+        let pegs: Vec<(OrderId, Peg)> = self.pegs.iter().map(|(id, peg)| (*id, *peg)).collect();
+        for (id, peg) in pegs {
+            let (side, price) = match self.index.get(&id) {
+                Some(entry) => *entry,
+                None => {
+                    self.pegs.remove(&id);
+                    continue;
+                }
+            };
+            let target = match peg
+                .price(side, best_bid, best_ask)
+                .and_then(|target| self.round_passive(side, target))
+            {
+                Some(target) => target,
+                None => continue,
+            };
+            let crosses = match side {
+                OrderType::Bid => self.asks.keys().next().is_some_and(|ask| target >= *ask),
+                OrderType::Ask => self
+                    .bids
+                    .keys()
+                    .next_back()
+                    .is_some_and(|bid| target <= *bid),
+            };
+            if target == price || crosses {
+                continue;
+            }
+            if let Some(order) = self.remove_order(id) {
+                self.rest(id, order, target.into());
+                moved = true;
+            }
+        }
+        moved
+    }
+
+    /// Take the notifications generated since the last call
+    pub fn drain_events(&mut self) -> Vec<OrderEvent> {
+        let events = std::mem::take(&mut self.events);
+        #[cfg(feature = "chaos")]
+        if let Some(faults) = self.faults.as_mut() {
+            return faults.publish(events);
+        }
+        events
+    }
+
+    /// Drop and delay the events returned by [`OrderBook::drain_events`]
+    ///
+    /// # Arguments
+    /// * `config` - The faults to inject, None to publish normally again
+    /// * `seed` - Seed for the drop decisions
+    #[cfg(feature = "chaos")]
+    pub fn set_event_faults(&mut self, config: Option<super::chaos::FaultConfig>, seed: u64) {
+        self.faults = config.map(|config| super::chaos::FaultInjector::new(config, seed));
+    }
+
+    fn assign_id(&mut self, order: &mut Order) -> OrderId {
+        match order.id {
+            Some(id) => id,
+            None => {
+                let id = self.next_order_id;
+                self.next_order_id += 1;
+                order.id = Some(id);
+                id
+            }
+        }
+    }
+
+    fn apply_mm_protection(&mut self, trades: &[Trade]) {
+        let now = self.clock.now();
+        for trade in trades {
+            let maker = match trade.maker_account {
+                Some(maker) => maker,
+                None => continue,
+            };
+            let delta = match trade.maker_side() {
+                OrderType::Bid => trade.size,
+                OrderType::Ask => -trade.size,
+            };
+            if self.protection.record_fill(maker, delta, now) {
+                self.pull_quotes(maker);
+            }
+        }
+    }
+
+    /// Combined notional (price × size) of the open orders belonging to any of `accounts`
+    pub fn open_notional(&self, accounts: &[AccountId]) -> f64 {
+        exact_sum(
+            self.asks
+                .values()
+                .chain(self.bids.values())
+                .flat_map(|limit| {
+                    let price: f64 = limit.price.into();
+                    limit
+                        .orders
+                        .iter()
+                        .filter(|order| {
+                            order.account.is_some_and(|owner| accounts.contains(&owner))
+                        })
+                        .map(move |order| price * from_lots(order.remaining()))
+                }),
+        )
+    }
+
+    /// Number of open orders belonging to `account`
+    pub fn open_orders_of(&self, account: AccountId) -> usize {
+        self.asks
+            .values()
+            .chain(self.bids.values())
+            .flat_map(|limit| limit.orders.iter())
+            .filter(|order| order.account == Some(account) && !order.is_filled())
+            .count()
+    }
+
+    /// Ids of an account's open orders, resting and stop
+    pub fn order_ids_of(&self, account: AccountId) -> Vec<OrderId> {
+        let resting = self
+            .asks
+            .values()
+            .chain(self.bids.values())
+            .flat_map(|limit| limit.orders.iter())
+            .filter(|order| !order.is_filled());
+        resting
+            .chain(self.stops.iter().map(|stop| &stop.order))
+            .filter(|order| order.account == Some(account))
+            .filter_map(|order| order.id)
+            .collect()
+    }
+
+    /// Price of the most recent trade, however old
+    pub fn last_trade_price(&self) -> Option<f64> {
+        self.stats.last_price()
+    }
+
+    /// Remove every good-till-time order whose expiry has been reached
+    ///
+    /// Expiries are tracked in a timer wheel, so this only visits the orders that are due rather
+    /// than scanning the book. Orders that were filled before their expiry are skipped.
+    ///
+    /// # Returns
+    /// * `Vec<OrderEvent>` - An `Expired` event per removed order, in expiry order
+    pub fn expire_orders(&mut self) -> Vec<OrderEvent> {
+        self.expire_orders_at(self.clock.now())
+    }
+
+    /// Remove every good-till-time order whose expiry is at or before `now`, see
+    /// [`OrderBook::expire_orders`]
+    pub fn expire_orders_at(&mut self, now: Timestamp) -> Vec<OrderEvent> {
+        let mut events = Vec::new();
+        for (expires_at, id) in self.expiries.advance(now) {
+            if let Some(order) = self.remove_order(id) {
+                events.push(OrderEvent::Expired {
+                    order_id: id,
+                    account: order.account,
+                    remaining: from_lots(order.remaining()),
+                    at: expires_at,
+                });
+            }
+        }
+        self.book_changed();
+        events
+    }
+
+    /// Remove an open order from whichever level it rests at
+    fn remove_order(&mut self, id: OrderId) -> Option<Order> {
+        let (side, price) = self.index.remove(&id)?;
+        let side = match side {
+            OrderType::Ask => &mut self.asks,
+            OrderType::Bid => &mut self.bids,
+        };
+        let order = side.get_mut(&price)?.remove(id);
+        if side.get(&price).is_some_and(|limit| limit.volume() == 0) {
+            side.remove(&price);
+        }
+        order
+    }
+
+    pub fn status(&self) -> MarketStatus {
+        self.status
+    }
+
+    /// Choose what happens to resting orders when this market halts
+    pub fn set_halt_policy(&mut self, policy: HaltPolicy) {
+        self.halt_policy = policy;
+    }
+
+    /// Stop matching on this market and apply the halt policy to resting orders
+    ///
+    /// # Returns
+    /// * `Vec<OrderEvent>` - A `Cancelled` event for every order the policy removed
+    pub fn halt(&mut self) -> Vec<OrderEvent> {
+        self.status = MarketStatus::Halted;
+        let mut events = Vec::new();
+        if self.halt_policy != HaltPolicy::KeepOrders {
+            events.extend(
+                self.stops
+                    .drain()
+                    .into_iter()
+                    .map(|stop| OrderEvent::Cancelled {
+                        order_id: stop.order.id.unwrap_or_default(),
+                        account: stop.order.account,
+                        remaining: from_lots(stop.order.remaining()),
+                        reason: CancelReason::Halt,
+                    }),
+            );
+        }
+        if self.halt_policy == HaltPolicy::CancelAll {
+            for side in [&mut self.asks, &mut self.bids] {
+                for limit in std::mem::take(side).into_values() {
+                    events.extend(
+                        limit
+                            .orders
+                            .into_iter()
+                            .filter(|order| !order.is_filled())
+                            .map(|order| OrderEvent::Cancelled {
+                                order_id: order.id.unwrap_or_default(),
+                                account: order.account,
+                                remaining: from_lots(order.remaining()),
+                                reason: CancelReason::Halt,
+                            }),
+                    );
+                }
+            }
+            self.index.clear();
+            self.pegs.clear();
+            self.book_changed();
+        }
+        if self.halt_policy == HaltPolicy::CancelConditional {
+            for id in std::mem::take(&mut self.pegs).into_keys() {
+                if let Some(order) = self.remove_order(id) {
+                    events.push(OrderEvent::Cancelled {
+                        order_id: id,
+                        account: order.account,
+                        remaining: from_lots(order.remaining()),
+                        reason: CancelReason::Halt,
+                    });
+                }
+            }
+            self.book_changed();
+        }
+        events
+    }
+
+    /// Enable limit-up/limit-down price bands on this market
+    pub fn set_price_bands(&mut self, config: BandConfig) {
+        self.bands = Some(PriceBands::new(config));
+    }
+
+    /// The current lower and upper price band, if bands are enabled and there is a reference price
+    pub fn price_bands(&mut self) -> Option<(f64, f64)> {
+        let now = self.clock.now();
+        self.bands.as_mut()?.bands(now)
+    }
+
+    /// Record the latest external index price for bands anchored to it
+    ///
+    /// Ignored unless price bands are enabled.
+    pub fn update_index_price(&mut self, price: f64) {
+        let now = self.clock.now();
+        if let Some(bands) = self.bands.as_mut() {
+            bands.update_index(price, now);
+        }
+    }
+
+    /// Halt for the configured pause after a band breach, then reopen through an auction
+    ///
+    /// Cancellations caused by the halt policy are queued for [`OrderBook::drain_events`].
+    fn pause(&mut self) {
+        let pause = match self.bands.as_ref() {
+            Some(bands) => bands.config().pause,
+            None => return,
+        };
+        let events = self.halt();
+        self.events.extend(events);
+        self.halt_end = Some(self.clock.now().saturating_add(pause));
+    }
+
+    /// Set how long the re-opening auction after a halt lasts, in nanoseconds
+    pub fn set_reopening_auction(&mut self, duration: u64) {
+        self.reopening_auction = duration;
+    }
+
+    /// Close auctions at a random time up to `window` nanoseconds after their scheduled end
+    ///
+    /// `seed` makes the sequence of close times reproducible.
+    pub fn set_random_close(&mut self, window: u64, seed: u64) {
+        self.random_close = match window {
+            0 => None,
+            _ => Some(RandomClose::new(window, seed)),
+        };
+    }
+
+    /// Leave a halt through a re-opening auction
+    ///
+    /// Orders keep resting without matching until the auction ends, at which point
+    /// [`OrderBook::poll_auction`] uncrosses the book and continuous trading resumes.
+    pub fn resume(&mut self) {
+        if self.status == MarketStatus::Halted {
+            self.halt_end = None;
+            let offset = self.random_close.as_mut().map_or(0, RandomClose::offset);
+            self.status = MarketStatus::Auction;
+            self.auction_end = Some(
+                self.clock
+                    .now()
+                    .saturating_add(self.reopening_auction)
+                    .saturating_add(offset),
+            );
+        }
+    }
+
+    /// When the current auction is due to end
+    ///
+    /// With a random close this is the drawn close time, which should not be published to
+    /// participants.
+    pub fn auction_end(&self) -> Option<Timestamp> {
+        self.auction_end
+    }
+
+    /// The price the book would uncross at right now
+    pub fn indicative_price(&self) -> Option<IndicativePrice> {
+        indicative_price(&self.depth(usize::MAX), self.stats.last_price())
+    }
+
+    /// Apply timed session transitions: end band pauses that are over and uncross auctions that
+    /// have reached their end time
+    pub fn poll_auction(&mut self) -> Vec<Trade> {
+        let now = self.clock.now();
+        if self.status == MarketStatus::Halted && self.halt_end.is_some_and(|end| now >= end) {
+            self.resume();
+        }
+        match (self.status, self.auction_end) {
+            (MarketStatus::Auction, Some(end)) if now >= end => self.uncross(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// End the auction now: match everything that crosses at the indicative price and reopen
+    ///
+    /// Within each pair of matched orders, the later one is reported as the aggressor.
+    pub fn uncross(&mut self) -> Vec<Trade> {
+        if self.status != MarketStatus::Auction {
+            return Vec::new();
+        }
+        let indicative = self.indicative_price();
+        self.status = MarketStatus::Open;
+        self.auction_end = None;
+        let price = match indicative {
+            Some(indicative) => indicative.price,
+            None => return Vec::new(),
+        };
+
+        let now = self.clock.now();
+        let mut trades = Vec::new();
+        while let (Some(mut bid), Some(mut ask)) = (self.bids.last_entry(), self.asks.first_entry())
+        {
+            if *bid.key() < price || *ask.key() > price {
+                break;
+            }
+            let bid_order = bid.get_mut().orders.iter_mut().find(|o| !o.is_filled());
+            let ask_order = ask.get_mut().orders.iter_mut().find(|o| !o.is_filled());
+            if let (Some(bid_order), Some(ask_order)) = (bid_order, ask_order) {
+                let size = bid_order.size.min(ask_order.size);
+                let (maker, taker) = match bid_order.id > ask_order.id {
+                    true => (&*ask_order, &*bid_order),
+                    false => (&*bid_order, &*ask_order),
+                };
+                trades.push(Trade {
+                    id: 0,
+                    price,
+                    size: from_lots(size),
+                    aggressor_side: taker.order_type,
+                    maker_order_id: maker.id,
+                    taker_order_id: taker.id,
+                    maker_account: maker.account,
+                    taker_account: taker.account,
+                    maker_tag: maker.tag.clone(),
+                    taker_tag: taker.tag.clone(),
+                    maker_version: maker.version,
+                    taker_version: taker.version,
+                    transaction_time: now,
+                    triggered_stops: false,
+                    triggered_band: false,
+                    dark: false,
+                });
+                bid_order.size -= size;
+                ask_order.size -= size;
+            }
+            for level in [bid.get_mut(), ask.get_mut()] {
+                level.replenish_exhausted(&mut self.next_sequence);
+                for id in level.remove_filled() {
+                    self.index.remove(&id);
+                }
+            }
+            if bid.get().volume() == 0 {
+                bid.remove();
+            }
+            if ask.get().volume() == 0 {
+                ask.remove();
+            }
+        }
+
+        self.last_auction_price = Some(price);
+        if let Some(bands) = self.bands.as_mut() {
+            bands.recenter(price.into(), now);
+        }
+        self.record_trades(&mut trades);
+        self.apply_mm_protection(&trades);
+        self.trigger_stops(&mut trades);
+        self.book_changed();
+        trades
+    }
+
+    /// Cancel every resting DAY order at the end of the trading day
+    ///
+    /// # Returns
+    /// * `Vec<OrderEvent>` - A `Cancelled` event (reason `EndOfDay`) per expired order
+    pub fn expire_day_orders(&mut self) -> Vec<OrderEvent> {
+        let mut events = Vec::new();
+        for side in [&mut self.asks, &mut self.bids] {
+            for limit in side.values_mut() {
+                let (day, rest): (OrderQueue, OrderQueue) = std::mem::take(&mut limit.orders)
+                    .into_iter()
+                    .partition(|order| order.time_in_force == TimeInForce::Day);
+                limit.orders = rest;
+                events.extend(
+                    day.into_iter()
+                        .filter(|order| !order.is_filled())
+                        .map(|order| OrderEvent::Cancelled {
+                            order_id: order.id.unwrap_or_default(),
+                            account: order.account,
+                            remaining: from_lots(order.remaining()),
+                            reason: CancelReason::EndOfDay,
+                        }),
+                );
+            }
+            side.retain(|_, limit| limit.volume() > 0);
+        }
+        for event in &events {
+            if let OrderEvent::Cancelled { order_id, .. } = event {
+                self.index.remove(order_id);
+            }
+        }
+        self.book_changed();
+        events
+    }
+
+    /// Read up to `max_orders` resting orders of a snapshot of the book
+    ///
+    /// Large books are transferred as a series of chunks, each continuing from the previous
+    /// chunk's `next` cursor. Changes to the book between chunks are not captured; a receiver
+    /// applies later events on top of the restored book.
+    ///
+    /// # Arguments
+    /// * `from` - The cursor to continue from, None for the first chunk
+    /// * `max_orders` - The chunk size
+    pub fn snapshot_chunk(&self, from: Option<SnapshotCursor>, max_orders: usize) -> SnapshotChunk {
+        let side_rank = |side: OrderType| match side {
+            OrderType::Ask => 0,
+            OrderType::Bid => 1,
+        };
+        let after_cursor = |side: OrderType, price: Price, sequence: u64| match from {
+            None => true,
+            Some(cursor) => {
+                (side_rank(side), price, sequence)
+                    > (side_rank(cursor.side), cursor.price, cursor.sequence)
+            }
+        };
+        let mut orders = self
+            .asks
+            .iter()
+            .map(|level| (OrderType::Ask, level))
+            .chain(self.bids.iter().map(|level| (OrderType::Bid, level)))
+            .flat_map(|(side, (price, limit))| {
+                limit
+                    .orders
+                    .iter()
+                    .filter(|order| !order.is_filled())
+                    .map(move |order| (side, *price, order))
+            })
+            .filter(|(side, price, order)| after_cursor(*side, *price, order.sequence))
+            .map(|(side, price, order)| RestingOrder {
+                id: order.id.unwrap_or_default(),
+                side,
+                price,
+                size: order.size(),
+                reserve: order.reserve(),
+                display_size: order.display_size(),
+                account: order.account,
+                expires_at: order.expires_at,
+                time_in_force: order.time_in_force,
+                tag: order.tag.clone(),
+                sequence: order.sequence,
+                version: order.version,
+                peg: order.id.and_then(|id| self.pegs.get(&id)).copied(),
+            });
+        let chunk: Vec<RestingOrder> = orders.by_ref().take(max_orders).collect();
+        let next = match orders.next() {
+            Some(_) => chunk.last().map(|last| SnapshotCursor {
+                side: last.side,
+                price: last.price,
+                sequence: last.sequence,
+            }),
+            None => None,
+        };
+        SnapshotChunk {
+            orders: chunk,
+            next,
+        }
+    }
+
+    /// Rest orders received in snapshot chunks, keeping their ids and time priority
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if an order id is already in use in this book
+    pub fn restore(&mut self, orders: &[RestingOrder]) -> Result<(), String> {
+        for resting in orders {
+            if self.index.contains_key(&resting.id) {
+                return Err(format!("Order {} already exists", resting.id));
+            }
+            let mut order =
+                Order::new(resting.side, resting.size).with_time_in_force(resting.time_in_force);
+            order.id = Some(resting.id);
+            order.account = resting.account;
+            order.expires_at = resting.expires_at;
+            order.tag = resting.tag.clone();
+            order.sequence = resting.sequence;
+            order.version = resting.version;
+            order.reserve = to_lots(resting.reserve);
+            order.display_size = resting.display_size.map(to_lots);
+            if let Some(expires_at) = resting.expires_at {
+                self.expiries.schedule(expires_at, resting.id);
+            }
+            if let Some(peg) = resting.peg {
+                self.pegs.insert(resting.id, peg);
+            }
+            self.index.insert(resting.id, (resting.side, resting.price));
+            let side = match resting.side {
+                OrderType::Ask => &mut self.asks,
+                OrderType::Bid => &mut self.bids,
+            };
+            side.entry(resting.price)
+                .or_insert(Limit {
+                    price: resting.price,
+                    orders: OrderQueue::default(),
+                })
+                .add(order);
+            self.next_order_id = self.next_order_id.max(resting.id + 1);
+            self.next_sequence = self.next_sequence.max(resting.sequence + 1);
+        }
+        Ok(())
+    }
+
+    /// Every stop order that has not triggered yet, buys then sells, each stop price in arrival
+    /// order
+    pub fn stop_snapshot(&self) -> Vec<WaitingStop> {
+        self.stops
+            .iter()
+            .map(|stop| WaitingStop {
+                id: stop.order.id.unwrap_or_default(),
+                side: stop.order.order_type,
+                stop_price: stop.stop_price,
+                kind: stop.kind,
+                size: stop.order.size(),
+                reserve: stop.order.reserve(),
+                display_size: stop.order.display_size(),
+                account: stop.order.account,
+                expires_at: stop.order.expires_at,
+                time_in_force: stop.order.time_in_force,
+                collar_override: stop.order.collar_override,
+                tag: stop.order.tag.clone(),
+            })
+            .collect()
+    }
+
+    /// Put back the stop orders of a snapshot, keeping their ids and trigger order
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if an order id is already in use in this book
+    pub fn restore_stops(&mut self, stops: &[WaitingStop]) -> Result<(), String> {
+        for waiting in stops {
+            if self.index.contains_key(&waiting.id) || self.stops.get(waiting.id).is_some() {
+                return Err(format!("Order {} already exists", waiting.id));
+            }
+            let mut order =
+                Order::new(waiting.side, waiting.size).with_time_in_force(waiting.time_in_force);
+            order.id = Some(waiting.id);
+            order.account = waiting.account;
+            order.expires_at = waiting.expires_at;
+            order.collar_override = waiting.collar_override;
+            order.tag = waiting.tag.clone();
+            order.reserve = to_lots(waiting.reserve);
+            order.display_size = waiting.display_size.map(to_lots);
+            self.stops.insert(StopOrder {
+                order,
+                stop_price: waiting.stop_price,
+                kind: waiting.kind,
+            });
+            self.next_order_id = self.next_order_id.max(waiting.id + 1);
+        }
+        Ok(())
+    }
+
+    /// Check that an order's client order id has not been used by its account on this market
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err naming the order the id was given to
+    pub fn check_client_order_id(&self, order: &Order) -> Result<(), String> {
+        let client = match &order.client_order_id {
+            Some(client) => client,
+            None => return Ok(()),
+        };
+        match self.client_orders.get(&(order.account, client.clone())) {
+            Some(id) => Err(format!(
+                "Client order id {:?} was already used for order {}",
+                client, id
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Every client order id used on this market, by order id
+    pub fn client_orders(&self) -> Vec<ClientOrder> {
+        let mut client_orders: Vec<ClientOrder> = self
+            .client_orders
+            .iter()
+            .map(|((account, client), id)| ClientOrder {
+                account: *account,
+                client_order_id: client.clone(),
+                order_id: *id,
+            })
+            .collect();
+        client_orders.sort_by_key(|client_order| client_order.order_id);
+        client_orders
+    }
+
+    /// Take back the client order ids of a snapshot so they cannot be reused after a restart
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err if an id is already in use here for a different order
+    pub fn restore_client_orders(&mut self, client_orders: &[ClientOrder]) -> Result<(), String> {
+        for client_order in client_orders {
+            let key = (client_order.account, client_order.client_order_id.clone());
+            match self.client_orders.get(&key) {
+                Some(id) if *id != client_order.order_id => {
+                    return Err(format!(
+                        "Client order id {:?} is already used for order {}",
+                        client_order.client_order_id, id
+                    ))
+                }
+                _ => {
+                    self.client_orders.insert(key, client_order.order_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of open orders resting in the book
+    pub fn open_orders(&self) -> usize {
+        self.asks
+            .values()
+            .chain(self.bids.values())
+            .map(Limit::order_count)
+            .sum()
+    }
+
+    /// Remove all of an account's resting orders from both sides of the book
+    ///
+    /// A `Cancelled` event for each order is queued for [`OrderBook::drain_events`].
+    ///
+    /// # Returns
+    /// * `Vec<OrderId>` - The ids of the orders removed
+    pub fn pull_quotes(&mut self, account: AccountId) -> Vec<OrderId> {
+        let mut pulled = Vec::new();
+        for side in [&mut self.asks, &mut self.bids] {
+            for limit in side.values_mut() {
+                pulled.extend(limit.remove_account(account));
+            }
+            side.retain(|_, limit| limit.volume() > 0);
+        }
+        for order in &pulled {
+            self.index.remove(&order.id.unwrap_or_default());
+            self.events.push(OrderEvent::Cancelled {
+                order_id: order.id.unwrap_or_default(),
+                account: order.account,
+                remaining: from_lots(order.remaining()),
+                reason: CancelReason::Pulled,
+            });
+        }
+        self.book_changed();
+        pulled.into_iter().filter_map(|order| order.id).collect()
+    }
+
+    /// Returns the ask limits, lowest price first
+    pub fn ask_limits(&mut self) -> Vec<&mut Limit> {
+        self.asks.values_mut().collect()
+    }
+
+    /// Returns the bid limits, highest price first
+    pub fn bid_limits(&mut self) -> Vec<&mut Limit> {
+        self.bids.values_mut().rev().collect()
+    }
+
+    /// The highest bid price, None if there are no bids
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids.keys().next_back().map(|price| (*price).into())
+    }
+
+    /// The lowest ask price, None if there are no asks
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks.keys().next().map(|price| (*price).into())
+    }
+
+    /// Best ask minus best bid, None unless both sides have orders
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()? - self.best_bid()?)
+    }
+
+    /// Halfway between the best bid and the best ask, None unless both sides have orders
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::{Order, OrderBook, OrderType};
+    /// let mut order_book = OrderBook::new();
+    /// order_book.add(Order::new(OrderType::Bid, 1.0), 99.0);
+    /// assert_eq!(order_book.mid_price(), None);
+    /// order_book.add(Order::new(OrderType::Ask, 1.0), 101.0);
+    /// assert_eq!(order_book.best_bid(), Some(99.0));
+    /// assert_eq!(order_book.best_ask(), Some(101.0));
+    /// assert_eq!(order_book.mid_price(), Some(100.0));
+    /// ```
+    pub fn mid_price(&self) -> Option<f64> {
+        Some((self.best_ask()? + self.best_bid()?) / 2.0)
+    }
+
+    /// Order book imbalance over the best `levels` levels of each side
+    ///
+    /// Bid volume minus ask volume over their sum: 1 when only bids rest, -1 when only asks do.
+    /// None if both sides are empty.
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::{Order, OrderBook, OrderType};
+    /// let mut order_book = OrderBook::new();
+    /// order_book.add(Order::new(OrderType::Bid, 3.0), 99.0);
+    /// order_book.add(Order::new(OrderType::Ask, 1.0), 101.0);
+    /// assert_eq!(order_book.imbalance(5), Some(0.5));
+    /// ```
+    pub fn imbalance(&self, levels: usize) -> Option<f64> {
+        let depth = self.depth(levels);
+        let volume = |levels: &[DepthLevel]| exact_sum(levels.iter().map(|level| level.volume));
+        let (bids, asks) = (volume(&depth.bids), volume(&depth.asks));
+        match bids + asks > 0.0 {
+            true => Some((bids - asks) / (bids + asks)),
+            false => None,
+        }
+    }
+
+    /// Midprice weighted by the size at the touch, leaning towards the side with less size
+    ///
+    /// The best bid weighted by the ask size plus the best ask weighted by the bid size, over
+    /// both sizes. None unless both sides have orders.
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::{Order, OrderBook, OrderType};
+    /// let mut order_book = OrderBook::new();
+    /// order_book.add(Order::new(OrderType::Bid, 3.0), 99.0);
+    /// order_book.add(Order::new(OrderType::Ask, 1.0), 101.0);
+    /// assert_eq!(order_book.microprice(), Some(100.5));
+    /// ```
+    pub fn microprice(&self) -> Option<f64> {
+        let top = self.top_of_book();
+        let (bid, ask) = (top.bid?, top.ask?);
+        let (bid_price, ask_price) = (f64::from(bid.price), f64::from(ask.price));
+        Some((bid_price * ask.volume + ask_price * bid.volume) / (bid.volume + ask.volume))
+    }
+
+    /// Best bid and ask with their sizes, the spread and the midprice
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::{Order, OrderBook, OrderType};
+    /// let mut order_book = OrderBook::new();
+    /// order_book.add(Order::new(OrderType::Bid, 1.0), 99.0);
+    /// order_book.add(Order::new(OrderType::Bid, 2.0), 99.0);
+    /// order_book.add(Order::new(OrderType::Ask, 0.5), 101.0);
+    /// let top = order_book.top_of_book();
+    /// assert_eq!(top.bid.unwrap().volume, 3.0);
+    /// assert_eq!(top.ask.unwrap().volume, 0.5);
+    /// assert_eq!(top.spread, Some(2.0));
+    /// assert_eq!(top.mid, Some(100.0));
+    /// ```
+    pub fn top_of_book(&self) -> TopOfBook {
+        TopOfBook {
+            bid: self.bids.values().next_back().map(Limit::depth_level),
+            ask: self.asks.values().next().map(Limit::depth_level),
+            spread: self.spread(),
+            mid: self.mid_price(),
+        }
+    }
+
+    /// Snapshot of the best `levels` price levels on each side, with the number of orders at each
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::{Order, OrderBook, OrderType};
+    /// let mut order_book = OrderBook::new();
+    /// order_book.add(Order::new(OrderType::Bid, 1.0), 99.0);
+    /// order_book.add(Order::new(OrderType::Bid, 2.0), 99.0);
+    /// let depth = order_book.depth(5);
+    /// assert_eq!(depth.bids[0].order_count, 2);
+    /// ```
+    pub fn depth(&self, levels: usize) -> Depth {
+        Depth {
+            bids: self
+                .bids
+                .values()
+                .rev()
+                .take(levels)
+                .map(Limit::depth_level)
+                .collect(),
+            asks: self
+                .asks
+                .values()
+                .take(levels)
+                .map(Limit::depth_level)
+                .collect(),
+        }
+    }
+
+    /// The price of the most recent auction uncross
+    pub fn last_auction_price(&self) -> Option<Price> {
+        self.last_auction_price
+    }
+
+    /// Rolling trade statistics for this book
+    pub fn stats(&mut self) -> &mut TradeStats {
+        &mut self.stats
+    }
+
+    /// The current time according to the book's clock
+    pub fn now(&self) -> Timestamp {
+        self.clock.now()
+    }
+
+    /// Add an order to the order book
+    ///
+    /// # Arguments
+    /// * `order` - The order to add to the order book
+    /// * `price` - The price of the order
+    ///
+    /// # Returns
+    /// * `OrderId` - The id assigned to the order
+    ///
+    /// # Example
+    /// ```
+    /// use orderbook::matching::orderbook::{OrderBook, Order, OrderType};
+    /// let mut order_book = OrderBook::new();
+    /// let order = Order::new(OrderType::Bid, 100.0);
+    /// order_book.add(order, 1000.00);
+    /// ```
+    pub fn add(&mut self, mut order: Order, price: f64) -> OrderId {
+        let id = self.assign_id(&mut order);
+        if let Some(expires_at) = order.expires_at {
+            self.expiries.schedule(expires_at, id);
+        }
+        self.rest(id, order, price);
+        // A new lit quote can create the midprice that resting dark orders were waiting for
+        self.cross_dark_pool();
+        self.book_changed();
+        id
+    }
+
+    /// Queue an order with an id at the back of its price level
+    fn rest(&mut self, id: OrderId, mut order: Order, price: f64) {
+        order.show_slice();
+        order.sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.index.insert(id, (order.order_type, Price::new(price)));
+        match order.order_type {
+            OrderType::Ask => {
+                let limit = self
+                    .asks
+                    .entry(Price::new(price))
+                    .or_insert(Limit::new(price));
+                limit.add(order);
+            }
+            OrderType::Bid => {
+                let limit = self
+                    .bids
+                    .entry(Price::new(price))
+                    .or_insert(Limit::new(price));
+                limit.add(order);
+            }
+        }
+    }
+
+    /// Change the price and/or remaining size of a resting order
+    ///
+    /// Reducing the size at the same price keeps the order's place in the queue. A new price or a
+    /// larger size moves it to the back of the queue at its (new) level, after matching it against
+    /// the opposite side if the new price crosses.
+    ///
+    /// Either way the order's version goes up by one. An amend in place is reported with an
+    /// `Amended` event, one that loses priority with a `Replaced` event.
+    ///
+    /// # Arguments
+    /// * `id` - The order to amend
+    /// * `new_price` - The order's price after the amend
+    /// * `new_size` - The order's remaining size after the amend
+    ///
+    /// # Returns
+    /// * `Result<Vec<Trade>, String>` - Fills caused by a crossing reprice, Err if the order is not open or the new size
+    ///   is not positive
+    pub fn amend(
+        &mut self,
+        id: OrderId,
+        new_price: f64,
+        new_size: f64,
+    ) -> Result<Vec<Trade>, String> {
+        if !new_size.is_finite() || to_lots(new_size) == 0 {
+            return Err(format!("Amended size must be positive, got {}", new_size));
+        }
+        let (side, price) = *self
+            .index
+            .get(&id)
+            .ok_or_else(|| format!("Order {} is not open", id))?;
+        let limits = match side {
+            OrderType::Ask => &mut self.asks,
+            OrderType::Bid => &mut self.bids,
+        };
+        let order = limits
+            .get_mut(&price)
+            .and_then(|limit| limit.orders.get_mut(id).filter(|order| !order.is_filled()))
+            .ok_or_else(|| format!("Order {} is not open", id))?;
+
+        let new_lots = to_lots(new_size);
+        if price == Price::new(new_price) && new_lots <= order.remaining() {
+            order.reserve = new_lots.saturating_sub(order.size);
+            order.size = order.size.min(new_lots);
+            order.version += 1;
+            self.events.push(OrderEvent::Amended {
+                order_id: id,
+                account: order.account,
+                version: order.version,
+                price: new_price,
+                size: new_size,
+            });
+            self.book_changed();
+            return Ok(Vec::new());
+        }
+        self.replace(id, new_price, new_size)
+    }
+
+    /// Replace a resting order with a new price and size, giving up its place in the queue
+    ///
+    /// Works like cancelling the order and placing a new one in a single command, except that the
+    /// order keeps its id and its version goes up by one. Each replace is reported with a
+    /// `Replaced` event. Replacing a pegged order at a new price unpegs it.
+    ///
+    /// # Returns
+    /// * `Result<Vec<Trade>, String>` - Fills caused by a crossing price, Err if the order is not open or the new size
+    ///   is not positive
+    pub fn replace(
+        &mut self,
+        id: OrderId,
+        new_price: f64,
+        new_size: f64,
+    ) -> Result<Vec<Trade>, String> {
+        if !new_size.is_finite() || to_lots(new_size) == 0 {
+            return Err(format!("Amended size must be positive, got {}", new_size));
+        }
+        if self
+            .index
+            .get(&id)
+            .is_some_and(|(_, price)| *price != Price::new(new_price))
+        {
+            self.pegs.remove(&id);
+        }
+        let mut order = self
+            .remove_order(id)
+            .ok_or_else(|| format!("Order {} is not open", id))?;
+        order.version += 1;
+        self.events.push(OrderEvent::Replaced {
+            order_id: id,
+            account: order.account,
+            version: order.version,
+            price: new_price,
+            size: new_size,
+        });
+        Ok(self.requeue(id, order, new_price, new_size))
+    }
+
+    /// Match an order taken out of the book at its new price, then queue what is left at the back
+    /// of its level
+    fn requeue(&mut self, id: OrderId, mut order: Order, price: f64, size: f64) -> Vec<Trade> {
+        order.size = to_lots(size);
+        order.reserve = 0;
+        let mut trades = self.match_order(&mut order, Some(Price::new(price)));
+        if !order.is_filled() {
+            self.rest(id, order, price);
+            self.cross_dark_pool();
+        }
+        self.trigger_stops(&mut trades);
+        self.book_changed();
+        trades
+    }
+}
+
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct TradingPair {
+    base: String,
+    quote: String,
+}
+
+impl TradingPair {
+    pub fn new(base: String, quote: String) -> Self {
+        TradingPair { base, quote }
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    pub fn quote(&self) -> &str {
+        &self.quote
+    }
+}
+
+impl From<(String, String)> for TradingPair {
+    fn from(pair: (String, String)) -> Self {
+        TradingPair {
+            base: pair.0,
+            quote: pair.1,
+        }
+    }
+}
+
+impl FromStr for TradingPair {
+    type Err = String;
+
+    /// Parse a pair such as `BTC/USD`, `btc-usd` or `eth_btc`
+    ///
+    /// The base and quote may be separated by `/`, `-`, `_` or `:` and are upper-cased.
+    fn from_str(pair: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid trading pair {:?}", pair);
+        let mut parts = pair.trim().split(['/', '-', '_', ':']);
+        let (base, quote) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(base), Some(quote), None) => (base, quote),
+            _ => return Err(invalid()),
+        };
+        let is_symbol =
+            |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_alphanumeric());
+        if !is_symbol(base) || !is_symbol(quote) {
+            return Err(invalid());
+        }
+        Ok(TradingPair::new(
+            base.to_ascii_uppercase(),
+            quote.to_ascii_uppercase(),
+        ))
+    }
+}
+
+impl From<TradingPair> for String {
+    fn from(pair: TradingPair) -> Self {
+        format!("{}/{}", pair.base, pair.quote)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::matching::clock::ManualClock;
+    use crate::matching::peg::PegReference;
+    use crate::matching::trade::Liquidity;
+
+    fn crossed_book() -> OrderBook {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Ask, 5.0), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 5.0), 101.0);
+        orderbook.add(Order::new(OrderType::Ask, 5.0), 103.0);
+        orderbook
+    }
+
+    #[test]
+    fn imbalance_and_microprice_lean_towards_the_heavier_side() {
+        let mut orderbook = OrderBook::new();
+        assert_eq!(orderbook.imbalance(5), None);
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 99.0);
+        assert_eq!(orderbook.imbalance(5), Some(1.0));
+        assert_eq!(orderbook.microprice(), None);
+
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 101.0);
+        orderbook.add(Order::new(OrderType::Ask, 2.0), 102.0);
+        assert_eq!(orderbook.microprice(), Some(100.0));
+        assert_eq!(orderbook.imbalance(1), Some(0.0));
+        assert_eq!(orderbook.imbalance(2), Some(-0.5));
+
+        orderbook.add(Order::new(OrderType::Bid, 3.0), 99.0);
+        assert_eq!(orderbook.microprice(), Some(100.6));
+    }
+
+    #[test]
+    fn cost_to_fill_walks_the_opposite_side() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 99.0);
+        orderbook
+            .place_limit_order(Order::new(OrderType::Bid, 4.0).with_display_size(1.0), 98.0)
+            .unwrap();
+
+        let cost = orderbook.cost_to_fill(OrderType::Ask, 3.0);
+        assert_eq!(cost.filled, 3.0);
+        assert_eq!(cost.average_price, Some((99.0 + 2.0 * 98.0) / 3.0));
+        assert_eq!(cost.worst_price, Some(98.0));
+        assert!(cost.sufficient);
+
+        let short = orderbook.cost_to_fill(OrderType::Ask, 6.0);
+        assert_eq!(short.filled, 5.0);
+        assert!(!short.sufficient);
+        assert_eq!(orderbook.best_bid(), Some(99.0));
+
+        let empty = orderbook.cost_to_fill(OrderType::Bid, 1.0);
+        assert_eq!(empty.average_price, None);
+        assert_eq!(empty.worst_price, None);
+        assert!(!empty.sufficient);
+    }
+
+    #[test]
+    fn trading_pairs_parse_with_any_separator() {
+        let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+        for text in ["BTC/USD", "btc-usd", " Btc_Usd ", "btc:usd"] {
+            assert_eq!(text.parse::<TradingPair>(), Ok(btc_usd.clone()));
+        }
+        for text in ["BTCUSD", "BTC/", "/USD", "BTC/USD/EUR", "BT C/USD", ""] {
+            assert!(text.parse::<TradingPair>().is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn amend_keeps_priority_only_for_size_decreases() {
+        let mut orderbook = OrderBook::new();
+        let first = orderbook.add(Order::new(OrderType::Bid, 2.0), 99.0);
+        let second = orderbook.add(Order::new(OrderType::Bid, 2.0), 99.0);
+
+        orderbook.amend(first, 99.0, 1.0).unwrap();
+        assert_eq!(orderbook.queue_position(first).unwrap().orders_ahead, 0);
+        assert_eq!(orderbook.order(first).unwrap().size(), 1.0);
+
+        orderbook.amend(first, 99.0, 3.0).unwrap();
+        assert_eq!(orderbook.queue_position(first).unwrap().orders_ahead, 1);
+        assert_eq!(orderbook.queue_position(second).unwrap().orders_ahead, 0);
+
+        orderbook.amend(second, 98.0, 2.0).unwrap();
+        assert_eq!(orderbook.queue_position(first).unwrap().orders_ahead, 0);
+        let depth = orderbook.depth(5);
+        assert_eq!(f64::from(depth.bids[1].price), 98.0);
+        assert_eq!(depth.bids[1].volume, 2.0);
+
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        let trades = orderbook.amend(first, 100.0, 3.0).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].taker_order_id, Some(first));
+        assert_eq!(orderbook.order(first).unwrap().size(), 2.0);
+        assert_eq!(f64::from(orderbook.depth(1).bids[0].price), 100.0);
+
+        assert!(orderbook.amend(first, 99.0, 0.0).is_err());
+        assert!(orderbook.amend(42, 99.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn tags_are_echoed_on_trades() {
+        let mut orderbook = OrderBook::new();
+        let maker = Order::new(OrderType::Ask, 1.0).with_tag("grid-7".to_string());
+        orderbook.place_limit_order(maker, 100.0).unwrap();
+        let taker = Order::new(OrderType::Bid, 1.0).with_tag("arb".to_string());
+        let placement = orderbook.place_limit_order(taker, 100.0).unwrap();
+        assert_eq!(placement.trades[0].maker_tag.as_deref(), Some("grid-7"));
+        assert_eq!(placement.trades[0].taker_tag.as_deref(), Some("arb"));
+
+        let oversized = Order::new(OrderType::Bid, 1.0).with_tag("x".repeat(MAX_TAG_LEN + 1));
+        assert!(orderbook.place_limit_order(oversized, 99.0).is_err());
+    }
+
+    #[test]
+    fn time_priority_survives_cancels_and_amends() {
+        let mut orderbook = OrderBook::new();
+        let ids: Vec<OrderId> = (0..4)
+            .map(|_| orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0))
+            .collect();
+        let sequences: Vec<u64> = ids
+            .iter()
+            .map(|id| orderbook.order(*id).unwrap().sequence())
+            .collect();
+        assert!(sequences.windows(2).all(|pair| pair[0] < pair[1]));
+
+        orderbook.cancel(ids[1]);
+        // Decreasing keeps priority, increasing loses it
+        orderbook.amend(ids[2], 100.0, 0.5).unwrap();
+        orderbook.amend(ids[0], 100.0, 2.0).unwrap();
+        let late = orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+
+        let mut buy = Order::new(OrderType::Bid, 10.0);
+        let makers: Vec<Option<OrderId>> = orderbook
+            .place_market_order(&mut buy)
+            .iter()
+            .map(|trade| trade.maker_order_id)
+            .collect();
+        assert_eq!(
+            makers,
+            vec![Some(ids[2]), Some(ids[3]), Some(ids[0]), Some(late)]
+        );
+    }
+
+    #[test]
+    fn cancel_by_id() {
+        let mut orderbook = OrderBook::new();
+        let first = orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        let second = orderbook.add(Order::new(OrderType::Ask, 2.0), 100.0);
+        let other = orderbook.add(Order::new(OrderType::Ask, 3.0), 101.0);
+
+        assert!(orderbook.cancel(first).is_some());
+        assert!(orderbook.cancel(first).is_none());
+        assert_eq!(orderbook.queue_position(second).unwrap().orders_ahead, 0);
+
+        let mut buy = Order::new(OrderType::Bid, 2.5);
+        let trades = orderbook.place_market_order(&mut buy);
+        assert_eq!(trades[0].maker_order_id, Some(second));
+        assert!(orderbook.cancel(second).is_none());
+        assert_eq!(orderbook.order(other).unwrap().size(), 2.5);
+        assert!(orderbook.cancel(other).is_some());
+        assert_eq!(orderbook.depth(1).asks, Vec::new());
+        assert!(orderbook.index.is_empty());
+    }
+
+    #[test]
+    fn cancels_leave_the_rest_of_the_queue_in_priority() {
+        let mut orderbook = OrderBook::new();
+        let ids: Vec<OrderId> = (0..7)
+            .map(|_| orderbook.add(Order::new(OrderType::Bid, 1.0), 100.0))
+            .collect();
+        for id in [ids[1], ids[3], ids[4], ids[5]] {
+            assert!(orderbook.cancel(id).is_some());
+        }
+        // Over half the slots are vacant, so the queue has been compacted
+        let limit = &orderbook.bids[&Price::new(100.0)];
+        assert_eq!(limit.orders.slots.len(), 3);
+        assert_eq!(orderbook.queue_position(ids[6]).unwrap().orders_ahead, 2);
+
+        let trades = orderbook.place_market_order(&mut Order::new(OrderType::Ask, 2.0));
+        let makers: Vec<Option<OrderId>> = trades.iter().map(|t| t.maker_order_id).collect();
+        assert_eq!(makers, vec![Some(ids[0]), Some(ids[2])]);
+        assert_eq!(orderbook.queue_position(ids[6]).unwrap().orders_ahead, 0);
+    }
+
+    #[test]
+    fn ioc_remainder_is_cancelled() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 102.0);
+
+        let ioc = Order::new(OrderType::Bid, 3.0).with_time_in_force(TimeInForce::Ioc);
+        let placement = orderbook.place_limit_order(ioc, 101.0).unwrap();
+        assert_eq!(placement.trades.len(), 1);
+        assert_eq!(
+            orderbook.drain_events(),
+            vec![OrderEvent::Cancelled {
+                order_id: placement.id,
+                account: None,
+                remaining: 2.0,
+                reason: CancelReason::Unfilled,
+            }]
+        );
+        assert!(orderbook.order(placement.id).is_none());
+        assert_eq!(orderbook.open_orders(), 1);
+        assert_eq!(orderbook.best_bid(), None);
+    }
+
+    #[test]
+    fn fok_fills_in_full_or_not_at_all() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 102.0);
+
+        let fok = || Order::new(OrderType::Bid, 2.0).with_time_in_force(TimeInForce::Fok);
+        let killed = orderbook.place_limit_order(fok(), 101.0).unwrap();
+        assert!(killed.trades.is_empty());
+        assert!(matches!(
+            orderbook.drain_events()[..],
+            [OrderEvent::Cancelled {
+                remaining: 2.0,
+                reason: CancelReason::Unfilled,
+                ..
+            }]
+        ));
+        assert_eq!(orderbook.open_orders(), 2);
+
+        let filled = orderbook.place_limit_order(fok(), 102.0).unwrap();
+        assert_eq!(filled.trades.len(), 2);
+        assert!(orderbook.drain_events().is_empty());
+        assert_eq!(orderbook.open_orders(), 0);
+    }
+
+    #[test]
+    fn stops_trigger_after_trades() {
+        let mut orderbook = OrderBook::new();
+        orderbook.set_halt_policy(HaltPolicy::CancelConditional);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 101.0);
+        orderbook.add(Order::new(OrderType::Ask, 5.0), 110.0);
+
+        let stop_market = orderbook
+            .place_stop_order(Order::new(OrderType::Bid, 1.0), 100.0, StopKind::Market)
+            .unwrap();
+        let protected = orderbook
+            .place_stop_order(
+                Order::new(OrderType::Bid, 2.0),
+                101.0,
+                StopKind::Protected(2.0),
+            )
+            .unwrap();
+        let cancelled = orderbook
+            .place_stop_order(Order::new(OrderType::Ask, 1.0), 90.0, StopKind::Market)
+            .unwrap();
+        assert!(orderbook.stop_order(protected).is_some());
+
+        // The first fill at 100 triggers the stop-market order, whose fill at 101 triggers the
+        // protected stop; that one may not pay more than 103 and rests
+        let trades = orderbook.place_market_order(&mut Order::new(OrderType::Bid, 1.0));
+        assert_eq!(trades.len(), 2);
+        assert!(trades[0].triggered_stops);
+        assert!(trades[1].triggered_stops);
+        assert_eq!(trades[1].taker_order_id, Some(stop_market));
+        assert_eq!(f64::from(trades[1].price), 101.0);
+        assert_eq!(orderbook.order(protected).unwrap().size(), 2.0);
+        assert_eq!(orderbook.best_bid(), Some(103.0));
+        assert!(orderbook.stop_order(protected).is_none());
+
+        assert!(orderbook
+            .place_stop_order(Order::new(OrderType::Bid, 1.0), 100.0, StopKind::Market)
+            .is_err());
+        let events = orderbook.halt();
+        assert!(matches!(
+            events[..],
+            [OrderEvent::Cancelled {
+                order_id,
+                reason: CancelReason::Halt,
+                ..
+            }] if order_id == cancelled
+        ));
+        assert_eq!(orderbook.open_orders(), 2);
+    }
+
+    #[test]
+    fn replace_gives_up_priority_and_bumps_the_version() {
+        let mut orderbook = OrderBook::new();
+        let first = orderbook.add(Order::new(OrderType::Bid, 2.0), 99.0);
+        let second = orderbook.add(Order::new(OrderType::Bid, 1.0), 99.0);
+
+        assert!(orderbook.replace(first, 99.0, 1.0).unwrap().is_empty());
+        assert_eq!(orderbook.queue_position(second).unwrap().orders_ahead, 0);
+        assert_eq!(orderbook.queue_position(first).unwrap().orders_ahead, 1);
+        assert_eq!(orderbook.order(first).unwrap().version(), 2);
+        assert_eq!(
+            orderbook.drain_events(),
+            vec![OrderEvent::Replaced {
+                order_id: first,
+                account: None,
+                version: 2,
+                price: 99.0,
+                size: 1.0,
+            }]
+        );
+        assert!(orderbook.replace(first, 99.0, 0.0).is_err());
+        assert!(orderbook.replace(42, 99.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn every_amend_bumps_the_version() {
+        let mut orderbook = OrderBook::new();
+        let bid = orderbook.add(Order::new(OrderType::Bid, 3.0), 99.0);
+        let ask = orderbook.add(Order::new(OrderType::Ask, 1.0), 101.0);
+
+        orderbook.amend(bid, 99.0, 2.0).unwrap();
+        orderbook.amend(bid, 100.0, 2.0).unwrap();
+        assert_eq!(orderbook.order(bid).unwrap().version(), 3);
+        let versions: Vec<u32> = orderbook
+            .drain_events()
+            .iter()
+            .map(|event| match event {
+                OrderEvent::Amended { version, .. } | OrderEvent::Replaced { version, .. } => {
+                    *version
+                }
+                _ => 0,
+            })
+            .collect();
+        assert_eq!(versions, vec![2, 3]);
+
+        // Fills and snapshots carry the version the order had when it matched or was captured
+        let trades = orderbook.place_market_order(&mut Order::new(OrderType::Ask, 0.5));
+        assert_eq!(trades[0].maker_version, 3);
+        assert_eq!(trades[0].taker_version, 1);
+        let chunk = orderbook.snapshot_chunk(None, 10);
+        assert!(chunk
+            .orders
+            .iter()
+            .any(|order| order.id == bid && order.version == 3));
+        let mut restored = OrderBook::new();
+        restored.restore(&chunk.orders).unwrap();
+        assert_eq!(restored.order(bid).unwrap().version(), 3);
+        assert_eq!(restored.order(ask).unwrap().version(), 1);
+    }
+
+    #[test]
+    fn pegged_orders_reprice_when_the_touch_moves() {
+        let mut orderbook = OrderBook::new();
+        let bid = orderbook.add(Order::new(OrderType::Bid, 1.0), 99.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 102.0);
+        let primary = orderbook
+            .place_pegged_order(
+                Order::new(OrderType::Bid, 2.0),
+                Peg::new(PegReference::Primary, 0.5),
+            )
+            .unwrap()
+            .id;
+        let market = orderbook
+            .place_pegged_order(
+                Order::new(OrderType::Ask, 1.0),
+                Peg::new(PegReference::Market, 1.0),
+            )
+            .unwrap()
+            .id;
+        assert_eq!(orderbook.best_bid(), Some(99.5));
+        assert_eq!(orderbook.best_ask(), Some(100.0));
+
+        // The pegs follow the unpegged best bid up, moving aside for each other rather than crossing
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 99.5);
+        assert_eq!(
+            orderbook.limit_of(primary).unwrap().price,
+            Price::new(100.0)
+        );
+        assert_eq!(orderbook.limit_of(market).unwrap().price, Price::new(100.5));
+        let top = orderbook.add(Order::new(OrderType::Bid, 1.0), 100.0);
+        assert_eq!(
+            orderbook.limit_of(primary).unwrap().price,
+            Price::new(100.5)
+        );
+        assert_eq!(orderbook.limit_of(market).unwrap().price, Price::new(101.0));
+        assert_eq!(orderbook.queue_position(primary).unwrap().orders_ahead, 0);
+
+        // And back down when the touch leaves
+        orderbook.cancel(top);
+        assert_eq!(
+            orderbook.limit_of(primary).unwrap().price,
+            Price::new(100.0)
+        );
+        assert_eq!(orderbook.limit_of(market).unwrap().price, Price::new(100.5));
+        let mut sell = Order::new(OrderType::Ask, 1.0);
+        assert_eq!(
+            orderbook.place_market_order(&mut sell)[0].maker_order_id,
+            Some(primary)
+        );
+        assert!(orderbook.cancel(bid).is_some());
+
+        // Repricing a pegged order unpegs it; a halt cancels the conditional orders
+        orderbook.replace(market, 105.0, 1.0).unwrap();
+        assert!(orderbook.peg(market).is_none());
+        orderbook.set_halt_policy(HaltPolicy::CancelConditional);
+        let events = orderbook.halt();
+        assert_eq!(events.len(), 1);
+        assert!(orderbook.order(primary).is_none());
+        assert!(orderbook.order(market).is_some());
+    }
+
+    #[test]
+    fn pegged_prices_round_toward_the_passive_side() {
+        let mut orderbook = OrderBook::new();
+        orderbook.set_tick_size(Price::new(0.5));
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 102.0);
+        let bid = orderbook
+            .place_pegged_order(
+                Order::new(OrderType::Bid, 1.0),
+                Peg::new(PegReference::Primary, 0.3),
+            )
+            .unwrap()
+            .id;
+        let ask = orderbook
+            .place_pegged_order(
+                Order::new(OrderType::Ask, 1.0),
+                Peg::new(PegReference::Primary, -0.3),
+            )
+            .unwrap()
+            .id;
+        assert_eq!(orderbook.order_price(bid), Some(100.0));
+        assert_eq!(orderbook.order_price(ask), Some(102.0));
+
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 100.5);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 101.5);
+        assert_eq!(orderbook.order_price(bid), Some(100.5));
+        assert_eq!(orderbook.order_price(ask), Some(101.5));
+    }
+
+    #[test]
+    fn prices_are_exact_tick_counts() {
+        assert_eq!(Price::new(0.1 + 0.2), Price::new(0.3));
+        assert_eq!(Price::new(0.29).ticks(), 29_000);
+        assert!(Price::new(-1.5) < Price::new(-1.0));
+        assert_eq!(f64::from(Price::new(-1.5)), -1.5);
+
+        assert_eq!("101.25".parse::<Price>(), Ok(Price::from_ticks(10_125_000)));
+        assert_eq!("-0.5".parse::<Price>(), Ok(Price::new(-0.5)));
+        assert!("1.000001".parse::<Price>().is_err());
+        assert!("1e3".parse::<Price>().is_err());
+        assert!("-".parse::<Price>().is_err());
+        assert_eq!(Price::new(101.25).to_string(), "101.25");
+        assert_eq!(Price::new(-3.0).to_string(), "-3");
+        assert_eq!(Price::new(-0.00001).to_string(), "-0.00001");
+
+        let tick = Price::new(0.01);
+        assert!(Price::new(99.01).is_multiple_of(tick));
+        assert!(!Price::new(99.005).is_multiple_of(tick));
+        assert_eq!(Price::new(99.006).round_to(tick), Ok(Price::new(99.01)));
+        assert_eq!(Price::new(99.005).round_to(tick), Ok(Price::new(99.01)));
+        assert_eq!(Price::new(-99.005).round_to(tick), Ok(Price::new(-99.01)));
+        assert_eq!(Price::new(99.009).floor_to(tick), Ok(Price::new(99.0)));
+        assert_eq!(Price::new(-99.001).floor_to(tick), Ok(Price::new(-99.01)));
+        assert_eq!(Price::new(99.001).ceil_to(tick), Ok(Price::new(99.01)));
+        assert_eq!(Price::new(99.0).ceil_to(tick), Ok(Price::new(99.0)));
+        assert!(Price::new(99.0).round_to(Price::default()).is_err());
+        assert_eq!(
+            Price::new(200.0).offset_percent(-5.0),
+            Ok(Price::new(190.0))
+        );
+        assert_eq!(
+            Price::new(99.99).offset_percent(0.01),
+            Ok(Price::new(100.0))
+        );
+        assert!(Price::new(1.0).offset_percent(f64::NAN).is_err());
+        assert!(Price::from_ticks(i64::MAX).offset_percent(10.0).is_err());
+        assert_eq!(
+            Price::new(1.5).checked_add(Price::new(2.25)),
+            Ok(Price::new(3.75))
+        );
+        assert_eq!(
+            Price::new(1.5).checked_sub(Price::new(2.25)),
+            Ok(Price::new(-0.75))
+        );
+        assert_eq!(tick.checked_mul(7), Ok(Price::new(0.07)));
+        assert!(Price::from_ticks(i64::MAX).checked_add(tick).is_err());
+        assert!(Price::from_ticks(i64::MIN).checked_sub(tick).is_err());
+        assert!(Price::from_ticks(i64::MAX).checked_mul(2).is_err());
+    }
+
+    #[test]
+    fn icebergs_show_one_slice_at_a_time() {
+        let mut orderbook = OrderBook::new();
+        let iceberg = orderbook
+            .place_limit_order(
+                Order::new(OrderType::Ask, 5.0).with_display_size(2.0),
+                100.0,
+            )
+            .unwrap()
+            .id;
+        let behind = orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        assert_eq!(orderbook.depth(1).asks[0].volume, 3.0);
+        assert_eq!(orderbook.order(iceberg).unwrap().reserve(), 3.0);
+        assert_eq!(orderbook.fillable_volume(OrderType::Bid, None), 6.0);
+
+        // The first slice fills, the next one queues behind the order that was waiting
+        let trades = orderbook.place_market_order(&mut Order::new(OrderType::Bid, 3.0));
+        let makers: Vec<_> = trades.iter().map(|trade| trade.maker_order_id).collect();
+        assert_eq!(makers, vec![Some(iceberg), Some(behind)]);
+        assert_eq!(orderbook.queue_position(iceberg).unwrap().orders_ahead, 0);
+        assert_eq!(orderbook.order(iceberg).unwrap().size(), 2.0);
+        assert_eq!(orderbook.depth(1).asks[0].volume, 2.0);
+
+        assert_eq!(
+            orderbook.cancel(iceberg),
+            Some(OrderEvent::Cancelled {
+                order_id: iceberg,
+                account: None,
+                remaining: 3.0,
+                reason: CancelReason::Requested,
+            })
+        );
+        assert!(orderbook
+            .place_limit_order(
+                Order::new(OrderType::Ask, 1.0).with_display_size(0.0),
+                100.0
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn limit_order_single_fill() {
+        let mut limit = Limit::new(1000.00);
+        let buy_limit_order = Order::new(OrderType::Bid, 100.0);
+
+        limit.add(buy_limit_order);
+
+        let mut market_sell_order = Order::new(OrderType::Ask, 99.0);
+        limit.fill(&mut market_sell_order, &mut 1);
+        println!("{:?}", limit);
+        assert!(market_sell_order.is_filled());
+        assert_eq!(limit.orders.iter().next().unwrap().size(), 1.0);
+    }
+
+    #[test]
+    fn limit_order_multi_fill() {
+        let mut limit = Limit::new(1000.00);
+        let buy_limit_order_a = Order::new(OrderType::Bid, 50.0);
+        let buy_limit_order_b = Order::new(OrderType::Bid, 50.0);
+
+        limit.add(buy_limit_order_a);
+        limit.add(buy_limit_order_b);
+
+        let mut market_sell_order = Order::new(OrderType::Ask, 99.0);
+        limit.fill(&mut market_sell_order, &mut 1);
+        println!("{:?}", limit);
+        assert!(market_sell_order.is_filled());
+        // The filled order leaves the level, the partly filled one keeps its place
+        assert_eq!(limit.orders.iter().count(), 1);
+        assert_eq!(limit.orders.iter().next().unwrap().size(), 1.0);
+    }
+
+    #[test]
+    fn limit_total_volume() {
+        let mut limit = Limit::new(1000.00);
+
+        let buy_limit_order_a = Order::new(OrderType::Bid, 50.0);
+        let buy_limit_order_b = Order::new(OrderType::Bid, 50.0);
+
+        limit.add(buy_limit_order_a);
+        limit.add(buy_limit_order_b);
+
+        assert_eq!(from_lots(limit.volume()), 100.0);
+
+        let mut market_sell_order = Order::new(OrderType::Ask, 99.0);
+
+        limit.fill(&mut market_sell_order, &mut 1);
+
+        assert_eq!(from_lots(limit.volume()), 1.0);
+    }
+
+    #[test]
+    fn limit_volume_is_exact_for_decimal_sizes() {
+        let mut limit = Limit::new(1000.00);
+        for _ in 0..10 {
+            limit.add(Order::new(OrderType::Bid, 0.1));
+        }
+
+        assert_eq!(from_lots(limit.volume()), 1.0);
+    }
+
+    #[test]
+    fn orderbook_fill_market_order() {
         let mut orderbook = OrderBook::new();
         orderbook.add(Order::new(OrderType::Ask, 10.0), 100.0);
         orderbook.add(Order::new(OrderType::Ask, 5.0), 200.0);
@@ -337,16 +3368,529 @@ pub mod tests {
         orderbook.place_market_order(&mut market);
 
         let ask_limits = orderbook.ask_limits();
-        let matched_limits = ask_limits.get(0).unwrap();
+        let matched_limits = ask_limits.first().unwrap();
         assert_eq!(matched_limits.price, Price::from(100.0));
         assert!(market.is_filled());
 
-        let matched_order = matched_limits.orders.get(0);
-        match matched_order {
-            Some(mo) => {
-                assert!(mo.is_filled())
-            }
-            None => eprintln!("Order No Longer Exists"),
+        // The filled order left the level, leaving the one queued behind it
+        let remaining: Vec<f64> = matched_limits.orders.iter().map(Order::size).collect();
+        assert_eq!(remaining, vec![10.0]);
+    }
+
+    #[test]
+    fn orderbook_market_order_reports_trades() {
+        let mut orderbook = OrderBook::new();
+        let maker = orderbook.add(Order::new(OrderType::Ask, 10.0).with_account(1), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 5.0).with_account(2), 200.0);
+
+        let mut market = Order::new(OrderType::Bid, 12.0).with_account(3);
+        let trades = orderbook.place_market_order(&mut market);
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].price, Price::from(100.0));
+        assert_eq!(trades[0].size, 10.0);
+        assert_eq!(trades[0].maker_account, Some(1));
+        assert_eq!(trades[0].aggressor_side, OrderType::Bid);
+        assert_eq!(
+            trades[0].liquidity(market.id().unwrap()),
+            Some(Liquidity::Taker)
+        );
+        assert_eq!(trades[0].liquidity(maker), Some(Liquidity::Maker));
+        assert_eq!(trades[1].price, Price::from(200.0));
+        assert_eq!(trades[1].size, 2.0);
+        assert_eq!(trades[1].taker_account, Some(3));
+        assert_eq!(orderbook.ask_limits().len(), 1);
+    }
+
+    #[test]
+    fn mm_protection_pulls_quotes_on_breach() {
+        let clock = ManualClock::new(0);
+        let mut orderbook = OrderBook::with_clock(Arc::new(clock.clone()));
+        orderbook.set_mm_protection(
+            1,
+            MmProtectionConfig {
+                interval: 1_000_000,
+                max_fills: 10,
+                max_delta: 15.0,
+            },
+        );
+        orderbook.add(Order::new(OrderType::Bid, 10.0).with_account(1), 99.0);
+        orderbook.add(Order::new(OrderType::Bid, 10.0).with_account(1), 98.0);
+        orderbook.add(Order::new(OrderType::Bid, 10.0).with_account(2), 97.0);
+        orderbook.add(Order::new(OrderType::Ask, 10.0).with_account(1), 101.0);
+
+        let mut sell = Order::new(OrderType::Ask, 10.0);
+        orderbook.place_market_order(&mut sell);
+        assert_eq!(orderbook.bid_limits().len(), 2);
+
+        clock.advance(10);
+        let mut sell = Order::new(OrderType::Ask, 6.0);
+        let trades = orderbook.place_market_order(&mut sell);
+        assert!(trades.iter().all(|trade| trade.transaction_time == 10));
+
+        // Account 1 is long 16, so everything it still had resting is gone
+        let bids = orderbook.bid_limits();
+        assert_eq!(bids.len(), 1);
+        assert_eq!(bids[0].price, Price::from(97.0));
+        assert!(orderbook.ask_limits().is_empty());
+    }
+
+    #[test]
+    fn crossing_limit_executes_at_resting_prices() {
+        let mut orderbook = crossed_book();
+        let mut bid = Order::new(OrderType::Bid, 12.0);
+        let trades = orderbook.match_order(&mut bid, Some(Price::new(102.0)));
+
+        let prices = trades
+            .iter()
+            .map(|trade| trade.price)
+            .collect::<Vec<Price>>();
+        assert_eq!(prices, vec![Price::new(100.0), Price::new(101.0)]);
+        assert_eq!(bid.size(), 2.0);
+        assert_eq!(orderbook.ask_limits().len(), 1);
+    }
+
+    #[test]
+    fn crossing_limit_executes_at_midpoint() {
+        let mut orderbook = crossed_book();
+        orderbook.set_execution_price(ExecutionPrice::Midpoint);
+        let mut bid = Order::new(OrderType::Bid, 12.0);
+        let trades = orderbook.match_order(&mut bid, Some(Price::new(102.0)));
+
+        let prices = trades
+            .iter()
+            .map(|trade| trade.price)
+            .collect::<Vec<Price>>();
+        assert_eq!(prices, vec![Price::new(101.0), Price::new(101.5)]);
+    }
+
+    #[test]
+    fn crossing_limit_sell_side() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Bid, 5.0), 100.0);
+        orderbook.add(Order::new(OrderType::Bid, 5.0), 98.0);
+        orderbook.set_execution_price(ExecutionPrice::Midpoint);
+
+        let mut ask = Order::new(OrderType::Ask, 10.0);
+        let trades = orderbook.match_order(&mut ask, Some(Price::new(99.0)));
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, Price::new(99.5));
+        assert_eq!(ask.size(), 5.0);
+    }
+
+    #[test]
+    fn odd_tick_spreads_round_toward_the_resting_price() {
+        let mut orderbook = OrderBook::new();
+        orderbook.set_execution_price(ExecutionPrice::Midpoint);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        let mut bid = Order::new(OrderType::Bid, 1.0);
+        let limit = Price::from_ticks(100 * PRICE_SCALE + 3);
+        let trades = orderbook.match_order(&mut bid, Some(limit));
+        assert_eq!(trades[0].price, Price::from_ticks(100 * PRICE_SCALE + 1));
+
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 100.00003);
+        let mut ask = Order::new(OrderType::Ask, 1.0);
+        let trades = orderbook.match_order(&mut ask, Some(Price::new(100.0)));
+        assert_eq!(trades[0].price, Price::from_ticks(100 * PRICE_SCALE + 2));
+
+        let extreme = ExecutionPrice::Midpoint.select(
+            Price::from_ticks(i64::MIN),
+            Some(Price::from_ticks(i64::MAX)),
+            Price::from_ticks(1),
+        );
+        assert_eq!(extreme, Price::from_ticks(-1));
+    }
+
+    #[test]
+    fn midpoints_round_onto_the_market_tick() {
+        let mut orderbook = OrderBook::new();
+        orderbook.set_tick_size(Price::new(1.0));
+        orderbook.set_execution_price(ExecutionPrice::Midpoint);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        let mut bid = Order::new(OrderType::Bid, 1.0);
+        let trades = orderbook.match_order(&mut bid, Some(Price::new(101.0)));
+        assert_eq!(trades[0].price, Price::new(100.0));
+
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        let mut bid = Order::new(OrderType::Bid, 1.0);
+        let trades = orderbook.match_order(&mut bid, Some(Price::new(103.0)));
+        assert_eq!(trades[0].price, Price::new(101.0));
+
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 100.0);
+        let mut ask = Order::new(OrderType::Ask, 1.0);
+        let trades = orderbook.match_order(&mut ask, Some(Price::new(97.0)));
+        assert_eq!(trades[0].price, Price::new(99.0));
+
+        let tick = Price::new(1.0);
+        assert_eq!(
+            Price::new(100.0).midpoint_toward(Price::new(99.0), tick),
+            Ok(Price::new(100.0))
+        );
+        assert!(Price::new(100.0)
+            .midpoint_toward(Price::new(99.0), Price::from_ticks(0))
+            .is_err());
+    }
+
+    #[test]
+    fn market_orders_ignore_midpoint_rule() {
+        let mut orderbook = crossed_book();
+        orderbook.set_execution_price(ExecutionPrice::Midpoint);
+        let mut bid = Order::new(OrderType::Bid, 6.0);
+        let trades = orderbook.place_market_order(&mut bid);
+
+        let prices = trades
+            .iter()
+            .map(|trade| trade.price)
+            .collect::<Vec<Price>>();
+        assert_eq!(prices, vec![Price::new(100.0), Price::new(101.0)]);
+    }
+
+    #[test]
+    fn dark_orders_cross_at_lit_midpoint() {
+        let mut orderbook = OrderBook::new();
+        orderbook.enable_dark_pool(10.0);
+
+        let small = orderbook.place_dark_order(Order::new(OrderType::Bid, 5.0));
+        assert!(small.is_err());
+
+        let resting = orderbook.place_dark_order(Order::new(OrderType::Bid, 20.0).with_account(1));
+        assert_eq!(resting, Ok(vec![]));
+        // One-sided dark liquidity never crosses, and nothing is visible in the lit book
+        let trades = orderbook.place_dark_order(Order::new(OrderType::Bid, 10.0));
+        assert_eq!(trades, Ok(vec![]));
+        assert!(orderbook.bid_limits().is_empty());
+
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 99.0);
+        let trades = orderbook
+            .place_dark_order(Order::new(OrderType::Ask, 25.0).with_account(2))
+            .unwrap();
+        assert!(trades.is_empty(), "no midprice without a lit ask");
+
+        // The lit ask creates a midprice, crossing the resting dark orders
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 101.0);
+        let pool = orderbook.dark_pool().unwrap();
+        assert_eq!(pool.volume(OrderType::Bid), 5.0);
+        assert_eq!(pool.volume(OrderType::Ask), 0.0);
+        assert_eq!(orderbook.stats().last_price(), Some(100.0));
+
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 100.0);
+        orderbook
+            .place_dark_order(Order::new(OrderType::Ask, 10.0).with_account(2))
+            .unwrap();
+        assert_eq!(orderbook.stats().last_price(), Some(100.5));
+    }
+
+    #[test]
+    fn dark_trades_are_flagged() {
+        let mut orderbook = OrderBook::new();
+        orderbook.enable_dark_pool(1.0);
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 99.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+
+        orderbook
+            .place_dark_order(Order::new(OrderType::Ask, 3.0).with_account(2))
+            .unwrap();
+        let trades = orderbook
+            .place_dark_order(Order::new(OrderType::Bid, 2.0).with_account(1))
+            .unwrap();
+
+        assert_eq!(trades.len(), 1);
+        assert!(trades[0].dark);
+        assert_eq!(trades[0].price, Price::new(99.5));
+        assert_eq!(trades[0].size, 2.0);
+        assert_eq!(trades[0].aggressor_side, OrderType::Bid);
+        assert_eq!(trades[0].maker_account, Some(2));
+        assert_eq!(orderbook.ask_limits()[0].volume(), LOT_SCALE);
+    }
+
+    #[test]
+    fn good_till_time_orders_expire_on_time() {
+        let clock = ManualClock::new(0);
+        let mut orderbook = OrderBook::with_clock(Arc::new(clock.clone()));
+        let early = orderbook.add(
+            Order::new(OrderType::Bid, 5.0)
+                .with_account(1)
+                .with_expiry(1_500),
+            99.0,
+        );
+        let late = orderbook.add(Order::new(OrderType::Bid, 5.0).with_expiry(2_000_000), 99.0);
+        let filled = orderbook.add(Order::new(OrderType::Ask, 5.0).with_expiry(1_000), 101.0);
+        orderbook.add(Order::new(OrderType::Ask, 5.0), 102.0);
+
+        let mut buy = Order::new(OrderType::Bid, 5.0);
+        orderbook.place_market_order(&mut buy);
+
+        clock.set(1_499);
+        assert!(orderbook.expire_orders().is_empty());
+
+        clock.set(1_500);
+        assert_eq!(
+            orderbook.expire_orders(),
+            vec![OrderEvent::Expired {
+                order_id: early,
+                account: Some(1),
+                remaining: 5.0,
+                at: 1_500,
+            }]
+        );
+        assert_eq!(orderbook.bid_limits()[0].volume(), 5 * LOT_SCALE);
+
+        clock.set(5_000_000);
+        let events = orderbook.expire_orders();
+        assert_eq!(
+            events.len(),
+            1,
+            "order {} was filled before expiring",
+            filled
+        );
+        assert!(matches!(events[0], OrderEvent::Expired { order_id, .. } if order_id == late));
+        assert!(orderbook.bid_limits().is_empty());
+    }
+
+    #[test]
+    fn depth_reports_order_counts_and_deltas() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 99.0);
+        orderbook.add(Order::new(OrderType::Bid, 2.0), 99.0);
+        orderbook.add(Order::new(OrderType::Bid, 4.0), 98.0);
+        orderbook.add(Order::new(OrderType::Ask, 3.0), 101.0);
+        orderbook.add(Order::new(OrderType::Ask, 3.0), 102.0);
+
+        let before = orderbook.depth(1);
+        assert_eq!(
+            before.bids,
+            vec![DepthLevel {
+                price: Price::new(99.0),
+                volume: 3.0,
+                order_count: 2,
+            }]
+        );
+        assert_eq!(before.asks.len(), 1);
+
+        let mut sell = Order::new(OrderType::Ask, 1.5);
+        orderbook.place_market_order(&mut sell);
+
+        // The fully filled order no longer counts, the partially filled one does
+        let after = orderbook.depth(1);
+        assert_eq!(after.bids[0].order_count, 1);
+        assert_eq!(after.bids[0].volume, 1.5);
+
+        let updates = after.diff(&before);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].side, OrderType::Bid);
+        assert_eq!(updates[0].level, after.bids[0]);
+
+        let mut sell = Order::new(OrderType::Ask, 1.5);
+        orderbook.place_market_order(&mut sell);
+        let emptied = orderbook.depth(1).diff(&after);
+        assert_eq!(emptied.len(), 2);
+        assert_eq!(emptied[1].level.order_count, 0);
+        assert_eq!(emptied[1].level.price, Price::new(99.0));
+    }
+
+    #[test]
+    fn queue_ahead_notifications() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Bid, 5.0).with_account(1), 99.0);
+        orderbook.add(Order::new(OrderType::Bid, 5.0).with_account(2), 99.0);
+        let watched = orderbook.add(Order::new(OrderType::Bid, 5.0).with_account(3), 99.0);
+
+        let position = orderbook.watch_queue_position(watched, 2.0).unwrap();
+        assert_eq!(position.orders_ahead, 2);
+        assert_eq!(position.size_ahead, 10.0);
+
+        // Moving by less than the threshold stays quiet
+        let mut sell = Order::new(OrderType::Ask, 2.0);
+        orderbook.place_market_order(&mut sell);
+        assert!(orderbook.drain_events().is_empty());
+
+        let mut sell = Order::new(OrderType::Ask, 4.0);
+        orderbook.place_market_order(&mut sell);
+        assert_eq!(
+            orderbook.drain_events(),
+            vec![OrderEvent::QueueAhead {
+                order_id: watched,
+                account: Some(3),
+                orders_ahead: 1,
+                size_ahead: 4.0,
+            }]
+        );
+
+        assert_eq!(orderbook.pull_quotes(2).len(), 1);
+        let events = orderbook.drain_events();
+        assert!(matches!(
+            events[..],
+            [
+                OrderEvent::Cancelled {
+                    account: Some(2),
+                    reason: CancelReason::Pulled,
+                    ..
+                },
+                OrderEvent::QueueAhead {
+                    orders_ahead: 0,
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn pulling_quotes_leaves_no_empty_levels() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Bid, 1.0).with_account(1), 100.0);
+        let quote = orderbook.add(Order::new(OrderType::Bid, 1.0).with_account(2), 100.0);
+        orderbook.add(Order::new(OrderType::Bid, 1.0).with_account(2), 99.0);
+        orderbook.place_market_order(&mut Order::new(OrderType::Ask, 1.0));
+
+        let pulled = orderbook.pull_quotes(2);
+        assert_eq!(pulled.len(), 2);
+        assert!(pulled.contains(&quote));
+        assert_eq!(orderbook.best_bid(), None);
+        assert_eq!(orderbook.open_orders(), 0);
+        assert_eq!(orderbook.drain_events().len(), 2);
+        assert!(orderbook.pull_quotes(2).is_empty());
+    }
+
+    #[test]
+    fn halted_market_does_not_match() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Ask, 5.0), 101.0);
+
+        assert!(orderbook.halt().is_empty());
+        assert_eq!(orderbook.status(), MarketStatus::Halted);
+        let mut buy = Order::new(OrderType::Bid, 5.0);
+        assert!(orderbook.place_market_order(&mut buy).is_empty());
+        assert_eq!(buy.size(), 5.0);
+
+        orderbook.set_reopening_auction(0);
+        orderbook.resume();
+        assert_eq!(orderbook.status(), MarketStatus::Auction);
+        assert!(orderbook.poll_auction().is_empty());
+        assert_eq!(orderbook.status(), MarketStatus::Open);
+        assert_eq!(orderbook.place_market_order(&mut buy).len(), 1);
+    }
+
+    #[test]
+    fn reopening_auction_uncrosses_at_indicative_price() {
+        let clock = ManualClock::new(0);
+        let mut orderbook = OrderBook::with_clock(Arc::new(clock.clone()));
+        orderbook.set_reopening_auction(1_000);
+        orderbook.halt();
+        orderbook.resume();
+        assert_eq!(orderbook.auction_end(), Some(1_000));
+
+        // Orders collected during the auction cross without matching
+        orderbook.add(Order::new(OrderType::Bid, 5.0).with_account(1), 102.0);
+        orderbook.add(Order::new(OrderType::Bid, 10.0).with_account(2), 101.0);
+        orderbook.add(Order::new(OrderType::Ask, 8.0).with_account(3), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 4.0).with_account(4), 101.0);
+        let mut buy = Order::new(OrderType::Bid, 1.0);
+        assert!(orderbook.place_market_order(&mut buy).is_empty());
+
+        let indicative = orderbook.indicative_price().unwrap();
+        assert_eq!(indicative.price, Price::new(101.0));
+        assert_eq!(indicative.volume, 12.0);
+
+        clock.set(999);
+        assert!(orderbook.poll_auction().is_empty());
+        clock.set(1_000);
+        let trades = orderbook.poll_auction();
+
+        assert_eq!(orderbook.status(), MarketStatus::Open);
+        assert!(trades.iter().all(|trade| trade.price == Price::new(101.0)));
+        assert_eq!(exact_sum(trades.iter().map(|trade| trade.size)), 12.0);
+        let depth = orderbook.depth(5);
+        assert_eq!(depth.bids[0].price, Price::new(101.0));
+        assert_eq!(depth.bids[0].volume, 3.0);
+        assert!(depth.asks.is_empty());
+        assert_eq!(orderbook.spread(), None);
+    }
+
+    #[test]
+    fn cancel_all_halt_policy() {
+        let mut orderbook = OrderBook::new();
+        orderbook.set_halt_policy(HaltPolicy::CancelAll);
+        let bid = orderbook.add(Order::new(OrderType::Bid, 5.0).with_account(1), 99.0);
+        orderbook.add(Order::new(OrderType::Ask, 5.0), 101.0);
+
+        let events = orderbook.halt();
+
+        assert_eq!(events.len(), 2);
+        assert!(events.contains(&OrderEvent::Cancelled {
+            order_id: bid,
+            account: Some(1),
+            remaining: 5.0,
+            reason: CancelReason::Halt,
+        }));
+        let depth = orderbook.depth(10);
+        assert!(depth.bids.is_empty() && depth.asks.is_empty());
+    }
+
+    #[test]
+    fn random_auction_close_within_window() {
+        let clock = ManualClock::new(0);
+        let mut orderbook = OrderBook::with_clock(Arc::new(clock.clone()));
+        orderbook.set_reopening_auction(1_000);
+        orderbook.set_random_close(500, 7);
+
+        let mut ends = Vec::new();
+        for _ in 0..20 {
+            orderbook.halt();
+            orderbook.resume();
+            let end = orderbook.auction_end().unwrap();
+            assert!((clock.now() + 1_000..=clock.now() + 1_500).contains(&end));
+            ends.push(end - clock.now());
+
+            clock.set(end - 1);
+            orderbook.poll_auction();
+            assert_eq!(orderbook.status(), MarketStatus::Auction);
+            clock.set(end);
+            orderbook.poll_auction();
+            assert_eq!(orderbook.status(), MarketStatus::Open);
         }
+        ends.dedup();
+        assert!(ends.len() > 1, "close times should vary");
+    }
+
+    #[test]
+    fn band_breach_pauses_then_reopens() {
+        let clock = ManualClock::new(0);
+        let mut orderbook = OrderBook::with_clock(Arc::new(clock.clone()));
+        orderbook.set_price_bands(BandConfig {
+            percent: 5.0,
+            reference_window: 1_000,
+            pause: 500,
+            reopen_multiplier: 2.0,
+            index: None,
+        });
+        orderbook.set_reopening_auction(100);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 104.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 110.0);
+
+        let mut buy = Order::new(OrderType::Bid, 3.0);
+        let trades = orderbook.place_market_order(&mut buy);
+        assert_eq!(trades.len(), 2, "110 is outside the 100 ± 5% band");
+        assert!(!trades[0].triggered_band);
+        assert!(trades[1].triggered_band);
+        assert_eq!(orderbook.status(), MarketStatus::Halted);
+        assert_eq!(buy.size(), 1.0);
+
+        clock.set(499);
+        orderbook.poll_auction();
+        assert_eq!(orderbook.status(), MarketStatus::Halted);
+        clock.set(500);
+        orderbook.poll_auction();
+        assert_eq!(orderbook.status(), MarketStatus::Auction);
+
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 110.0);
+        clock.set(600);
+        let trades = orderbook.poll_auction();
+        assert_eq!(trades[0].price, Price::new(110.0));
+        assert_eq!(orderbook.status(), MarketStatus::Open);
+        // Recentred on the uncross price and widened after reopening
+        let (lower, upper) = orderbook.price_bands().unwrap();
+        assert_eq!((lower, upper), (99.0, 121.0));
     }
 }
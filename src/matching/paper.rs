@@ -0,0 +1,197 @@
+use super::clock::Timestamp;
+use super::depth::{Depth, DepthLevel};
+use super::orderbook::{AccountId, Order, OrderId, OrderType, Price};
+use super::trade::Trade;
+use std::collections::BTreeMap;
+
+/// A paper order resting in the shadow of a real book
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperOrder {
+    pub id: OrderId,
+    pub account: Option<AccountId>,
+    pub side: OrderType,
+    pub price: Price,
+    pub remaining: f64,
+}
+
+/// A simulated execution of a paper order
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperFill {
+    pub order_id: OrderId,
+    pub account: Option<AccountId>,
+    pub side: OrderType,
+    pub price: Price,
+    pub size: f64,
+    pub at: Timestamp,
+}
+
+/// Paper orders for one market, filled off the real book without ever changing it
+///
+/// On arrival a paper order takes the liquidity shown in the real book, as a real order would,
+/// but leaves the book as it was. Whatever is left rests here and fills at its limit price once a
+/// real trade prints at or through it, sharing each print's size among the resting paper orders
+/// in time priority. Paper orders never match each other, and their ids are separate from the
+/// real book's.
+#[derive(Debug, Default)]
+pub struct PaperBook {
+    next_id: OrderId,
+    orders: BTreeMap<OrderId, PaperOrder>,
+}
+
+impl PaperBook {
+    pub fn new() -> Self {
+        PaperBook::default()
+    }
+
+    /// Match a paper order against a snapshot of the real book, resting what a limit order leaves
+    ///
+    /// # Arguments
+    /// * `order` - The order to simulate
+    /// * `price` - Its limit price, None for a market order
+    /// * `depth` - The real book's levels, best price first
+    /// * `now` - The time the fills are stamped with
+    ///
+    /// # Returns
+    /// * `(OrderId, Vec<PaperFill>)` - The paper order's id and its fills on arrival
+    pub fn place(
+        &mut self,
+        order: &Order,
+        price: Option<Price>,
+        depth: &Depth,
+        now: Timestamp,
+    ) -> (OrderId, Vec<PaperFill>) {
+        self.next_id += 1;
+        let id = self.next_id;
+        let side = order.order_type();
+        let levels: &[DepthLevel] = match side {
+            OrderType::Bid => &depth.asks,
+            OrderType::Ask => &depth.bids,
+        };
+        let mut remaining = order.size();
+        let mut fills = Vec::new();
+        for level in levels {
+            let crosses = match (side, price) {
+                (_, None) => true,
+                (OrderType::Bid, Some(price)) => level.price <= price,
+                (OrderType::Ask, Some(price)) => level.price >= price,
+            };
+            if remaining <= 0.0 || !crosses {
+                break;
+            }
+            let size = remaining.min(level.volume);
+            remaining -= size;
+            fills.push(PaperFill {
+                order_id: id,
+                account: order.account(),
+                side,
+                price: level.price,
+                size,
+                at: now,
+            });
+        }
+        if let (Some(price), true) = (price, remaining > 0.0) {
+            self.orders.insert(
+                id,
+                PaperOrder {
+                    id,
+                    account: order.account(),
+                    side,
+                    price,
+                    remaining,
+                },
+            );
+        }
+        (id, fills)
+    }
+
+    /// Fill resting paper orders off real trades
+    ///
+    /// # Returns
+    /// * `Vec<PaperFill>` - The fills, in the order they happened
+    pub fn record_trades(&mut self, trades: &[Trade]) -> Vec<PaperFill> {
+        let mut fills = Vec::new();
+        for trade in trades.iter().filter(|trade| !trade.dark) {
+            let mut available = trade.size;
+            for order in self.orders.values_mut() {
+                let through = match order.side {
+                    OrderType::Bid => trade.price <= order.price,
+                    OrderType::Ask => trade.price >= order.price,
+                };
+                if available <= 0.0 || !through {
+                    continue;
+                }
+                let size = available.min(order.remaining);
+                available -= size;
+                order.remaining -= size;
+                fills.push(PaperFill {
+                    order_id: order.id,
+                    account: order.account,
+                    side: order.side,
+                    price: order.price,
+                    size,
+                    at: trade.transaction_time,
+                });
+            }
+            self.orders.retain(|_, order| order.remaining > 0.0);
+        }
+        fills
+    }
+
+    /// Cancel a resting paper order, returning it if it was open
+    pub fn cancel(&mut self, id: OrderId) -> Option<PaperOrder> {
+        self.orders.remove(&id)
+    }
+
+    pub fn order(&self, id: OrderId) -> Option<&PaperOrder> {
+        self.orders.get(&id)
+    }
+
+    /// Every resting paper order, oldest first
+    pub fn orders(&self) -> impl Iterator<Item = &PaperOrder> {
+        self.orders.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::clock::ManualClock;
+    use crate::matching::orderbook::OrderBook;
+    use std::sync::Arc;
+
+    #[test]
+    fn fills_off_real_liquidity_and_prints() {
+        let mut real = OrderBook::with_clock(Arc::new(ManualClock::new(5)));
+        real.add(Order::new(OrderType::Ask, 1.0), 101.0);
+        real.add(Order::new(OrderType::Ask, 2.0), 102.0);
+        real.add(Order::new(OrderType::Bid, 3.0), 99.0);
+
+        let mut paper = PaperBook::new();
+        let buy = Order::new(OrderType::Bid, 2.0).with_account(7);
+        let (id, fills) = paper.place(&buy, Some(Price::new(101.5)), &real.depth(10), 5);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, Price::new(101.0));
+        assert_eq!(fills[0].size, 1.0);
+        assert_eq!(paper.order(id).unwrap().remaining, 1.0);
+        // The real book is untouched
+        assert_eq!(real.best_ask(), Some(101.0));
+
+        let sell = Order::new(OrderType::Ask, 1.0);
+        let (sold, _) = paper.place(&sell, Some(Price::new(103.0)), &real.depth(10), 5);
+
+        // A real print through the paper bid's price fills it at its own price
+        let trades = real.place_market_order(&mut Order::new(OrderType::Ask, 0.5));
+        let fills = paper.record_trades(&trades);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].order_id, id);
+        assert_eq!(fills[0].price, Price::new(101.5));
+        assert_eq!(fills[0].size, 0.5);
+        assert_eq!(paper.order(id).unwrap().remaining, 0.5);
+
+        assert!(paper.cancel(sold).is_some());
+        let market = Order::new(OrderType::Ask, 10.0);
+        let (_, fills) = paper.place(&market, None, &real.depth(10), 5);
+        assert_eq!(fills[0].size, 2.5);
+        assert_eq!(paper.orders().count(), 1);
+    }
+}
@@ -0,0 +1,76 @@
+use super::orderbook::OrderType;
+
+/// The book price a pegged order follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PegReference {
+    /// The best price on the order's own side: the best bid for a buy, the best ask for a sell
+    Primary,
+    /// Halfway between the best bid and the best ask
+    Midpoint,
+    /// The best price on the opposite side: the best ask for a buy, the best bid for a sell
+    Market,
+}
+
+/// Pegging instructions for a resting order
+///
+/// The order's price is recalculated whenever its reference moves. Pegged orders are left out
+/// when the reference is worked out, so a pegged order never follows itself or another peg.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peg {
+    pub reference: PegReference,
+    /// Amount added to the reference price, negative to peg below it
+    pub offset: f64,
+}
+
+impl Peg {
+    pub fn new(reference: PegReference, offset: f64) -> Self {
+        Peg { reference, offset }
+    }
+
+    /// The price an order on `side` is pegged to
+    ///
+    /// # Arguments
+    /// * `side` - The side of the pegged order
+    /// * `best_bid` - The best bid, leaving out pegged orders
+    /// * `best_ask` - The best ask, leaving out pegged orders
+    ///
+    /// # Returns
+    /// * `Option<f64>` - None if the reference side of the book is empty
+    pub fn price(
+        &self,
+        side: OrderType,
+        best_bid: Option<f64>,
+        best_ask: Option<f64>,
+    ) -> Option<f64> {
+        let reference =
+            match (self.reference, side) {
+                (PegReference::Primary, OrderType::Bid)
+                | (PegReference::Market, OrderType::Ask) => best_bid?,
+                (PegReference::Primary, OrderType::Ask)
+                | (PegReference::Market, OrderType::Bid) => best_ask?,
+                (PegReference::Midpoint, _) => (best_bid? + best_ask?) / 2.0,
+            };
+        Some(reference + self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pegs_follow_their_reference() {
+        let (bid, ask) = (Some(99.0), Some(101.0));
+        let primary = Peg::new(PegReference::Primary, -0.5);
+        assert_eq!(primary.price(OrderType::Bid, bid, ask), Some(98.5));
+        assert_eq!(primary.price(OrderType::Ask, bid, ask), Some(100.5));
+
+        let market = Peg::new(PegReference::Market, 0.0);
+        assert_eq!(market.price(OrderType::Bid, bid, ask), Some(101.0));
+        assert_eq!(market.price(OrderType::Bid, bid, None), None);
+
+        let midpoint = Peg::new(PegReference::Midpoint, 0.25);
+        assert_eq!(midpoint.price(OrderType::Ask, bid, ask), Some(100.25));
+        assert_eq!(midpoint.price(OrderType::Ask, None, ask), None);
+    }
+}
@@ -0,0 +1,115 @@
+use super::orderbook::{OrderType, Price};
+
+/// A limit price given relative to the book, resolved when the order reaches the engine
+///
+/// Clients pricing off their own copy of the book can be a few updates behind; resolving the price
+/// at matching time removes that race.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelativePrice {
+    /// The best price on the order's own side
+    Join,
+    /// One tick better than the best price on the order's own side, or joining it when the
+    /// spread is only one tick wide
+    Improve,
+    /// The best price on the opposite side
+    CrossToTouch,
+    /// A percentage of the way from the best price on the order's own side to the best price on
+    /// the opposite side: 0 joins, 100 crosses to the touch
+    Spread(f64),
+}
+
+impl RelativePrice {
+    /// Resolve to a limit price for an order on `side`
+    ///
+    /// # Arguments
+    /// * `side` - The side of the order being priced
+    /// * `best_bid` - The best bid, if any
+    /// * `best_ask` - The best ask, if any
+    /// * `tick_size` - The market's smallest price increment
+    ///
+    /// # Returns
+    /// * `Result<f64, String>` - The limit price, Err if a side it is priced from is empty or the
+    ///   price is out of range
+    pub fn resolve(
+        &self,
+        side: OrderType,
+        best_bid: Option<f64>,
+        best_ask: Option<f64>,
+        tick_size: f64,
+    ) -> Result<f64, String> {
+        let (own, opposite) = match side {
+            OrderType::Bid => (best_bid, best_ask),
+            OrderType::Ask => (best_ask, best_bid),
+        };
+        let own = || own.ok_or_else(|| format!("No {:?} to price from", side));
+        let opposite = || opposite.ok_or("The opposite side of the book is empty".to_string());
+        match self {
+            RelativePrice::Join => own(),
+            RelativePrice::Improve => {
+                let (price, tick) = (Price::new(own()?), Price::new(tick_size));
+                let improved = match side {
+                    OrderType::Bid => price.checked_add(tick)?,
+                    OrderType::Ask => price.checked_sub(tick)?,
+                };
+                let locks = opposite().is_ok_and(|touch| match side {
+                    OrderType::Bid => improved >= Price::new(touch),
+                    OrderType::Ask => improved <= Price::new(touch),
+                });
+                match locks {
+                    true => own(),
+                    false => Ok(improved.into()),
+                }
+            }
+            RelativePrice::CrossToTouch => opposite(),
+            RelativePrice::Spread(percent) => {
+                if !(0.0..=100.0).contains(percent) {
+                    return Err(format!(
+                        "Spread percentage must be between 0 and 100, got {}",
+                        percent
+                    ));
+                }
+                let (own, opposite) = (own()?, opposite()?);
+                let price = own + (opposite - own) * percent / 100.0;
+                Ok(Price::new(price).round_to(Price::new(tick_size))?.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_against_the_touch() {
+        let (bid, ask) = (Some(99.0), Some(101.0));
+        let resolve = |mode: RelativePrice, side| mode.resolve(side, bid, ask, 0.5);
+
+        assert_eq!(resolve(RelativePrice::Join, OrderType::Bid), Ok(99.0));
+        assert_eq!(resolve(RelativePrice::Improve, OrderType::Bid), Ok(99.5));
+        assert_eq!(resolve(RelativePrice::Improve, OrderType::Ask), Ok(100.5));
+        assert_eq!(
+            resolve(RelativePrice::CrossToTouch, OrderType::Ask),
+            Ok(99.0)
+        );
+        assert_eq!(
+            resolve(RelativePrice::Spread(50.0), OrderType::Bid),
+            Ok(100.0)
+        );
+        assert_eq!(
+            resolve(RelativePrice::Spread(80.0), OrderType::Ask),
+            Ok(99.5)
+        );
+        assert!(resolve(RelativePrice::Spread(120.0), OrderType::Ask).is_err());
+
+        // A one-tick spread cannot be improved without crossing it
+        let tight = RelativePrice::Improve.resolve(OrderType::Bid, Some(99.5), Some(100.0), 0.5);
+        assert_eq!(tight, Ok(99.5));
+        assert!(RelativePrice::Join
+            .resolve(OrderType::Bid, None, ask, 0.5)
+            .is_err());
+        assert!(RelativePrice::CrossToTouch
+            .resolve(OrderType::Bid, bid, None, 0.5)
+            .is_err());
+    }
+}
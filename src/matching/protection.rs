@@ -0,0 +1,113 @@
+use super::clock::Timestamp;
+use super::numeric::exact_sum;
+use super::orderbook::AccountId;
+use std::collections::{HashMap, VecDeque};
+
+/// Market-maker protection limits for one account on one market
+///
+/// Once an account's resting orders are hit more than `max_fills` times, or its net position
+/// changes by more than `max_delta`, within `interval` nanoseconds, the account's remaining
+/// quotes are pulled from the book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MmProtectionConfig {
+    pub interval: u64,
+    pub max_fills: usize,
+    pub max_delta: f64,
+}
+
+/// Tracks maker fills per account over a sliding window
+#[derive(Debug, Default)]
+pub struct MmProtection {
+    configs: HashMap<AccountId, MmProtectionConfig>,
+    windows: HashMap<AccountId, VecDeque<(Timestamp, f64)>>,
+}
+
+impl MmProtection {
+    pub fn new() -> Self {
+        MmProtection::default()
+    }
+
+    /// Enable (or replace) protection for an account
+    pub fn configure(&mut self, account: AccountId, config: MmProtectionConfig) {
+        self.configs.insert(account, config);
+        self.windows.remove(&account);
+    }
+
+    /// Disable protection for an account
+    pub fn remove(&mut self, account: AccountId) {
+        self.configs.remove(&account);
+        self.windows.remove(&account);
+    }
+
+    pub fn config(&self, account: AccountId) -> Option<&MmProtectionConfig> {
+        self.configs.get(&account)
+    }
+
+    /// Record a fill against one of the account's resting orders
+    ///
+    /// `delta` is signed: positive when the maker bought, negative when it sold.
+    ///
+    /// # Returns
+    /// * `bool` - true if the fill breached the account's limits, in which case the window is reset
+    pub fn record_fill(&mut self, account: AccountId, delta: f64, now: Timestamp) -> bool {
+        let config = match self.configs.get(&account) {
+            Some(config) => config,
+            None => return false,
+        };
+        let window = self.windows.entry(account).or_default();
+        window.push_back((now, delta));
+        while let Some(&(at, _)) = window.front() {
+            if at.saturating_add(config.interval) > now {
+                break;
+            }
+            window.pop_front();
+        }
+
+        let consumed = exact_sum(window.iter().map(|(_, delta)| *delta));
+        let breached = window.len() > config.max_fills || consumed.abs() > config.max_delta;
+        if breached {
+            window.clear();
+        }
+        breached
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: MmProtectionConfig = MmProtectionConfig {
+        interval: 1_000,
+        max_fills: 2,
+        max_delta: 100.0,
+    };
+
+    #[test]
+    fn breach_on_fill_count() {
+        let mut protection = MmProtection::new();
+        protection.configure(1, CONFIG);
+
+        assert!(!protection.record_fill(1, 1.0, 0));
+        assert!(!protection.record_fill(1, -1.0, 10));
+        assert!(protection.record_fill(1, 1.0, 20));
+    }
+
+    #[test]
+    fn fills_outside_interval_are_forgotten() {
+        let mut protection = MmProtection::new();
+        protection.configure(1, CONFIG);
+
+        assert!(!protection.record_fill(1, 60.0, 0));
+        assert!(!protection.record_fill(1, 30.0, 500));
+        assert!(!protection.record_fill(1, 30.0, 1_000));
+        assert!(protection.record_fill(1, 50.0, 1_200));
+    }
+
+    #[test]
+    fn unprotected_accounts_never_breach() {
+        let mut protection = MmProtection::new();
+        for at in 0..10 {
+            assert!(!protection.record_fill(7, 1_000.0, at));
+        }
+    }
+}
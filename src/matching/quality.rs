@@ -0,0 +1,218 @@
+use super::clock::Timestamp;
+use super::depth::{Depth, DepthLevel};
+use super::numeric::exact_sum;
+use super::orderbook::OrderId;
+use super::trade::Trade;
+use std::io::{self, Write};
+
+/// Levels per side counted in a sample's depth
+pub const QUALITY_LEVELS: usize = 5;
+
+/// Market quality of one book at one point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualitySample {
+    pub at: Timestamp,
+    /// None unless both sides have orders
+    pub spread: Option<f64>,
+    /// Resting size in the top five bid levels
+    pub bid_depth: f64,
+    /// Resting size in the top five ask levels
+    pub ask_depth: f64,
+    /// Trades since the previous sample that printed through the touch
+    pub trade_throughs: u64,
+}
+
+/// Market quality over a period, e.g. one trading day
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    pub from: Timestamp,
+    pub to: Timestamp,
+    pub samples: usize,
+    /// Average over the samples with both sides quoted
+    pub average_spread: Option<f64>,
+    pub widest_spread: Option<f64>,
+    /// Share of the samples with both sides quoted
+    pub two_sided: f64,
+    pub average_bid_depth: f64,
+    pub average_ask_depth: f64,
+    pub trade_throughs: u64,
+}
+
+/// Samples spread, top-of-book depth and trade-throughs into a time series
+///
+/// A trade-through is a fill of an incoming order at a worse price than its first fill, i.e. one
+/// that swept past the touch. Samples are taken at most once per `interval` nanoseconds.
+#[derive(Debug)]
+pub struct QualityRecorder {
+    interval: u64,
+    next_sample: Timestamp,
+    trade_throughs: u64,
+    samples: Vec<QualitySample>,
+}
+
+impl QualityRecorder {
+    pub fn new(interval: u64) -> Self {
+        QualityRecorder {
+            interval: interval.max(1),
+            next_sample: 0,
+            trade_throughs: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Count the trade-throughs among trades in execution order
+    pub fn record_trades(&mut self, trades: &[Trade]) {
+        let mut first_fill: Option<(OrderId, f64)> = None;
+        for trade in trades.iter().filter(|trade| !trade.dark) {
+            let price: f64 = trade.price.into();
+            let taker = match trade.taker_order_id {
+                Some(taker) => taker,
+                None => continue,
+            };
+            match first_fill {
+                Some((order, first)) if order == taker => {
+                    if price != first {
+                        self.trade_throughs += 1;
+                    }
+                }
+                _ => first_fill = Some((taker, price)),
+            }
+        }
+    }
+
+    /// Take a sample of a depth snapshot if one is due at `now`
+    ///
+    /// # Arguments
+    /// * `depth` - The book's top levels, at least five per side
+    /// * `now` - When the snapshot was taken
+    ///
+    /// # Returns
+    /// * `bool` - Whether a sample was taken
+    pub fn sample(&mut self, depth: &Depth, now: Timestamp) -> bool {
+        if now < self.next_sample {
+            return false;
+        }
+        let spread = match (depth.bids.first(), depth.asks.first()) {
+            (Some(bid), Some(ask)) => Some(f64::from(ask.price) - f64::from(bid.price)),
+            _ => None,
+        };
+        let top = |levels: &[DepthLevel]| {
+            exact_sum(levels.iter().take(QUALITY_LEVELS).map(|level| level.volume))
+        };
+        self.samples.push(QualitySample {
+            at: now,
+            spread,
+            bid_depth: top(&depth.bids),
+            ask_depth: top(&depth.asks),
+            trade_throughs: std::mem::take(&mut self.trade_throughs),
+        });
+        self.next_sample = now.saturating_add(self.interval);
+        true
+    }
+
+    /// Every sample taken, oldest first
+    pub fn samples(&self) -> &[QualitySample] {
+        &self.samples
+    }
+
+    /// Summarize the samples taken in `[from, to)`
+    pub fn report(&self, from: Timestamp, to: Timestamp) -> QualityReport {
+        let samples: Vec<&QualitySample> = self
+            .samples
+            .iter()
+            .filter(|sample| sample.at >= from && sample.at < to)
+            .collect();
+        let spreads: Vec<f64> = samples.iter().filter_map(|sample| sample.spread).collect();
+        let average = |values: &[f64]| match values.is_empty() {
+            true => None,
+            false => Some(exact_sum(values.iter().copied()) / values.len() as f64),
+        };
+        let depth = |side: fn(&QualitySample) -> f64| {
+            let depths: Vec<f64> = samples.iter().map(|sample| side(sample)).collect();
+            average(&depths).unwrap_or(0.0)
+        };
+        QualityReport {
+            from,
+            to,
+            samples: samples.len(),
+            average_spread: average(&spreads),
+            widest_spread: spreads.iter().copied().reduce(f64::max),
+            two_sided: match samples.is_empty() {
+                true => 0.0,
+                false => spreads.len() as f64 / samples.len() as f64,
+            },
+            average_bid_depth: depth(|sample| sample.bid_depth),
+            average_ask_depth: depth(|sample| sample.ask_depth),
+            trade_throughs: samples.iter().map(|sample| sample.trade_throughs).sum(),
+        }
+    }
+
+    /// Write the time series as CSV rows of `time,spread,bid_depth,ask_depth,trade_throughs`
+    ///
+    /// The spread is left empty for one-sided samples.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "time,spread,bid_depth,ask_depth,trade_throughs")?;
+        for sample in &self.samples {
+            let spread = sample.spread.map(|spread| spread.to_string());
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                sample.at,
+                spread.unwrap_or_default(),
+                sample.bid_depth,
+                sample.ask_depth,
+                sample.trade_throughs
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::{Order, OrderBook, OrderType};
+
+    #[test]
+    fn samples_quality_and_reports_per_period() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Bid, 1.0), 99.0);
+        orderbook.add(Order::new(OrderType::Ask, 1.0), 101.0);
+        orderbook.add(Order::new(OrderType::Ask, 2.0), 102.0);
+
+        let mut recorder = QualityRecorder::new(100);
+        assert!(recorder.sample(&orderbook.depth(QUALITY_LEVELS), 0));
+        assert!(!recorder.sample(&orderbook.depth(QUALITY_LEVELS), 50));
+
+        // A buy that sweeps two levels trades through the touch once
+        let trades = orderbook.place_market_order(&mut Order::new(OrderType::Bid, 2.0));
+        recorder.record_trades(&trades);
+        assert!(recorder.sample(&orderbook.depth(QUALITY_LEVELS), 100));
+        orderbook.place_market_order(&mut Order::new(OrderType::Bid, 1.0));
+        assert!(recorder.sample(&orderbook.depth(QUALITY_LEVELS), 200));
+
+        let samples = recorder.samples();
+        assert_eq!(samples[0].spread, Some(2.0));
+        assert_eq!(samples[0].ask_depth, 3.0);
+        assert_eq!(samples[1].spread, Some(3.0));
+        assert_eq!(samples[1].trade_throughs, 1);
+        assert_eq!(samples[2].spread, None);
+
+        let report = recorder.report(0, 200);
+        assert_eq!(report.samples, 2);
+        assert_eq!(report.average_spread, Some(2.5));
+        assert_eq!(report.widest_spread, Some(3.0));
+        assert_eq!(report.two_sided, 1.0);
+        assert_eq!(report.average_ask_depth, 2.0);
+        assert_eq!(report.trade_throughs, 1);
+        assert_eq!(recorder.report(0, 1_000).two_sided, 2.0 / 3.0);
+
+        let mut csv = Vec::new();
+        recorder.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines = csv.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "time,spread,bid_depth,ask_depth,trade_throughs");
+        assert_eq!(lines[2], "100,3,1,1,1");
+        assert_eq!(lines[3], "200,,1,0,0");
+    }
+}
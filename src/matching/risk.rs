@@ -0,0 +1,202 @@
+use super::orderbook::{AccountId, OrderType, TradingPair};
+use std::fmt;
+
+/// What a pre-trade check sees of an incoming order and the state it would trade into
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskContext<'a> {
+    pub pair: &'a TradingPair,
+    pub side: OrderType,
+    /// Limit price, None for a market order
+    pub price: Option<f64>,
+    pub size: f64,
+    pub account: Option<AccountId>,
+    /// Orders the account already has open across every market, 0 without an account. For a
+    /// stop order being triggered, only the orders open on its own market are counted
+    pub open_orders: usize,
+    /// Price of the market's most recent trade, None if it has not traded
+    pub last_trade: Option<f64>,
+}
+
+/// Why a pre-trade check turned an order away
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskRejection {
+    SizeAboveLimit {
+        size: f64,
+        max: f64,
+    },
+    NotionalAboveLimit {
+        notional: f64,
+        max: f64,
+    },
+    TooManyOpenOrders {
+        account: AccountId,
+        open: usize,
+        max: usize,
+    },
+    /// The limit price is further than `percent` from the last trade
+    OutsideCollar {
+        price: f64,
+        last_trade: f64,
+        percent: f64,
+    },
+    /// A rejection from a custom check
+    Other(String),
+}
+
+impl fmt::Display for RiskRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskRejection::SizeAboveLimit { size, max } => {
+                write!(f, "Order size {} exceeds the risk limit of {}", size, max)
+            }
+            RiskRejection::NotionalAboveLimit { notional, max } => write!(
+                f,
+                "Order notional {} exceeds the risk limit of {}",
+                notional, max
+            ),
+            RiskRejection::TooManyOpenOrders { account, open, max } => write!(
+                f,
+                "Account {} has {} open orders, the limit is {}",
+                account, open, max
+            ),
+            RiskRejection::OutsideCollar {
+                price,
+                last_trade,
+                percent,
+            } => write!(
+                f,
+                "Order price {} is more than {}% from the last trade at {}",
+                price, percent, last_trade
+            ),
+            RiskRejection::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<RiskRejection> for String {
+    fn from(rejection: RiskRejection) -> Self {
+        rejection.to_string()
+    }
+}
+
+/// A pre-trade check run on every order before it reaches a book, and on stop orders again as
+/// they trigger, see `Engine::add_risk_checker`
+pub trait RiskChecker: fmt::Debug + Send + Sync {
+    fn check(&self, order: &RiskContext) -> Result<(), RiskRejection>;
+}
+
+/// The standard pre-trade limits; limits left as None are not checked
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RiskLimits {
+    pub max_order_size: Option<f64>,
+    /// Largest price × size of a limit order; market orders have no notional up front
+    pub max_notional: Option<f64>,
+    /// Most orders an account may have open at once, across every market
+    pub max_open_orders: Option<usize>,
+    /// Furthest a limit price may be from the market's last trade, as a percentage
+    pub collar_percent: Option<f64>,
+}
+
+impl RiskChecker for RiskLimits {
+    fn check(&self, order: &RiskContext) -> Result<(), RiskRejection> {
+        if let Some(max) = self.max_order_size.filter(|max| order.size > *max) {
+            return Err(RiskRejection::SizeAboveLimit {
+                size: order.size,
+                max,
+            });
+        }
+        if let (Some(max), Some(price)) = (self.max_notional, order.price) {
+            let notional = price * order.size;
+            if notional > max {
+                return Err(RiskRejection::NotionalAboveLimit { notional, max });
+            }
+        }
+        if let (Some(max), Some(account)) = (self.max_open_orders, order.account) {
+            if order.open_orders >= max {
+                return Err(RiskRejection::TooManyOpenOrders {
+                    account,
+                    open: order.open_orders,
+                    max,
+                });
+            }
+        }
+        if let (Some(percent), Some(price), Some(last_trade)) =
+            (self.collar_percent, order.price, order.last_trade)
+        {
+            if (price - last_trade).abs() / last_trade * 100.0 > percent {
+                return Err(RiskRejection::OutsideCollar {
+                    price,
+                    last_trade,
+                    percent,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_reject_with_the_breached_rule() {
+        let pair: TradingPair = "BTC/USD".parse().unwrap();
+        let limits = RiskLimits {
+            max_order_size: Some(10.0),
+            max_notional: Some(500.0),
+            max_open_orders: Some(2),
+            collar_percent: Some(5.0),
+        };
+        let order = RiskContext {
+            pair: &pair,
+            side: OrderType::Bid,
+            price: Some(100.0),
+            size: 4.0,
+            account: Some(7),
+            open_orders: 1,
+            last_trade: Some(98.0),
+        };
+        assert_eq!(limits.check(&order), Ok(()));
+        assert_eq!(
+            limits.check(&RiskContext {
+                size: 11.0,
+                ..order.clone()
+            }),
+            Err(RiskRejection::SizeAboveLimit {
+                size: 11.0,
+                max: 10.0
+            })
+        );
+        assert!(matches!(
+            limits.check(&RiskContext {
+                size: 6.0,
+                ..order.clone()
+            }),
+            Err(RiskRejection::NotionalAboveLimit { .. })
+        ));
+        assert!(matches!(
+            limits.check(&RiskContext {
+                open_orders: 2,
+                ..order.clone()
+            }),
+            Err(RiskRejection::TooManyOpenOrders { open: 2, .. })
+        ));
+        assert!(matches!(
+            limits.check(&RiskContext {
+                price: Some(104.0),
+                ..order.clone()
+            }),
+            Err(RiskRejection::OutsideCollar { .. })
+        ));
+        // Market orders have no price to collar or notional to cap
+        assert_eq!(
+            limits.check(&RiskContext {
+                price: None,
+                size: 9.0,
+                ..order
+            }),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use super::orderbook::{AccountId, Order, OrderBook, OrderType, Price};
+use super::trade::Trade;
+use std::collections::HashMap;
+
+/// One statement of a scenario script
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// `bid 100@99.5 as alice` / `ask 50@99.5 as bob`
+    Limit {
+        side: OrderType,
+        size: f64,
+        price: f64,
+        owner: Option<String>,
+    },
+    /// `buy 10 as carol` / `sell 10`
+    Market {
+        side: OrderType,
+        size: f64,
+        owner: Option<String>,
+    },
+    /// `expect trade 50@99.5`
+    ExpectTrade { size: f64, price: f64 },
+    /// `expect no trades`
+    ExpectNoTrades,
+}
+
+/// A small executable script describing order flow and the trades it should produce
+///
+/// Statements are separated by `;` or newlines, and `#` starts a comment. Each `expect trade`
+/// consumes the next trade that has not been checked yet, and `expect no trades` asserts that
+/// every trade so far has been checked.
+///
+/// # Example
+/// ```
+/// use orderbook::matching::scenario::Scenario;
+/// let scenario = Scenario::parse("ask 50@99.5 as bob; buy 20 as alice; expect trade 20@99.5").unwrap();
+/// assert!(scenario.run().is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    pub fn parse(script: &str) -> Result<Scenario, String> {
+        let mut steps = Vec::new();
+        for (index, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            for statement in line.split(';') {
+                let statement = statement.trim();
+                if statement.is_empty() {
+                    continue;
+                }
+                let step = parse_step(statement)
+                    .map_err(|e| format!("line {}: {}: {:?}", index + 1, e, statement))?;
+                steps.push(step);
+            }
+        }
+        Ok(Scenario { steps })
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Run the scenario against a fresh order book
+    ///
+    /// # Returns
+    /// * `Result<Vec<Trade>, String>` - Every trade produced, or the first failed expectation
+    pub fn run(&self) -> Result<Vec<Trade>, String> {
+        let mut orderbook = OrderBook::new();
+        let mut accounts: HashMap<String, AccountId> = HashMap::new();
+        let mut trades: Vec<Trade> = Vec::new();
+        let mut checked = 0;
+
+        for (index, step) in self.steps.iter().enumerate() {
+            let mut account_for = |owner: &Option<String>| {
+                owner.as_ref().map(|name| {
+                    let next = accounts.len() as AccountId + 1;
+                    *accounts.entry(name.clone()).or_insert(next)
+                })
+            };
+            match step {
+                Step::Limit {
+                    side,
+                    size,
+                    price,
+                    owner,
+                } => {
+                    let order = with_owner(Order::new(*side, *size), account_for(owner));
+                    orderbook.add(order, *price);
+                }
+                Step::Market { side, size, owner } => {
+                    let mut order = with_owner(Order::new(*side, *size), account_for(owner));
+                    trades.extend(orderbook.place_market_order(&mut order));
+                }
+                Step::ExpectTrade { size, price } => {
+                    let trade = trades.get(checked).ok_or_else(|| {
+                        format!(
+                            "step {}: expected trade {}@{}, found none",
+                            index + 1,
+                            size,
+                            price
+                        )
+                    })?;
+                    if trade.size != *size || trade.price != Price::new(*price) {
+                        let found: f64 = trade.price.into();
+                        return Err(format!(
+                            "step {}: expected trade {}@{}, found {}@{}",
+                            index + 1,
+                            size,
+                            price,
+                            trade.size,
+                            found
+                        ));
+                    }
+                    checked += 1;
+                }
+                Step::ExpectNoTrades => {
+                    if checked != trades.len() {
+                        return Err(format!(
+                            "step {}: expected no trades, found {} unchecked",
+                            index + 1,
+                            trades.len() - checked
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(trades)
+    }
+}
+
+fn with_owner(order: Order, account: Option<AccountId>) -> Order {
+    match account {
+        Some(account) => order.with_account(account),
+        None => order,
+    }
+}
+
+fn parse_step(statement: &str) -> Result<Step, String> {
+    let words = statement.split_whitespace().collect::<Vec<&str>>();
+    match words.as_slice() {
+        ["expect", "no", "trades"] => Ok(Step::ExpectNoTrades),
+        ["expect", "trade", fill] => {
+            let (size, price) = parse_fill(fill)?;
+            Ok(Step::ExpectTrade { size, price })
+        }
+        [verb, amount, rest @ ..] => {
+            let owner = parse_owner(rest)?;
+            match *verb {
+                "bid" | "ask" => {
+                    let (size, price) = parse_fill(amount)?;
+                    let side = match *verb {
+                        "bid" => OrderType::Bid,
+                        _ => OrderType::Ask,
+                    };
+                    Ok(Step::Limit {
+                        side,
+                        size,
+                        price,
+                        owner,
+                    })
+                }
+                "buy" | "sell" => {
+                    let side = match *verb {
+                        "buy" => OrderType::Bid,
+                        _ => OrderType::Ask,
+                    };
+                    Ok(Step::Market {
+                        side,
+                        size: parse_number(amount)?,
+                        owner,
+                    })
+                }
+                _ => Err("unknown statement".to_string()),
+            }
+        }
+        _ => Err("unknown statement".to_string()),
+    }
+}
+
+fn parse_owner(words: &[&str]) -> Result<Option<String>, String> {
+    match words {
+        [] => Ok(None),
+        ["as", name] => Ok(Some(name.to_string())),
+        _ => Err("expected `as <name>`".to_string()),
+    }
+}
+
+/// Parses `size@price`
+fn parse_fill(fill: &str) -> Result<(f64, f64), String> {
+    let (size, price) = fill
+        .split_once('@')
+        .ok_or_else(|| "expected size@price".to_string())?;
+    Ok((parse_number(size)?, parse_number(price)?))
+}
+
+fn parse_number(number: &str) -> Result<f64, String> {
+    match number.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!("invalid number {:?}", number)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_statements() {
+        let scenario = Scenario::parse(
+            "bid 100@99.5 as alice; ask 50@100\n# comment\nsell 10 as bob\nexpect trade 10@99.5",
+        )
+        .unwrap();
+
+        assert_eq!(
+            scenario.steps(),
+            &[
+                Step::Limit {
+                    side: OrderType::Bid,
+                    size: 100.0,
+                    price: 99.5,
+                    owner: Some("alice".to_string()),
+                },
+                Step::Limit {
+                    side: OrderType::Ask,
+                    size: 50.0,
+                    price: 100.0,
+                    owner: None,
+                },
+                Step::Market {
+                    side: OrderType::Ask,
+                    size: 10.0,
+                    owner: Some("bob".to_string()),
+                },
+                Step::ExpectTrade {
+                    size: 10.0,
+                    price: 99.5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_statements() {
+        assert!(Scenario::parse("bid 100 as alice").is_err());
+        assert!(Scenario::parse("bid 100@x").is_err());
+        assert!(Scenario::parse("hold 1@1").is_err());
+        assert!(Scenario::parse("sell 1 for bob").is_err());
+    }
+
+    #[test]
+    fn runs_expectations() {
+        let passing = Scenario::parse(
+            "ask 30@101 as bob; ask 20@102 as carol
+             buy 40 as alice
+             expect trade 30@101; expect trade 10@102; expect no trades",
+        )
+        .unwrap();
+        assert_eq!(passing.run().unwrap().len(), 2);
+
+        let failing = Scenario::parse("ask 30@101; buy 10; expect trade 10@100").unwrap();
+        assert!(failing.run().unwrap_err().contains("found 10@101"));
+
+        let unchecked = Scenario::parse("ask 30@101; buy 10; expect no trades").unwrap();
+        assert!(unchecked.run().is_err());
+    }
+}
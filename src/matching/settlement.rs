@@ -0,0 +1,22 @@
+use super::clock::Timestamp;
+use super::orderbook::TradingPair;
+
+/// How a market's settlement price is determined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettlementMethod {
+    /// Volume-weighted average price of the trades in the last `window` nanoseconds
+    Vwap { window: u64 },
+    /// The price of the most recent auction uncross
+    AuctionPrice,
+}
+
+/// An official settlement price for one market
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settlement {
+    pub pair: TradingPair,
+    pub price: f64,
+    pub method: SettlementMethod,
+    /// True when the method had no data and the last traded price was used instead
+    pub fallback: bool,
+    pub at: Timestamp,
+}
@@ -0,0 +1,479 @@
+use super::clock::Timestamp;
+use super::orderbook::{
+    AccountId, MarketStatus, Order, OrderBook, OrderId, OrderType, Price, TimeInForce, TradingPair,
+};
+use super::peg::Peg;
+use super::stop::StopKind;
+use std::collections::BTreeMap;
+
+/// A resting order as carried in a chunked book snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestingOrder {
+    pub id: OrderId,
+    pub side: OrderType,
+    pub price: Price,
+    /// Visible size
+    pub size: f64,
+    /// Hidden size of an iceberg order
+    pub reserve: f64,
+    pub display_size: Option<f64>,
+    pub account: Option<AccountId>,
+    pub expires_at: Option<Timestamp>,
+    pub time_in_force: TimeInForce,
+    pub tag: Option<String>,
+    pub sequence: u64,
+    /// Number of times the order has been amended or replaced, plus one
+    pub version: u32,
+    /// Pegging instructions, None for an order at a fixed price
+    pub peg: Option<Peg>,
+}
+
+/// A stop order waiting for its stop price, as carried in a [`MarketSnapshot`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaitingStop {
+    pub id: OrderId,
+    pub side: OrderType,
+    pub stop_price: Price,
+    pub kind: StopKind,
+    /// Visible size
+    pub size: f64,
+    /// Hidden size of an iceberg order
+    pub reserve: f64,
+    pub display_size: Option<f64>,
+    pub account: Option<AccountId>,
+    pub expires_at: Option<Timestamp>,
+    pub time_in_force: TimeInForce,
+    pub collar_override: bool,
+    pub tag: Option<String>,
+}
+
+/// Position in a book snapshot: asks from the lowest price, then bids from the lowest price, each
+/// level in time priority
+///
+/// A transfer that broke off resumes from the cursor of the last chunk that arrived.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapshotCursor {
+    pub side: OrderType,
+    pub price: Price,
+    pub sequence: u64,
+}
+
+/// One piece of a book snapshot, see `OrderBook::snapshot_chunk`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotChunk {
+    pub orders: Vec<RestingOrder>,
+    /// Where the following chunk starts, None if this is the last one
+    pub next: Option<SnapshotCursor>,
+}
+
+/// A client order id already used on a market, and the order it was given to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientOrder {
+    pub account: Option<AccountId>,
+    pub client_order_id: String,
+    pub order_id: OrderId,
+}
+
+/// The resting and stop orders of one market in an [`EngineSnapshot`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketSnapshot {
+    pub pair: TradingPair,
+    pub status: MarketStatus,
+    /// In `OrderBook::snapshot_chunk` order
+    pub orders: Vec<RestingOrder>,
+    /// Stop orders that have not triggered, in `OrderBook::stop_snapshot` order
+    pub stops: Vec<WaitingStop>,
+    /// Client order ids in use, by order id, so a restored market still rejects reused ids
+    pub client_orders: Vec<ClientOrder>,
+}
+
+/// Every market of an engine as of a single engine sequence number, see `Engine::snapshot`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineSnapshot {
+    /// Number of commands the engine had applied when the snapshot was taken
+    pub sequence: u64,
+    /// Sorted by pair
+    pub markets: Vec<MarketSnapshot>,
+}
+
+/// External order book snapshot formats that can seed an [`OrderBook`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// Binance REST `GET /api/v3/depth`: `{"lastUpdateId":1,"bids":[["price","qty"]],"asks":[...]}`
+    Binance,
+    /// Kraken REST `GET /0/public/Depth`:
+    /// `{"error":[],"result":{"XXBTZUSD":{"bids":[["price","volume",timestamp]],"asks":[...]}}}`
+    Kraken,
+}
+
+/// One aggregated level of an external snapshot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapshotLevel {
+    pub side: OrderType,
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Parse an exchange depth snapshot
+///
+/// Levels with zero size are skipped.
+///
+/// # Example
+/// ```
+/// use orderbook::matching::snapshot::{parse_snapshot, SnapshotFormat};
+/// let levels = parse_snapshot(
+///     SnapshotFormat::Binance,
+///     r#"{"lastUpdateId":1027024,"bids":[["4.00000000","431.00000000"]],"asks":[["4.00000200","12.00000000"]]}"#,
+/// )
+/// .unwrap();
+/// assert_eq!(levels.len(), 2);
+/// ```
+pub fn parse_snapshot(format: SnapshotFormat, text: &str) -> Result<Vec<SnapshotLevel>, String> {
+    let root = Parser::new(text).parse_document()?;
+    let book = match format {
+        SnapshotFormat::Binance => &root,
+        SnapshotFormat::Kraken => {
+            if let Some(Json::Array(errors)) = root.get("error") {
+                if let Some(Json::String(error)) = errors.first() {
+                    return Err(format!("snapshot reports an error: {}", error));
+                }
+            }
+            match root.get("result") {
+                Some(Json::Object(markets)) => markets
+                    .values()
+                    .next()
+                    .ok_or("snapshot result has no market")?,
+                _ => return Err("snapshot has no result object".to_string()),
+            }
+        }
+    };
+
+    let mut levels = Vec::new();
+    for (key, side) in [("bids", OrderType::Bid), ("asks", OrderType::Ask)] {
+        let entries = match book.get(key) {
+            Some(Json::Array(entries)) => entries,
+            _ => return Err(format!("snapshot has no {} array", key)),
+        };
+        for entry in entries {
+            let fields = match entry {
+                Json::Array(fields) if fields.len() >= 2 => fields,
+                _ => return Err(format!("malformed {} entry", key)),
+            };
+            let price = fields[0].decimal()?;
+            let size = fields[1].decimal()?;
+            if price <= 0.0 || size < 0.0 {
+                return Err(format!("invalid {} level {} x {}", key, price, size));
+            }
+            if size > 0.0 {
+                levels.push(SnapshotLevel { side, price, size });
+            }
+        }
+    }
+    Ok(levels)
+}
+
+/// Rest one order per snapshot level in an order book
+///
+/// # Returns
+/// * `usize` - The number of orders added
+pub fn seed_orderbook(orderbook: &mut OrderBook, levels: &[SnapshotLevel]) -> usize {
+    for level in levels {
+        orderbook.add(Order::new(level.side, level.size), level.price);
+    }
+    levels.len()
+}
+
+/// Just enough JSON for exchange snapshots
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    /// Numbers keep their source text so decimals are parsed once, like strings
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.get(key),
+            _ => None,
+        }
+    }
+
+    /// Read a decimal given either as a number or as a string
+    fn decimal(&self) -> Result<f64, String> {
+        let text = match self {
+            Json::Number(text) | Json::String(text) => text,
+            other => return Err(format!("expected a decimal, found {:?}", other)),
+        };
+        match text.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("invalid decimal {:?}", text)),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser {
+            text,
+            bytes: text.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Json, String> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.position != self.bytes.len() {
+            return Err(format!(
+                "unexpected trailing data at byte {}",
+                self.position
+            ));
+        }
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            Some(&found) if found == byte => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(format!(
+                "expected '{}' at byte {}",
+                byte as char, self.position
+            )),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b't') => self.parse_literal("true", Json::Bool(true)),
+            Some(b'f') => self.parse_literal("false", Json::Bool(false)),
+            Some(b'n') => self.parse_literal("null", Json::Null),
+            Some(byte) if *byte == b'-' || byte.is_ascii_digit() => {
+                let start = self.position;
+                while self.bytes.get(self.position).is_some_and(|byte| {
+                    byte.is_ascii_digit() || matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E')
+                }) {
+                    self.position += 1;
+                }
+                Ok(Json::Number(self.text[start..self.position].to_string()))
+            }
+            _ => Err(format!("unexpected input at byte {}", self.position)),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Json) -> Result<Json, String> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(format!("unexpected input at byte {}", self.position))
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut text = String::new();
+        loop {
+            let mut chars = self.text[self.position..].chars();
+            match chars.next() {
+                None => return Err("unterminated string".to_string()),
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(text);
+                }
+                Some('\\') => {
+                    let escaped = match chars.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        _ => return Err(format!("unsupported escape at byte {}", self.position)),
+                    };
+                    text.push(escaped);
+                    self.position += 2;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.position += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.position) == Some(&b']') {
+            self.position += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.bytes.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(format!("expected ',' or ']' at byte {}", self.position)),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect(b'{')?;
+        let mut fields = BTreeMap::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.position) == Some(&b'}') {
+            self.position += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.expect(b':')?;
+            fields.insert(key, self.parse_value()?);
+            self.skip_whitespace();
+            match self.bytes.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(format!("expected ',' or '}}' at byte {}", self.position)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_from_kraken_snapshot() {
+        let text = r#"{
+            "error": [],
+            "result": {
+                "XXBTZUSD": {
+                    "asks": [["30001.10000", "1.500", 1688671869], ["30002.00000", "0.000", 1688671870]],
+                    "bids": [["29999.90000", "2.250", 1688671868]]
+                }
+            }
+        }"#;
+        let levels = parse_snapshot(SnapshotFormat::Kraken, text).unwrap();
+        assert_eq!(
+            levels,
+            vec![
+                SnapshotLevel {
+                    side: OrderType::Bid,
+                    price: 29999.9,
+                    size: 2.25
+                },
+                SnapshotLevel {
+                    side: OrderType::Ask,
+                    price: 30001.1,
+                    size: 1.5
+                },
+            ]
+        );
+
+        let mut orderbook = OrderBook::new();
+        assert_eq!(seed_orderbook(&mut orderbook, &levels), 2);
+        let depth = orderbook.depth(5);
+        assert_eq!(depth.bids[0].volume, 2.25);
+        assert_eq!(depth.asks[0].volume, 1.5);
+    }
+
+    #[test]
+    fn chunked_snapshot_round_trip() {
+        let mut source = OrderBook::new();
+        for (side, price) in [
+            (OrderType::Ask, 101.0),
+            (OrderType::Ask, 102.0),
+            (OrderType::Bid, 99.0),
+            (OrderType::Bid, 98.0),
+            (OrderType::Ask, 101.0),
+        ] {
+            source.add(Order::new(side, 1.0).with_account(3), price);
+        }
+        source.add(
+            Order::new(OrderType::Bid, 2.0).with_tag("t".to_string()),
+            98.0,
+        );
+
+        let mut target = OrderBook::new();
+        let mut cursor = None;
+        let mut chunks = 0;
+        loop {
+            let chunk = source.snapshot_chunk(cursor, 4);
+            target.restore(&chunk.orders).unwrap();
+            chunks += 1;
+            cursor = match chunk.next {
+                Some(next) => Some(next),
+                None => break,
+            };
+        }
+        assert_eq!(chunks, 2);
+        assert_eq!(target.depth(10), source.depth(10));
+        assert_eq!(target.queue_position(5), source.queue_position(5));
+        assert_eq!(target.order(6).unwrap().tag(), Some("t"));
+        assert!(target
+            .restore(&source.snapshot_chunk(None, 1).orders)
+            .is_err());
+
+        let id = target.add(Order::new(OrderType::Ask, 1.0), 101.0);
+        assert_eq!(id, 7);
+        assert_eq!(target.queue_position(id).unwrap().orders_ahead, 2);
+    }
+
+    #[test]
+    fn rejects_malformed_snapshots() {
+        let kraken_error = r#"{"error":["EQuery:Unknown asset pair"]}"#;
+        assert!(parse_snapshot(SnapshotFormat::Kraken, kraken_error).is_err());
+        assert!(parse_snapshot(SnapshotFormat::Binance, r#"{"bids":[]}"#).is_err());
+        assert!(
+            parse_snapshot(SnapshotFormat::Binance, r#"{"bids":[["x","1"]],"asks":[]}"#).is_err()
+        );
+        assert!(parse_snapshot(SnapshotFormat::Binance, r#"{"bids":[],"asks":[]"#).is_err());
+        assert_eq!(
+            parse_snapshot(SnapshotFormat::Binance, r#"{"bids":[],"asks":[[1.5, 2]]}"#).unwrap()[0]
+                .price,
+            1.5
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use super::clock::Timestamp;
+use super::numeric::exact_sum;
+use super::trade::Trade;
+use std::collections::VecDeque;
+
+/// Nanoseconds in a day
+pub const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Rolling 24 hour trade statistics for a single market
+#[derive(Debug, Default)]
+pub struct TradeStats {
+    window: VecDeque<(Timestamp, f64, f64)>,
+    last_price: Option<f64>,
+}
+
+impl TradeStats {
+    pub fn new() -> Self {
+        TradeStats::default()
+    }
+
+    pub fn record(&mut self, trade: &Trade) {
+        let price: f64 = trade.price.into();
+        self.window
+            .push_back((trade.transaction_time, price, trade.size));
+        self.last_price = Some(price);
+    }
+
+    fn expire(&mut self, now: Timestamp) {
+        while let Some(&(at, _, _)) = self.window.front() {
+            if at.saturating_add(DAY) > now {
+                break;
+            }
+            self.window.pop_front();
+        }
+    }
+
+    /// Price of the most recent trade, however old
+    pub fn last_price(&self) -> Option<f64> {
+        self.last_price
+    }
+
+    /// Traded size over the last 24 hours
+    pub fn volume(&mut self, now: Timestamp) -> f64 {
+        self.expire(now);
+        exact_sum(self.window.iter().map(|(_, _, size)| *size))
+    }
+
+    /// Volume-weighted average price of the trades since `since`
+    pub fn vwap(&mut self, since: Timestamp, now: Timestamp) -> Option<f64> {
+        self.expire(now);
+        let recent = self.window.iter().filter(|(at, _, _)| *at >= since);
+        let volume = exact_sum(recent.clone().map(|(_, _, size)| *size));
+        if volume <= 0.0 {
+            return None;
+        }
+        Some(exact_sum(recent.map(|(_, price, size)| price * size)) / volume)
+    }
+
+    /// Percentage change between the first and last trade of the last 24 hours
+    pub fn change(&mut self, now: Timestamp) -> Option<f64> {
+        self.expire(now);
+        let (_, open, _) = self.window.front()?;
+        let (_, close, _) = self.window.back()?;
+        Some((close - open) / open * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::{OrderType, Price};
+
+    fn trade(at: Timestamp, price: f64, size: f64) -> Trade {
+        Trade {
+            id: 0,
+            price: Price::new(price),
+            size,
+            aggressor_side: OrderType::Bid,
+            maker_order_id: None,
+            taker_order_id: None,
+            maker_account: None,
+            taker_account: None,
+            maker_tag: None,
+            taker_tag: None,
+            maker_version: 1,
+            taker_version: 1,
+            transaction_time: at,
+            triggered_stops: false,
+            triggered_band: false,
+            dark: false,
+        }
+    }
+
+    #[test]
+    fn rolling_volume_and_change() {
+        let mut stats = TradeStats::new();
+        assert_eq!(stats.change(0), None);
+
+        stats.record(&trade(0, 100.0, 1.0));
+        stats.record(&trade(DAY / 2, 110.0, 2.0));
+        stats.record(&trade(DAY, 120.0, 3.0));
+
+        assert_eq!(stats.volume(DAY), 5.0);
+        assert_eq!(stats.change(DAY), Some((120.0 - 110.0) / 110.0 * 100.0));
+        assert_eq!(stats.last_price(), Some(120.0));
+
+        assert_eq!(stats.vwap(DAY / 2, DAY), Some((220.0 + 360.0) / 5.0));
+        assert_eq!(stats.vwap(DAY + 1, DAY + 1), None);
+
+        assert_eq!(stats.volume(3 * DAY), 0.0);
+        assert_eq!(stats.change(3 * DAY), None);
+        assert_eq!(stats.last_price(), Some(120.0));
+    }
+}
@@ -0,0 +1,185 @@
+use super::orderbook::{Order, OrderId, OrderType, Price};
+use std::collections::BTreeMap;
+
+/// What a stop order becomes once its stop price trades
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopKind {
+    /// A market order
+    Market,
+    /// A limit order at the given price
+    Limit(f64),
+    /// A limit order `offset` through the stop price: above it for buys, below it for sells
+    ///
+    /// Protects a triggered stop from sweeping a thin book at any price.
+    Protected(f64),
+}
+
+impl StopKind {
+    /// The limit price a stop on `side` at `stop_price` is placed with once triggered, None for a
+    /// stop-market order
+    pub fn limit_price(&self, side: OrderType, stop_price: f64) -> Option<f64> {
+        match (*self, side) {
+            (StopKind::Market, _) => None,
+            (StopKind::Limit(price), _) => Some(price),
+            (StopKind::Protected(offset), OrderType::Bid) => Some(stop_price + offset),
+            (StopKind::Protected(offset), OrderType::Ask) => Some(stop_price - offset),
+        }
+    }
+}
+
+/// An order waiting in a [`StopBook`] for its stop price to trade
+#[derive(Debug)]
+pub struct StopOrder {
+    pub order: Order,
+    pub stop_price: Price,
+    pub kind: StopKind,
+}
+
+impl StopOrder {
+    /// The limit price the order is placed with once triggered, None for a stop-market order
+    pub fn limit_price(&self) -> Option<f64> {
+        self.kind
+            .limit_price(self.order.order_type(), self.stop_price.into())
+    }
+}
+
+/// Stop orders of one market, keyed by stop price
+///
+/// Buy stops trigger once a trade prints at or above their stop price, sell stops once one prints
+/// at or below it. Stops at the same price trigger in arrival order.
+#[derive(Debug, Default)]
+pub struct StopBook {
+    buys: BTreeMap<Price, Vec<StopOrder>>,
+    sells: BTreeMap<Price, Vec<StopOrder>>,
+}
+
+impl StopBook {
+    pub fn new() -> Self {
+        StopBook::default()
+    }
+
+    pub fn insert(&mut self, stop: StopOrder) {
+        let side = match stop.order.order_type() {
+            OrderType::Bid => &mut self.buys,
+            OrderType::Ask => &mut self.sells,
+        };
+        side.entry(stop.stop_price).or_default().push(stop);
+    }
+
+    /// Remove a stop order by id
+    pub fn remove(&mut self, id: OrderId) -> Option<StopOrder> {
+        for side in [&mut self.buys, &mut self.sells] {
+            let found = side.iter().find_map(|(price, stops)| {
+                let index = stops.iter().position(|stop| stop.order.id() == Some(id))?;
+                Some((*price, index))
+            });
+            if let Some((price, index)) = found {
+                let stops = side.get_mut(&price)?;
+                let stop = stops.remove(index);
+                if stops.is_empty() {
+                    side.remove(&price);
+                }
+                return Some(stop);
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, id: OrderId) -> Option<&StopOrder> {
+        self.iter().find(|stop| stop.order.id() == Some(id))
+    }
+
+    /// Every waiting stop order, buys then sells
+    pub fn iter(&self) -> impl Iterator<Item = &StopOrder> {
+        self.buys.values().chain(self.sells.values()).flatten()
+    }
+
+    /// Take the next stop order triggered by a trade at `last_price`
+    ///
+    /// Buy stops are taken lowest stop price first and sell stops highest first, so the stops
+    /// nearest the market fire before those further away.
+    pub fn pop_triggered(&mut self, last_price: Price) -> Option<StopOrder> {
+        let mut buy = self
+            .buys
+            .first_entry()
+            .filter(|entry| *entry.key() <= last_price);
+        let mut sell = self
+            .sells
+            .last_entry()
+            .filter(|entry| *entry.key() >= last_price);
+        let entry = match (buy.as_mut(), sell.as_mut()) {
+            (Some(buy), Some(sell)) => match buy.get()[0].order.id() <= sell.get()[0].order.id() {
+                true => buy,
+                false => sell,
+            },
+            (Some(buy), None) => buy,
+            (None, Some(sell)) => sell,
+            (None, None) => return None,
+        };
+        let stop = entry.get_mut().remove(0);
+        if entry.get().is_empty() {
+            match stop.order.order_type() {
+                OrderType::Bid => self.buys.remove(&stop.stop_price),
+                OrderType::Ask => self.sells.remove(&stop.stop_price),
+            };
+        }
+        Some(stop)
+    }
+
+    /// Remove every stop order, buys then sells
+    pub fn drain(&mut self) -> Vec<StopOrder> {
+        std::mem::take(&mut self.buys)
+            .into_values()
+            .chain(std::mem::take(&mut self.sells).into_values())
+            .flatten()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buys
+            .values()
+            .chain(self.sells.values())
+            .map(Vec::len)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buys.is_empty() && self.sells.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(side: OrderType, size: f64, stop_price: f64, kind: StopKind) -> StopOrder {
+        StopOrder {
+            order: Order::new(side, size),
+            stop_price: Price::new(stop_price),
+            kind,
+        }
+    }
+
+    #[test]
+    fn triggers_nearest_stops_first() {
+        let mut stops = StopBook::new();
+        stops.insert(stop(OrderType::Bid, 1.0, 105.0, StopKind::Market));
+        stops.insert(stop(OrderType::Bid, 2.0, 102.0, StopKind::Limit(103.0)));
+        stops.insert(stop(OrderType::Ask, 3.0, 95.0, StopKind::Protected(1.0)));
+        stops.insert(stop(OrderType::Bid, 4.0, 102.0, StopKind::Market));
+        assert_eq!(stops.len(), 4);
+
+        assert!(stops.pop_triggered(Price::new(101.0)).is_none());
+        let first = stops.pop_triggered(Price::new(105.0)).unwrap();
+        assert_eq!(first.order.size(), 2.0);
+        assert_eq!(first.limit_price(), Some(103.0));
+        let sizes: Vec<f64> = std::iter::from_fn(|| stops.pop_triggered(Price::new(105.0)))
+            .map(|stop| stop.order.size())
+            .collect();
+        assert_eq!(sizes, vec![4.0, 1.0]);
+
+        let sell = stops.pop_triggered(Price::new(95.0)).unwrap();
+        assert_eq!(sell.limit_price(), Some(94.0));
+        assert!(stops.is_empty());
+    }
+}
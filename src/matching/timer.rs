@@ -0,0 +1,124 @@
+use super::clock::Timestamp;
+
+/// A hashed timer wheel
+///
+/// Deadlines are bucketed into `slots` slots of `resolution` nanoseconds each, so advancing the
+/// wheel only visits the slots that elapsed rather than every pending timer. Deadlines themselves
+/// are kept at full precision: an item is only returned once `now` has reached its deadline.
+#[derive(Debug)]
+pub struct TimerWheel<T> {
+    resolution: u64,
+    slots: Vec<Vec<(Timestamp, T)>>,
+    /// First tick that may still hold pending timers
+    next_tick: u64,
+    overdue: Vec<(Timestamp, T)>,
+    len: usize,
+}
+
+impl<T> TimerWheel<T> {
+    /// Create a wheel starting at `start`
+    ///
+    /// # Arguments
+    /// * `resolution` - Width of a slot in nanoseconds
+    /// * `slots` - Number of slots; a full turn of the wheel covers `resolution * slots` nanoseconds
+    /// * `start` - The current time
+    pub fn new(resolution: u64, slots: usize, start: Timestamp) -> Self {
+        let resolution = resolution.max(1);
+        TimerWheel {
+            resolution,
+            slots: (0..slots.max(1)).map(|_| Vec::new()).collect(),
+            next_tick: start / resolution,
+            overdue: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Schedule `item` to fire at `deadline`
+    pub fn schedule(&mut self, deadline: Timestamp, item: T) {
+        let tick = deadline / self.resolution;
+        self.len += 1;
+        if tick < self.next_tick {
+            self.overdue.push((deadline, item));
+        } else {
+            let slot = (tick % self.slots.len() as u64) as usize;
+            self.slots[slot].push((deadline, item));
+        }
+    }
+
+    /// Advance the wheel to `now`
+    ///
+    /// # Returns
+    /// * `Vec<(Timestamp, T)>` - Every item whose deadline is at or before `now`, earliest first
+    pub fn advance(&mut self, now: Timestamp) -> Vec<(Timestamp, T)> {
+        let mut fired = std::mem::take(&mut self.overdue);
+        let target = now / self.resolution;
+        if target >= self.next_tick {
+            // A jump of a full turn or more has to look at every slot once
+            let ticks = (target - self.next_tick + 1).min(self.slots.len() as u64);
+            for offset in 0..ticks {
+                let slot = ((self.next_tick + offset) % self.slots.len() as u64) as usize;
+                let pending = std::mem::take(&mut self.slots[slot]);
+                for (deadline, item) in pending {
+                    if deadline <= now {
+                        fired.push((deadline, item));
+                    } else {
+                        self.slots[slot].push((deadline, item));
+                    }
+                }
+            }
+            // The target tick may still hold deadlines later in the same slot
+            self.next_tick = target;
+        }
+        self.len -= fired.len();
+        fired.sort_by_key(|(deadline, _)| *deadline);
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_at_exact_deadlines() {
+        let mut wheel = TimerWheel::new(1_000, 8, 0);
+        wheel.schedule(1_500, "a");
+        wheel.schedule(1_200, "b");
+        wheel.schedule(2_000, "c");
+
+        assert!(wheel.advance(1_199).is_empty());
+        assert_eq!(wheel.advance(1_499), vec![(1_200, "b")]);
+        assert_eq!(wheel.advance(1_500), vec![(1_500, "a")]);
+        assert_eq!(wheel.len(), 1);
+        assert_eq!(wheel.advance(2_000), vec![(2_000, "c")]);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn deadlines_beyond_one_turn_wait_for_their_round() {
+        let mut wheel = TimerWheel::new(10, 4, 0);
+        wheel.schedule(15, 1);
+        wheel.schedule(55, 2);
+
+        assert_eq!(wheel.advance(20), vec![(15, 1)]);
+        assert!(wheel.advance(54).is_empty());
+        assert_eq!(wheel.advance(1_000), vec![(55, 2)]);
+    }
+
+    #[test]
+    fn past_deadlines_fire_on_next_advance() {
+        let mut wheel = TimerWheel::new(10, 4, 100);
+        wheel.schedule(5, "late");
+        wheel.schedule(100, "now");
+
+        assert_eq!(wheel.advance(100), vec![(5, "late"), (100, "now")]);
+    }
+}
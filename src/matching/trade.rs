@@ -0,0 +1,66 @@
+use super::clock::Timestamp;
+use super::orderbook::{AccountId, OrderId, OrderType, Price};
+
+/// Sequence number of a trade within its order book
+pub type TradeId = u64;
+
+/// Which side of a trade an order was on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liquidity {
+    /// The order was resting in the book and provided liquidity
+    Maker,
+    /// The order crossed the book and removed liquidity
+    Taker,
+}
+
+/// An execution between a resting (maker) order and an incoming (taker) order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub id: TradeId,
+    pub price: Price,
+    pub size: f64,
+    /// The side of the incoming order that crossed the book
+    pub aggressor_side: OrderType,
+    pub maker_order_id: Option<OrderId>,
+    pub taker_order_id: Option<OrderId>,
+    pub maker_account: Option<AccountId>,
+    pub taker_account: Option<AccountId>,
+    /// The client tags of the maker and taker orders, echoed back unchanged
+    pub maker_tag: Option<String>,
+    pub taker_tag: Option<String>,
+    /// The versions of the maker and taker orders when they matched
+    pub maker_version: u32,
+    pub taker_version: u32,
+    /// When the match happened, according to the order book's clock
+    pub transaction_time: Timestamp,
+    /// The trade set off one or more stop orders
+    pub triggered_stops: bool,
+    /// The order that made the trade went on to breach a price band, halting the market
+    pub triggered_band: bool,
+    /// The match happened in the hidden midpoint pool rather than the lit book
+    pub dark: bool,
+}
+
+impl Trade {
+    /// The liquidity flag of an order that took part in this trade
+    ///
+    /// # Returns
+    /// * `Option<Liquidity>` - None if the order is not part of the trade
+    pub fn liquidity(&self, order_id: OrderId) -> Option<Liquidity> {
+        if self.maker_order_id == Some(order_id) {
+            Some(Liquidity::Maker)
+        } else if self.taker_order_id == Some(order_id) {
+            Some(Liquidity::Taker)
+        } else {
+            None
+        }
+    }
+
+    /// The side of the resting order
+    pub fn maker_side(&self) -> OrderType {
+        match self.aggressor_side {
+            OrderType::Bid => OrderType::Ask,
+            OrderType::Ask => OrderType::Bid,
+        }
+    }
+}
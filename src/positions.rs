@@ -0,0 +1,148 @@
+use crate::matching::orderbook::{from_lots, to_lots, AccountId, OrderType, TradingPair};
+use std::collections::HashMap;
+
+/// An account's net position in one market
+///
+/// Size is kept in whole lots so that a position closed in several fills returns to exactly
+/// flat. Trades that add to the position move the entry price to the size-weighted average;
+/// trades that reduce it realize profit against the entry price, and a trade that flips the
+/// position opens the remainder at the trade price.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetPosition {
+    /// Signed lots: positive long, negative short
+    lots: i64,
+    /// Average price of the open position, 0 when flat
+    pub entry_price: f64,
+    /// Profit taken by closing trades, in the quote currency
+    pub realized_pnl: f64,
+}
+
+impl NetPosition {
+    /// Signed size in the base currency: positive long, negative short
+    pub fn size(&self) -> f64 {
+        match self.lots < 0 {
+            true => -from_lots(self.lots.unsigned_abs()),
+            false => from_lots(self.lots as u64),
+        }
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.lots == 0
+    }
+
+    /// Profit the open position would realize if closed at `mark`
+    pub fn unrealized_pnl(&self, mark: f64) -> f64 {
+        (mark - self.entry_price) * self.size()
+    }
+
+    /// Apply a fill of `size` at `price` on `side`
+    fn fill(&mut self, side: OrderType, size: f64, price: f64) {
+        let lots = to_lots(size) as i64;
+        let delta = match side {
+            OrderType::Bid => lots,
+            OrderType::Ask => -lots,
+        };
+        let current = self.lots;
+        if current == 0 || current.signum() == delta.signum() {
+            let total = current + delta;
+            self.entry_price = (self.entry_price * current.abs() as f64
+                + price * delta.abs() as f64)
+                / total.abs() as f64;
+            self.lots = total;
+            return;
+        }
+        let closed = delta.abs().min(current.abs());
+        let direction = current.signum() as f64;
+        self.realized_pnl += (price - self.entry_price) * from_lots(closed as u64) * direction;
+        self.lots = current + delta;
+        self.entry_price = match self.lots.signum() {
+            0 => 0.0,
+            sign if sign == current.signum() => self.entry_price,
+            _ => price,
+        };
+    }
+}
+
+/// Net positions of every account in every market it has traded
+#[derive(Debug, Default)]
+pub struct PositionLedger {
+    positions: HashMap<(AccountId, TradingPair), NetPosition>,
+}
+
+impl PositionLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one side of a trade
+    ///
+    /// # Arguments
+    /// * `account` - The account that traded
+    /// * `trading_pair` - The market traded
+    /// * `side` - The side of the account's order
+    /// * `size` - The size filled
+    /// * `price` - The trade price
+    pub fn record(
+        &mut self,
+        account: AccountId,
+        trading_pair: &TradingPair,
+        side: OrderType,
+        size: f64,
+        price: f64,
+    ) {
+        self.positions
+            .entry((account, trading_pair.clone()))
+            .or_default()
+            .fill(side, size, price);
+    }
+
+    /// An account's position in a market, None if it has never traded there
+    pub fn get(&self, account: AccountId, trading_pair: &TradingPair) -> Option<&NetPosition> {
+        self.positions.get(&(account, trading_pair.clone()))
+    }
+
+    /// Every market an account has traded, with its position in each
+    pub fn account(&self, account: AccountId) -> Vec<(&TradingPair, &NetPosition)> {
+        let mut positions: Vec<_> = self
+            .positions
+            .iter()
+            .filter(|((owner, _), _)| *owner == account)
+            .map(|((_, pair), position)| (pair, position))
+            .collect();
+        positions.sort_by_key(|(pair, _)| String::from((*pair).clone()));
+        positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_entry_price_and_realized_pnl_through_a_flip() {
+        let pair: TradingPair = "BTC/USD".parse().unwrap();
+        let mut ledger = PositionLedger::new();
+        ledger.record(1, &pair, OrderType::Bid, 1.0, 100.0);
+        ledger.record(1, &pair, OrderType::Bid, 3.0, 104.0);
+        let position = ledger.get(1, &pair).unwrap();
+        assert_eq!((position.size(), position.entry_price), (4.0, 103.0));
+        assert_eq!(position.unrealized_pnl(105.0), 8.0);
+
+        // Close 1.5, then sell through flat into a short opened at the trade price
+        ledger.record(1, &pair, OrderType::Ask, 1.5, 107.0);
+        ledger.record(1, &pair, OrderType::Ask, 3.5, 101.0);
+        let position = ledger.get(1, &pair).unwrap();
+        assert_eq!(position.size(), -1.0);
+        assert_eq!(position.entry_price, 101.0);
+        assert_eq!(position.realized_pnl, 6.0 - 5.0);
+
+        ledger.record(1, &pair, OrderType::Bid, 0.1, 100.0);
+        ledger.record(1, &pair, OrderType::Bid, 0.2, 100.0);
+        ledger.record(1, &pair, OrderType::Bid, 0.7, 100.0);
+        let position = ledger.get(1, &pair).unwrap();
+        assert!(position.is_flat());
+        assert_eq!(position.entry_price, 0.0);
+        assert!(ledger.get(2, &pair).is_none());
+        assert_eq!(ledger.account(1).len(), 1);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::matching::orderbook::{OrderType, Price, TradingPair};
+use crate::matching::trade::{Trade, TradeId};
+use std::io::BufRead;
+
+/// A fill reported by an external venue's FIX drop copy
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalFill {
+    /// ExecID (17)
+    pub exec_id: String,
+    /// Symbol (55), e.g. `BTC/USD`
+    pub symbol: String,
+    /// Side (54)
+    pub side: OrderType,
+    /// LastPx (31)
+    pub price: Price,
+    /// LastQty (32)
+    pub size: f64,
+}
+
+/// A difference between the external record and ours
+#[derive(Debug, Clone, PartialEq)]
+pub enum Break {
+    /// The venue reported a fill we have no trade for
+    MissingInternally(ExternalFill),
+    /// We have a trade the venue did not report
+    MissingExternally {
+        pair: TradingPair,
+        trade_id: TradeId,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReconciliationReport {
+    pub matched: usize,
+    pub breaks: Vec<Break>,
+}
+
+impl ReconciliationReport {
+    pub fn is_clean(&self) -> bool {
+        self.breaks.is_empty()
+    }
+}
+
+/// Parse a FIX drop copy file into fills
+///
+/// Each line holds one message with fields separated by SOH (`\x01`) or `|`. Only execution reports
+/// (`35=8`) for trades (`150=F`, or `150` absent as in FIX 4.2 fills) are returned; other messages
+/// are skipped.
+///
+/// # Returns
+/// * `Vec<Result<ExternalFill, String>>` - One entry per execution report, with the reason for every unreadable one
+pub fn parse_drop_copy<R: BufRead>(reader: R) -> Vec<Result<ExternalFill, String>> {
+    let mut fills = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                fills.push(Err(format!("line {}: {}", index + 1, e)));
+                break;
+            }
+        };
+        let fields = line
+            .split(['\x01', '|'])
+            .filter_map(|field| field.split_once('='))
+            .collect::<Vec<(&str, &str)>>();
+        let tag = |tag: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| *key == tag)
+                .map(|(_, value)| *value)
+        };
+        if tag("35") != Some("8") || !matches!(tag("150"), None | Some("F")) {
+            continue;
+        }
+        fills.push(parse_fill(&tag).map_err(|e| format!("line {}: {}", index + 1, e)));
+    }
+    fills
+}
+
+fn parse_fill<'a>(tag: &impl Fn(&str) -> Option<&'a str>) -> Result<ExternalFill, String> {
+    let required = |number: &str, name: &str| {
+        tag(number).ok_or_else(|| format!("missing {} ({})", name, number))
+    };
+    let side = match required("54", "Side")? {
+        "1" => OrderType::Bid,
+        "2" => OrderType::Ask,
+        other => return Err(format!("unsupported Side {:?}", other)),
+    };
+    let price = required("31", "LastPx")?
+        .parse::<f64>()
+        .map_err(|_| "invalid LastPx".to_string())?;
+    let size = required("32", "LastQty")?
+        .parse::<f64>()
+        .map_err(|_| "invalid LastQty".to_string())?;
+    Ok(ExternalFill {
+        exec_id: required("17", "ExecID")?.to_string(),
+        symbol: required("55", "Symbol")?.to_string(),
+        side,
+        price: Price::new(price),
+        size,
+    })
+}
+
+/// Match external fills against our trades
+///
+/// A fill matches an unmatched trade on the same market with the same price and size where the
+/// fill's side is either side of the trade. Everything left over on either side is a break.
+pub fn reconcile(
+    external: &[ExternalFill],
+    internal: &[(TradingPair, Trade)],
+) -> ReconciliationReport {
+    let mut unmatched = internal.iter().collect::<Vec<&(TradingPair, Trade)>>();
+    let mut report = ReconciliationReport::default();
+    for fill in external {
+        let found = unmatched.iter().position(|(pair, trade)| {
+            String::from(pair.clone()) == fill.symbol
+                && trade.price == fill.price
+                && trade.size == fill.size
+                && (trade.aggressor_side == fill.side || trade.maker_side() == fill.side)
+        });
+        match found {
+            Some(index) => {
+                unmatched.swap_remove(index);
+                report.matched += 1;
+            }
+            None => report.breaks.push(Break::MissingInternally(fill.clone())),
+        }
+    }
+    report.breaks.extend(
+        unmatched
+            .into_iter()
+            .map(|(pair, trade)| Break::MissingExternally {
+                pair: pair.clone(),
+                trade_id: trade.id,
+            }),
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::orderbook::{Order, OrderBook};
+
+    const DROP_COPY: &str = "8=FIX.4.4|35=8|17=E1|150=F|55=BTC/USD|54=1|31=100|32=2\n\
+                             8=FIX.4.4|35=0|\n\
+                             8=FIX.4.4|35=8|17=E2|150=0|55=BTC/USD|54=1|31=100|32=2\n\
+                             8=FIX.4.4|35=8|17=E3|150=F|55=BTC/USD|54=1|31=101|32=1\n\
+                             8=FIX.4.4|35=8|17=E4|150=F|55=BTC/USD|54=1|31=abc|32=1\n";
+
+    #[test]
+    fn parses_execution_reports() {
+        let fills = parse_drop_copy(DROP_COPY.as_bytes());
+
+        assert_eq!(fills.len(), 3);
+        assert_eq!(
+            fills[0],
+            Ok(ExternalFill {
+                exec_id: "E1".to_string(),
+                symbol: "BTC/USD".to_string(),
+                side: OrderType::Bid,
+                price: Price::new(100.0),
+                size: 2.0,
+            })
+        );
+        assert!(fills[2].as_ref().unwrap_err().contains("LastPx"));
+    }
+
+    #[test]
+    fn reports_breaks_on_both_sides() {
+        let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let mut orderbook = OrderBook::new();
+        orderbook.add(Order::new(OrderType::Ask, 2.0), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 5.0), 102.0);
+        let mut buy = Order::new(OrderType::Bid, 3.0);
+        let internal = orderbook
+            .place_market_order(&mut buy)
+            .into_iter()
+            .map(|trade| (pair.clone(), trade))
+            .collect::<Vec<(TradingPair, Trade)>>();
+
+        let external = parse_drop_copy(DROP_COPY.as_bytes())
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect::<Vec<ExternalFill>>();
+        let report = reconcile(&external, &internal);
+
+        assert_eq!(report.matched, 1);
+        assert!(!report.is_clean());
+        assert_eq!(report.breaks.len(), 2);
+        assert!(
+            matches!(&report.breaks[0], Break::MissingInternally(fill) if fill.exec_id == "E3")
+        );
+        assert_eq!(
+            report.breaks[1],
+            Break::MissingExternally {
+                pair,
+                trade_id: internal[1].1.id,
+            }
+        );
+    }
+}
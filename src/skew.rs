@@ -0,0 +1,169 @@
+use crate::matching::clock::Timestamp;
+use crate::matching::numeric::exact_sum;
+use crate::matching::orderbook::{AccountId, OrderType, TradingPair};
+use crate::matching::trade::Trade;
+
+/// How a maker account's inventory and recent fills translate into a quote skew
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkewConfig {
+    /// Position, in the base currency, at which the inventory skew is at its maximum
+    pub max_position: f64,
+    /// Largest price offset suggested for the account's quotes, in the quote currency
+    pub max_skew: f64,
+    /// Fills in this many nanoseconds make up the recent fill flow
+    pub flow_window: u64,
+    /// Weight of the fill flow against the inventory in the suggested skew, between 0 and 1
+    pub flow_weight: f64,
+}
+
+/// A maker account's inventory skew in one market
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventorySkew {
+    pub account: AccountId,
+    pub pair: TradingPair,
+    /// Bought minus sold over all the trades given
+    pub position: f64,
+    /// Position as a fraction of the maximum, between -1 (max short) and 1 (max long)
+    pub inventory_skew: f64,
+    /// Bought minus sold over the flow window, as a fraction of the size traded in it, between
+    /// -1 (only sells) and 1 (only buys)
+    pub flow_imbalance: f64,
+    /// Offset to add to the account's bid and ask prices: negative when long or buying, to shed
+    /// inventory, positive when short or selling
+    pub quote_skew: f64,
+}
+
+impl InventorySkew {
+    /// Compute an account's skew in a market from its trades up to `now`
+    ///
+    /// # Arguments
+    /// * `account` - The maker account
+    /// * `pair` - The market to compute the skew for
+    /// * `trades` - Trades by market since the account was flat, e.g. everything the engine produced since
+    ///   the start of the day
+    /// * `config` - Maximum position and skew, flow window and weight
+    /// * `now` - End of the flow window
+    ///
+    /// # Returns
+    /// * `Result<InventorySkew, String>` - Err if the maximum position is not positive
+    pub fn compute(
+        account: AccountId,
+        pair: &TradingPair,
+        trades: &[(TradingPair, Trade)],
+        config: &SkewConfig,
+        now: Timestamp,
+    ) -> Result<InventorySkew, String> {
+        if !config.max_position.is_finite() || config.max_position <= 0.0 {
+            return Err(format!(
+                "Maximum position must be positive, got {}",
+                config.max_position
+            ));
+        }
+        let fills: Vec<(Timestamp, f64)> = trades
+            .iter()
+            .filter(|(trade_pair, trade)| trade_pair == pair && trade.transaction_time <= now)
+            .filter_map(|(_, trade)| {
+                let side = if trade.maker_account == Some(account) {
+                    trade.maker_side()
+                } else if trade.taker_account == Some(account) {
+                    trade.aggressor_side
+                } else {
+                    return None;
+                };
+                let signed = match side {
+                    OrderType::Bid => trade.size,
+                    OrderType::Ask => -trade.size,
+                };
+                Some((trade.transaction_time, signed))
+            })
+            .collect();
+
+        let position = exact_sum(fills.iter().map(|(_, signed)| *signed));
+        let recent = fills
+            .iter()
+            .filter(|(at, _)| at.saturating_add(config.flow_window) > now);
+        let net_flow = exact_sum(recent.clone().map(|(_, signed)| *signed));
+        let gross_flow = exact_sum(recent.map(|(_, signed)| signed.abs()));
+
+        let inventory_skew = (position / config.max_position).clamp(-1.0, 1.0);
+        let flow_imbalance = match gross_flow > 0.0 {
+            true => net_flow / gross_flow,
+            false => 0.0,
+        };
+        let weight = config.flow_weight.clamp(0.0, 1.0);
+        let signal = inventory_skew * (1.0 - weight) + flow_imbalance * weight;
+        Ok(InventorySkew {
+            account,
+            pair: pair.clone(),
+            position,
+            inventory_skew,
+            flow_imbalance,
+            quote_skew: -signal.clamp(-1.0, 1.0) * config.max_skew,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::clock::ManualClock;
+    use crate::matching::orderbook::{Order, OrderBook};
+    use std::sync::Arc;
+
+    #[test]
+    fn skew_leans_against_inventory_and_flow() {
+        let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let clock = ManualClock::new(0);
+        let mut orderbook = OrderBook::with_clock(Arc::new(clock.clone()));
+        let mut trades = Vec::new();
+        // The maker is hit on its bids three times, then lifted once
+        for (side, taker) in [
+            (OrderType::Bid, OrderType::Ask),
+            (OrderType::Bid, OrderType::Ask),
+            (OrderType::Bid, OrderType::Ask),
+            (OrderType::Ask, OrderType::Bid),
+        ] {
+            orderbook.add(Order::new(side, 1.0).with_account(1), 100.0);
+            let mut order = Order::new(taker, 1.0).with_account(2);
+            trades.extend(
+                orderbook
+                    .place_market_order(&mut order)
+                    .into_iter()
+                    .map(|trade| (pair.clone(), trade)),
+            );
+            clock.advance(10);
+        }
+
+        let config = SkewConfig {
+            max_position: 4.0,
+            max_skew: 2.0,
+            flow_window: 25,
+            flow_weight: 0.5,
+        };
+        let skew = InventorySkew::compute(1, &pair, &trades, &config, 40).unwrap();
+        assert_eq!(skew.position, 2.0);
+        assert_eq!(skew.inventory_skew, 0.5);
+        // Only the last two fills, one buy and one sell, are recent
+        assert_eq!(skew.flow_imbalance, 0.0);
+        assert_eq!(skew.quote_skew, -0.5);
+
+        let taker = InventorySkew::compute(2, &pair, &trades, &config, 40).unwrap();
+        assert_eq!(taker.position, -2.0);
+        assert_eq!(taker.quote_skew, 0.5);
+
+        let other = TradingPair::new("ETH".to_string(), "USD".to_string());
+        let flat = InventorySkew::compute(1, &other, &trades, &config, 40).unwrap();
+        assert_eq!(flat.quote_skew, 0.0);
+        assert!(InventorySkew::compute(
+            1,
+            &pair,
+            &trades,
+            &SkewConfig {
+                max_position: 0.0,
+                ..config
+            },
+            40
+        )
+        .is_err());
+    }
+}
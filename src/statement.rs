@@ -0,0 +1,189 @@
+use crate::matching::clock::Timestamp;
+use crate::matching::numeric::exact_sum;
+use crate::matching::orderbook::{AccountId, OrderType, TradingPair};
+use crate::matching::trade::{Liquidity, Trade, TradeId};
+use std::io::{self, Write};
+
+/// One execution on an account statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementLine {
+    pub at: Timestamp,
+    pub pair: TradingPair,
+    pub trade_id: TradeId,
+    pub side: OrderType,
+    pub liquidity: Liquidity,
+    pub price: f64,
+    pub size: f64,
+    pub notional: f64,
+}
+
+/// Net traded position in one market over the statement period
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionLine {
+    pub pair: TradingPair,
+    /// Bought minus sold
+    pub net_size: f64,
+    /// Quote currency paid for buys minus received for sells
+    pub net_notional: f64,
+}
+
+/// An account's activity over a period
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub account: AccountId,
+    pub from: Timestamp,
+    pub to: Timestamp,
+    pub lines: Vec<StatementLine>,
+    pub positions: Vec<PositionLine>,
+}
+
+impl Statement {
+    /// Build a statement from trades in `[from, to)`
+    ///
+    /// # Arguments
+    /// * `account` - The account the statement is for
+    /// * `trades` - Trades by market, e.g. everything the engine produced in the period
+    /// * `from` - Start of the period, inclusive
+    /// * `to` - End of the period, exclusive
+    pub fn build(
+        account: AccountId,
+        trades: &[(TradingPair, Trade)],
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Statement {
+        let mut lines = Vec::new();
+        for (pair, trade) in trades {
+            if trade.transaction_time < from || trade.transaction_time >= to {
+                continue;
+            }
+            let (side, liquidity) = if trade.maker_account == Some(account) {
+                (trade.maker_side(), Liquidity::Maker)
+            } else if trade.taker_account == Some(account) {
+                (trade.aggressor_side, Liquidity::Taker)
+            } else {
+                continue;
+            };
+            let price: f64 = trade.price.into();
+            lines.push(StatementLine {
+                at: trade.transaction_time,
+                pair: pair.clone(),
+                trade_id: trade.id,
+                side,
+                liquidity,
+                price,
+                size: trade.size,
+                notional: price * trade.size,
+            });
+        }
+        lines.sort_by_key(|line| line.at);
+
+        let mut positions: Vec<PositionLine> = Vec::new();
+        for line in &lines {
+            if !positions.iter().any(|position| position.pair == line.pair) {
+                let signed = lines
+                    .iter()
+                    .filter(|other| other.pair == line.pair)
+                    .map(|other| match other.side {
+                        OrderType::Bid => (other.size, other.notional),
+                        OrderType::Ask => (-other.size, -other.notional),
+                    });
+                positions.push(PositionLine {
+                    pair: line.pair.clone(),
+                    net_size: exact_sum(signed.clone().map(|(size, _)| size)),
+                    net_notional: exact_sum(signed.map(|(_, notional)| notional)),
+                });
+            }
+        }
+
+        Statement {
+            account,
+            from,
+            to,
+            lines,
+            positions,
+        }
+    }
+
+    /// Write the executions as CSV
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "time,pair,trade_id,side,liquidity,price,size,notional"
+        )?;
+        for line in &self.lines {
+            let side = match line.side {
+                OrderType::Bid => "buy",
+                OrderType::Ask => "sell",
+            };
+            let liquidity = match line.liquidity {
+                Liquidity::Maker => "maker",
+                Liquidity::Taker => "taker",
+            };
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{}",
+                line.at,
+                String::from(line.pair.clone()),
+                line.trade_id,
+                side,
+                liquidity,
+                line.price,
+                line.size,
+                line.notional
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::clock::ManualClock;
+    use crate::matching::orderbook::{Order, OrderBook};
+    use std::sync::Arc;
+
+    #[test]
+    fn statement_lists_executions_and_positions() {
+        let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+        let clock = ManualClock::new(10);
+        let mut orderbook = OrderBook::with_clock(Arc::new(clock.clone()));
+        orderbook.add(Order::new(OrderType::Ask, 2.0).with_account(1), 100.0);
+        orderbook.add(Order::new(OrderType::Ask, 2.0).with_account(2), 101.0);
+
+        let mut trades = Vec::new();
+        for size in [3.0, 1.0] {
+            let mut buy = Order::new(OrderType::Bid, size).with_account(7);
+            trades.extend(
+                orderbook
+                    .place_market_order(&mut buy)
+                    .into_iter()
+                    .map(|trade| (pair.clone(), trade)),
+            );
+            clock.advance(10);
+        }
+
+        let statement = Statement::build(7, &trades, 0, 20);
+        assert_eq!(statement.lines.len(), 2);
+        assert_eq!(statement.lines[0].liquidity, Liquidity::Taker);
+        assert_eq!(statement.lines[1].notional, 101.0);
+        assert_eq!(
+            statement.positions,
+            vec![PositionLine {
+                pair: pair.clone(),
+                net_size: 3.0,
+                net_notional: 301.0,
+            }]
+        );
+
+        let maker = Statement::build(2, &trades, 0, 100);
+        assert_eq!(maker.lines.len(), 2);
+        assert_eq!(maker.lines[0].side, OrderType::Ask);
+        assert_eq!(maker.positions[0].net_size, -2.0);
+
+        let mut csv = Vec::new();
+        statement.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("10,BTC/USD,1,buy,taker,100,2,200"));
+    }
+}